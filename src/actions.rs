@@ -0,0 +1,201 @@
+use std::fmt::Write as _;
+
+use anyhow::Error;
+use config::Config;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::pipeline::{parse_summary_sections, Meeting, RunContext};
+use crate::progress::{self, Progress};
+use crate::report::RunReport;
+use crate::session::{self, Session};
+use crate::{limits, status, summarize};
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Open,
+    Done,
+}
+
+// An action item from a meeting in a session, kept in session.json
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ActionItem {
+    // Numbered from 1 within the session
+    pub id: u32,
+    pub text: String,
+    // The meeting it came from, e.g. "Weekly sync (2024-05-14)"
+    pub meeting: String,
+    pub status: Status,
+    // The meeting that reported it done
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub done_in: Option<String>,
+}
+
+// Prompt asking the model which of the numbered open items the meeting after
+// it reports as done
+pub fn done_prompt() -> &'static str {
+    "Below are the open action items from earlier meetings in a series, each with its number, followed by the \
+transcript of the latest meeting. Find the items the meeting reports as done, or as no longer needed. Reply \
+with only a JSON array of their numbers, e.g. [2, 5], or [] when there are none.\n\n"
+}
+
+// The action items in a summary's action items section, without their bullets
+pub fn items(summary: &str) -> Vec<String> {
+    let (_, action_items, _) = parse_summary_sections(summary);
+    action_items.lines().map(item_text).filter(|item| !item.is_empty()).collect()
+}
+
+// "- [ ] Sam to ship it" or "2. Sam to ship it" is "Sam to ship it"
+fn item_text(line: &str) -> String {
+    let line = line.trim().trim_start_matches(['-', '*', '•']).trim_start();
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    let line = match line[digits..].strip_prefix(['.', ')']) {
+        Some(rest) if digits > 0 => rest.trim_start(),
+        _ => line,
+    };
+    let line = ["[ ]", "[x]", "[X]"]
+        .iter()
+        .find_map(|box_| line.strip_prefix(box_))
+        .unwrap_or(line);
+    line.trim().to_string()
+}
+
+// After a run is added to the session: ask the model which of the session's
+// open items the meeting reports as done, add the meeting's own items, and
+// report the items from earlier meetings that are still open
+pub async fn track(
+    ctx: &RunContext,
+    session: &Session,
+    meeting: &Meeting,
+    transcript: &str,
+    summary: &str,
+    spinner: &mut Progress,
+    run_report: &mut RunReport,
+) -> Result<(), Error> {
+    let open: Vec<ActionItem> = session::open(&ctx.settings, session.id())?
+        .actions()
+        .iter()
+        .filter(|item| item.status == Status::Open)
+        .cloned()
+        .collect();
+    let mut done = Vec::new();
+    if !open.is_empty() {
+        spinner.update("Checking the open action items...");
+        let mut text = String::from("Open action items:\n");
+        for item in &open {
+            let _ = writeln!(text, "{}. {}", item.id, item.text);
+        }
+        let (transcript, _) = limits::truncate(transcript, ctx.size_limit.max_words);
+        let _ = write!(text, "\nTranscript of the latest meeting:\n{}", transcript);
+        let response =
+            summarize::summarize_json_array(&ctx.settings, &ctx.config, &text, done_prompt(), spinner).await?;
+        run_report.add_usage("Action items", response.usage);
+        done = response.values.iter().filter_map(|value| value.as_u64()).collect();
+    }
+
+    let label = format!("{} ({})", meeting.title, meeting.date);
+    let new = items(summary);
+    let actions = session.update_actions(|actions| {
+        for item in actions.iter_mut() {
+            if item.status == Status::Open && done.contains(&(item.id as u64)) {
+                item.status = Status::Done;
+                item.done_in = Some(label.clone());
+            }
+        }
+        let mut id = actions.iter().map(|item| item.id).max().unwrap_or(0);
+        for text in new {
+            // An item raised again while still open is kept once
+            let raised = |item: &ActionItem| item.status == Status::Open && item.text.eq_ignore_ascii_case(&text);
+            if actions.iter().any(raised) {
+                continue;
+            }
+            id += 1;
+            actions.push(ActionItem { id, text, meeting: label.clone(), status: Status::Open, done_in: None });
+        }
+    })?;
+
+    let closed = open.iter().filter(|item| done.contains(&(item.id as u64))).count();
+    let outstanding: Vec<&ActionItem> = actions
+        .iter()
+        .filter(|item| item.status == Status::Open && open.iter().any(|earlier| earlier.id == item.id))
+        .collect();
+    let open_items: Vec<_> = outstanding
+        .iter()
+        .map(|item| json!({ "id": item.id, "text": item.text, "meeting": item.meeting }))
+        .collect();
+    progress::event("action_items", json!({ "done": closed, "open": open_items }));
+    if closed > 0 {
+        status!("✅", "{} action item(s) from earlier meetings are done", closed);
+    }
+    if !outstanding.is_empty() {
+        let mut lines = format!("{} action item(s) from earlier meetings are still open:", outstanding.len());
+        for item in outstanding {
+            let _ = write!(lines, "\n  #{} {} ({})", item.id, item.text, item.meeting);
+        }
+        status!("📌", "{}", lines);
+    }
+    Ok(())
+}
+
+// `distill actions list`: the action items of the named session, or of every
+// session, with --open only the ones not done yet
+pub fn list(settings: &Config, series: Option<&str>, open_only: bool) -> Result<(), Error> {
+    let sessions = match series {
+        Some(name) => vec![session::open(settings, name)?],
+        None => session::all(settings)?,
+    };
+    let mut listed = 0;
+    for session in sessions {
+        let items: Vec<&ActionItem> = session
+            .actions()
+            .iter()
+            .filter(|item| !open_only || item.status == Status::Open)
+            .collect();
+        if items.is_empty() {
+            continue;
+        }
+        if listed > 0 {
+            println!();
+        }
+        println!("{}", session.name);
+        for item in items {
+            listed += 1;
+            match (&item.status, &item.done_in) {
+                (Status::Done, Some(done_in)) => {
+                    println!("  [x] #{} {} ({}; done in {})", item.id, item.text, item.meeting, done_in)
+                }
+                (Status::Done, None) => println!("  [x] #{} {} ({})", item.id, item.text, item.meeting),
+                (Status::Open, _) => println!("  [ ] #{} {} ({})", item.id, item.text, item.meeting),
+            }
+        }
+    }
+    if listed == 0 {
+        eprintln!(
+            "No {}action items. They are tracked for the runs kept in a session.",
+            if open_only { "open " } else { "" }
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_bullets_and_checkboxes() {
+        assert_eq!(item_text("- Sam to ship it"), "Sam to ship it");
+        assert_eq!(item_text("  * [ ] Ana: book the room"), "Ana: book the room");
+        assert_eq!(item_text("2. Review the budget"), "Review the budget");
+        assert_eq!(item_text("10) Send the notes"), "Send the notes");
+        assert_eq!(item_text("3D models to the vendor"), "3D models to the vendor");
+    }
+
+    #[test]
+    fn reads_the_action_items_section() {
+        let summary = "## Summary\nThe team met.\n\n## Action Items\n- Sam to ship it\n- Ana to book the room\n";
+        assert_eq!(items(summary), ["Sam to ship it", "Ana to book the room"]);
+        assert!(items("## Summary\nNothing was decided.").is_empty());
+    }
+}
@@ -0,0 +1,81 @@
+use serde_json::Value;
+
+// Agenda items from a Markdown (or plain text) agenda: list items, numbered
+// items and headings each become one item. Other lines are ignored.
+pub fn parse_agenda_items(agenda: &str) -> Vec<String> {
+    agenda
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let item = if let Some(rest) = line.strip_prefix(['-', '*', '+', '#']) {
+                rest.trim_start_matches('#')
+            } else {
+                let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+                if digits == 0 {
+                    return None;
+                }
+                line[digits..].strip_prefix(['.', ')'])?
+            };
+            // Drop any existing checkbox, e.g. "- [ ] Budget"
+            let item = item
+                .trim()
+                .trim_start_matches("[ ]")
+                .trim_start_matches("[x]")
+                .trim();
+            item.chars()
+                .any(|c| c.is_alphanumeric())
+                .then(|| item.to_string())
+        })
+        .collect()
+}
+
+// Prompt asking the model to classify each agenda item against a timestamped transcript
+pub fn agenda_prompt(items: &[String]) -> String {
+    let numbered: Vec<String> = items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| format!("{}. {}", i + 1, item))
+        .collect();
+
+    format!(
+        "Below is a meeting agenda followed by a transcript of the meeting. Each line of the transcript \
+starts with a timestamp in [HH:MM:SS] format. For every agenda item, decide whether it was \"covered\", \
+\"partial\" (touched on but not concluded) or \"not_discussed\", and list the timestamps of the transcript \
+lines where it was discussed. Reply with only a JSON array with one object per agenda item, in order, \
+of the form {{\"item\": 1, \"status\": \"covered\", \"timestamps\": [\"00:12:30\"]}}.\n\nAgenda:\n{}\n\nTranscript:",
+        numbered.join("\n")
+    )
+}
+
+// Render the model's parsed JSON reply as a Markdown-style checklist
+pub fn render_checklist(items: &[String], statuses: &[Value]) -> String {
+    let lines: Vec<String> = items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let entry = statuses
+                .iter()
+                .find(|s| s["item"].as_u64() == Some(i as u64 + 1));
+            let status = entry
+                .and_then(|e| e["status"].as_str())
+                .unwrap_or("not_discussed");
+            let timestamps: Vec<&str> = entry
+                .and_then(|e| e["timestamps"].as_array())
+                .map(|t| t.iter().filter_map(|t| t.as_str()).collect())
+                .unwrap_or_default();
+
+            let (checkbox, label) = match status {
+                "covered" => ("[x]", "covered"),
+                "partial" => ("[~]", "partially covered"),
+                _ => ("[ ]", "not discussed"),
+            };
+            if timestamps.is_empty() {
+                format!("- {} {} ({})", checkbox, item, label)
+            } else {
+                format!("- {} {} ({}; {})", checkbox, item, label, timestamps.join(", "))
+            }
+        })
+        .collect();
+
+    lines.join("\n")
+}
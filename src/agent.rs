@@ -0,0 +1,209 @@
+use anyhow::{anyhow, bail, Error};
+use aws_config::SdkConfig;
+use aws_smithy_eventstream::frame::{DecodedFrame, MessageFrameDecoder};
+use aws_smithy_types::event_stream::Message;
+use config::Config;
+use serde_json::{json, Value};
+use tracing::{debug, instrument};
+
+use crate::aws::signed_request;
+use crate::progress::Progress;
+use crate::summarize::{Summary, Usage};
+
+// The alias every agent has for its working draft
+const DRAFT_ALIAS: &str = "TSTALIASID";
+
+// A Bedrock Agent that summaries are routed through instead of calling the
+// model directly, from agent.agent_id (or --agent) and agent.alias_id. The
+// agent can use its action groups and knowledge bases while it writes, e.g.
+// to look up the ticket IDs or people mentioned in the meeting and link them.
+pub struct Agent {
+    pub agent_id: String,
+    pub alias_id: String,
+}
+
+impl Agent {
+    // None unless agent.agent_id is set
+    pub fn from_settings(settings: &Config) -> Option<Self> {
+        let agent_id = settings.get_string("agent.agent_id").ok().filter(|id| !id.trim().is_empty())?;
+        let alias_id = settings
+            .get_string("agent.alias_id")
+            .ok()
+            .filter(|id| !id.trim().is_empty())
+            .unwrap_or_else(|| DRAFT_ALIAS.to_string());
+        Some(Agent {
+            agent_id: agent_id.trim().to_string(),
+            alias_id: alias_id.trim().to_string(),
+        })
+    }
+
+    // Send `input` to the agent in a new session and collect its answer, with
+    // the sources of any knowledge base citations listed after it. With
+    // `on_text`, the answer is passed on as it is streamed.
+    #[instrument(name = "invoke_agent", skip_all, fields(agent_id = %self.agent_id, alias_id = %self.alias_id))]
+    pub async fn invoke(
+        &self,
+        settings: &Config,
+        sdk_config: &SdkConfig,
+        input: &str,
+        spinner: &mut Progress,
+        mut on_text: Option<&mut dyn FnMut(&str)>,
+    ) -> Result<Summary, Error> {
+        let region = sdk_config.region().map_or("us-east-1".to_string(), |r| r.to_string());
+        let url = format!(
+            "https://bedrock-agent-runtime.{}.amazonaws.com/agents/{}/agentAliases/{}/sessions/{}/text",
+            region,
+            self.agent_id,
+            self.alias_id,
+            uuid::Uuid::new_v4()
+        );
+        // The trace reports the tools the agent calls and the tokens it uses
+        let body = json!({
+            "inputText": input,
+            "enableTrace": true,
+            "streamingConfigurations": { "streamFinalResponse": on_text.is_some() },
+        });
+
+        spinner.update("Summarizing with the Bedrock agent...");
+        let request = signed_request(
+            settings,
+            sdk_config,
+            "POST",
+            "bedrock",
+            &url,
+            &[("content-type", "application/json")],
+            body.to_string().into_bytes(),
+        )
+        .await?;
+        let mut response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body: Value = response.json().await.unwrap_or(Value::Null);
+            bail!(
+                "\nThe Bedrock agent {} failed: {} {}",
+                self.agent_id,
+                status,
+                body["message"].as_str().unwrap_or_default()
+            );
+        }
+
+        // The answer comes as an AWS event stream of chunk and trace events
+        let mut decoder = MessageFrameDecoder::new();
+        let mut buffer: Vec<u8> = Vec::new();
+        let mut text = String::new();
+        let mut usage = Usage::default();
+        let mut sources: Vec<String> = Vec::new();
+        while let Some(bytes) = response.chunk().await? {
+            buffer.extend_from_slice(&bytes);
+            loop {
+                let mut unread: &[u8] = &buffer;
+                let frame = decoder.decode_frame(&mut unread)?;
+                let consumed = buffer.len() - unread.len();
+                buffer.drain(..consumed);
+                let DecodedFrame::Complete(message) = frame else {
+                    break;
+                };
+
+                let payload: Value = serde_json::from_slice(message.payload()).unwrap_or(Value::Null);
+                if header(&message, ":message-type").as_deref() == Some("exception") {
+                    bail!(
+                        "\nThe Bedrock agent {} failed: {} {}",
+                        self.agent_id,
+                        header(&message, ":exception-type").unwrap_or_default(),
+                        payload["message"].as_str().unwrap_or_default()
+                    );
+                }
+                match header(&message, ":event-type").as_deref() {
+                    Some("chunk") => {
+                        if let Some(encoded) = payload["bytes"].as_str() {
+                            let piece = String::from_utf8(aws_smithy_types::base64::decode(encoded)?)?;
+                            if let Some(on_text) = on_text.as_mut() {
+                                if text.is_empty() {
+                                    spinner.success("Summary ready");
+                                }
+                                on_text(&piece);
+                            }
+                            text.push_str(&piece);
+                        }
+                        for url in cited_sources(&payload) {
+                            if !sources.contains(&url) {
+                                sources.push(url);
+                            }
+                        }
+                    }
+                    Some("trace") => {
+                        let steps = payload["trace"].as_object().into_iter().flat_map(|trace| trace.values());
+                        for step in steps {
+                            trace_step(step, spinner, &mut usage);
+                        }
+                    }
+                    // Action groups set to return control expect the caller to run the tool
+                    Some("returnControl") => bail!(
+                        "\nThe Bedrock agent {} asked the Distill CLI to run one of its actions, which it can't. Set the agent's action groups to call a Lambda function instead.",
+                        self.agent_id
+                    ),
+                    _ => {}
+                }
+            }
+        }
+
+        if text.trim().is_empty() {
+            return Err(anyhow!("The Bedrock agent {} returned an empty answer", self.agent_id));
+        }
+        if !sources.is_empty() {
+            let list: String = sources.iter().map(|url| format!("\n- {}", url)).collect();
+            if let Some(on_text) = on_text.as_mut() {
+                on_text(&format!("\n\nSources:{}", list));
+            }
+            text.push_str(&format!("\n\nSources:{}", list));
+        }
+        Ok(Summary { text, usage })
+    }
+}
+
+// A string header of an event stream message, e.g. ":event-type"
+fn header(message: &Message, name: &str) -> Option<String> {
+    message
+        .headers()
+        .iter()
+        .find(|header| header.name().as_str() == name)
+        .and_then(|header| header.value().as_string().ok())
+        .map(|value| value.as_str().to_string())
+}
+
+// URLs of the documents a chunk of the answer cites
+fn cited_sources(payload: &Value) -> Vec<String> {
+    payload["attribution"]["citations"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|citation| citation["retrievedReferences"].as_array().into_iter().flatten())
+        .filter_map(|reference| {
+            let location = &reference["location"];
+            ["webLocation", "confluenceLocation", "salesforceLocation", "sharePointLocation"]
+                .iter()
+                .find_map(|kind| location[kind]["url"].as_str())
+                .or_else(|| location["s3Location"]["uri"].as_str())
+                .map(str::to_string)
+        })
+        .collect()
+}
+
+// Show the tool or knowledge base the agent is using on the spinner, and add
+// up the tokens of each model call it makes
+fn trace_step(step: &Value, spinner: &mut Progress, usage: &mut Usage) {
+    let invocation = &step["invocationInput"];
+    let action = &invocation["actionGroupInvocationInput"];
+    if let Some(group) = action["actionGroupName"].as_str() {
+        let tool = action["function"].as_str().or_else(|| action["apiPath"].as_str()).unwrap_or_default();
+        debug!(action_group = group, tool, "agent called a tool");
+        spinner.update(&format!("The agent is calling {} {}...", group, tool));
+    }
+    if let Some(knowledge_base) = invocation["knowledgeBaseLookupInput"]["knowledgeBaseId"].as_str() {
+        debug!(knowledge_base, "agent searched a knowledge base");
+        spinner.update(&format!("The agent is searching knowledge base {}...", knowledge_base));
+    }
+    let tokens = &step["modelInvocationOutput"]["metadata"]["usage"];
+    usage.input_tokens += tokens["inputTokens"].as_u64().unwrap_or_default();
+    usage.output_tokens += tokens["outputTokens"].as_u64().unwrap_or_default();
+}
@@ -0,0 +1,223 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Error};
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+const AUDIO_EXTENSIONS: [&str; 9] = ["amr", "flac", "m4a", "mp3", "mp4", "ogg", "opus", "wav", "webm"];
+
+// Whether `path` has the extension of an audio format Transcribe accepts
+pub fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+// Parse a duration such as "90", "45s", "30m", "1h30m", "10:00" or "01:10:00" into seconds
+pub fn parse_duration(value: &str) -> Result<f64, Error> {
+    let value = value.trim();
+
+    if value.contains(':') {
+        let mut seconds = 0.0;
+        for part in value.split(':') {
+            let part: f64 = part
+                .parse()
+                .with_context(|| format!("Invalid timestamp: {}", value))?;
+            seconds = seconds * 60.0 + part;
+        }
+        return Ok(seconds);
+    }
+
+    if let Ok(seconds) = value.parse::<f64>() {
+        return Ok(seconds);
+    }
+
+    let mut seconds = 0.0;
+    let mut number = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() || c == '.' {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            'h' => 3600.0,
+            'm' => 60.0,
+            's' => 1.0,
+            _ => bail!("Invalid duration: {}", value),
+        };
+        let amount: f64 = number
+            .parse()
+            .with_context(|| format!("Invalid duration: {}", value))?;
+        seconds += amount * unit;
+        number.clear();
+    }
+    if !number.is_empty() {
+        bail!("Invalid duration: {} (missing unit after {})", value, number);
+    }
+
+    Ok(seconds)
+}
+
+// Parse a range such as "00:10:00-00:55:00" into start and end seconds
+pub fn parse_range(value: &str) -> Result<(f64, f64), Error> {
+    let (start, end) = value
+        .split_once('-')
+        .with_context(|| format!("Invalid range: {} (expected START-END)", value))?;
+    let (start, end) = (parse_duration(start)?, parse_duration(end)?);
+    if end <= start {
+        bail!("Invalid range: {} (end must be after start)", value);
+    }
+    Ok((start, end))
+}
+
+// Copy the part of `input` between `start` and `start + duration` seconds into a
+// temporary directory using ffmpeg, keeping the original file name so uploads
+// and format detection behave as they would for the full recording. The clip
+// is deleted when the returned TempDir is dropped.
+pub fn trim(input: &Path, start: f64, duration: f64) -> Result<(TempDir, PathBuf), Error> {
+    let dir = tempfile::Builder::new().prefix("distill-").tempdir()?;
+    let output = dir.path().join(input.file_name().context("Input has no file name")?);
+
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(["-y", "-loglevel", "error", "-ss"])
+        .arg(format!("{:.3}", start))
+        .arg("-i")
+        .arg(input)
+        .arg("-t")
+        .arg(format!("{:.3}", duration))
+        .args(["-c", "copy"])
+        .arg(&output);
+
+    let result = command
+        .output()
+        .context("Failed to run ffmpeg; it is required for --first and --range")?;
+    if !result.status.success() {
+        bail!(
+            "\nffmpeg failed to trim {}: {}",
+            input.display(),
+            String::from_utf8_lossy(&result.stderr).trim()
+        );
+    }
+
+    Ok((dir, output))
+}
+
+// Length of a recording in seconds, read with ffprobe
+pub fn duration(input: &Path) -> Result<f64, Error> {
+    let result = Command::new("ffprobe")
+        .args(["-v", "error", "-show_entries", "format=duration", "-of", "default=noprint_wrappers=1:nokey=1"])
+        .arg(input)
+        .output()
+        .context("Failed to run ffprobe; it is required to read the length of a recording")?;
+    if !result.status.success() {
+        bail!(
+            "\nffprobe failed to read {}: {}",
+            input.display(),
+            String::from_utf8_lossy(&result.stderr).trim()
+        );
+    }
+    String::from_utf8_lossy(&result.stdout)
+        .trim()
+        .parse()
+        .with_context(|| format!("ffprobe reported no duration for {}", input.display()))
+}
+
+// Details embedded in a recording, e.g. ID3 tags or a phone's creation time
+#[derive(Default)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    // YYYY-MM-DD
+    pub recorded: Option<String>,
+    pub duration: Option<f64>,
+}
+
+impl Metadata {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "title": self.title,
+            "artist": self.artist,
+            "recorded": self.recorded,
+            "duration": self.duration,
+        })
+    }
+}
+
+// Read a recording's tags with ffprobe. Recordings without tags, or a missing
+// ffprobe, give empty metadata rather than an error.
+pub fn metadata(input: &Path) -> Metadata {
+    let Ok(result) = Command::new("ffprobe")
+        .args(["-v", "error", "-show_entries", "format=duration:format_tags", "-of", "json"])
+        .arg(input)
+        .output()
+    else {
+        return Metadata::default();
+    };
+    let format = match serde_json::from_slice::<Value>(&result.stdout) {
+        Ok(probe) if result.status.success() => probe["format"].clone(),
+        _ => return Metadata::default(),
+    };
+
+    // Tag names differ in case between formats, e.g. "title" and "TITLE"
+    let tag = |names: &[&str]| {
+        format["tags"].as_object().and_then(|tags| {
+            tags.iter()
+                .filter(|(key, _)| names.iter().any(|name| key.eq_ignore_ascii_case(name)))
+                .filter_map(|(_, value)| value.as_str())
+                .map(str::trim)
+                .find(|value| !value.is_empty())
+                .map(str::to_string)
+        })
+    };
+    // Dates come as "2024-03-05", "2024-03-05T10:00:00.000000Z" and the like;
+    // a bare year isn't enough to date a meeting
+    let recorded = ["creation_time", "com.apple.quicktime.creationdate", "date"]
+        .iter()
+        .filter_map(|name| tag(&[name]))
+        .map(|date| date.chars().take(10).collect::<String>())
+        .find(|date| {
+            date.len() == 10
+                && date.char_indices().all(|(i, c)| if i == 4 || i == 7 { c == '-' } else { c.is_ascii_digit() })
+        });
+
+    Metadata {
+        title: tag(&["title"]),
+        artist: tag(&["artist", "album_artist"]),
+        recorded,
+        duration: format["duration"].as_str().and_then(|d| d.parse().ok()),
+    }
+}
+
+// Copy `duration` seconds of `input` (or the rest of it) from `start` into a
+// 16 kHz mono WAV file, the format speaker-embedding and Whisper models
+// expect. Deleted with the returned TempDir.
+pub fn extract_wav(input: &Path, start: f64, duration: Option<f64>) -> Result<(TempDir, PathBuf), Error> {
+    let dir = tempfile::Builder::new().prefix("distill-").tempdir()?;
+    let output = dir.path().join("clip.wav");
+
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(["-y", "-loglevel", "error", "-ss"])
+        .arg(format!("{:.3}", start))
+        .arg("-i")
+        .arg(input);
+    if let Some(duration) = duration {
+        command.arg("-t").arg(format!("{:.3}", duration));
+    }
+    let result = command
+        .args(["-ac", "1", "-ar", "16000", "-c:a", "pcm_s16le"])
+        .arg(&output)
+        .output()
+        .context("Failed to run ffmpeg; it is required for speaker matching and local transcription")?;
+    if !result.status.success() {
+        bail!(
+            "\nffmpeg failed to extract audio from {}: {}",
+            input.display(),
+            String::from_utf8_lossy(&result.stderr).trim()
+        );
+    }
+
+    Ok((dir, output))
+}
@@ -0,0 +1,207 @@
+use std::time::{Duration, SystemTime};
+
+use anyhow::{bail, Context, Error};
+use aws_config::default_provider::region::DefaultRegionChain;
+use aws_config::meta::region::RegionProviderChain;
+use aws_config::retry::RetryConfig;
+use aws_config::timeout::TimeoutConfig;
+use aws_config::{Region, SdkConfig};
+use aws_credential_types::provider::ProvideCredentials;
+use aws_sdk_s3::config::StalledStreamProtectionConfig;
+use aws_sdk_s3::Client;
+use aws_sigv4::http_request::{sign, SignableBody, SignableRequest, SigningSettings};
+use aws_sigv4::sign::v4;
+use config::Config;
+
+use crate::{net, progress, status};
+
+// Load the user's aws config, default region to us-east-1 if none is provided or can be found
+pub async fn load_config(settings: &Config, region: Option<Region>) -> Result<SdkConfig, Error> {
+    let mut config = aws_config::from_env();
+    // A named profile (--aws-profile or aws.profile) supplies the credentials,
+    // and the default region unless the bucket's region is known
+    let profile = settings.get_string("aws.profile").ok().filter(|name| !name.is_empty());
+    if let Some(name) = &profile {
+        config = config.profile_name(name);
+    }
+    match (region, &profile) {
+        (Some(region), _) => config = config.region(region),
+        (None, Some(name)) => {
+            let profile_region = DefaultRegionChain::builder().profile_name(name).build();
+            config = config.region(RegionProviderChain::first_try(profile_region).or_else("us-east-1"))
+        }
+        (None, None) => {
+            config = config.region(RegionProviderChain::default_provider().or_else("us-east-1"))
+        }
+    }
+
+    // Resolves issues with uploading large S3 files
+    // See https://github.com/awslabs/aws-sdk-rust/issues/1146
+    config = config
+        .stalled_stream_protection(
+            StalledStreamProtectionConfig::disabled()
+        );
+
+    // Timeouts and retries for the S3, Transcribe and Bedrock clients. Unset
+    // values keep the SDK defaults (and AWS_MAX_ATTEMPTS / AWS_RETRY_MODE).
+    let connect_timeout = settings.get_int("aws.connect_timeout_secs").ok();
+    let read_timeout = settings.get_int("aws.read_timeout_secs").ok();
+    if connect_timeout.is_some() || read_timeout.is_some() {
+        let mut timeouts = TimeoutConfig::builder();
+        if let Some(seconds) = connect_timeout {
+            timeouts = timeouts.connect_timeout(Duration::from_secs(seconds.max(1) as u64));
+        }
+        if let Some(seconds) = read_timeout {
+            timeouts = timeouts.read_timeout(Duration::from_secs(seconds.max(1) as u64));
+        }
+        config = config.timeout_config(timeouts.build());
+    }
+
+    let retry_mode = settings.get_string("aws.retry_mode").ok();
+    let max_attempts = settings.get_int("aws.max_attempts").ok();
+    if retry_mode.is_some() || max_attempts.is_some() {
+        let mut retry = match retry_mode.as_deref() {
+            None | Some("standard") => RetryConfig::standard(),
+            Some("adaptive") => RetryConfig::adaptive(),
+            Some(other) => bail!("\nUnknown aws.retry_mode '{}'. Use standard or adaptive.", other),
+        };
+        if let Some(attempts) = max_attempts {
+            retry = retry.with_max_attempts(attempts.max(1) as u32);
+        }
+        config = config.retry_config(retry);
+    }
+
+    // Route AWS traffic through network.proxy or HTTP(S)_PROXY
+    if let Some(http_client) = net::aws_http_client(settings)? {
+        config = config.http_client(http_client);
+    }
+
+    Ok(config.load().await)
+}
+
+// Use the configured bucket if it exists, otherwise ask the user to pick one
+pub async fn select_bucket(s3_client: &Client, s3_bucket_name: String, non_interactive: bool) -> Result<String, Error> {
+    let mut bucket_name = String::new();
+
+    // Without a prompt to fall back on, the configured bucket must be usable
+    if non_interactive && s3_bucket_name.is_empty() {
+        bail!("\nNo S3 bucket configured. Set aws.s3_bucket_name in config.toml to run with --non-interactive.");
+    }
+
+    // Keep the cause when AWS can't be reached, so the caller can queue the run
+    let resp = match list_buckets(s3_client).await {
+        Err(err) if net::is_unreachable(&err) => return Err(err.context("Could not reach Amazon S3")),
+        resp => resp,
+    };
+    let resp = &resp;
+
+    if !s3_bucket_name.is_empty() {
+        if resp
+            .as_ref()
+            .ok()
+            .and_then(|buckets| buckets.iter().find(|b| b.as_str() == s3_bucket_name))
+            .is_some()
+        {
+            status!("📦", "S3 bucket name: {}", s3_bucket_name);
+            bucket_name = s3_bucket_name;
+        } else if non_interactive {
+            match resp {
+                Ok(_) => bail!("\nThe S3 bucket '{}' was not found.", s3_bucket_name),
+                Err(err) => bail!("\nError getting bucket list: {}", err),
+            }
+        } else {
+            eprintln!(
+                "Error: The configured S3 bucket '{}' was not found.",
+                s3_bucket_name
+            );
+        }
+    }
+
+    if bucket_name.is_empty() {
+        match resp {
+            Ok(bucket_names) => {
+                let selection = progress::choose("Choose a destination S3 bucket for your audio file", bucket_names)?;
+
+                bucket_name.clone_from(&bucket_names[selection]);
+            }
+            Err(err) => {
+                eprintln!("Error getting bucket list: {}", err);
+                bail!("\nError getting bucket list: {}", err);
+            }
+        };
+    }
+
+    if bucket_name.is_empty() {
+        bail!("\nNo valid S3 bucket found. Please check your AWS configuration.");
+    }
+
+    Ok(bucket_name)
+}
+
+pub async fn list_buckets(client: &Client) -> Result<Vec<String>, Error> {
+    let resp = client.list_buckets().send().await?;
+    let buckets = resp.buckets();
+
+    let bucket_names: Vec<String> = buckets
+        .iter()
+        .map(|bucket| bucket.name().unwrap_or_default().to_string())
+        .collect();
+
+    Ok(bucket_names)
+}
+
+pub async fn bucket_region(client: &Client, bucket_name: &str) -> Result<Region, Error> {
+    let resp = client
+        .get_bucket_location()
+        .bucket(bucket_name)
+        .send()
+        .await?;
+
+    let location_constraint = resp
+        .location_constraint()
+        .context("Bucket has no location_constraint")?;
+
+    if location_constraint.as_str() == "" {
+        Ok(Region::new("us-east-1"))
+    } else {
+        Ok(Region::new(location_constraint.as_str().to_owned()))
+    }
+}
+
+// A request to an AWS API that has no SDK client in the CLI, signed with the
+// run's credentials (Signature Version 4) and sent through network.proxy.
+// `service` is the signing name, e.g. "bedrock", and `headers` are signed too.
+pub async fn signed_request(
+    settings: &Config,
+    sdk_config: &SdkConfig,
+    method: &str,
+    service: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: Vec<u8>,
+) -> Result<reqwest::RequestBuilder, Error> {
+    let region = sdk_config.region().map_or("us-east-1".to_string(), |r| r.to_string());
+    let credentials = sdk_config
+        .credentials_provider()
+        .context("No AWS credentials found")?
+        .provide_credentials()
+        .await?;
+    let identity = credentials.into();
+    let params = v4::SigningParams::builder()
+        .identity(&identity)
+        .region(&region)
+        .name(service)
+        .time(SystemTime::now())
+        .settings(SigningSettings::default())
+        .build()?
+        .into();
+    let request = SignableRequest::new(method, url, headers.iter().copied(), SignableBody::Bytes(&body))?;
+    let (instructions, _) = sign(request, &params)?.into_parts();
+
+    let method = reqwest::Method::from_bytes(method.as_bytes())?;
+    let mut request = net::http_client(settings)?.request(method, url);
+    for (name, value) in headers.iter().copied().chain(instructions.headers()) {
+        request = request.header(name, value);
+    }
+    Ok(request.body(body))
+}
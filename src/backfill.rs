@@ -0,0 +1,500 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Error};
+use aws_sdk_s3::Client;
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::audio::is_audio_file;
+use crate::batch::{self, Batch};
+use crate::output::write_atomic;
+use crate::pipeline::{self, process_recording, Meeting, RunContext, Transcription};
+use crate::progress::{self, Progress};
+use crate::retry::{self, retry};
+use crate::{report, status, summarize};
+
+// How `distill backfill` picks recordings and how much it takes on
+pub struct Options {
+    pub bucket: String,
+    // Keys starting with this are backfilled; empty for the whole bucket
+    pub prefix: String,
+    // Seconds since the Unix epoch; objects last modified before it are left alone
+    pub since: Option<u64>,
+    // Recordings processed at the same time
+    pub concurrency: usize,
+    // No more recordings are started once the backfill's estimated cost reaches this
+    pub max_spend_usd: Option<f64>,
+    // The recordings already processed, from backfill.state_file
+    pub state_file: PathBuf,
+    // List the recordings that would be processed without processing them
+    pub dry_run: bool,
+    // Summarize with a Bedrock batch inference job rather than on demand
+    pub batch: Option<Batch>,
+}
+
+// The recordings backfills have processed, by S3 URI
+#[derive(Default, Serialize, Deserialize)]
+struct State {
+    objects: BTreeMap<String, Record>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Record {
+    at: u64,
+    // The files written for it
+    files: Vec<String>,
+}
+
+// How one recording of the backfill went
+enum Outcome {
+    Processed(Vec<String>),
+    Failed(Error),
+    // A spend limit or monthly quota was reached before it started
+    NotStarted,
+    // Transcribed, and waiting for the batch inference job to summarize it
+    Transcribed(Box<Transcribed>),
+}
+
+// A recording of a --batch backfill between its transcription and its summary
+struct Transcribed {
+    uri: String,
+    file_output: Option<String>,
+    meeting: Meeting,
+    transcription: Transcription,
+    run_report: report::RunReport,
+    spinner: Progress,
+    // What the transcription cost, already counted in the budget
+    cost: f64,
+}
+
+// How the recordings of the backfill went so far
+#[derive(Default)]
+struct Tally {
+    processed: usize,
+    failures: usize,
+    not_started: usize,
+}
+
+impl Tally {
+    fn record(&mut self, options: &Options, state: &mut State, uri: String, outcome: Outcome) -> Result<(), Error> {
+        match outcome {
+            Outcome::Processed(files) => {
+                self.processed += 1;
+                // Saved after each recording, so stopping the backfill loses nothing done
+                state.objects.insert(uri, Record { at: now(), files });
+                save(&options.state_file, state)?;
+            }
+            Outcome::Failed(err) => {
+                eprintln!("Error processing {}: {:#}", uri, err);
+                progress::event("error", json!({ "message": format!("{:#}", err), "file": uri }));
+                self.failures += 1;
+            }
+            Outcome::NotStarted => self.not_started += 1,
+            Outcome::Transcribed(_) => {}
+        }
+        Ok(())
+    }
+}
+
+// What the backfill has spent, and what the recordings still running are
+// expected to. Each recording reserves the average cost of those finished so
+// far when it starts, so `limit` isn't overshot by every run in flight. The
+// first ones start before any cost is known, so the limit can still be passed
+// by up to `concurrency` recordings.
+#[derive(Default)]
+struct Budget {
+    limit: Option<f64>,
+    spent: Cell<f64>,
+    reserved: Cell<f64>,
+    finished: Cell<u32>,
+}
+
+impl Budget {
+    // The amount reserved for a recording about to start, or None when
+    // starting it would go over the limit
+    fn reserve(&self) -> Option<f64> {
+        let estimate = match self.finished.get() {
+            0 => 0.0,
+            finished => self.spent.get() / finished as f64,
+        };
+        if let Some(limit) = self.limit {
+            let committed = self.spent.get() + self.reserved.get();
+            if committed >= limit || committed + estimate > limit {
+                return None;
+            }
+        }
+        self.reserved.set(self.reserved.get() + estimate);
+        Some(estimate)
+    }
+
+    // A recording is done; `cost` is None when it failed
+    fn settle(&self, reserved: f64, cost: Option<f64>) {
+        self.reserved.set((self.reserved.get() - reserved).max(0.0));
+        if let Some(cost) = cost {
+            self.spent.set(self.spent.get() + cost);
+            self.finished.set(self.finished.get() + 1);
+        }
+    }
+
+    // What a settled recording cost afterwards, e.g. its batch summary
+    fn add(&self, cost: f64) {
+        self.spent.set(self.spent.get() + cost);
+    }
+}
+
+// Split s3://bucket/prefix into the bucket and the prefix
+pub fn parse_uri(uri: &str) -> Result<(String, String), Error> {
+    let location = uri.strip_prefix("s3://").unwrap_or_default();
+    let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+    if bucket.is_empty() {
+        bail!("\nInvalid S3 URI {}; expected s3://bucket/prefix", uri);
+    }
+    Ok((bucket.to_string(), prefix.to_string()))
+}
+
+// `distill backfill s3://bucket/prefix --since 2024-01-01`: summarize the
+// recordings already archived in S3. Each audio object under the prefix is
+// transcribed where it is and written to the run's outputs, `concurrency` at a
+// time. Recordings in the state file are skipped, so a backfill that was
+// stopped or had failures can be run again and only does what is left. No more
+// recordings are started once `max_spend_usd` or a monthly quota is reached.
+// With a batch, every recording is transcribed first and their summaries are
+// made by one Bedrock batch inference job, see `summarize_in_batch`.
+pub async fn backfill(ctx: RunContext, options: Options) -> Result<(), Error> {
+    let model_id = match &options.batch {
+        Some(_) => match summarize::batch_model(&ctx.settings)? {
+            Some(model_id) => Some(model_id),
+            None => bail!("\n--batch needs a Bedrock model; agents and OpenAI-compatible APIs have no batch inference"),
+        },
+        None => None,
+    };
+    let mut state = load(&options.state_file)?;
+    let client = Client::new(&ctx.regional_config);
+    let policy = retry::Policy::from_settings(&ctx.settings, "s3")?;
+    let location = format!("s3://{}/{}", options.bucket, options.prefix);
+
+    let mut spinner = Progress::new(&format!("Listing recordings in {}...", location));
+    let mut pending = Vec::new();
+    let mut skipped = 0;
+    let mut token = None;
+    loop {
+        let page = retry(&policy, &mut spinner, "Amazon S3", || {
+            client
+                .list_objects_v2()
+                .bucket(&options.bucket)
+                .prefix(&options.prefix)
+                .set_continuation_token(token.clone())
+                .send()
+        })
+        .await
+        .with_context(|| format!("Error listing {}", location))?;
+        for object in page.contents() {
+            let Some(key) = object.key().filter(|key| is_audio_file(Path::new(key))) else {
+                continue;
+            };
+            let modified = object.last_modified().map_or(0, |modified| modified.secs());
+            if options.since.is_some_and(|since| modified < since as i64) {
+                continue;
+            }
+            let uri = format!("s3://{}/{}", options.bucket, key);
+            if state.objects.contains_key(&uri) {
+                skipped += 1;
+            } else {
+                pending.push((uri, output_stem(key, &options.prefix)));
+            }
+        }
+        token = page.next_continuation_token().map(str::to_string);
+        if token.is_none() {
+            break;
+        }
+    }
+    spinner.success(&format!(
+        "{} recording(s) to process in {}, {} already processed",
+        pending.len(),
+        location,
+        skipped
+    ));
+
+    if options.dry_run {
+        for (uri, _) in &pending {
+            println!("{}", uri);
+        }
+        return Ok(());
+    }
+    if options.max_spend_usd.is_some() && !report::Pricing::from_settings(&ctx.settings).knows_model_price() {
+        eprintln!(
+            "Warning: The model's price isn't known, so --max-spend only counts Amazon Transcribe. Set [pricing] in config.toml."
+        );
+    }
+
+    let total = pending.len();
+    let budget = Budget { limit: options.max_spend_usd, ..Default::default() };
+    // Why no more recordings are started, once one is
+    let stopped = RefCell::new(None::<String>);
+    let ctx = &ctx;
+    let (budget_ref, stopped_ref) = (&budget, &stopped);
+    let batched = options.batch.is_some();
+    let mut runs = stream::iter(pending.into_iter().enumerate())
+        .map(|(index, (uri, stem))| async move {
+            if stopped_ref.borrow().is_some() {
+                return (uri, Outcome::NotStarted);
+            }
+            if let Err(err) = ctx.quota.check() {
+                *stopped_ref.borrow_mut() = Some(format!("{:#}", err).trim().to_string());
+                return (uri, Outcome::NotStarted);
+            }
+            let Some(reserved) = budget_ref.reserve() else {
+                let limit = budget_ref.limit.unwrap_or_default();
+                *stopped_ref.borrow_mut() = Some(format!("the spend limit of ${:.2} would be passed", limit));
+                return (uri, Outcome::NotStarted);
+            };
+
+            status!("🎧", "[{}/{}] {}", index + 1, total, uri);
+            // Outputs are named after the key, like a batch run's are after each file
+            let file_output = ctx
+                .output_types
+                .iter()
+                .find_map(|t| t.extension())
+                .map(|ext| format!("{}.{}", stem, ext));
+            if batched {
+                return transcribe(ctx, budget_ref, reserved, uri, file_output).await;
+            }
+            match process_recording(ctx, &[PathBuf::from(&uri)], file_output.as_deref()).await {
+                Ok(outputs) => {
+                    budget_ref.settle(reserved, Some(outputs.cost_usd.unwrap_or_default()));
+                    (uri, Outcome::Processed(outputs.files))
+                }
+                Err(err) => {
+                    budget_ref.settle(reserved, None);
+                    (uri, Outcome::Failed(err))
+                }
+            }
+        })
+        .buffer_unordered(options.concurrency.max(1));
+
+    let mut tally = Tally::default();
+    let mut transcribed = Vec::new();
+    while let Some((uri, outcome)) = runs.next().await {
+        match outcome {
+            Outcome::Transcribed(recording) => transcribed.push(*recording),
+            outcome => tally.record(&options, &mut state, uri, outcome)?,
+        }
+    }
+    drop(runs);
+
+    if let (Some(batch), Some(model_id)) = (&options.batch, &model_id) {
+        // With their summaries, the outputs are written as any run's are
+        let summarized = summarize_in_batch(ctx, batch, model_id, transcribed).await;
+        let mut finished = stream::iter(summarized)
+            .map(|(mut recording, summary)| async move {
+                let Transcribed { file_output, meeting, transcription, run_report, spinner, cost, .. } = &mut recording;
+                let result =
+                    pipeline::complete(ctx, meeting, transcription, summary, file_output.as_deref(), spinner, run_report)
+                        .await;
+                pipeline::announce(ctx, meeting, run_report, result.as_ref()).await;
+                match result {
+                    Ok(outputs) => {
+                        budget_ref.add(outputs.cost_usd.unwrap_or_default() - *cost);
+                        (recording.uri, Outcome::Processed(outputs.files))
+                    }
+                    Err(err) => (recording.uri, Outcome::Failed(err)),
+                }
+            })
+            .buffer_unordered(options.concurrency.max(1));
+        while let Some((uri, outcome)) = finished.next().await {
+            tally.record(&options, &mut state, uri, outcome)?;
+        }
+    }
+    let Tally { processed, failures, not_started } = tally;
+
+    status!("💵", "Backfill's estimated cost: ${:.2}", budget.spent.get());
+    if let Some(reason) = stopped.borrow().as_ref() {
+        eprintln!(
+            "Warning: {} recording(s) were not started because {}; run the backfill again to process them",
+            not_started, reason
+        );
+    }
+    if failures > 0 {
+        bail!(
+            "\n{} of {} recordings failed to process; run the backfill again to retry them",
+            failures,
+            processed + failures
+        );
+    }
+    Ok(())
+}
+
+// Transcribe a recording of a --batch backfill, and keep it for its summary
+async fn transcribe(
+    ctx: &RunContext,
+    budget: &Budget,
+    reserved: f64,
+    uri: String,
+    file_output: Option<String>,
+) -> (String, Outcome) {
+    let meeting = Meeting::new(ctx, &[PathBuf::from(&uri)]);
+    let mut run_report = report::RunReport::new(report::Pricing::from_settings(&ctx.settings));
+    let mut spinner = Progress::new("Transcribing...");
+    match pipeline::transcribe(ctx, &meeting, &mut spinner, &mut run_report).await {
+        Ok(transcription) => {
+            // The summary's cost is added once the batch inference job is done
+            let cost = run_report.total_cost().unwrap_or_default();
+            budget.settle(reserved, Some(cost));
+            spinner.success("Transcribed; waiting for the other recordings to summarize them together");
+            let recording = Transcribed { uri: uri.clone(), file_output, meeting, transcription, run_report, spinner, cost };
+            (uri, Outcome::Transcribed(Box::new(recording)))
+        }
+        Err(err) => {
+            budget.settle(reserved, None);
+            pipeline::announce(ctx, &meeting, &run_report, Err(&err)).await;
+            (uri, Outcome::Failed(err))
+        }
+    }
+}
+
+// Summarize the transcribed recordings with one batch inference job. A
+// recording without a summary is summarized on demand: transcripts that
+// aren't a single model call (short, oversized or with --samples), the ones
+// the job couldn't summarize, and every one when there are fewer than the
+// job's minimum or the job fails.
+async fn summarize_in_batch(
+    ctx: &RunContext,
+    batch: &Batch,
+    model_id: &str,
+    transcribed: Vec<Transcribed>,
+) -> Vec<(Transcribed, Option<summarize::Summary>)> {
+    let mut records = Vec::new();
+    for (index, recording) in transcribed.iter().enumerate() {
+        if !pipeline::single_call(ctx, &recording.transcription) {
+            continue;
+        }
+        // A prompt that can't be filled in fails the same way on demand
+        if let Ok(prompt) = pipeline::summary_prompt(ctx, &recording.meeting, &recording.transcription) {
+            let text = recording.transcription.text.clone();
+            records.push(batch::Record { id: record_id(index), text, prompt });
+        }
+    }
+
+    let mut summaries = HashMap::new();
+    if records.len() < batch.min_records {
+        if !records.is_empty() {
+            eprintln!(
+                "Warning: {} transcript(s) can be summarized in a batch, fewer than backfill.batch_min_records ({}); summarizing them on demand",
+                records.len(),
+                batch.min_records
+            );
+        }
+    } else {
+        let mut spinner = Progress::new(&format!("Starting a batch inference job for {} summaries...", records.len()));
+        match batch::summarize(&ctx.settings, &ctx.config, batch, model_id, &records, &mut spinner).await {
+            Ok(batch_summaries) => {
+                spinner.success(&format!("Batch inference job finished with {} summaries", batch_summaries.len()));
+                summaries = batch_summaries;
+            }
+            Err(err) => {
+                spinner.stop_and_persist("⚠️", "Batch inference job failed");
+                eprintln!("Warning: {:#}; summarizing the transcripts on demand", err);
+            }
+        }
+    }
+
+    transcribed
+        .into_iter()
+        .enumerate()
+        .map(|(index, recording)| {
+            let summary = match summaries.remove(&record_id(index)) {
+                Some(Ok(summary)) => Some(summary),
+                Some(Err(err)) => {
+                    eprintln!(
+                        "Warning: The batch inference job couldn't summarize {}: {:#}; summarizing it on demand",
+                        recording.uri, err
+                    );
+                    None
+                }
+                None => None,
+            };
+            (recording, summary)
+        })
+        .collect()
+}
+
+// Bedrock takes record IDs of 11 characters
+fn record_id(index: usize) -> String {
+    format!("{:011}", index)
+}
+
+// e.g. "2024/05/standup.m4a" under "2024/" becomes "05-standup"
+fn output_stem(key: &str, prefix: &str) -> String {
+    let relative = key.strip_prefix(prefix).unwrap_or(key).trim_start_matches('/');
+    let stem = Path::new(relative).with_extension("");
+    stem.to_string_lossy().replace('/', "-")
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+fn load(path: &Path) -> Result<State, Error> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text).with_context(|| format!("Error reading {}", path.display())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(State::default()),
+        Err(err) => Err(err).with_context(|| format!("Error reading {}", path.display())),
+    }
+}
+
+fn save(path: &Path, state: &State) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("Error creating {}", dir.display()))?;
+    }
+    write_atomic(path, |out| Ok(serde_json::to_writer_pretty(out, state)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_bucket_and_prefix() {
+        let (bucket, prefix) = parse_uri("s3://recordings/meetings/2024/").unwrap();
+        assert_eq!((bucket.as_str(), prefix.as_str()), ("recordings", "meetings/2024/"));
+        assert_eq!(parse_uri("s3://recordings").unwrap(), ("recordings".into(), String::new()));
+        assert_eq!(parse_uri("s3://recordings/").unwrap(), ("recordings".into(), String::new()));
+        assert!(parse_uri("recordings/meetings").is_err());
+        assert!(parse_uri("s3:///meetings").is_err());
+    }
+
+    #[test]
+    fn names_outputs_after_the_key_below_the_prefix() {
+        assert_eq!(output_stem("2024/05/standup.m4a", "2024/"), "05-standup");
+        assert_eq!(output_stem("meetings/2024/05/standup.m4a", "meetings"), "2024-05-standup");
+        assert_eq!(output_stem("standup.m4a", ""), "standup");
+        assert_eq!(output_stem("a/b.c/retro.v2.mp3", "a/"), "b.c-retro.v2");
+    }
+
+    #[test]
+    fn reserves_the_average_cost_against_the_limit() {
+        let budget = Budget { limit: Some(10.0), ..Default::default() };
+        // Nothing is known about the first recordings
+        assert_eq!(budget.reserve(), Some(0.0));
+        assert_eq!(budget.reserve(), Some(0.0));
+        budget.settle(0.0, Some(4.0));
+        budget.settle(0.0, None);
+        // $4 spent and $4 reserved for the next leaves room for one more
+        assert_eq!(budget.reserve(), Some(4.0));
+        assert_eq!(budget.reserve(), None);
+        budget.settle(4.0, Some(3.0));
+        assert_eq!(budget.spent.get(), 7.0);
+        assert_eq!(budget.reserve(), None);
+    }
+
+    #[test]
+    fn has_no_limit_without_max_spend() {
+        let budget = Budget::default();
+        budget.settle(0.0, Some(1000.0));
+        assert_eq!(budget.reserve(), Some(1000.0));
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, bail, Context, Error};
+use aws_config::SdkConfig;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+use config::Config;
+use serde_json::{json, Value};
+use tracing::{debug, instrument};
+
+use crate::aws::signed_request;
+use crate::progress::Progress;
+use crate::retry::{self, retry};
+use crate::summarize::{self, Summary};
+
+// Bedrock won't start a batch inference job with fewer records than this
+const MIN_RECORDS: usize = 100;
+
+// How `distill backfill --batch` runs its Bedrock batch inference job, from [backfill]
+pub struct Batch {
+    // The service role Bedrock reads the input and writes the output with
+    pub role_arn: String,
+    // s3://bucket/prefix, in Bedrock's region, for the job's input and output
+    pub bucket: String,
+    pub prefix: String,
+    // Fewer summaries than this are made on demand instead
+    pub min_records: usize,
+    // How often the job's status is checked
+    pub poll_interval: Duration,
+}
+
+impl Batch {
+    pub fn from_settings(settings: &Config) -> Result<Self, Error> {
+        let role_arn = settings.get_string("backfill.batch_role_arn").unwrap_or_default();
+        let uri = settings.get_string("backfill.batch_s3_uri").unwrap_or_default();
+        if role_arn.trim().is_empty() || uri.trim().is_empty() {
+            bail!("\n--batch needs backfill.batch_role_arn and backfill.batch_s3_uri in config.toml");
+        }
+        let Some((bucket, prefix)) = uri.trim().strip_prefix("s3://").map(|location| {
+            let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+            (bucket.to_string(), prefix.trim_end_matches('/').to_string())
+        }) else {
+            bail!("\nbackfill.batch_s3_uri must be an S3 URI, e.g. s3://bucket/distill-batch, not '{}'", uri);
+        };
+        Ok(Batch {
+            role_arn: role_arn.trim().to_string(),
+            bucket,
+            prefix,
+            min_records: settings
+                .get_int("backfill.batch_min_records")
+                .map_or(MIN_RECORDS, |n| n.max(1) as usize),
+            poll_interval: Duration::from_secs(
+                settings.get_int("backfill.batch_poll_seconds").map_or(60, |n| n.max(1) as u64),
+            ),
+        })
+    }
+
+    fn key(&self, name: &str) -> String {
+        if self.prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", self.prefix, name)
+        }
+    }
+}
+
+// A record of the job: its ID, and the transcript and prompt to summarize
+pub struct Record {
+    pub id: String,
+    pub text: String,
+    pub prompt: String,
+}
+
+// Summarize each record with one Bedrock batch inference job: the requests are
+// written to S3 as JSONL, the job is started and polled until it ends, and
+// its output is read back. Records the job couldn't summarize map to their
+// error. `config` is Bedrock's, and the bucket must be in its region.
+#[instrument(skip_all, fields(records = records.len()))]
+pub async fn summarize(
+    settings: &Config,
+    config: &SdkConfig,
+    batch: &Batch,
+    model_id: &str,
+    records: &[Record],
+    spinner: &mut Progress,
+) -> Result<HashMap<String, Result<Summary, Error>>, Error> {
+    let client = Client::new(config);
+    let policy = retry::Policy::from_settings(settings, "s3")?;
+    let name = format!("distill-backfill-{}", now());
+
+    let mut input = String::new();
+    for record in records {
+        let line = json!({
+            "recordId": record.id,
+            "modelInput": summarize::batch_input(settings, model_id, &record.text, &record.prompt),
+        });
+        input.push_str(&line.to_string());
+        input.push('\n');
+    }
+    spinner.update("Uploading the batch inference input to S3...");
+    let input_key = batch.key(&format!("{}/input.jsonl", name));
+    let body = input.into_bytes();
+    retry(&policy, spinner, "Amazon S3", || {
+        client
+            .put_object()
+            .bucket(&batch.bucket)
+            .key(&input_key)
+            .body(ByteStream::from(body.clone()))
+            .send()
+    })
+    .await
+    .with_context(|| format!("Error uploading the batch inference input to s3://{}/{}", batch.bucket, input_key))?;
+
+    let output_prefix = batch.key(&format!("{}/output", name));
+    let job_id = create_job(settings, config, batch, &name, model_id, &input_key, &output_prefix).await?;
+    wait_for_job(settings, config, batch, &job_id, records.len(), spinner).await?;
+
+    // Bedrock writes the output under the job's ID, named after the input file
+    spinner.update("Reading the batch inference output...");
+    let output_key = format!("{}/{}/input.jsonl.out", output_prefix, job_id);
+    let output = retry(&policy, spinner, "Amazon S3", || {
+        client.get_object().bucket(&batch.bucket).key(&output_key).send()
+    })
+    .await
+    .with_context(|| format!("Error reading the batch inference output s3://{}/{}", batch.bucket, output_key))?;
+    let output = output
+        .body
+        .collect()
+        .await
+        .with_context(|| format!("Error reading the batch inference output s3://{}/{}", batch.bucket, output_key))?
+        .into_bytes();
+    Ok(parse_output(model_id, &String::from_utf8_lossy(&output)))
+}
+
+// CreateModelInvocationJob, which the runtime SDK doesn't have, so the request
+// is signed here. Returns the job's ID.
+async fn create_job(
+    settings: &Config,
+    config: &SdkConfig,
+    batch: &Batch,
+    name: &str,
+    model_id: &str,
+    input_key: &str,
+    output_prefix: &str,
+) -> Result<String, Error> {
+    let body = json!({
+        "jobName": name,
+        "roleArn": batch.role_arn,
+        "modelId": model_id,
+        "inputDataConfig": {
+            "s3InputDataConfig": {
+                "s3Uri": format!("s3://{}/{}", batch.bucket, input_key),
+                "s3InputFormat": "JSONL",
+            },
+        },
+        "outputDataConfig": {
+            "s3OutputDataConfig": { "s3Uri": format!("s3://{}/{}/", batch.bucket, output_prefix) },
+        },
+    });
+    let response = call(settings, config, "POST", "model-invocation-job", body.to_string().into_bytes())
+        .await
+        .context("Error starting the batch inference job")?;
+    let arn = response["jobArn"]
+        .as_str()
+        .ok_or_else(|| anyhow!("Unexpected response from Bedrock: {}", response))?;
+    debug!(job_arn = arn, "started batch inference job");
+    Ok(job_id(arn).to_string())
+}
+
+// Poll GetModelInvocationJob until the job ends. A partly completed job is
+// fine; the records it left out are summarized on demand.
+async fn wait_for_job(
+    settings: &Config,
+    config: &SdkConfig,
+    batch: &Batch,
+    job_id: &str,
+    records: usize,
+    spinner: &mut Progress,
+) -> Result<(), Error> {
+    loop {
+        let job = call(settings, config, "GET", &format!("model-invocation-job/{}", job_id), Vec::new())
+            .await
+            .with_context(|| format!("Error checking the batch inference job {}", job_id))?;
+        let status = job["status"].as_str().unwrap_or_default();
+        match status {
+            "Completed" | "PartiallyCompleted" => return Ok(()),
+            "Failed" | "Stopped" | "Stopping" | "Expired" => bail!(
+                "\nThe batch inference job {} is {}: {}",
+                job_id,
+                status.to_lowercase(),
+                job["message"].as_str().unwrap_or("no reason given")
+            ),
+            _ => spinner.update(&format!(
+                "Batch inference job {} is {} ({} summaries)...",
+                job_id,
+                status.to_lowercase(),
+                records
+            )),
+        }
+        tokio::time::sleep(batch.poll_interval).await;
+    }
+}
+
+// A signed call to Bedrock's control plane API, returning its JSON answer
+async fn call(settings: &Config, config: &SdkConfig, method: &str, path: &str, body: Vec<u8>) -> Result<Value, Error> {
+    let region = config.region().map_or("us-east-1".to_string(), |r| r.to_string());
+    let url = format!("https://bedrock.{}.amazonaws.com/{}", region, path);
+    let request =
+        signed_request(settings, config, method, "bedrock", &url, &[("content-type", "application/json")], body)
+            .await?;
+    let response = request.send().await?;
+    let status = response.status();
+    let body: Value = response.json().await.unwrap_or(Value::Null);
+    if !status.is_success() {
+        bail!("Bedrock answered {}: {}", status, body["message"].as_str().unwrap_or_default());
+    }
+    Ok(body)
+}
+
+// "arn:aws:bedrock:us-east-1:123456789012:model-invocation-job/abcd1234efgh" is "abcd1234efgh"
+fn job_id(arn: &str) -> &str {
+    arn.rsplit('/').next().unwrap_or(arn)
+}
+
+// The job's output has a line for each record, with its modelOutput or error
+fn parse_output(model_id: &str, output: &str) -> HashMap<String, Result<Summary, Error>> {
+    let mut summaries = HashMap::new();
+    for line in output.lines().filter(|line| !line.trim().is_empty()) {
+        let Ok(record) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        let Some(id) = record["recordId"].as_str() else {
+            continue;
+        };
+        let summary = if record["modelOutput"].is_object() {
+            summarize::batch_output(model_id, &record["modelOutput"])
+        } else {
+            Err(anyhow!(
+                "{}",
+                record["error"]["errorMessage"]
+                    .as_str()
+                    .unwrap_or("The batch inference job returned no summary")
+            ))
+        };
+        summaries.insert(id.to_string(), summary);
+    }
+    summaries
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_summaries_and_errors_from_the_output() {
+        let output = concat!(
+            r#"{"recordId":"00000000001","modelInput":{},"modelOutput":{"content":[{"text":"Summary one"}],"usage":{"input_tokens":10,"output_tokens":3}}}"#,
+            "\n",
+            r#"{"recordId":"00000000002","modelInput":{},"error":{"errorCode":400,"errorMessage":"Too long"}}"#,
+            "\n",
+        );
+        let summaries = parse_output("anthropic.claude-3-haiku-20240307-v1:0", output);
+        let first = summaries["00000000001"].as_ref().unwrap();
+        assert_eq!(first.text, "Summary one");
+        assert_eq!((first.usage.input_tokens, first.usage.output_tokens), (10, 3));
+        assert_eq!(summaries["00000000002"].as_ref().err().unwrap().to_string(), "Too long");
+        assert_eq!(job_id("arn:aws:bedrock:us-east-1:123456789012:model-invocation-job/abcd1234efgh"), "abcd1234efgh");
+    }
+}
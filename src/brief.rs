@@ -0,0 +1,63 @@
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::PathBuf;
+
+use anyhow::{bail, Error};
+use aws_config::SdkConfig;
+use config::Config;
+
+use crate::output::write_atomic;
+use crate::progress::Progress;
+use crate::session::Session;
+use crate::summarize::{self, Usage};
+
+// Kept in the session's directory, next to session.json
+pub const BRIEF: &str = "brief.md";
+
+// Prompt asking the model for a project brief from the summaries of a series
+// of meetings, oldest first
+pub fn brief_prompt() -> &'static str {
+    "Below are the summaries of a series of meetings, oldest first, each under its title and date. \
+Write a living project brief for the series in Markdown with these sections: \"## Current status\" \
+(where the project stands after the latest meeting), \"## Decisions\" (decisions made so far, each with the \
+meeting it was made in), \"## Open decisions\" (questions raised and not yet settled) and \"## Outstanding \
+action items\" (a list of \"- Owner: task (meeting)\", leaving out items a later meeting reported as done). \
+When meetings disagree, go by the later one. Use only what the summaries say, and write in the same language \
+as them.\n\nSummaries:"
+}
+
+// Synthesize the summaries of every run in the session into its brief, and
+// write it to brief.md in the session's directory. brief.prompt replaces the
+// default prompt.
+pub async fn write(
+    settings: &Config,
+    config: &SdkConfig,
+    session: &Session,
+    spinner: &mut Progress,
+) -> Result<(PathBuf, Usage), Error> {
+    let mut runs: Vec<_> = session.runs().iter().collect();
+    if runs.is_empty() {
+        bail!("\nThe session '{}' has no runs to write a brief from.", session.name);
+    }
+    // By meeting date; runs of the same day in the order they were added
+    runs.sort_by(|a, b| a.date.cmp(&b.date).then(a.recorded_at().cmp(&b.recorded_at())));
+    let mut summaries = String::new();
+    for run in runs {
+        let _ = writeln!(summaries, "## {} ({})\n\n{}\n", run.title, run.date, session.summary(run)?);
+    }
+
+    spinner.update(&format!("Writing the brief for '{}'...", session.name));
+    let prompt = settings
+        .get_string("brief.prompt")
+        .ok()
+        .filter(|prompt| !prompt.trim().is_empty())
+        .unwrap_or_else(|| brief_prompt().to_string());
+    let brief = summarize::summarize_text(settings, config, summaries.trim_end(), &prompt, spinner).await?;
+
+    let path = session.dir().join(BRIEF);
+    write_atomic(&path, |file| {
+        write!(file, "# {}\n\n{}\n", session.name, brief.text.trim())?;
+        Ok(())
+    })?;
+    Ok((path, brief.usage))
+}
@@ -0,0 +1,124 @@
+use anyhow::{Context, Error};
+use config::Config;
+use regex::Regex;
+use serde_json::{json, Value};
+
+use crate::transcribe::{format_timestamp, Transcript};
+
+// Characters of context kept on either side of a match in long turns
+const CONTEXT_CHARS: usize = 80;
+
+// A watchlist term found in the transcript
+pub struct Alert {
+    pub term: String,
+    pub speaker: String,
+    pub start_time: f64,
+    // The turn it was said in, shortened around the match when long
+    pub excerpt: String,
+}
+
+// Terms from `compliance.watchlist`, matched case-insensitively as whole words
+pub fn watchlist(settings: &Config) -> Result<Vec<(String, Regex)>, Error> {
+    let terms = settings.get_array("compliance.watchlist").unwrap_or_default();
+    let mut watchlist = Vec::new();
+    for term in terms {
+        let term = term
+            .into_string()
+            .context("compliance.watchlist must be a list of strings")?;
+        let term = term.trim();
+        if term.is_empty() {
+            continue;
+        }
+        // \b only applies next to word characters, so "$100" still matches
+        let boundary = |c: Option<char>| if c.is_some_and(char::is_alphanumeric) { r"\b" } else { "" };
+        let pattern = format!(
+            "(?i){}{}{}",
+            boundary(term.chars().next()),
+            regex::escape(term),
+            boundary(term.chars().last())
+        );
+        watchlist.push((term.to_string(), Regex::new(&pattern)?));
+    }
+    Ok(watchlist)
+}
+
+// Every turn that mentions a watchlist term, once per term and turn
+pub fn scan(transcript: &Transcript, watchlist: &[(String, Regex)]) -> Vec<Alert> {
+    let mut alerts = Vec::new();
+    for segment in &transcript.segments {
+        for (term, pattern) in watchlist {
+            if let Some(found) = pattern.find(&segment.text) {
+                alerts.push(Alert {
+                    term: term.clone(),
+                    speaker: segment.speaker.clone(),
+                    start_time: segment.start_time,
+                    excerpt: excerpt(&segment.text, found.start(), found.end()),
+                });
+            }
+        }
+    }
+    alerts
+}
+
+fn excerpt(text: &str, start: usize, end: usize) -> String {
+    let mut from = start.saturating_sub(CONTEXT_CHARS);
+    while !text.is_char_boundary(from) {
+        from -= 1;
+    }
+    let mut to = (end + CONTEXT_CHARS).min(text.len());
+    while !text.is_char_boundary(to) {
+        to += 1;
+    }
+    format!(
+        "{}{}{}",
+        if from > 0 { "…" } else { "" },
+        text[from..to].trim(),
+        if to < text.len() { "…" } else { "" }
+    )
+}
+
+// One line per alert, e.g. `- "guaranteed returns" (Speaker 2, 00:12:30): "…"`
+pub fn render_alerts(alerts: &[Alert]) -> String {
+    let lines: Vec<String> = alerts
+        .iter()
+        .map(|alert| {
+            format!(
+                "- \"{}\" ({}, {}): \"{}\"",
+                alert.term,
+                alert.speaker,
+                format_timestamp(alert.start_time),
+                alert.excerpt
+            )
+        })
+        .collect();
+    lines.join("\n")
+}
+
+pub fn alerts_json(alerts: &[Alert]) -> Value {
+    alerts
+        .iter()
+        .map(|alert| {
+            json!({
+                "term": alert.term,
+                "speaker": alert.speaker,
+                "start_time": alert.start_time,
+                "excerpt": alert.excerpt,
+            })
+        })
+        .collect()
+}
+
+// Payload for `compliance.alert_webhook`. The "text" field makes it readable
+// as-is when the webhook is a Slack incoming webhook.
+pub fn webhook_payload(recording: &str, alerts: &[Alert]) -> Value {
+    json!({
+        "text": format!(
+            "Compliance alert: {} watchlist mention(s) in {}\n{}",
+            alerts.len(),
+            recording,
+            render_alerts(alerts)
+        ),
+        "recording": recording,
+        "alerts": alerts_json(alerts),
+    })
+}
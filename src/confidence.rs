@@ -0,0 +1,87 @@
+use anyhow::{bail, Error};
+use config::Config;
+use serde_json::{json, Value};
+
+use crate::transcribe::Transcript;
+
+// What to do with a transcript whose average confidence is below
+// transcript.min_confidence
+#[derive(Clone, Copy, PartialEq)]
+pub enum Policy {
+    // Stop with an error before calling the model
+    Fail,
+    // Summarize anyway, with a warning at the top of the summary
+    Warn,
+}
+
+pub struct Threshold {
+    pub min_confidence: f64,
+    pub policy: Policy,
+}
+
+impl Threshold {
+    // None unless transcript.min_confidence (or --min-confidence) is set
+    pub fn from_settings(settings: &Config) -> Result<Option<Self>, Error> {
+        let Ok(min_confidence) = settings.get_float("transcript.min_confidence") else {
+            return Ok(None);
+        };
+        if !(0.0..=1.0).contains(&min_confidence) {
+            bail!("\nThe minimum confidence must be between 0 and 1, e.g. 0.85.");
+        }
+        let policy = match settings.get_string("transcript.low_confidence").as_deref() {
+            Ok("fail") | Err(_) => Policy::Fail,
+            Ok("warn") => Policy::Warn,
+            Ok(other) => bail!("\nUnknown transcript.low_confidence '{}'. Use fail or warn.", other),
+        };
+        Ok(Some(Threshold { min_confidence, policy }))
+    }
+}
+
+// How sure Amazon Transcribe was of the words in a transcript
+pub struct Confidence {
+    pub average: f64,
+    // 10% of the words were recognized with this confidence or less
+    pub p10: f64,
+    pub median: f64,
+    // Words with a score; plain text transcripts have none
+    pub words: usize,
+}
+
+impl Confidence {
+    // None when no word has a score, e.g. for a saved transcript
+    pub fn of(transcript: &Transcript) -> Option<Self> {
+        let mut scores: Vec<f64> = transcript
+            .segments
+            .iter()
+            .flat_map(|segment| &segment.words)
+            .filter_map(|word| word.confidence)
+            .collect();
+        if scores.is_empty() {
+            return None;
+        }
+        scores.sort_by(f64::total_cmp);
+        let percentile = |p: f64| scores[((scores.len() - 1) as f64 * p).round() as usize];
+        Some(Confidence {
+            average: scores.iter().sum::<f64>() / scores.len() as f64,
+            p10: percentile(0.1),
+            median: percentile(0.5),
+            words: scores.len(),
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "average {:.2}, median {:.2}, 10th percentile {:.2} over {} words",
+            self.average, self.median, self.p10, self.words
+        )
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "average": self.average,
+            "median": self.median,
+            "p10": self.p10,
+            "words": self.words,
+        })
+    }
+}
@@ -0,0 +1,166 @@
+use anyhow::{anyhow, bail, Error};
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use crate::html::escape;
+use crate::notion::list_item;
+
+// Where pages are published: a Confluence Cloud site such as
+// "https://example.atlassian.net/wiki", signed in with an API token
+pub struct Site<'a> {
+    pub base_url: &'a str,
+    pub email: &'a str,
+    pub api_token: &'a str,
+    pub space_key: &'a str,
+    // Pages are created under this page when set, otherwise at the top of the space
+    pub parent_id: Option<&'a str>,
+}
+
+pub struct Page<'a> {
+    pub title: &'a str,
+    pub summary: &'a str,
+    pub action_items: &'a str,
+    pub other: &'a str,
+    pub appendices: &'a [(&'a str, String)],
+    pub transcript: &'a str,
+}
+
+// Lines starting with "-", "*" or "1." are grouped into lists, the rest are paragraphs
+fn storage_text(text: &str) -> String {
+    let mut out = String::new();
+    let mut in_list = false;
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        match list_item(line) {
+            Some(item) => {
+                if !in_list {
+                    out.push_str("<ul>");
+                    in_list = true;
+                }
+                out.push_str(&format!("<li>{}</li>", escape(item)));
+            }
+            None => {
+                if in_list {
+                    out.push_str("</ul>");
+                    in_list = false;
+                }
+                out.push_str(&format!("<p>{}</p>", escape(line)));
+            }
+        }
+    }
+    if in_list {
+        out.push_str("</ul>");
+    }
+    out
+}
+
+// The page body in Confluence storage format. Action items become a task
+// list and the transcript goes in a collapsed expand macro.
+fn storage_body(page: &Page) -> String {
+    let mut body = String::new();
+    if !page.summary.is_empty() {
+        body.push_str("<h2>Summary</h2>");
+        body.push_str(&storage_text(page.summary));
+    }
+    if !page.action_items.is_empty() {
+        body.push_str("<h2>Action items</h2><ac:task-list>");
+        for line in page.action_items.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            body.push_str(&format!(
+                "<ac:task><ac:task-status>incomplete</ac:task-status><ac:task-body>{}</ac:task-body></ac:task>",
+                escape(list_item(line).unwrap_or(line))
+            ));
+        }
+        body.push_str("</ac:task-list>");
+    }
+    if !page.other.is_empty() {
+        body.push_str(&storage_text(page.other));
+    }
+    for (title, text) in page.appendices {
+        body.push_str(&format!("<h2>{}</h2>", escape(title)));
+        body.push_str(&storage_text(text));
+    }
+    if !page.transcript.is_empty() {
+        body.push_str(
+            "<ac:structured-macro ac:name=\"expand\"><ac:parameter ac:name=\"title\">Transcript</ac:parameter><ac:rich-text-body>",
+        );
+        for line in page.transcript.lines().filter(|line| !line.trim().is_empty()) {
+            body.push_str(&format!("<p>{}</p>", escape(line)));
+        }
+        body.push_str("</ac:rich-text-body></ac:structured-macro>");
+    }
+    body
+}
+
+async fn send(request: reqwest::RequestBuilder) -> Result<Value, Error> {
+    let response = request.send().await?;
+    let status = response.status();
+    let body: Value = response.json().await.unwrap_or(Value::Null);
+    if !status.is_success() {
+        bail!(
+            "{} {}",
+            status,
+            body["message"].as_str().unwrap_or("(no message)")
+        );
+    }
+    Ok(body)
+}
+
+// Create the page, or update it if the space already has a page with the
+// same title (e.g. a recurring meeting published again). Returns its URL.
+pub async fn publish(client: &Client, site: &Site<'_>, page: &Page<'_>) -> Result<String, Error> {
+    let api = format!("{}/rest/api/content", site.base_url.trim_end_matches('/'));
+    let body = json!({ "storage": { "value": storage_body(page), "representation": "storage" } });
+
+    let existing = send(
+        client
+            .get(&api)
+            .basic_auth(site.email, Some(site.api_token))
+            .query(&[
+                ("spaceKey", site.space_key),
+                ("title", page.title),
+                ("expand", "version"),
+            ]),
+    )
+    .await?;
+
+    let published = match existing["results"].get(0) {
+        Some(current) => {
+            let id = current["id"]
+                .as_str()
+                .ok_or_else(|| anyhow!("Confluence did not return a page ID"))?;
+            let version = current["version"]["number"].as_u64().unwrap_or(1);
+            send(
+                client
+                    .put(format!("{}/{}", api, id))
+                    .basic_auth(site.email, Some(site.api_token))
+                    .json(&json!({
+                        "type": "page",
+                        "title": page.title,
+                        "version": { "number": version + 1 },
+                        "body": body,
+                    })),
+            )
+            .await?
+        }
+        None => {
+            let mut request = json!({
+                "type": "page",
+                "title": page.title,
+                "space": { "key": site.space_key },
+                "body": body,
+            });
+            if let Some(parent_id) = site.parent_id {
+                request["ancestors"] = json!([{ "id": parent_id }]);
+            }
+            send(
+                client
+                    .post(&api)
+                    .basic_auth(site.email, Some(site.api_token))
+                    .json(&request),
+            )
+            .await?
+        }
+    };
+
+    let link = published["_links"]["webui"].as_str().unwrap_or_default();
+    Ok(format!("{}{}", site.base_url.trim_end_matches('/'), link))
+}
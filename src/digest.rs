@@ -0,0 +1,221 @@
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Error};
+use config::Config;
+use serde_json::json;
+
+use crate::output::write_atomic;
+use crate::settings::{self, Sources};
+use crate::{naming, net, session, slack, status};
+
+const WEEK: u64 = 7 * 86_400;
+
+const WEEKDAYS: [&str; 7] = ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+
+// When `distill schedule` delivers the digest: a day of the week and a time
+// of day, in UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Schedule {
+    // 0 for Monday
+    weekday: u64,
+    // Seconds after midnight
+    time: u64,
+}
+
+impl Schedule {
+    // `day` and `at` are --day and --at; digest.day and digest.time are used
+    // when they aren't given, and Monday at 09:00 when neither is
+    pub fn from_settings(settings: &Config, day: Option<&str>, at: Option<&str>) -> Result<Self, Error> {
+        let day = day
+            .map(str::to_string)
+            .or_else(|| settings.get_string("digest.day").ok())
+            .unwrap_or_else(|| "monday".to_string());
+        let at = at
+            .map(str::to_string)
+            .or_else(|| settings.get_string("digest.time").ok())
+            .unwrap_or_else(|| "09:00".to_string());
+        let day = day.trim().to_lowercase();
+        let Some(weekday) = WEEKDAYS.iter().position(|name| *name == day || name[..3] == day) else {
+            bail!("\nThe digest's day must be a day of the week, e.g. monday, not '{}'", day);
+        };
+        let Some(time) = parse_time(&at) else {
+            bail!("\nThe digest's time must be HH:MM in UTC, e.g. 09:00, not '{}'", at);
+        };
+        Ok(Schedule { weekday: weekday as u64, time })
+    }
+
+    // The first delivery after `now`, in seconds since the Unix epoch
+    fn next_after(&self, now: u64) -> u64 {
+        let day = now / 86_400;
+        // The epoch was a Thursday
+        let weekday = (day + 3) % 7;
+        let mut next = (day + (self.weekday + 7 - weekday) % 7) * 86_400 + self.time;
+        if next <= now {
+            next += WEEK;
+        }
+        next
+    }
+}
+
+// "09:00" as seconds after midnight
+fn parse_time(time: &str) -> Option<u64> {
+    let (hours, minutes) = time.trim().split_once(':')?;
+    let (hours, minutes) = (hours.parse::<u64>().ok()?, minutes.parse::<u64>().ok()?);
+    (hours < 24 && minutes < 60).then_some(hours * 3600 + minutes * 60)
+}
+
+// `distill schedule`: deliver the digest of the week's meetings at the
+// scheduled time each week until stopped. With `now`, deliver the digest of
+// the last seven days once and return, e.g. for cron or an EventBridge
+// Scheduler rule that starts the container. The settings are loaded again
+// before each digest, so changes to config.toml apply without a restart.
+pub async fn schedule(sources: &Sources, day: Option<&str>, at: Option<&str>, now: bool) -> Result<(), Error> {
+    if now {
+        let settings = settings::load_settings(sources)?;
+        let to = unix_now();
+        return send(&settings, to.saturating_sub(WEEK), to).await;
+    }
+    loop {
+        let settings = settings::load_settings(sources)?;
+        let schedule = Schedule::from_settings(&settings, day, at)?;
+        let at = schedule.next_after(unix_now());
+        status!(
+            "⏰",
+            "The next digest is delivered on {} at {:02}:{:02} UTC",
+            WEEKDAYS[schedule.weekday as usize],
+            schedule.time / 3600,
+            schedule.time % 3600 / 60
+        );
+        tokio::time::sleep(Duration::from_secs(at.saturating_sub(unix_now()))).await;
+
+        // A digest that can't be delivered is reported, and the next one is still sent
+        let settings = settings::load_settings(sources)?;
+        if let Err(err) = send(&settings, at.saturating_sub(WEEK), at).await {
+            eprintln!("Warning: The digest could not be delivered: {:#}", err);
+        }
+    }
+}
+
+// Build the digest of the runs between `from` and `to` and deliver it
+async fn send(settings: &Config, from: u64, to: u64) -> Result<(), Error> {
+    let Some(digest) = build(settings, from, to)? else {
+        status!(
+            "📭",
+            "No meetings were summarized in sessions between {} and {}",
+            naming::format_date(from),
+            naming::format_date(to)
+        );
+        return Ok(());
+    };
+    deliver(settings, &digest, &naming::format_date(to)).await
+}
+
+// The summaries of every session's runs recorded after `from` and by `to`,
+// as Markdown grouped by session, or None when there are none
+pub fn build(settings: &Config, from: u64, to: u64) -> Result<Option<String>, Error> {
+    let mut text = String::new();
+    let mut meetings = 0;
+    for session in session::all(settings)? {
+        let runs: Vec<_> = session
+            .runs()
+            .iter()
+            .filter(|run| run.recorded_at() > from && run.recorded_at() <= to)
+            .collect();
+        if runs.is_empty() {
+            continue;
+        }
+        let _ = writeln!(text, "\n## {}\n", session.name);
+        for run in runs {
+            meetings += 1;
+            let _ = writeln!(text, "### {} ({})\n", run.title, run.date);
+            if let Some(url) = &run.url {
+                let _ = writeln!(text, "{}\n", url);
+            }
+            match session.summary(run) {
+                Ok(summary) => {
+                    let _ = writeln!(text, "{}\n", summary);
+                }
+                Err(err) => eprintln!("Warning: {:#}", err),
+            }
+        }
+    }
+    if meetings == 0 {
+        return Ok(None);
+    }
+    Ok(Some(format!(
+        "# Meeting digest, {} to {}\n\n{} meeting(s) were summarized.\n{}",
+        naming::format_date(from),
+        naming::format_date(to),
+        meetings,
+        text.trim_end()
+    )))
+}
+
+// Write the digest to digest.directory, and post it to digest.slack_channel
+// with the Slack bot token when that is set
+async fn deliver(settings: &Config, digest: &str, date: &str) -> Result<(), Error> {
+    let dir = settings
+        .get_string("digest.directory")
+        .unwrap_or_else(|_| "~/.distill/digests".to_string());
+    let dir = PathBuf::from(shellexpand::tilde(&dir).to_string());
+    std::fs::create_dir_all(&dir).with_context(|| format!("Error creating {}", dir.display()))?;
+    let path = dir.join(format!("digest-{}.md", date));
+    write_atomic(&path, |file| Ok(file.write_all(digest.as_bytes())?))?;
+    status!("📰", "Digest written to {}", path.display());
+
+    let channel = settings.get_string("digest.slack_channel").unwrap_or_default();
+    if channel.is_empty() {
+        return Ok(());
+    }
+    let bot_token = settings.get_string("slack.bot_token").unwrap_or_default();
+    if bot_token.is_empty() {
+        bail!("\ndigest.slack_channel is set, but slack.bot_token isn't");
+    }
+    // The digest goes in a thread under a short message, as a transcript does
+    let client = net::http_client(settings)?;
+    let (title, body) = digest.split_once('\n').unwrap_or((digest, ""));
+    let message = json!({ "text": title.trim_start_matches("# ") });
+    let ts = slack::post_payload(&client, &bot_token, &channel, &message, None)
+        .await
+        .context("Error posting the digest to Slack")?;
+    slack::post_thread_text(&client, &bot_token, &channel, &ts, body.trim())
+        .await
+        .context("Error posting the digest to Slack")?;
+    status!("📰", "Digest posted to Slack");
+    Ok(())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(day: &str, at: &str) -> Schedule {
+        Schedule::from_settings(&Config::default(), Some(day), Some(at)).unwrap()
+    }
+
+    #[test]
+    fn finds_the_next_delivery() {
+        // Thursday 2024-05-16 12:00 UTC
+        let now = naming::parse_date("2024-05-16").unwrap() + 12 * 3600;
+        let monday = naming::parse_date("2024-05-20").unwrap();
+        assert_eq!(schedule("monday", "09:00").next_after(now), monday + 9 * 3600);
+        // Later the same day, and a week on when the time has passed
+        assert_eq!(schedule("thu", "13:30").next_after(now), now + 5400);
+        assert_eq!(schedule("Thursday", "12:00").next_after(now), now + WEEK);
+    }
+
+    #[test]
+    fn rejects_unknown_days_and_times() {
+        let settings = Config::default();
+        assert!(Schedule::from_settings(&settings, Some("someday"), None).is_err());
+        assert!(Schedule::from_settings(&settings, None, Some("25:00")).is_err());
+        assert!(Schedule::from_settings(&settings, None, Some("9am")).is_err());
+    }
+}
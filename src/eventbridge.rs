@@ -0,0 +1,89 @@
+use anyhow::{bail, Error};
+use aws_config::SdkConfig;
+use config::Config;
+use serde_json::{json, Value};
+use tracing::{debug, instrument};
+
+use crate::aws::signed_request;
+
+// An EventBridge bus that gets an event when each recording finishes or
+// fails, from eventbridge.bus_name, so other automations (a Step Functions
+// workflow, a Lambda filing the notes, an alarm) can react to runs without
+// polling the CLI. Events have "distill-cli" as their source unless
+// eventbridge.source says otherwise.
+pub struct Bus {
+    pub name: String,
+    pub source: String,
+}
+
+impl Bus {
+    // None unless eventbridge.bus_name is set
+    pub fn from_settings(settings: &Config) -> Option<Self> {
+        let name = settings.get_string("eventbridge.bus_name").ok().filter(|name| !name.trim().is_empty())?;
+        let source = settings
+            .get_string("eventbridge.source")
+            .ok()
+            .filter(|source| !source.trim().is_empty())
+            .unwrap_or_else(|| "distill-cli".to_string());
+        Some(Bus {
+            name: name.trim().to_string(),
+            source: source.trim().to_string(),
+        })
+    }
+
+    // Put one event on the bus with PutEvents. `detail_type` is e.g.
+    // "Distill Run Succeeded", which rules can match on.
+    #[instrument(name = "put_event", skip_all, fields(bus = %self.name, detail_type = %detail_type))]
+    pub async fn put(
+        &self,
+        settings: &Config,
+        sdk_config: &SdkConfig,
+        detail_type: &str,
+        detail: &Value,
+    ) -> Result<(), Error> {
+        let region = sdk_config.region().map_or("us-east-1".to_string(), |r| r.to_string());
+        let body = json!({
+            "Entries": [{
+                "EventBusName": self.name,
+                "Source": self.source,
+                "DetailType": detail_type,
+                "Detail": detail.to_string(),
+            }],
+        });
+        let request = signed_request(
+            settings,
+            sdk_config,
+            "POST",
+            "events",
+            &format!("https://events.{}.amazonaws.com/", region),
+            &[
+                ("content-type", "application/x-amz-json-1.1"),
+                ("x-amz-target", "AWSEvents.PutEvents"),
+            ],
+            body.to_string().into_bytes(),
+        )
+        .await?;
+
+        let response = request.send().await?;
+        let status = response.status();
+        let body: Value = response.json().await.unwrap_or(Value::Null);
+        if !status.is_success() {
+            bail!(
+                "EventBridge rejected the event: {} {}",
+                status,
+                body["message"].as_str().or_else(|| body["Message"].as_str()).unwrap_or_default()
+            );
+        }
+        // A bus that doesn't exist or a detail that's too large fails the entry, not the call
+        if body["FailedEntryCount"].as_u64().unwrap_or_default() > 0 {
+            let entry = &body["Entries"][0];
+            bail!(
+                "EventBridge rejected the event: {} {}",
+                entry["ErrorCode"].as_str().unwrap_or_default(),
+                entry["ErrorMessage"].as_str().unwrap_or_default()
+            );
+        }
+        debug!(event_id = body["Entries"][0]["EventId"].as_str(), "event sent");
+        Ok(())
+    }
+}
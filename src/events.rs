@@ -0,0 +1,120 @@
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{bail, Context, Error};
+use config::Config;
+use serde::Deserialize;
+
+use crate::progress::Progress;
+use crate::transcribe::Transcript;
+
+// Silences at least this long are marked, e.g. "[pause 4s]"
+const DEFAULT_MIN_PAUSE: f64 = 3.0;
+
+// A non-speech sound, e.g. laughter or applause, in seconds from the start
+// of the recording
+#[derive(Deserialize)]
+pub struct Event {
+    pub label: String,
+    #[serde(rename = "start")]
+    pub start_time: f64,
+}
+
+// Run `events.detector_command` on a recording. Amazon Transcribe only reports
+// speech, so sounds such as laughter come from a local audio-event model: the
+// command is given the recording in place of {input} and must print a JSON
+// array of {"label": "laughter", "start": 12.3} objects. `offset` is where the
+// transcript starts in the recording, for clipped runs.
+pub fn detect(
+    settings: &Config,
+    audio_file: &Path,
+    offset: f64,
+    spinner: &mut Progress,
+) -> Result<Vec<Event>, Error> {
+    let command: Vec<String> = settings
+        .get_array("events.detector_command")
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|part| part.into_string().ok())
+        .collect();
+    if command.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    spinner.update("Detecting audio events...");
+    let args = command[1..]
+        .iter()
+        .map(|arg| arg.replace("{input}", &audio_file.to_string_lossy()));
+    let result = Command::new(&command[0])
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to run events.detector_command '{}'", command[0]))?;
+    if !result.status.success() {
+        bail!(
+            "\nevents.detector_command failed: {}",
+            String::from_utf8_lossy(&result.stderr).trim()
+        );
+    }
+    let mut events: Vec<Event> = serde_json::from_slice(&result.stdout)
+        .context("events.detector_command must print a JSON array of {\"label\", \"start\"} objects")?;
+
+    events.retain(|event| event.start_time >= offset && !event.label.trim().is_empty());
+    for event in &mut events {
+        event.start_time -= offset;
+        event.label = event.label.trim().to_lowercase();
+    }
+    events.sort_by(|a, b| a.start_time.total_cmp(&b.start_time));
+    Ok(events)
+}
+
+pub fn min_pause(settings: &Config) -> f64 {
+    settings
+        .get_float("events.min_pause_secs")
+        .unwrap_or(DEFAULT_MIN_PAUSE)
+}
+
+// Mark long pauses and detected events inline, e.g.
+// "That's the plan. [pause 4s] [laughter] Any questions?". Events go before
+// the first word that starts after them. Transcripts without word timings
+// are marked at the start of each turn instead.
+pub fn annotate(transcript: &mut Transcript, events: &[Event], min_pause: f64) {
+    let mut events = events.iter().peekable();
+    let mut previous_end: Option<f64> = None;
+
+    let mut marks_before = |start_time: f64, previous_end: Option<f64>| {
+        let mut marks = Vec::new();
+        if let Some(gap) = previous_end.map(|end| start_time - end).filter(|gap| *gap >= min_pause) {
+            marks.push(format!("[pause {:.0}s]", gap));
+        }
+        while let Some(event) = events.next_if(|event| event.start_time < start_time) {
+            marks.push(format!("[{}]", event.label));
+        }
+        marks
+    };
+
+    for segment in &mut transcript.segments {
+        if segment.words.is_empty() {
+            let marks = marks_before(segment.start_time, previous_end);
+            if !marks.is_empty() {
+                segment.text = format!("{} {}", marks.join(" "), segment.text);
+            }
+            previous_end = Some(segment.end_time);
+            continue;
+        }
+
+        let mut text: Vec<String> = Vec::new();
+        for word in &segment.words {
+            text.extend(marks_before(word.start_time, previous_end));
+            text.push(word.text.clone());
+            previous_end = Some(word.end_time);
+        }
+        segment.text = text.join(" ");
+    }
+
+    // Anything after the last word, e.g. applause at the end of a talk
+    let trailing = marks_before(f64::INFINITY, None);
+    if let Some(segment) = transcript.segments.last_mut().filter(|_| !trailing.is_empty()) {
+        segment.text.push(' ');
+        segment.text.push_str(&trailing.join(" "));
+    }
+}
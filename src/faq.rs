@@ -0,0 +1,39 @@
+use serde_json::Value;
+
+// Prompt asking the model for the question and answer pairs in a timestamped transcript
+pub fn faq_prompt() -> &'static str {
+    "Below is a transcript of a webinar, AMA or similar session. Each line starts with a timestamp \
+in [HH:MM:SS] format and the speaker. Find every question asked by a participant and the answer given \
+to it. Rewrite each question so it stands on its own, and write the answer as a clear, self-contained \
+paragraph that keeps the facts the speaker gave; merge questions that were asked more than once. Skip \
+questions that were not answered. Reply with only a JSON array of objects of the form \
+{\"question\": \"...\", \"answer\": \"...\", \"asked_by\": \"Speaker 2\", \"timestamp\": \"00:12:30\"}, \
+in the order the questions were asked and in the same language as the transcript.\n\nTranscript:"
+}
+
+// Render the model's parsed JSON reply as FAQ entries, e.g.
+// "Q: Is there a free tier? (Speaker 2, 00:12:30)" followed by "A: ..."
+pub fn render_faq(pairs: &[Value]) -> String {
+    let entries: Vec<String> = pairs
+        .iter()
+        .filter_map(|pair| {
+            let question = pair["question"].as_str()?.trim();
+            let answer = pair["answer"].as_str()?.trim();
+            if question.is_empty() || answer.is_empty() {
+                return None;
+            }
+            let details: Vec<&str> = [pair["asked_by"].as_str(), pair["timestamp"].as_str()]
+                .into_iter()
+                .flatten()
+                .filter(|detail| !detail.trim().is_empty())
+                .collect();
+            Some(if details.is_empty() {
+                format!("Q: {}\nA: {}", question, answer)
+            } else {
+                format!("Q: {} ({})\nA: {}", question, details.join(", "), answer)
+            })
+        })
+        .collect();
+
+    entries.join("\n\n")
+}
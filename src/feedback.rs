@@ -0,0 +1,177 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Error};
+use config::Config;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::output::write_atomic;
+use crate::pipeline::{Meeting, RunContext};
+use crate::status;
+
+// Ratings at or below this are listed with their notes in the report
+const LOW_RATING: u8 = 2;
+
+// The summarized runs and the feedback given on them, by run ID
+#[derive(Default, Serialize, Deserialize)]
+struct Log {
+    runs: BTreeMap<String, Run>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Run {
+    at: u64,
+    title: String,
+    date: String,
+    // The Bedrock model, Bedrock Agent or OpenAI-compatible model that wrote the summary
+    model: String,
+    // The mode or "custom", and a hash of the prompt template, e.g. "meeting@3f2a9c1e"
+    prompt: String,
+    #[serde(default)]
+    rating: Option<u8>,
+    #[serde(default)]
+    note: Option<String>,
+}
+
+// A new ID for a run, short enough to type back into `distill-cli feedback`
+pub fn run_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()[..8].to_string()
+}
+
+// Remember which model and prompt summarized the run, so feedback given on it
+// later can be compared across them
+pub fn record_run(ctx: &RunContext, id: &str, meeting: &Meeting) -> Result<(), Error> {
+    let path = log_path(&ctx.settings);
+    let mut log = load(&path)?;
+    log.runs.insert(
+        id.to_string(),
+        Run {
+            at: now(),
+            title: meeting.title.clone(),
+            date: meeting.date.clone(),
+            model: model(&ctx.settings),
+            prompt: prompt_version(ctx),
+            rating: None,
+            note: None,
+        },
+    );
+    save(&path, &log)
+}
+
+// `distill-cli feedback <run-id> --rating 4 --note "..."`: rate a run's summary from 1 to 5
+pub fn rate(settings: &Config, id: &str, rating: u8, note: Option<&str>) -> Result<(), Error> {
+    let path = log_path(settings);
+    let mut log = load(&path)?;
+    let Some(run) = log.runs.get_mut(id) else {
+        bail!("\nNo run with ID '{}'. Each summarized run prints its ID when it finishes.", id);
+    };
+    run.rating = Some(rating);
+    if let Some(note) = note {
+        run.note = Some(note.to_string());
+    }
+    status!("⭐", "Rated '{}' {}/5 ({}, prompt {})", run.title, rating, run.model, run.prompt);
+    save(&path, &log)
+}
+
+// `distill-cli feedback-report`: the average rating of each model and prompt
+// version, and the notes on the runs rated lowest, to guide tuning
+pub fn report(settings: &Config) -> Result<(), Error> {
+    let log = load(&log_path(settings))?;
+    let rated: Vec<&Run> = log.runs.values().filter(|run| run.rating.is_some()).collect();
+    if rated.is_empty() {
+        eprintln!("No feedback yet. Rate a run with `distill-cli feedback <run-id> --rating 1-5`.");
+        return Ok(());
+    }
+
+    // (runs, rated, sum of ratings) by model and prompt
+    let mut groups: BTreeMap<(&str, &str), (usize, usize, u32)> = BTreeMap::new();
+    for run in log.runs.values() {
+        let group = groups.entry((&run.model, &run.prompt)).or_default();
+        group.0 += 1;
+        if let Some(rating) = run.rating {
+            group.1 += 1;
+            group.2 += rating as u32;
+        }
+    }
+    let mut rows: Vec<_> = groups.into_iter().filter(|(_, (_, rated, _))| *rated > 0).collect();
+    rows.sort_by(|(_, a), (_, b)| (b.2 as f64 / b.1 as f64).total_cmp(&(a.2 as f64 / a.1 as f64)));
+
+    println!("{:<44} {:<20} {:>5} {:>6} {:>7}", "Model", "Prompt", "Runs", "Rated", "Average");
+    for ((model, prompt), (runs, rated, sum)) in rows {
+        println!("{:<44} {:<20} {:>5} {:>6} {:>7.1}", model, prompt, runs, rated, sum as f64 / rated as f64);
+    }
+
+    let mut low: Vec<&Run> = rated
+        .into_iter()
+        .filter(|run| run.rating <= Some(LOW_RATING) && run.note.is_some())
+        .collect();
+    if !low.is_empty() {
+        low.sort_by_key(|run| std::cmp::Reverse(run.at));
+        println!();
+        println!("Notes on runs rated {} or lower:", LOW_RATING);
+        for run in low {
+            println!(
+                "  {}/5  {} ({}), {}, prompt {}: {}",
+                run.rating.unwrap_or_default(),
+                run.title,
+                run.date,
+                run.model,
+                run.prompt,
+                run.note.as_deref().unwrap_or_default()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn model(settings: &Config) -> String {
+    let setting = |key: &str| settings.get_string(key).ok().filter(|value| !value.is_empty());
+    match setting("agent.agent_id") {
+        Some(agent) => format!("agent {}", agent),
+        None => setting("model.model_id").unwrap_or_default(),
+    }
+}
+
+// The template before the meeting's details are filled in, so every run with
+// the same prompt has the same version
+fn prompt_version(ctx: &RunContext) -> String {
+    let (name, template) = match &ctx.prompt_template {
+        Some(template) => ("custom".to_string(), template.clone()),
+        None => (
+            format!("{:?}", ctx.mode).to_lowercase(),
+            ctx.settings
+                .get_string(ctx.mode.prompt_key())
+                .unwrap_or_else(|_| ctx.mode.default_prompt().to_string()),
+        ),
+    };
+    let hash = format!("{:x}", Sha256::digest(template.as_bytes()));
+    format!("{}@{}", name, &hash[..8])
+}
+
+fn log_path(settings: &Config) -> PathBuf {
+    let path = settings
+        .get_string("feedback.log")
+        .unwrap_or_else(|_| "~/.distill/runs.json".to_string());
+    PathBuf::from(shellexpand::tilde(&path).to_string())
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+fn load(path: &Path) -> Result<Log, Error> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text).with_context(|| format!("Error reading {}", path.display())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Log::default()),
+        Err(err) => Err(err).with_context(|| format!("Error reading {}", path.display())),
+    }
+}
+
+fn save(path: &Path, log: &Log) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("Error creating {}", dir.display()))?;
+    }
+    write_atomic(path, |out| Ok(serde_json::to_writer_pretty(out, log)?))
+}
@@ -0,0 +1,135 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Error};
+use aws_config::SdkConfig;
+use aws_sdk_s3::Client;
+use aws_smithy_types::error::display::DisplayErrorContext;
+use config::Config;
+use futures::future::{join_all, BoxFuture};
+use serde::Serialize;
+
+use crate::aws::signed_request;
+use crate::net;
+use crate::openai;
+use crate::pipeline::RunContext;
+use crate::summarize::Summarizer;
+
+// How long one check may take before the service counts as unreachable
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Whether one service could be reached with the run's credentials
+#[derive(Clone, Serialize)]
+pub struct Check {
+    pub service: &'static str,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// The services a run depends on. They are taken from the run context, which
+// can't be sent between threads, so they can be checked from any of them.
+pub struct Dependencies {
+    settings: Config,
+    // The bucket recordings are uploaded to and a client in its region; None
+    // when the transcriber doesn't read from S3
+    s3: Option<(Client, String)>,
+    transcribe: Option<SdkConfig>,
+    // Bedrock's region, for a Bedrock model or agent
+    bedrock: Option<SdkConfig>,
+    openai: Option<openai::Chat>,
+}
+
+impl Dependencies {
+    pub fn from_context(ctx: &RunContext) -> Result<Self, Error> {
+        let reads_from_s3 = ctx.transcriber.reads_from_s3();
+        let openai = match Summarizer::from_settings(&ctx.settings)? {
+            Summarizer::Openai => Some(openai::Chat::from_settings(&ctx.settings)?),
+            Summarizer::Bedrock => None,
+        };
+        Ok(Dependencies {
+            settings: ctx.settings.clone(),
+            s3: (reads_from_s3 && !ctx.bucket_name.is_empty())
+                .then(|| (Client::new(&ctx.regional_config), ctx.bucket_name.clone())),
+            transcribe: reads_from_s3.then(|| ctx.transcribe_config.clone()),
+            bedrock: (openai.is_none() && !ctx.skip_summary).then(|| ctx.config.clone()),
+            openai: openai.filter(|_| !ctx.skip_summary),
+        })
+    }
+
+    // Check every service at once, each with a cheap read-only call: HeadBucket
+    // on the bucket, ListTranscriptionJobs, Bedrock's ListFoundationModels and
+    // the OpenAI-compatible API's model list
+    pub async fn check(&self) -> Vec<Check> {
+        let mut checks: Vec<(&'static str, BoxFuture<'_, Result<(), Error>>)> = Vec::new();
+        if let Some((client, bucket)) = &self.s3 {
+            checks.push(("s3", Box::pin(s3(client, bucket))));
+        }
+        if let Some(config) = &self.transcribe {
+            checks.push(("transcribe", Box::pin(transcribe(config))));
+        }
+        if let Some(config) = &self.bedrock {
+            checks.push(("bedrock", Box::pin(bedrock(&self.settings, config))));
+        }
+        if let Some(chat) = &self.openai {
+            checks.push(("openai", Box::pin(openai_models(&self.settings, chat))));
+        }
+
+        join_all(checks.into_iter().map(|(service, check)| async move {
+            let result = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+                Ok(result) => result,
+                Err(_) => Err(Error::msg(format!("No answer within {}s", CHECK_TIMEOUT.as_secs()))),
+            };
+            Check {
+                service,
+                ok: result.is_ok(),
+                error: result.err().map(|err| format!("{:#}", err).trim().to_string()),
+            }
+        }))
+        .await
+    }
+}
+
+// SDK errors name the cause only in their sources, e.g. "service error: AccessDenied"
+async fn s3(client: &Client, bucket: &str) -> Result<(), Error> {
+    match client.head_bucket().bucket(bucket).send().await {
+        Ok(_) => Ok(()),
+        Err(err) => Err(anyhow!("{}: {}", bucket, DisplayErrorContext(err))),
+    }
+}
+
+async fn transcribe(config: &SdkConfig) -> Result<(), Error> {
+    let client = aws_sdk_transcribe::Client::new(config);
+    match client.list_transcription_jobs().max_results(1).send().await {
+        Ok(_) => Ok(()),
+        Err(err) => Err(anyhow!("{}", DisplayErrorContext(err))),
+    }
+}
+
+// The runtime SDK can't list models, so the request is signed here, as for
+// `distill init`
+async fn bedrock(settings: &Config, config: &SdkConfig) -> Result<(), Error> {
+    let region = config.region().map_or("us-east-1".to_string(), |r| r.to_string());
+    let url = format!("https://bedrock.{}.amazonaws.com/foundation-models?byOutputModality=TEXT", region);
+    let response = signed_request(settings, config, "GET", "bedrock", &url, &[], Vec::new())
+        .await?
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        bail!("Bedrock answered {}", response.status());
+    }
+    Ok(())
+}
+
+// GET /models, which OpenAI, Ollama and vLLM all answer
+async fn openai_models(settings: &Config, chat: &openai::Chat) -> Result<(), Error> {
+    let base = chat.endpoint.trim_end_matches('/').trim_end_matches("/chat/completions");
+    let mut request = net::http_client(settings)?.get(format!("{}/models", base));
+    if let Some(api_key) = &chat.api_key {
+        request = request.bearer_auth(api_key);
+    }
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        bail!("{} answered {}", chat.endpoint, response.status());
+    }
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::transcribe::{format_timestamp, Transcript};
+
+// Speakers are colored in order of first appearance
+const SPEAKER_COLORS: [&str; 8] = [
+    "#1f77b4", "#d62728", "#2ca02c", "#9467bd", "#ff7f0e", "#17becf", "#8c564b", "#e377c2",
+];
+
+const STYLE: &str = "body { font-family: -apple-system, 'Segoe UI', Helvetica, Arial, sans-serif; \
+max-width: 50em; margin: 2em auto; padding: 0 1em; line-height: 1.5; color: #222; }
+h1 { font-size: 1.4em; }
+h2 { font-size: 1.15em; margin-top: 1.5em; }
+summary { cursor: pointer; font-weight: bold; font-size: 1.15em; margin-top: 1.5em; }
+.turn { margin: 0.4em 0; }
+.time { color: #888; font-size: 0.85em; margin-right: 0.5em; }
+.speaker { font-weight: bold; margin-right: 0.3em; }
+pre { white-space: pre-wrap; font-family: inherit; }";
+
+// A self-contained HTML report: summary, any appendices, and the transcript
+// in a collapsible section with each speaker in their own color. Each turn is
+// written as it is rendered rather than building the whole page first.
+pub fn write<W: Write>(
+    out: &mut W,
+    title: &str,
+    summary_title: &str,
+    summary: &str,
+    transcript: Option<&Transcript>,
+    appendices: &[(&str, String)],
+) -> io::Result<()> {
+    write!(
+        out,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n",
+        escape(title),
+        STYLE
+    )?;
+    write!(
+        out,
+        "<h1>{}</h1>\n<h2>{}</h2>\n<pre>{}</pre>\n",
+        escape(title),
+        escape(summary_title),
+        escape(summary)
+    )?;
+
+    for (heading, text) in appendices {
+        write!(out, "<h2>{}</h2>\n<pre>{}</pre>\n", escape(heading), escape(text))?;
+    }
+
+    if let Some(transcript) = transcript {
+        let mut colors: HashMap<&str, &str> = HashMap::new();
+        writeln!(out, "<details>\n<summary>Transcription</summary>")?;
+        for segment in &transcript.segments {
+            write!(out, "<p class=\"turn\">")?;
+            if segment.end_time > 0.0 {
+                write!(
+                    out,
+                    "<span class=\"time\">{}</span>",
+                    format_timestamp(segment.start_time)
+                )?;
+            }
+            if !segment.speaker.is_empty() {
+                let next = SPEAKER_COLORS[colors.len() % SPEAKER_COLORS.len()];
+                let color = *colors.entry(segment.speaker.as_str()).or_insert(next);
+                write!(
+                    out,
+                    "<span class=\"speaker\" style=\"color: {}\">{}:</span>",
+                    color,
+                    escape(&segment.speaker)
+                )?;
+            }
+            writeln!(out, "{}</p>", escape(&segment.text))?;
+        }
+        writeln!(out, "</details>")?;
+    }
+
+    write!(out, "</body>\n</html>\n")
+}
+
+// Also used for Confluence storage format, which is XHTML
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,155 @@
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{bail, Error};
+use aws_config::SdkConfig;
+use aws_sdk_s3::Client;
+use config::Config;
+use dialoguer::theme::SimpleTheme;
+use dialoguer::Input;
+use serde_json::Value;
+
+use distill_core::aws::{list_buckets, load_config, signed_request};
+use distill_core::progress::choose;
+use distill_core::{output, progress};
+
+// The documented config.toml shipped with the CLI, filled in by the wizard
+const TEMPLATE: &str = include_str!("../config.toml");
+
+// Offered when Bedrock's model list can't be read, e.g. without the
+// bedrock:ListFoundationModels permission
+const COMMON_MODELS: [&str; 6] = [
+    "anthropic.claude-3-5-haiku-20241022-v1:0",
+    "anthropic.claude-3-5-sonnet-20240620-v1:0",
+    "anthropic.claude-3-sonnet-20240229-v1:0",
+    "amazon.nova-micro-v1:0",
+    "amazon.nova-lite-v1:0",
+    "amazon.nova-pro-v1:0",
+];
+
+const OUTPUT_TYPES: [&str; 8] = ["terminal", "markdown", "word", "text", "pdf", "html", "json", "slack"];
+
+// `distill init`: walk through the settings new users most often get wrong
+// and write config.toml in the current directory
+pub async fn run(settings: &Config) -> Result<(), Error> {
+    let path = Path::new("config.toml");
+    if path.exists() && !confirm("config.toml already exists here. Replace it?")? {
+        return Ok(());
+    }
+    let mut text = TEMPLATE.to_string();
+    let sdk_config = load_config(settings, None).await?;
+
+    // Bucket
+    eprintln!("{}Looking up your S3 buckets...", progress::icon("🪣"));
+    match list_buckets(&Client::new(&sdk_config)).await {
+        Ok(buckets) if !buckets.is_empty() => {
+            let mut items = buckets.clone();
+            items.push("Ask me each run".to_string());
+            let index = choose("S3 bucket for uploads", &items)?;
+            if let Some(bucket) = buckets.get(index) {
+                set(&mut text, "aws", "s3_bucket_name", &quoted(bucket))?;
+            }
+        }
+        Ok(_) => eprintln!("No S3 buckets found; you'll be asked for one on each run until you set aws.s3_bucket_name."),
+        Err(err) => eprintln!("Warning: Could not list S3 buckets ({:#}); you'll be asked for one on each run.", err),
+    }
+
+    // Model
+    eprintln!("{}Looking up Bedrock models...", progress::icon("🧠"));
+    let models = match list_models(settings, &sdk_config).await {
+        Ok(models) if !models.is_empty() => models,
+        Ok(_) => COMMON_MODELS.iter().map(|m| m.to_string()).collect(),
+        Err(err) => {
+            eprintln!("Warning: Could not list Bedrock models ({:#}); showing common ones.", err);
+            COMMON_MODELS.iter().map(|m| m.to_string()).collect()
+        }
+    };
+    let index = choose("Bedrock model for summaries", &models)?;
+    set(&mut text, "model", "model_id", &quoted(&models[index]))?;
+
+    // Output
+    let items: Vec<String> = OUTPUT_TYPES.iter().map(|t| t.to_string()).collect();
+    let output_type = OUTPUT_TYPES[choose("Default output (--output-type overrides it)", &items)?];
+    if output_type != "terminal" {
+        set(&mut text, "output", "types", &format!("[{}]", quoted(output_type)))?;
+    }
+    if output_type == "slack" || confirm("Post summaries to Slack?")? {
+        let endpoint: String = Input::with_theme(&SimpleTheme)
+            .with_prompt("Slack webhook URL (leave empty to add it later)")
+            .allow_empty(true)
+            .interact_text()?;
+        if !endpoint.trim().is_empty() {
+            set(&mut text, "slack", "webhook_endpoint", &quoted(endpoint.trim()))?;
+        }
+    }
+
+    output::write_atomic(path, |file| Ok(file.write_all(text.as_bytes())?))?;
+    eprintln!(
+        "{}Wrote {}. Edit it to adjust anything else, or run `distill init` again.",
+        progress::icon("✅"),
+        path.display()
+    );
+    Ok(())
+}
+
+fn confirm(prompt: &str) -> Result<bool, Error> {
+    Ok(choose(prompt, &["Yes".to_string(), "No".to_string()])? == 0)
+}
+
+// A TOML string, e.g. "my-bucket"
+fn quoted(value: &str) -> String {
+    Value::String(value.to_string()).to_string()
+}
+
+// Set `key` in `[section]` of the template, replacing its line (commented
+// out or not) so the comments around it are kept
+fn set(text: &mut String, section: &str, key: &str, value: &str) -> Result<(), Error> {
+    let header = format!("[{}]", section);
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+    let Some(start) = lines.iter().position(|line| line.trim() == header) else {
+        bail!("config.toml template has no [{}] section", section);
+    };
+    let end = lines[start + 1..]
+        .iter()
+        .position(|line| line.trim_start().starts_with('['))
+        .map_or(lines.len(), |offset| start + 1 + offset);
+    let setting = format!("{} = {}", key, value);
+    let existing = lines[start + 1..end].iter().position(|line| {
+        let line = line.trim_start_matches(['#', ' ']);
+        line.strip_prefix(key).is_some_and(|rest| rest.trim_start().starts_with('='))
+    });
+    match existing {
+        Some(offset) => lines[start + 1 + offset] = setting,
+        None => lines.insert(start + 1, setting),
+    }
+    *text = lines.join("\n") + "\n";
+    Ok(())
+}
+
+// Text models that can be called on demand in the configured region, from
+// Bedrock's ListFoundationModels. The runtime SDK can't list models, so the
+// request is signed here.
+async fn list_models(settings: &Config, sdk_config: &SdkConfig) -> Result<Vec<String>, Error> {
+    let region = sdk_config.region().map_or("us-east-1".to_string(), |r| r.to_string());
+    let url = format!(
+        "https://bedrock.{}.amazonaws.com/foundation-models?byInferenceType=ON_DEMAND&byOutputModality=TEXT",
+        region
+    );
+    let request = signed_request(settings, sdk_config, "GET", "bedrock", &url, &[], Vec::new()).await?;
+    let response = request.send().await?;
+    let status = response.status();
+    let body: Value = response.json().await.unwrap_or(Value::Null);
+    if !status.is_success() {
+        bail!("{} {}", status, body["message"].as_str().unwrap_or_default());
+    }
+
+    let mut models: Vec<String> = body["modelSummaries"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|model| model["modelLifecycle"]["status"].as_str() != Some("LEGACY"))
+        .filter_map(|model| model["modelId"].as_str().map(str::to_string))
+        .collect();
+    models.sort();
+    Ok(models)
+}
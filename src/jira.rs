@@ -0,0 +1,114 @@
+use anyhow::{bail, Error};
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use crate::notion::list_item;
+
+// Jira rejects issue summaries longer than this
+const MAX_SUMMARY_CHARS: usize = 255;
+
+// A Jira Cloud site and the project issues are created in
+pub struct Site<'a> {
+    pub base_url: &'a str,
+    pub email: &'a str,
+    pub api_token: &'a str,
+    pub project_key: &'a str,
+    pub issue_type: &'a str,
+}
+
+// Where an issue came from, for its description
+pub struct Meeting<'a> {
+    pub title: &'a str,
+    pub summary: &'a str,
+    // A page the summary was published to in the same run, if any
+    pub link: Option<&'a str>,
+}
+
+fn paragraph(content: Vec<Value>) -> Value {
+    json!({ "type": "paragraph", "content": content })
+}
+
+fn text(text: &str) -> Value {
+    json!({ "type": "text", "text": text })
+}
+
+// Description in Atlassian Document Format: the action item, which meeting it
+// came from, then a link to the published summary or the summary itself
+fn description(item: &str, meeting: &Meeting) -> Value {
+    let mut content = vec![
+        paragraph(vec![text(item)]),
+        paragraph(vec![text(&format!("From the meeting \"{}\".", meeting.title))]),
+    ];
+    match meeting.link {
+        Some(url) => content.push(paragraph(vec![json!({
+            "type": "text",
+            "text": "Meeting summary",
+            "marks": [{ "type": "link", "attrs": { "href": url } }],
+        })])),
+        None => {
+            content.push(json!({
+                "type": "heading",
+                "attrs": { "level": 3 },
+                "content": [text("Meeting summary")],
+            }));
+            content.extend(
+                meeting
+                    .summary
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(|line| paragraph(vec![text(line)])),
+            );
+        }
+    }
+    json!({ "type": "doc", "version": 1, "content": content })
+}
+
+// The action item as an issue summary: list markers dropped, one line, and
+// cut to Jira's limit
+fn issue_summary(item: &str) -> String {
+    let item = list_item(item).unwrap_or(item);
+    if item.chars().count() <= MAX_SUMMARY_CHARS {
+        return item.to_string();
+    }
+    let mut summary: String = item.chars().take(MAX_SUMMARY_CHARS - 1).collect();
+    summary.push('…');
+    summary
+}
+
+// Create one issue for an action item and return its key, e.g. "OPS-123"
+pub async fn create_issue(client: &Client, site: &Site<'_>, item: &str, meeting: &Meeting<'_>) -> Result<String, Error> {
+    let response = client
+        .post(format!("{}/rest/api/3/issue", site.base_url.trim_end_matches('/')))
+        .basic_auth(site.email, Some(site.api_token))
+        .json(&json!({
+            "fields": {
+                "project": { "key": site.project_key },
+                "issuetype": { "name": site.issue_type },
+                "summary": issue_summary(item),
+                "description": description(list_item(item).unwrap_or(item), meeting),
+            }
+        }))
+        .send()
+        .await?;
+    let status = response.status();
+    let body: Value = response.json().await.unwrap_or(Value::Null);
+    if !status.is_success() {
+        // Jira reports problems per field, e.g. {"errors": {"issuetype": "..."}}
+        let errors: Vec<String> = body["errorMessages"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|message| message.as_str().map(str::to_string))
+            .chain(
+                body["errors"]
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .map(|(field, message)| format!("{}: {}", field, message.as_str().unwrap_or_default())),
+            )
+            .collect();
+        bail!("{} {}", status, errors.join("; "));
+    }
+    Ok(body["key"].as_str().unwrap_or_default().to_string())
+}
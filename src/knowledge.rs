@@ -0,0 +1,223 @@
+use anyhow::{bail, Error};
+use aws_config::SdkConfig;
+use config::Config;
+use serde_json::{json, Value};
+use tracing::{debug, instrument};
+
+use crate::aws::signed_request;
+
+// An Amazon Kendra or Amazon Q Business index that `-o kendra` and
+// `-o q-business` add meeting summaries and transcripts to, so people can
+// find what was decided through the company search or assistant
+pub enum Index {
+    // From [kendra]
+    Kendra {
+        index_id: String,
+        role_arn: Option<String>,
+    },
+    // From [q_business]
+    QBusiness {
+        application_id: String,
+        index_id: String,
+        data_source_id: Option<String>,
+        role_arn: Option<String>,
+    },
+}
+
+impl Index {
+    // None when the index isn't configured
+    pub fn kendra(settings: &Config) -> Option<Self> {
+        let setting = |key: &str| setting(settings, "kendra", key);
+        Some(Index::Kendra {
+            index_id: setting("index_id")?,
+            role_arn: setting("role_arn"),
+        })
+    }
+
+    // None when the application or index isn't configured
+    pub fn q_business(settings: &Config) -> Option<Self> {
+        let setting = |key: &str| setting(settings, "q_business", key);
+        Some(Index::QBusiness {
+            application_id: setting("application_id")?,
+            index_id: setting("index_id")?,
+            data_source_id: setting("data_source_id"),
+            role_arn: setting("role_arn"),
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Index::Kendra { .. } => "Amazon Kendra",
+            Index::QBusiness { .. } => "Amazon Q Business",
+        }
+    }
+}
+
+fn setting(settings: &Config, table: &str, key: &str) -> Option<String> {
+    settings
+        .get_string(&format!("{}.{}", table, key))
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+// A plain text document and the metadata both services understand. The ID
+// stays the same when a recording is processed again, so its documents are
+// replaced rather than duplicated.
+pub struct Document<'a> {
+    pub id: String,
+    pub title: String,
+    // e.g. "Meeting summary", to filter on in search
+    pub category: &'a str,
+    pub text: &'a str,
+    // Seconds since the Unix epoch
+    pub created_at: Option<u64>,
+    // The attendees or named speakers
+    pub authors: &'a [String],
+    // Notion or Confluence page written in the same run
+    pub source_uri: Option<&'a str>,
+    pub language_code: &'a str,
+}
+
+// Add or replace `documents` in the index with BatchPutDocument
+#[instrument(name = "publish_index", skip_all, fields(index = index.name()))]
+pub async fn publish(
+    settings: &Config,
+    sdk_config: &SdkConfig,
+    index: &Index,
+    documents: &[Document<'_>],
+) -> Result<(), Error> {
+    let region = sdk_config.region().map_or("us-east-1".to_string(), |r| r.to_string());
+    let (request, failed_key) = match index {
+        Index::Kendra { index_id, role_arn } => {
+            let documents: Vec<Value> = documents.iter().map(kendra_document).collect();
+            let mut body = json!({ "IndexId": index_id, "Documents": documents });
+            if let Some(role_arn) = role_arn {
+                body["RoleArn"] = json!(role_arn);
+            }
+            let request = signed_request(
+                settings,
+                sdk_config,
+                "POST",
+                "kendra",
+                &format!("https://kendra.{}.amazonaws.com/", region),
+                &[
+                    ("content-type", "application/x-amz-json-1.1"),
+                    ("x-amz-target", "AWSKendraFrontendService.BatchPutDocument"),
+                ],
+                body.to_string().into_bytes(),
+            )
+            .await?;
+            (request, "FailedDocuments")
+        }
+        Index::QBusiness {
+            application_id,
+            index_id,
+            data_source_id,
+            role_arn,
+        } => {
+            let documents: Vec<Value> = documents.iter().map(q_business_document).collect();
+            let mut body = json!({ "documents": documents });
+            if let Some(data_source_id) = data_source_id {
+                body["dataSourceId"] = json!(data_source_id);
+            }
+            if let Some(role_arn) = role_arn {
+                body["roleArn"] = json!(role_arn);
+            }
+            let request = signed_request(
+                settings,
+                sdk_config,
+                "POST",
+                "qbusiness",
+                &format!(
+                    "https://qbusiness.{}.api.aws/applications/{}/indices/{}/documents",
+                    region, application_id, index_id
+                ),
+                &[("content-type", "application/json")],
+                body.to_string().into_bytes(),
+            )
+            .await?;
+            (request, "failedDocuments")
+        }
+    };
+
+    let response = request.send().await?;
+    let status = response.status();
+    let body: Value = response.json().await.unwrap_or(Value::Null);
+    if !status.is_success() {
+        bail!(
+            "\n{} rejected the documents: {} {}",
+            index.name(),
+            status,
+            body["message"].as_str().or_else(|| body["Message"].as_str()).unwrap_or_default()
+        );
+    }
+    // Documents are accepted one by one; any that weren't are listed
+    let failed: Vec<String> = body[failed_key]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|failure| {
+            let id = failure["Id"].as_str().or_else(|| failure["id"].as_str()).unwrap_or_default();
+            let message = failure["ErrorMessage"]
+                .as_str()
+                .or_else(|| failure["error"]["errorMessage"].as_str())
+                .unwrap_or_default();
+            format!("{}: {}", id, message)
+        })
+        .collect();
+    if !failed.is_empty() {
+        bail!("\n{} couldn't add {}", index.name(), failed.join("; "));
+    }
+    debug!(documents = documents.len(), "documents sent to the index");
+    Ok(())
+}
+
+// Reserved Kendra attributes, which every index has without configuring fields
+fn kendra_document(document: &Document) -> Value {
+    let mut attributes = vec![
+        json!({ "Key": "_category", "Value": { "StringValue": document.category } }),
+        json!({ "Key": "_file_type", "Value": { "StringValue": "PLAIN_TEXT" } }),
+    ];
+    if let Some(created_at) = document.created_at {
+        attributes.push(json!({ "Key": "_created_at", "Value": { "DateValue": created_at } }));
+    }
+    if !document.authors.is_empty() {
+        attributes.push(json!({ "Key": "_authors", "Value": { "StringListValue": document.authors } }));
+    }
+    if let Some(uri) = document.source_uri {
+        attributes.push(json!({ "Key": "_source_uri", "Value": { "StringValue": uri } }));
+    }
+    // Kendra wants the language alone, e.g. "es" for es-US
+    let language = document.language_code.split('-').next().unwrap_or_default().to_lowercase();
+    if !language.is_empty() {
+        attributes.push(json!({ "Key": "_language_code", "Value": { "StringValue": language } }));
+    }
+    json!({
+        "Id": document.id,
+        "Title": document.title,
+        "Blob": aws_smithy_types::base64::encode(document.text),
+        "ContentType": "PLAIN_TEXT",
+        "Attributes": attributes,
+    })
+}
+
+fn q_business_document(document: &Document) -> Value {
+    let mut attributes = vec![json!({ "name": "_category", "value": { "stringValue": document.category } })];
+    if let Some(created_at) = document.created_at {
+        attributes.push(json!({ "name": "_created_at", "value": { "dateValue": created_at } }));
+    }
+    if !document.authors.is_empty() {
+        attributes.push(json!({ "name": "_authors", "value": { "stringListValue": document.authors } }));
+    }
+    if let Some(uri) = document.source_uri {
+        attributes.push(json!({ "name": "_source_uri", "value": { "stringValue": uri } }));
+    }
+    json!({
+        "id": document.id,
+        "title": document.title,
+        "content": { "blob": aws_smithy_types::base64::encode(document.text) },
+        "contentType": "PLAIN_TEXT",
+        "attributes": attributes,
+    })
+}
@@ -0,0 +1,135 @@
+// Language identification for transcripts, to notice a recording transcribed
+// with the wrong --language-code. Each known language is recognized by its
+// most common words, which make up a large share of any text in it.
+pub struct Language {
+    pub name: &'static str,
+    // Amazon Transcribe language code suggested for it
+    pub code: &'static str,
+    words: &'static [&'static str],
+}
+
+const LANGUAGES: [Language; 10] = [
+    Language {
+        name: "English",
+        code: "en-US",
+        words: &[
+            "the", "and", "to", "of", "a", "in", "is", "that", "it", "you", "for", "we", "this", "on", "with", "be",
+            "are", "have", "was", "not", "but", "so", "they", "what", "just", "i", "do", "can", "yeah", "know",
+        ],
+    },
+    Language {
+        name: "Spanish",
+        code: "es-US",
+        words: &[
+            "el", "la", "de", "que", "y", "en", "los", "las", "es", "por", "un", "una", "para", "con", "no", "se",
+            "lo", "pero", "como", "más", "muy", "está", "eso", "sí", "también", "bueno", "entonces", "porque", "del",
+            "al",
+        ],
+    },
+    Language {
+        name: "French",
+        code: "fr-FR",
+        words: &[
+            "le", "la", "les", "de", "des", "et", "est", "que", "un", "une", "pas", "je", "vous", "nous", "il", "on",
+            "ce", "qui", "dans", "pour", "avec", "mais", "c'est", "sur", "du", "au", "oui", "alors", "très", "donc",
+        ],
+    },
+    Language {
+        name: "German",
+        code: "de-DE",
+        words: &[
+            "der", "die", "das", "und", "ist", "nicht", "ich", "wir", "sie", "es", "ein", "eine", "zu", "mit", "auf",
+            "für", "den", "dem", "auch", "aber", "so", "ja", "dass", "wie", "noch", "haben", "sind", "oder", "nur",
+            "was",
+        ],
+    },
+    Language {
+        name: "Italian",
+        code: "it-IT",
+        words: &[
+            "il", "la", "di", "che", "e", "è", "un", "una", "per", "non", "sono", "con", "del", "della", "ma", "anche",
+            "come", "questo", "più", "ci", "si", "lo", "gli", "le", "molto", "quindi", "allora", "perché", "io", "noi",
+        ],
+    },
+    Language {
+        name: "Portuguese",
+        code: "pt-BR",
+        words: &[
+            "o", "a", "de", "que", "e", "do", "da", "em", "um", "uma", "para", "com", "não", "é", "os", "as", "se",
+            "mas", "por", "mais", "no", "na", "isso", "muito", "então", "também", "ele", "eu", "você", "está",
+        ],
+    },
+    Language {
+        name: "Dutch",
+        code: "nl-NL",
+        words: &[
+            "de", "het", "een", "en", "van", "is", "dat", "niet", "ik", "je", "we", "op", "te", "met", "voor", "zijn",
+            "maar", "ook", "wat", "er", "dit", "die", "naar", "heb", "hebben", "nog", "wel", "dus", "als", "om",
+        ],
+    },
+    Language {
+        name: "Swedish",
+        code: "sv-SE",
+        words: &[
+            "och", "det", "att", "i", "en", "är", "som", "på", "för", "med", "inte", "jag", "vi", "den", "har", "till",
+            "av", "om", "så", "men", "ett", "de", "kan", "var", "ska", "också", "bara", "eller", "nu", "här",
+        ],
+    },
+    Language {
+        name: "Danish",
+        code: "da-DK",
+        words: &[
+            "og", "det", "at", "i", "en", "er", "som", "på", "for", "med", "ikke", "jeg", "vi", "den", "har", "til",
+            "af", "om", "så", "men", "et", "de", "kan", "var", "skal", "også", "bare", "eller", "nu", "her",
+        ],
+    },
+    Language {
+        name: "Polish",
+        code: "pl-PL",
+        words: &[
+            "i", "w", "nie", "na", "się", "to", "że", "z", "jest", "do", "jak", "ale", "o", "co", "tak", "ja", "po",
+            "od", "za", "dla", "już", "tylko", "czy", "bardzo", "więc", "są", "my", "ten", "też", "jestem",
+        ],
+    },
+];
+
+// Fewer words than this don't say much about the language
+const MIN_WORDS: usize = 20;
+
+// Share of a text's words that must be common words of its language
+const MIN_SHARE: f64 = 0.2;
+
+// How many times more common words the best language must have than the next
+const MARGIN: f64 = 1.5;
+
+// The language `text` is clearly in, or None when it is too short, in a
+// language that isn't known, or could be more than one (e.g. Swedish and Danish)
+pub fn detect(text: &str) -> Option<&'static Language> {
+    let words: Vec<String> = text
+        .split(|c: char| !(c.is_alphanumeric() || c == '\''))
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    if words.len() < MIN_WORDS {
+        return None;
+    }
+
+    let mut scores: Vec<(&'static Language, f64)> = LANGUAGES
+        .iter()
+        .map(|language| {
+            let common = words.iter().filter(|word| language.words.contains(&word.as_str())).count();
+            (language, common as f64 / words.len() as f64)
+        })
+        .collect();
+    scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let (best, share) = scores[0];
+    let runner_up = scores[1].1;
+    (share >= MIN_SHARE && share >= runner_up * MARGIN).then_some(best)
+}
+
+// The language a transcript is clearly in when that isn't the language it
+// was transcribed as, e.g. Spanish for a transcript made with en-US
+pub fn mismatch(text: &str, language_code: &str) -> Option<&'static Language> {
+    let expected = language_code.split('-').next().unwrap_or_default().to_lowercase();
+    detect(text).filter(|detected| !detected.code.to_lowercase().starts_with(&format!("{}-", expected)))
+}
@@ -0,0 +1,63 @@
+// distill_core: the upload → transcribe → summarize → render pipeline behind
+// the `distill` command, for running it from other programs such as a Lambda
+// function or a web service. `pipeline::process_recording` runs every stage
+// for one recording; the stages can also be run one at a time, each returning
+// what the next one needs.
+pub mod actions;
+pub mod agenda;
+pub mod agent;
+pub mod audio;
+pub mod aws;
+pub mod backfill;
+pub mod batch;
+pub mod brief;
+pub mod compliance;
+pub mod confidence;
+pub mod confluence;
+pub mod database;
+pub mod digest;
+pub mod eventbridge;
+pub mod events;
+pub mod faq;
+pub mod feedback;
+pub mod health;
+pub mod html;
+pub mod jira;
+pub mod knowledge;
+pub mod language;
+pub mod limits;
+pub mod logging;
+pub mod metrics;
+pub mod moderation;
+pub mod naming;
+pub mod net;
+pub mod notion;
+pub mod openai;
+pub mod output;
+pub mod pdf;
+pub mod pipeline;
+pub mod progress;
+pub mod queue;
+pub mod quota;
+pub mod repair;
+pub mod report;
+pub mod retry;
+pub mod review;
+pub mod serve;
+pub mod session;
+pub mod settings;
+pub mod state;
+pub mod slack;
+pub mod sns;
+pub mod speech;
+pub mod ssm;
+pub mod subtitles;
+pub mod summarize;
+pub mod template;
+pub mod terms;
+pub mod transcribe;
+pub mod transforms;
+pub mod upload;
+pub mod voiceprint;
+pub mod watch;
+pub mod whisper;
@@ -0,0 +1,176 @@
+use anyhow::{bail, Error};
+use config::Config;
+use serde_json::{json, Value};
+
+// About 130,000 tokens, which fits the context window of the common Claude
+// and Nova models with room for the prompt and summary
+const DEFAULT_MAX_WORDS: usize = 100_000;
+
+// What to do with a transcript longer than summary.max_words
+#[derive(Clone, Copy, PartialEq)]
+pub enum Policy {
+    // Stop with an error before calling the model
+    Fail,
+    // Summarize the start of the transcript, with a marker where it was cut
+    Truncate,
+    // Summarize the transcript in parts, then combine the partial summaries
+    Chunk,
+}
+
+impl Policy {
+    fn name(self) -> &'static str {
+        match self {
+            Policy::Fail => "fail",
+            Policy::Truncate => "truncate",
+            Policy::Chunk => "chunk",
+        }
+    }
+}
+
+pub struct SizeLimit {
+    // 0 turns the limit off
+    pub max_words: usize,
+    pub policy: Policy,
+    // With the "chunk" policy, how many parts are summarized at the same time
+    pub parallel_parts: usize,
+}
+
+impl SizeLimit {
+    pub fn from_settings(settings: &Config) -> Result<Self, Error> {
+        let max_words = match settings.get_int("summary.max_words") {
+            Ok(words) => words.max(0) as usize,
+            Err(_) => DEFAULT_MAX_WORDS,
+        };
+        let policy = match settings.get_string("summary.oversize_policy").as_deref() {
+            Ok("fail") | Err(_) => Policy::Fail,
+            Ok("truncate") => Policy::Truncate,
+            Ok("chunk") => Policy::Chunk,
+            Ok(other) => bail!(
+                "\nUnknown summary.oversize_policy '{}'. Use fail, truncate or chunk.",
+                other
+            ),
+        };
+        let parallel_parts = settings.get_int("summary.parallel_parts").unwrap_or(4);
+        if parallel_parts < 1 {
+            bail!("\nsummary.parallel_parts must be at least 1.");
+        }
+        Ok(SizeLimit {
+            max_words,
+            policy,
+            parallel_parts: parallel_parts as usize,
+        })
+    }
+
+    pub fn exceeded_by(&self, words: usize) -> bool {
+        self.max_words > 0 && words > self.max_words
+    }
+}
+
+// How a transcript's length was handled, for the run's output metadata
+pub struct Decision {
+    pub words: usize,
+    pub max_words: usize,
+    // None when the transcript was within the limit
+    pub policy: Option<Policy>,
+    // Words sent to the model, and the number of parts they were sent in
+    pub words_summarized: usize,
+    pub parts: usize,
+}
+
+impl Decision {
+    pub fn within_limit(words: usize, max_words: usize) -> Self {
+        Decision {
+            words,
+            max_words,
+            policy: None,
+            words_summarized: words,
+            parts: 1,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "words": self.words,
+            "max_words": self.max_words,
+            "exceeded": self.policy.is_some(),
+            "policy": self.policy.map(Policy::name),
+            "words_summarized": self.words_summarized,
+            "parts": self.parts,
+        })
+    }
+}
+
+// Whole lines of `transcription` in parts of at most `max_words` words. A
+// single turn longer than that is split between words.
+pub fn split(transcription: &str, max_words: usize) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut part: Vec<&str> = Vec::new();
+    let mut part_words = 0;
+    for line in transcription.lines() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if part_words + words.len() > max_words && !part.is_empty() {
+            parts.push(part.join("\n"));
+            part.clear();
+            part_words = 0;
+        }
+        if words.len() > max_words {
+            for piece in words.chunks(max_words) {
+                parts.push(piece.join(" "));
+            }
+            continue;
+        }
+        part.push(line);
+        part_words += words.len();
+    }
+    if !part.is_empty() {
+        parts.push(part.join("\n"));
+    }
+    parts.retain(|part| !part.trim().is_empty());
+    parts
+}
+
+// The first part of the transcript, with a marker saying how much was left out
+pub fn truncate(transcription: &str, max_words: usize) -> (String, usize) {
+    let total = transcription.split_whitespace().count();
+    let kept = split(transcription, max_words).into_iter().next().unwrap_or_default();
+    let kept_words = kept.split_whitespace().count();
+    (
+        format!(
+            "{}\n\n[Transcript truncated: the remaining {} of {} words were not included]",
+            kept,
+            total - kept_words,
+            total
+        ),
+        kept_words,
+    )
+}
+
+// The partial summaries of a chunked transcript, in place of the transcript,
+// for the final summary
+pub fn combine(partial_summaries: &[String]) -> String {
+    let mut combined = String::from(
+        "The meeting was too long to summarize at once, so these are summaries of its consecutive parts.",
+    );
+    for (index, summary) in partial_summaries.iter().enumerate() {
+        combined.push_str(&format!("\n\nPart {} of {}:\n{}", index + 1, partial_summaries.len(), summary.trim()));
+    }
+    combined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_at_line_ends_within_the_limit() {
+        let text = "a b c\nd e\nf g h i\nj";
+        assert_eq!(split(text, 5), ["a b c\nd e", "f g h i\nj"]);
+        assert_eq!(split(text, 100), [text]);
+        assert!(split("", 5).is_empty());
+    }
+
+    #[test]
+    fn splits_a_line_longer_than_the_limit() {
+        assert_eq!(split("one\na b c d e f g\ntwo", 3), ["one", "a b c", "d e f", "g", "two"]);
+    }
+}
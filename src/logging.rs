@@ -0,0 +1,50 @@
+use anyhow::{anyhow, bail, Error};
+use config::Config;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
+
+// How log records are written to stderr with --log-format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    // One human-readable line per record
+    Text,
+    // One JSON object per record, for log collectors
+    Json,
+}
+
+// Set up logging for the run. The level comes from --log-level, then
+// `logging.level`, then RUST_LOG, and is "error" otherwise. It is a level
+// such as "debug" or a filter such as "distill_core=debug,aws_config=warn".
+// Spans for the pipeline stages (upload, transcription, polling,
+// summarization and output) are logged when they close, with their duration
+// as time.busy and time.idle, so slow runs can be traced to a stage.
+pub fn init(settings: &Config, level: Option<&str>, format: Option<Format>) -> Result<(), Error> {
+    let level = level
+        .map(str::to_string)
+        .or_else(|| settings.get_string("logging.level").ok())
+        .or_else(|| std::env::var("RUST_LOG").ok())
+        .filter(|level| !level.trim().is_empty())
+        .unwrap_or_else(|| "error".to_string());
+    let filter = EnvFilter::try_new(&level).map_err(|e| anyhow!("\nInvalid log level '{}': {}", level, e))?;
+
+    let format = match format {
+        Some(format) => format,
+        None => match settings.get_string("logging.format").as_deref() {
+            Ok("text") | Err(_) => Format::Text,
+            Ok("json") => Format::Json,
+            Ok(other) => bail!("\nUnknown logging.format '{}'. Use text or json.", other),
+        },
+    };
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(std::io::stderr);
+    // A logger set up earlier in the process, e.g. by a program using
+    // distill_core as a library, is kept
+    let _ = match format {
+        Format::Text => builder.with_ansi(console::colors_enabled_stderr()).try_init(),
+        Format::Json => builder.json().with_current_span(true).try_init(),
+    };
+    Ok(())
+}
@@ -1,468 +1,1296 @@
-mod summarize;
-mod transcribe;
+mod init;
 
-use std::fs::File;
-use std::io::Write;
-use std::path::Path;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 
 use anyhow::{bail, Context, Result};
-use aws_config::meta::region::RegionProviderChain;
-use aws_config::{Region, SdkConfig};
-use aws_sdk_s3::config::StalledStreamProtectionConfig;
+use aws_config::Region;
+use aws_sdk_s3::Client;
 use clap::Parser;
-use config::{Config, File as ConfigFile};
-use docx_rs::{Docx, Paragraph, Run};
-use reqwest::Client as ReqwestClient;
+use config::Config;
+use distill_core::agent::Agent;
+use distill_core::aws::{bucket_region, load_config, select_bucket};
+use distill_core::pipeline::{
+    process_recording, FilterMethod, Meeting, Mode, OutputType, RunContext, TranscriberKind,
+};
+use distill_core::progress::{self, Progress};
+use distill_core::status;
+use distill_core::summarize::Summarizer;
+use distill_core::{
+    actions, agenda, audio, backfill, batch, brief, compliance, confidence, database, digest, feedback, knowledge,
+    limits, logging, naming, net, openai, queue, quota, report, serve, session, settings, sns, speech, ssm, state,
+    summarize, transcribe, transforms, voiceprint, watch, whisper,
+};
 use serde_json::json;
-use spinoff::{spinners, Color, Spinner};
-
-use aws_sdk_s3::primitives::ByteStream;
-use aws_sdk_s3::Client;
-use dialoguer::{theme::ColorfulTheme, Select};
 
 #[derive(Debug, Parser)]
 #[clap(
     about = "Distill CLI can summarize an audio file (e.g., a meeting) using Amazon Transcribe and Amazon Bedrock.",
-    after_help = "For supported languages, consult: https://docs.aws.amazon.com/transcribe/latest/dg/supported-languages.html"
+    after_help = "For supported languages, consult: https://docs.aws.amazon.com/transcribe/latest/dg/supported-languages.html",
+    disable_version_flag = true,
+    subcommand_negates_reqs = true
 )]
 struct Opt {
-    #[clap(short, long)]
-    input_audio_file: String,
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    #[clap(short, long, required_unless_present_any = ["input_transcript", "track", "resume_job", "version", "build_info"])]
+    input_audio_file: Vec<String>,
+
+    #[clap(
+        long,
+        value_name = "NAME=FILE",
+        conflicts_with_all = ["input_audio_file", "input_transcript", "merge"],
+        help = "A per-speaker audio track of one recording; repeat for each speaker"
+    )]
+    track: Vec<String>,
+
+    #[clap(
+        long,
+        conflicts_with_all = ["input_audio_file", "first", "range", "skip_summary"],
+        help = "Summarize an existing transcript (plain text or Amazon Transcribe JSON) instead of an audio file"
+    )]
+    input_transcript: Vec<String>,
+
+    #[clap(long, help = "Treat all inputs as consecutive parts of one meeting and produce a single summary")]
+    merge: bool,
 
     #[clap(
         short,
         long,
         value_enum,
-        ignore_case = true
+        ignore_case = true,
+        value_delimiter = ',',
+        help = "Output format; repeat or separate with commas to write several outputs from one run"
     )]
-    output_type: Option<OutputType>,
+    output_type: Vec<OutputType>,
 
-    #[clap(long, help = "Specify the output filename (only valid with text, word, or markdown output types)")]
+    #[clap(long, help = "Specify the output filename (only valid with file output types)")]
     output_filename: Option<String>,
 
-    #[clap(short, long, default_value = "en-US")]
-    language_code: String,
+    #[clap(
+        long,
+        conflicts_with_all = ["output_filename", "resume_job"],
+        help = "Name outputs and S3 keys by the SHA-256 of the input, so the same recording always gets the same names"
+    )]
+    name_by_hash: bool,
+
+    #[clap(short, long, help = "Input language code [default: en-US]")]
+    language_code: Option<String>,
 
     #[clap(short, long, default_value = "n")]
     delete_s3_object: String,
+
+    #[clap(long, help = "Print a report of stage durations, upload size and token usage at the end of the run")]
+    report: bool,
+
+    #[clap(long, help = "Summarize the transcript even if it is shorter than summary.min_words")]
+    always_summarize: bool,
+
+    #[clap(
+        long,
+        value_enum,
+        ignore_case = true,
+        default_value = "meeting",
+        help = "Preset tuned for the kind of recording being summarized"
+    )]
+    mode: Mode,
+
+    #[clap(long, value_name = "NAME", help = "Use the settings in the [profile.NAME] section of config.toml, e.g. for a kind of meeting")]
+    profile: Option<String>,
+
+    #[clap(long, value_name = "PATH", help = "Read settings from the SSM Parameter Store parameters under PATH, e.g. /distill/prod; config.toml and the environment still apply")]
+    config_from_ssm: Option<String>,
+
+    #[clap(long, value_name = "MODEL_ID", help = "Bedrock model (or inference profile) to summarize with; overrides model.model_id")]
+    model: Option<String>,
+
+    #[clap(long, value_name = "AGENT_ID", help = "Bedrock Agent to summarize with, so it can use its tools; overrides agent.agent_id")]
+    agent: Option<String>,
+
+    #[clap(long, value_enum, ignore_case = true, help = "Summarize with Bedrock or an OpenAI-compatible chat API such as Ollama or vLLM; overrides model.summarizer [default: bedrock]")]
+    summarizer: Option<Summarizer>,
+
+    #[clap(long, value_name = "URL", help = "Base URL of the OpenAI-compatible API, e.g. http://localhost:11434/v1; overrides openai.endpoint")]
+    endpoint: Option<String>,
+
+    #[clap(long, value_name = "CHANNEL_ID", help = "Slack channel to post the summary to with the bot token; overrides slack.channel")]
+    slack_channel: Option<String>,
+
+    #[clap(long, value_name = "NAME", help = "Group this run under the named session instead of the active one")]
+    session: Option<String>,
+
+    #[clap(long, value_name = "N", help = "Append the N most frequent terms in the transcript to the output")]
+    top_terms: Option<usize>,
+
+    #[clap(long, value_name = "PATH", help = "Agenda file (Markdown); adds a checklist of which agenda items were covered")]
+    context_file: Option<String>,
+
+    #[clap(long, value_name = "NAME", help = "Summarize with the prompt.NAME template from config.toml instead of the mode's prompt")]
+    prompt: Option<String>,
+
+    #[clap(long, value_name = "PATH", conflicts_with = "prompt", help = "Read the summarization prompt template from a file")]
+    prompt_file: Option<String>,
+
+    #[clap(long, value_name = "NAMES", value_delimiter = ',', help = "Attendees for the {attendees} prompt placeholder, comma-separated (defaults to the transcript's speakers)")]
+    attendees: Vec<String>,
+
+    #[clap(long, value_name = "DURATION", conflicts_with = "range", help = "Only process the beginning of the recording, e.g. 30m or 1h15m (requires ffmpeg)")]
+    first: Option<String>,
+
+    #[clap(long, value_name = "START-END", help = "Only process part of the recording, e.g. 00:10:00-00:55:00 (requires ffmpeg)")]
+    range: Option<String>,
+
+    #[clap(long, help = "Only transcribe the audio and write the transcript, without calling Bedrock")]
+    skip_summary: bool,
+
+    #[clap(long, help = "Rename speakers and correct misheard words in the transcript before it is summarized")]
+    review: bool,
+
+    #[clap(long, value_name = "SCORE", help = "Stop before summarizing when Transcribe's average word confidence is below SCORE, e.g. 0.85; overrides transcript.min_confidence")]
+    min_confidence: Option<f64>,
+
+    #[clap(long, value_name = "N", value_parser = clap::value_parser!(i32).range(2..=30), conflicts_with = "no_diarization", help = "Maximum number of speakers to tell apart in the transcript [default: 10]")]
+    speakers: Option<i32>,
+
+    #[clap(long, help = "Don't label speakers; the transcript is split into paragraphs at pauses instead")]
+    no_diarization: bool,
+
+    #[clap(
+        long,
+        value_enum,
+        ignore_case = true,
+        conflicts_with = "resume_job",
+        help = "Transcribe with Amazon Transcribe, or with whisper.cpp on this machine so the audio isn't uploaded; overrides transcript.transcriber [default: amazon]"
+    )]
+    transcriber: Option<TranscriberKind>,
+
+    #[clap(long, value_name = "NAME", help = "Amazon Transcribe vocabulary filter to apply to the transcript")]
+    vocabulary_filter_name: Option<String>,
+
+    #[clap(
+        long,
+        value_enum,
+        ignore_case = true,
+        default_value = "mask",
+        requires = "vocabulary_filter_name",
+        help = "How words matched by the vocabulary filter are handled"
+    )]
+    vocabulary_filter_method: FilterMethod,
+
+    #[clap(long, help = "Mark long pauses, and laughter or applause found by events.detector_command, inline in the transcript")]
+    annotate_events: bool,
+
+    #[clap(long, help = "Screen-reader friendly output: plain progress lines, periodic status announcements and numbered prompts")]
+    a11y: bool,
+
+    #[clap(long, help = "Disable colored output (NO_COLOR is also honored)")]
+    no_color: bool,
+
+    #[clap(
+        long,
+        value_enum,
+        ignore_case = true,
+        default_value = "human",
+        help = "How progress is reported on stderr: spinners (human) or one JSON event per line (json)"
+    )]
+    progress: progress::Format,
+
+    #[clap(long, help = "Print plain status lines instead of animated spinners")]
+    no_spinner: bool,
+
+    #[clap(short, long, help = "Only print results, warnings and errors: no spinners, progress lines or banners")]
+    quiet: bool,
+
+    #[clap(long, value_name = "LEVEL", help = "Log level or filter for diagnostic logs on stderr, e.g. debug or distill_core=trace [default: error, or RUST_LOG]")]
+    log_level: Option<String>,
+
+    #[clap(long, value_enum, ignore_case = true, help = "Format of diagnostic logs: text or json [default: text]")]
+    log_format: Option<logging::Format>,
+
+    #[clap(long, visible_alias = "yes", help = "Never prompt; fail if aws.s3_bucket_name is not configured or does not exist")]
+    non_interactive: bool,
+
+    #[clap(long, value_name = "NAME", help = "Named profile from ~/.aws/credentials or ~/.aws/config to use; overrides aws.profile and AWS_PROFILE")]
+    aws_profile: Option<String>,
+
+    #[clap(long, value_name = "REGION", help = "Region for Amazon Transcribe and Bedrock, e.g. us-west-2; overrides aws.region. Uploads still go to the bucket's region.")]
+    region: Option<String>,
+
+    #[clap(long, value_name = "BUCKET", help = "Destination S3 bucket; overrides aws.s3_bucket_name and skips the bucket selector")]
+    s3_bucket: Option<String>,
+
+    #[clap(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=10), help = "Generate N candidate summaries and keep the best one")]
+    samples: u32,
+
+    #[clap(long, requires = "samples", conflicts_with = "non_interactive", help = "Choose between the --samples candidates yourself instead of letting the model judge")]
+    pick_sample: bool,
+
+    #[clap(
+        long,
+        value_name = "JOB_NAME",
+        conflicts_with_all = ["input_audio_file", "input_transcript", "track", "merge", "first", "range", "vocabulary_filter_name"],
+        help = "Reattach to an Amazon Transcribe job started by an earlier run and continue from its transcript"
+    )]
+    resume_job: Option<String>,
+
+    #[clap(long, conflicts_with = "resume_job", help = "Save the run to the offline queue instead of processing it now; run `distill-cli flush` later")]
+    queue: bool,
+
+    #[clap(long, conflicts_with = "queue", help = "Check inputs, bucket access and settings and print an estimated cost, without uploading or calling Bedrock")]
+    dry_run: bool,
+
+    // Set by `flush` on the runs it replays, so they fail rather than queue again
+    #[clap(long, hide = true)]
+    from_queue: bool,
+
+    #[clap(short = 'V', long, help = "Print version")]
+    version: bool,
+
+    #[clap(long, help = "Print version, build and capability details as JSON (also with --version)")]
+    build_info: bool,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
-enum OutputType {
-    Terminal,
-    Text,
-    Word,
-    Markdown,
-    Slack,
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    #[clap(about = "Create config.toml interactively: S3 bucket, Bedrock model and output defaults")]
+    Init,
+    #[clap(about = "Process the runs saved to the offline queue, oldest first")]
+    Flush,
+    #[clap(about = "Enroll a voice sample so the speaker is named automatically in later transcripts")]
+    Enroll {
+        #[clap(long, help = "Name to show in transcripts, e.g. \"Jane Doe\"")]
+        name: String,
+        #[clap(help = "Recording of only this person speaking, ideally 10 to 30 seconds")]
+        sample: String,
+    },
+    #[clap(about = "Bundle the offline queue, sessions and enrolled voices into a zip archive for another machine")]
+    ExportState {
+        #[clap(help = "Archive to write")]
+        path: String,
+    },
+    #[clap(about = "Add the queued runs, sessions and enrolled voices from an `export-state` archive")]
+    ImportState {
+        #[clap(help = "Archive written by `export-state`")]
+        path: String,
+        #[clap(long, help = "Replace sessions that already exist on this machine")]
+        overwrite: bool,
+    },
+    #[clap(about = "Summarize each new recording that appears in a directory, e.g. the folder Zoom saves to")]
+    Watch {
+        #[clap(help = "Directory to watch")]
+        dir: String,
+        #[clap(long, help = "Also process the recordings already in the directory the first time it is watched")]
+        existing: bool,
+        #[clap(
+            last = true,
+            value_name = "OPTIONS",
+            help = "Options for each run, after --, e.g. -- --profile zoom -o markdown"
+        )]
+        args: Vec<String>,
+    },
+    #[clap(about = "Serve an HTTP API that transcribes and summarizes recordings sent to it, using this machine's AWS access")]
+    Serve {
+        #[clap(long, value_name = "ADDRESS", help = "Address and port to listen on [default: serve.listen or 127.0.0.1:8080]")]
+        listen: Option<String>,
+        #[clap(long, value_name = "N", help = "Recordings processed at the same time [default: serve.concurrency or 2]")]
+        concurrency: Option<usize>,
+    },
+    #[clap(about = "Summarize the recordings already in S3, skipping ones an earlier backfill processed")]
+    Backfill {
+        #[clap(help = "Location of the recordings, e.g. s3://recordings/meetings/")]
+        uri: String,
+        #[clap(long, value_name = "DATE", help = "Only recordings uploaded on or after this date, e.g. 2024-01-01")]
+        since: Option<String>,
+        #[clap(long, value_name = "N", help = "Recordings processed at the same time [default: backfill.concurrency or 2]")]
+        concurrency: Option<usize>,
+        #[clap(long, value_name = "USD", help = "Start no more recordings once the estimated cost reaches this [default: backfill.max_spend_usd]")]
+        max_spend: Option<f64>,
+        #[clap(long, help = "Summarize with a Bedrock batch inference job, at the batch price but hours later [default: backfill.batch]")]
+        batch: bool,
+    },
+    #[clap(about = "Deliver a weekly digest of the meetings summarized in sessions, each week until stopped")]
+    Schedule {
+        #[clap(long, value_name = "DAY", help = "Day of the week to deliver it on, e.g. friday [default: digest.day or monday]")]
+        day: Option<String>,
+        #[clap(long, value_name = "HH:MM", help = "Time of day to deliver it at, in UTC [default: digest.time or 09:00]")]
+        at: Option<String>,
+        #[clap(long, help = "Deliver the digest of the last seven days now and exit, e.g. from cron")]
+        now: bool,
+    },
+    #[clap(about = "Rate a run's summary, to compare models and prompts with `feedback-report`")]
+    Feedback {
+        #[clap(help = "ID printed when the run finished, or a `serve` job's ID")]
+        id: String,
+        #[clap(long, value_parser = clap::value_parser!(u8).range(1..=5), help = "From 1 (poor) to 5 (excellent)")]
+        rating: u8,
+        #[clap(long, help = "What was good or missing, e.g. \"missed the budget decision\"")]
+        note: Option<String>,
+    },
+    #[clap(about = "Average rating by model and prompt version, with the notes on low-rated runs")]
+    FeedbackReport,
+    #[clap(about = "List the action items tracked across the meetings of sessions")]
+    Actions {
+        #[clap(subcommand)]
+        action: ActionsCommand,
+    },
+    #[clap(about = "Write a project brief for a session's series of meetings from the summaries of its runs")]
+    Brief {
+        #[clap(long, value_name = "SESSION", help = "Session holding the series [default: the active session]")]
+        series: Option<String>,
+    },
+    #[clap(about = "Group the runs for related recordings, e.g. a series of planning meetings, and export them")]
+    Session {
+        #[clap(subcommand)]
+        action: SessionCommand,
+    },
 }
 
-impl std::fmt::Display for OutputType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            OutputType::Terminal => write!(f, "terminal"),
-            OutputType::Text => write!(f, "text"),
-            OutputType::Word => write!(f, "word"),
-            OutputType::Markdown => write!(f, "markdown"),
-            OutputType::Slack => write!(f, "slack"),
-        }
-    }
+#[derive(Debug, clap::Subcommand)]
+enum ActionsCommand {
+    #[clap(about = "List the action items of every session, or of one")]
+    List {
+        #[clap(long, help = "Only the items no later meeting has reported done")]
+        open: bool,
+        #[clap(long, value_name = "SESSION", help = "Only the items of this session")]
+        series: Option<String>,
+    },
 }
 
-impl OutputType {
-    fn from_filename(filename: &str) -> Option<Self> {
-        let extension = std::path::Path::new(filename)
-            .extension()
-            .and_then(|ext| ext.to_str())
-            .map(|s| s.to_lowercase());
-        
-        match extension.as_deref() {
-            Some("md") => Some(OutputType::Markdown),
-            Some("txt") => Some(OutputType::Text),
-            Some("doc" | "docx") => Some(OutputType::Word),
-            _ => None
-        }
-    }
+#[derive(Debug, clap::Subcommand)]
+enum SessionCommand {
+    #[clap(about = "Start a session; later runs are grouped under it until `session close`")]
+    New { name: String },
+    #[clap(about = "Group later runs under an existing session")]
+    Use { name: String },
+    #[clap(about = "Stop grouping runs under the active session")]
+    Close,
+    #[clap(about = "List the sessions, marking the active one with *")]
+    List,
+    #[clap(about = "Bundle a session's summaries, transcripts and output files into a zip archive")]
+    Export {
+        #[clap(help = "Session to export [default: the active session]")]
+        name: Option<String>,
+        #[clap(long, value_name = "PATH", help = "Archive to write [default: NAME.zip in the current directory]")]
+        output: Option<String>,
+    },
 }
 
 #[::tokio::main]
 async fn main() -> Result<()> {
-    env_logger::init();
-    let config = load_config(None).await;
+    let opt = Opt::parse();
+    let mut sources = settings::Sources {
+        profile: opt.profile.clone(),
+        parameters: Vec::new(),
+        overrides: opt.overrides(),
+    };
+    let mut settings = settings::load_settings(&sources)?;
+
+    if opt.build_info {
+        println!("{}", serde_json::to_string_pretty(&build_info_json())?);
+        return Ok(());
+    }
+    if opt.version {
+        println!("distill-cli {}", env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
+    let output = progress::Output::new(
+        &settings,
+        opt.a11y,
+        opt.no_color,
+        opt.progress,
+        opt.no_spinner || opt.serving(),
+        opt.quiet,
+    )?;
+    if output.accent.is_none() {
+        progress::disable_colors();
+    }
+
+    // The parameters are read with the credentials, region and proxy the rest
+    // of the settings give, then layered in with them
+    if let Some(path) = &opt.config_from_ssm {
+        let region = settings.get_string("aws.region").ok().filter(|region| !region.is_empty());
+        let config = load_config(&settings, region.map(Region::new)).await?;
+        sources.parameters = ssm::parameters(&settings, &config, path).await?;
+        settings = settings::load_settings(&sources)?;
+    }
+
+    // With --progress json a failed run ends with an error event, so wrapping
+    // tools don't have to parse the message printed after it
+    output
+        .clone()
+        .scope(async move {
+            let result = run(opt, settings, sources, output).await;
+            if let Err(err) = &result {
+                progress::event("error", json!({ "message": format!("{:#}", err) }));
+            }
+            result
+        })
+        .await
+}
 
-    let settings = Config::builder()
-        .add_source(ConfigFile::with_name("./config.toml"))
-        .build()?;
+impl Opt {
+    // Several recordings at once would draw their spinners over each other,
+    // and nobody is watching to answer questions or review transcripts
+    fn serving(&self) -> bool {
+        matches!(self.command, Some(Command::Serve { .. }) | Some(Command::Backfill { .. }))
+    }
 
-    let s3_bucket_name = settings
-        .get_string("aws.s3_bucket_name")
-        .unwrap_or_default();
+    // Options that set a value of config.toml, which they take precedence over
+    fn overrides(&self) -> Vec<(&'static str, String)> {
+        let model = self.model.clone();
+        model
+            .clone()
+            .map(|id| ("model.model_id", id))
+            .into_iter()
+            // --model also names the model of an OpenAI-compatible API
+            .chain(model.map(|id| ("openai.model", id)))
+            .chain(self.agent.clone().map(|id| ("agent.agent_id", id)))
+            .chain(self.summarizer.map(|summarizer| ("model.summarizer", summarizer.name().to_string())))
+            .chain(self.endpoint.clone().map(|url| ("openai.endpoint", url)))
+            .chain(self.slack_channel.clone().map(|channel| ("slack.channel", channel)))
+            .chain(self.aws_profile.clone().map(|name| ("aws.profile", name)))
+            .chain(self.region.clone().map(|region| ("aws.region", region)))
+            .chain(self.min_confidence.map(|min| ("transcript.min_confidence", min.to_string())))
+            .collect()
+    }
+}
 
+async fn run(opt: Opt, settings: Config, sources: settings::Sources, output: progress::Output) -> Result<()> {
+    let serving = opt.serving();
     let Opt {
+        command,
         input_audio_file,
+        input_transcript,
+        track,
+        merge,
         output_type,
         output_filename,
+        name_by_hash,
         language_code,
         delete_s3_object,
-    } = Opt::parse();
+        report,
+        always_summarize,
+        mode,
+        profile,
+        session,
+        top_terms,
+        context_file,
+        prompt,
+        prompt_file,
+        attendees,
+        first,
+        range,
+        skip_summary,
+        review,
+        speakers,
+        no_diarization,
+        transcriber,
+        vocabulary_filter_name,
+        vocabulary_filter_method,
+        annotate_events,
+        log_level,
+        log_format,
+        non_interactive,
+        s3_bucket,
+        samples,
+        pick_sample,
+        resume_job,
+        queue,
+        dry_run,
+        from_queue,
+        ..
+    } = opt;
+
+    logging::init(&settings, log_level.as_deref(), log_format)?;
+    let mut backfill_options = None;
+    let serve_options = match command {
+        Some(Command::Init) => return init::run(&settings).await,
+        Some(Command::Flush) => return queue::flush(&settings),
+        Some(Command::Enroll { name, sample }) => {
+            return voiceprint::enroll(&settings, &name, Path::new(&shellexpand::tilde(&sample).to_string()))
+        }
+        Some(Command::Session { action }) => return session_command(&settings, action),
+        Some(Command::Actions { action: ActionsCommand::List { open, series } }) => {
+            return actions::list(&settings, series.as_deref(), open)
+        }
+        Some(Command::Brief { series }) => return brief_command(&settings, series.as_deref()).await,
+        Some(Command::Feedback { id, rating, note }) => return feedback::rate(&settings, &id, rating, note.as_deref()),
+        Some(Command::FeedbackReport) => return feedback::report(&settings),
+        Some(Command::ExportState { path }) => {
+            return state::export(&settings, Path::new(&shellexpand::tilde(&path).to_string()))
+        }
+        Some(Command::ImportState { path, overwrite }) => {
+            return state::import(&settings, Path::new(&shellexpand::tilde(&path).to_string()), overwrite)
+        }
+        Some(Command::Watch { dir, existing, args }) => {
+            return watch::watch(&settings, Path::new(&shellexpand::tilde(&dir).to_string()), existing, &args)
+        }
+        Some(Command::Schedule { day, at, now }) => {
+            return digest::schedule(&sources, day.as_deref(), at.as_deref(), now).await
+        }
+        Some(Command::Serve { listen, concurrency }) => Some(serve::Options {
+            listen: listen
+                .or_else(|| settings.get_string("serve.listen").ok())
+                .unwrap_or_else(|| "127.0.0.1:8080".to_string()),
+            concurrency: concurrency
+                .or_else(|| settings.get_int("serve.concurrency").ok().map(|n| n.max(1) as usize))
+                .unwrap_or(2),
+            token: settings.get_string("serve.token").ok().filter(|token| !token.is_empty()),
+            max_upload: settings.get_int("serve.max_upload_mb").unwrap_or(500).max(1) as u64 * 1024 * 1024,
+            shutdown_timeout: std::time::Duration::from_secs(
+                settings.get_int("serve.shutdown_timeout_secs").unwrap_or(120).max(0) as u64,
+            ),
+            job_ttl: std::time::Duration::from_secs(
+                settings.get_int("serve.job_ttl_secs").unwrap_or(86_400).max(0) as u64,
+            ),
+            max_finished_jobs: settings.get_int("serve.max_finished_jobs").unwrap_or(1000).max(0) as usize,
+        }),
+        Some(Command::Backfill { uri, since, concurrency, max_spend, batch }) => {
+            let (bucket, prefix) = backfill::parse_uri(&uri)?;
+            let since = match since {
+                Some(date) => match naming::parse_date(&date) {
+                    Some(seconds) => Some(seconds),
+                    None => bail!("\n--since must be a date like 2024-01-01, not '{}'", date),
+                },
+                None => None,
+            };
+            let state_file = settings
+                .get_string("backfill.state_file")
+                .unwrap_or_else(|_| "~/.distill/backfill.json".to_string());
+            backfill_options = Some(backfill::Options {
+                bucket,
+                prefix,
+                since,
+                concurrency: concurrency
+                    .or_else(|| settings.get_int("backfill.concurrency").ok().map(|n| n.max(1) as usize))
+                    .unwrap_or(2),
+                max_spend_usd: max_spend.or_else(|| settings.get_float("backfill.max_spend_usd").ok()),
+                state_file: PathBuf::from(shellexpand::tilde(&state_file).to_string()),
+                dry_run,
+                batch: match batch || settings.get_bool("backfill.batch").unwrap_or(false) {
+                    true => Some(batch::Batch::from_settings(&settings)?),
+                    false => None,
+                },
+            });
+            None
+        }
+        None => None,
+    };
+
+    // Options the profile sets are used when they aren't given on the command line
+    let profile_value = |key: &str| {
+        profile
+            .as_ref()
+            .and_then(|name| settings.get_string(&format!("profile.{}.{}", name, key)).ok())
+    };
+    if let Some(name) = &profile {
+        let Ok(table) = settings.get_table(&format!("profile.{}", name)) else {
+            bail!("\nNo profile named '{}'. Add a [profile.{}] section to config.toml.", name, name);
+        };
+        if let Some(key) = table.keys().find(|key| !settings::PROFILE_KEYS.contains(&key.as_str())) {
+            bail!(
+                "\nUnknown setting '{}' in [profile.{}]. Use {}.",
+                key,
+                name,
+                settings::PROFILE_KEYS.join(", ")
+            );
+        }
+    }
+    let language_code = language_code
+        .or_else(|| profile_value("language_code"))
+        .unwrap_or_else(|| "en-US".to_string());
+    let (prompt, prompt_file) = if prompt.is_none() && prompt_file.is_none() {
+        (profile_value("prompt"), profile_value("prompt_file"))
+    } else {
+        (prompt, prompt_file)
+    };
+
+    // Bedrock, and Transcribe too, run in aws.region when it is set rather than
+    // in the default region or the bucket's
+    let service_region = settings
+        .get_string("aws.region")
+        .ok()
+        .filter(|region| !region.is_empty())
+        .map(Region::new);
+    let config = load_config(&settings, service_region.clone()).await?;
+
+    // Questions during a run, e.g. whether to transcribe again in another
+    // language, need someone at the terminal
+    let interactive = !non_interactive && std::io::stdin().is_terminal() && !output.json && !serving;
+
+    // A backfill transcribes its recordings where they are, so Transcribe runs in their bucket's region
+    let s3_bucket = s3_bucket.or_else(|| backfill_options.as_ref().map(|options| options.bucket.clone()));
+
+    // A bucket given on the command line is used as-is, never falling back to the selector
+    let non_interactive = non_interactive || s3_bucket.is_some();
+    let s3_bucket_name = s3_bucket.unwrap_or_else(|| {
+        settings
+            .get_string("aws.s3_bucket_name")
+            .unwrap_or_default()
+    });
 
     // Handle output type inference and validation
-    let actual_output_type = match (&output_filename, output_type) {
-        (Some(filename), None) => {
+    let mut actual_output_types: Vec<OutputType> = Vec::new();
+    for t in output_type {
+        if !actual_output_types.contains(&t) {
+            actual_output_types.push(t);
+        }
+    }
+    match (&output_filename, actual_output_types.as_slice()) {
+        (Some(filename), []) => {
             // Try to infer from filename if type not explicitly specified
-            OutputType::from_filename(filename).unwrap_or_else(|| {
-                println!("Warning: Could not infer output type from filename '{}', defaulting to text", filename);
+            actual_output_types.push(OutputType::from_filename(filename).unwrap_or_else(|| {
+                eprintln!("Warning: Could not infer output type from filename '{}', defaulting to text", filename);
                 OutputType::Text
-            })
+            }));
         },
-        (Some(filename), Some(explicit_type)) => {
+        (Some(filename), [explicit_type]) => {
             match (filename, explicit_type) {
                 (_, OutputType::Terminal) => bail!("Output filename cannot be used with terminal output type"),
                 (_, OutputType::Slack) => bail!("Output filename cannot be used with Slack output type"),
+                (_, OutputType::Notion) => bail!("Output filename cannot be used with Notion output type"),
+                (_, OutputType::Confluence) => bail!("Output filename cannot be used with Confluence output type"),
+                (_, OutputType::Jira) => bail!("Output filename cannot be used with Jira output type"),
+                (_, OutputType::Kendra) => bail!("Output filename cannot be used with Kendra output type"),
+                (_, OutputType::QBusiness) => bail!("Output filename cannot be used with Q Business output type"),
+                (_, OutputType::Sns) => bail!("Output filename cannot be used with SNS output type"),
+                (_, OutputType::Dynamodb) => bail!("Output filename cannot be used with DynamoDB output type"),
+                (_, OutputType::Postgres) => bail!("Output filename cannot be used with Postgres output type"),
                 (_, _) => {}
             }
         
             if let Some(inferred_type) = OutputType::from_filename(filename) {
-                if explicit_type != inferred_type {
-                    println!("Warning: Output filename extension suggests {} output type, but {} was explicitly specified",
+                if *explicit_type != inferred_type {
+                    eprintln!("Warning: Output filename extension suggests {} output type, but {} was explicitly specified",
                         inferred_type,
                         explicit_type);
                 }
             }
-            explicit_type
         },
-        (None, Some(t)) => t,
-        (None, None) => OutputType::Terminal,
+        (Some(_), explicit_types) => {
+            if explicit_types.iter().all(|t| t.extension().is_none()) {
+                bail!("Output filename cannot be used without a file output type");
+            }
+        },
+        // Without --output-type, output.types from config.toml, or the terminal
+        (None, []) => {
+            for name in settings.get_array("output.types").unwrap_or_default() {
+                let name = name.into_string()?;
+                let Ok(configured) = <OutputType as clap::ValueEnum>::from_str(&name, true) else {
+                    bail!("\nUnknown output type '{}' in output.types.", name);
+                };
+                if !actual_output_types.contains(&configured) {
+                    actual_output_types.push(configured);
+                }
+            }
+            if actual_output_types.is_empty() {
+                actual_output_types.push(OutputType::Terminal);
+            }
+        }
+        (None, _) => {}
+    }
+
+    let agenda_items = match &context_file {
+        Some(path) => {
+            let path = shellexpand::tilde(path).to_string();
+            let agenda = std::fs::read_to_string(&path)
+                .with_context(|| format!("Error reading context file: {}", path))?;
+            let items = agenda::parse_agenda_items(&agenda);
+            if items.is_empty() {
+                bail!("\nNo agenda items found in {}", path);
+            }
+            items
+        }
+        None => Vec::new(),
     };
 
-    let s3_client = Client::new(&config);
+    // A custom prompt replaces the mode's template for every recording in the run
+    let prompt_template = match (&prompt_file, &prompt) {
+        (Some(path), _) => {
+            let path = shellexpand::tilde(path).to_string();
+            Some(
+                std::fs::read_to_string(&path)
+                    .with_context(|| format!("Error reading prompt file: {}", path))?,
+            )
+        }
+        (None, Some(name)) => match settings.get_string(&format!("prompt.{}", name)) {
+            Ok(template) => Some(template),
+            Err(_) => bail!("\nNo prompt named '{}'. Add a `{} = \"\"\"...\"\"\"` template under [prompt] in config.toml.", name, name),
+        },
+        (None, None) => None,
+    };
 
-    let mut bucket_name = String::new();
+    let watchlist = compliance::watchlist(&settings)?;
+    let transforms = transforms::pipeline(&settings)?;
+    let size_limit = limits::SizeLimit::from_settings(&settings)?;
+    let confidence_threshold = confidence::Threshold::from_settings(&settings)?;
+    let speech_check = speech::SpeechCheck::from_settings(&settings)?;
+    let review = (review || settings.get_bool("transcript.review").unwrap_or(false)) && !serving;
+    let quota = quota::Quota::from_settings(&settings, profile.as_deref())?;
+    let session = match &session {
+        Some(name) => Some(session::open(&settings, name)?),
+        None => session::active(&settings)?,
+    };
 
-    println!("🧙 Welcome to Distill CLI");
+    let clip = match (&first, &range) {
+        (Some(first), _) => Some((0.0, audio::parse_duration(first)?)),
+        (_, Some(range)) => {
+            let (start, end) = audio::parse_range(range)?;
+            Some((start, end - start))
+        }
+        (None, None) => None,
+    };
 
-    let resp = &list_buckets(&s3_client).await;
+    // Confidential recordings can be transcribed locally, so no audio leaves the machine
+    let transcriber = match transcriber {
+        Some(transcriber) => transcriber,
+        None => match settings.get_string("transcript.transcriber") {
+            Ok(name) => match <TranscriberKind as clap::ValueEnum>::from_str(&name, true) {
+                Ok(transcriber) => transcriber,
+                Err(_) => bail!("\nUnknown transcriber '{}' in transcript.transcriber. Use amazon or whisper-local.", name),
+            },
+            Err(_) => TranscriberKind::Amazon,
+        },
+    };
+    // Amazon Transcribe is set up once the bucket's region is known
+    let other_transcriber: Option<Box<dyn transcribe::Transcriber>> = match transcriber {
+        TranscriberKind::WhisperLocal if !input_transcript.is_empty() => None,
+        TranscriberKind::WhisperLocal => {
+            if resume_job.is_some() {
+                bail!("\n--resume-job continues an Amazon Transcribe job and can't be used with the whisper-local transcriber.");
+            }
+            if vocabulary_filter_name.is_some() {
+                bail!("\n--vocabulary-filter-name is applied by Amazon Transcribe and can't be used with the whisper-local transcriber.");
+            }
+            Some(Box::new(whisper::Whisper::from_settings(&settings)?))
+        }
+        TranscriberKind::Amazon => None,
+    };
+    let skip_s3 = other_transcriber.as_ref().is_some_and(|transcriber| !transcriber.reads_from_s3());
 
-    if !s3_bucket_name.is_empty() {
-        if resp
-            .as_ref()
-            .ok()
-            .and_then(|buckets| buckets.iter().find(|b| b.as_str() == s3_bucket_name))
-            .is_some()
-        {
-            println!("📦 S3 bucket name: {}", s3_bucket_name);
-            bucket_name = s3_bucket_name;
+    // Directories are expanded into every supported audio file they contain
+    let transcript_input = !input_transcript.is_empty();
+    let mut input_files: Vec<PathBuf> = input_transcript
+        .iter()
+        .map(|input| PathBuf::from(shellexpand::tilde(input).to_string()))
+        .collect();
+    for input in &input_audio_file {
+        let input_path = PathBuf::from(shellexpand::tilde(input).to_string());
+        if input_path.is_dir() {
+            let files = audio_files_in(&input_path)?;
+            if files.is_empty() {
+                bail!("\nNo supported audio files found in {}", input_path.display());
+            }
+            status!("📂", "Found {} audio files in {}", files.len(), input_path.display());
+            input_files.extend(files);
         } else {
-            println!(
-                "Error: The configured S3 bucket '{}' was not found.",
-                s3_bucket_name
-            );
+            input_files.push(input_path);
         }
     }
 
-    if bucket_name.is_empty() {
-        match resp {
-            Ok(bucket_names) => {
-                let selection = Select::with_theme(&ColorfulTheme::default())
-                    .with_prompt("Choose a destination S3 bucket for your audio file")
-                    .default(0)
-                    .items(&bucket_names[..])
-                    .interact()?;
+    // Tracks of the same recording are always processed together
+    let mut track_names = Vec::new();
+    for spec in &track {
+        let (name, file) = spec
+            .split_once('=')
+            .with_context(|| format!("Invalid track '{}', expected NAME=FILE", spec))?;
+        track_names.push(name.trim().to_string());
+        input_files.push(PathBuf::from(shellexpand::tilde(file.trim()).to_string()));
+    }
 
-                bucket_name.clone_from(&bucket_names[selection]);
-            }
-            Err(err) => {
-                println!("Error getting bucket list: {}", err);
-                bail!("\nError getting bucket list: {}", err);
-            }
-        };
+    // A resumed job has no local file; its name stands in for the recording when naming outputs
+    if let Some(job_name) = &resume_job {
+        input_files.push(PathBuf::from(job_name));
     }
 
-    if bucket_name.is_empty() {
-        bail!("\nNo valid S3 bucket found. Please check your AWS configuration.");
+    // Each recording is processed on its own unless --merge combines them into one
+    let recordings: Vec<Vec<PathBuf>> = if merge || !track_names.is_empty() {
+        vec![input_files]
+    } else {
+        input_files.into_iter().map(|file| vec![file]).collect()
+    };
+    let is_batch = recordings.len() > 1;
+    if is_batch && output_filename.is_some() {
+        bail!("\nOutput filename cannot be used with multiple recordings unless --merge is set");
     }
 
-    let mut spinner = Spinner::new(spinners::Dots7, "Uploading file to S3...", Color::Green);
+    if queue {
+        let path = queue::add(&settings)?;
+        eprintln!("{}Run queued as {}; process it later with `distill-cli flush`", progress::icon("📥"), path.display());
+        return Ok(());
+    }
 
-    // Load the bucket region and create a new client to use that region
-    let region = bucket_region(&s3_client, &bucket_name).await?;
-    println!();
-    spinner.update(
-        spinners::Dots7,
-        format!("Using bucket region {}", region),
-        None,
-    );
-    let regional_config = load_config(Some(region)).await;
-    let regional_s3_client = Client::new(&regional_config);
+    let s3_client = Client::new(&config);
+
+    status!("🧙", "Welcome to Distill CLI");
+
+    // Transcript inputs, resumed jobs and transcribers that don't read from S3
+    // skip it entirely, so there is no bucket to resolve
+    let (bucket_name, regional_config) = if transcript_input || resume_job.is_some() || skip_s3 {
+        (String::new(), config.clone())
+    } else {
+        // Without a connection the run is queued for `flush`, e.g. when recording on a flight
+        let queue_when_offline = !from_queue && !dry_run && !serving && settings.get_bool("queue.when_offline").unwrap_or(true);
+        let bucket_name = match select_bucket(&s3_client, s3_bucket_name, non_interactive).await {
+            Ok(bucket_name) => bucket_name,
+            Err(err) if queue_when_offline && net::is_unreachable(&err) => {
+                let path = queue::add(&settings)?;
+                eprintln!("{}No connection to AWS. Run queued as {}; process it later with `distill-cli flush`", progress::icon("📥"), path.display());
+                return Ok(());
+            }
+            Err(err) => return Err(err),
+        };
 
-    // Handle conversion of relative paths to absolute paths
-    let file_path = Path::new(&input_audio_file);
-    let file_name = file_path
-        .file_name()
-        .unwrap()
-        .to_string_lossy()
-        .into_owned();
+        // Load the bucket region and create a new client to use that region
+        let mut spinner = Progress::new("Looking up bucket region...");
+        let region = bucket_region(&s3_client, &bucket_name).await?;
+        spinner.stop_and_persist("🌍", &format!("Using bucket region {}", region));
+        if let Some(service_region) = service_region.as_ref().filter(|service_region| **service_region != region) {
+            eprintln!(
+                "Warning: Amazon Transcribe runs in {} but the bucket is in {}; Transcribe may not be able to read the upload.",
+                service_region, region
+            );
+        }
+        (bucket_name, load_config(&settings, Some(region)).await?)
+    };
 
-    let absolute_path = shellexpand::tilde(file_path.to_str().unwrap()).to_string();
-    let absolute_path = Path::new(&absolute_path);
+    let transcribe_config = if service_region.is_some() { config.clone() } else { regional_config.clone() };
+    let transcriber = other_transcriber.unwrap_or_else(|| {
+        Box::new(transcribe::AmazonTranscribe {
+            config: transcribe_config.clone(),
+        })
+    });
+    let ctx = RunContext {
+        settings,
+        config,
+        regional_config,
+        transcribe_config,
+        s3_client,
+        bucket_name,
+        output_types: actual_output_types,
+        language_code,
+        mode,
+        delete_s3_object,
+        report,
+        always_summarize,
+        top_terms,
+        agenda_items,
+        clip,
+        skip_summary,
+        transcript_input,
+        transcriber,
+        track_names,
+        samples,
+        pick_sample,
+        resume_job,
+        max_speakers: (!no_diarization).then(|| speakers.unwrap_or(10)),
+        vocabulary_filter: vocabulary_filter_name.map(|name| (name, vocabulary_filter_method)),
+        prompt_template,
+        attendees,
+        watchlist,
+        annotate_events,
+        transforms,
+        size_limit,
+        confidence_threshold,
+        speech_check,
+        session,
+        quota,
+        name_by_hash,
+        interactive,
+        review,
+        sources,
+        output,
+    };
 
-    if !absolute_path.exists() {
-        bail!("\nThe path {} does not exist.", absolute_path.display());
+    if let Some(options) = serve_options {
+        return serve::serve(ctx, options).await;
+    }
+    if let Some(options) = backfill_options {
+        return backfill::backfill(ctx, options).await;
     }
 
-    let canonicalized_path = absolute_path.canonicalize()?;
-    let body = ByteStream::from_path(&canonicalized_path)
-        .await
-        .with_context(|| format!("Error loading file: {}", canonicalized_path.display()))?;
-
-    let _upload_result = regional_s3_client
-        .put_object()
-        .bucket(&bucket_name)
-        .key(&file_name)
-        .body(body)
-        .send()
-        .await
-        .context("Failed to upload to S3")?;
-
-    let s3_uri = format!("s3://{}/{}", bucket_name, file_name);
-
-    println!();
-    spinner.update(spinners::Dots7, "Summarizing text...", None);
-
-    // Transcribe the audio
-    let transcription: String = transcribe::transcribe_audio(
-        &regional_config,
-        file_path,
-        &s3_uri,
-        &mut spinner,
-        &language_code,
-    )
-    .await?;
-
-    // Summarize the transcription
-    spinner.update(spinners::Dots7, "Summarizing text...", None);
-    let summarized_text = summarize::summarize_text(&config, &transcription, &mut spinner).await?;
-
-    match actual_output_type {
-        OutputType::Word => {
-            let filename = match &output_filename {
-                Some(f) => f,
-                None => "summary.docx",
-            };
-            let file = File::create(filename)
-                .map_err(|e| anyhow::anyhow!("Error creating file: {}", e))?;
-
-            // Creating a new document and adding paragraphs
-            let doc = Docx::new()
-                .add_paragraph(Paragraph::new().add_run(Run::new().add_text(&summarized_text)))
-                .add_paragraph(Paragraph::new().add_run(Run::new().add_text("\n\n")))
-                .add_paragraph(Paragraph::new().add_run(Run::new().add_text("Transcription:\n")))
-                .add_paragraph(Paragraph::new().add_run(Run::new().add_text(&transcription)));
-
-            // Building and saving the document
-            doc.build()
-                .pack(file)
-                .map_err(|e| anyhow::anyhow!("Error writing Word document: {}", e))?;
-
-            spinner.success("Done!");
-            println!(
-                "💾 Summary and transcription written to {}",
-                filename
-            );
+    if dry_run {
+        ctx.quota.check()?;
+        for files in &recordings {
+            preflight(&ctx, files)?;
         }
-        OutputType::Text => {
-            let filename = match &output_filename {
-                Some(f) => f,
-                None => "summary.txt",
-            };
-            let mut file = File::create(filename)
-                .map_err(|e| anyhow::anyhow!("Error creating file: {}", e))?;
-
-            file.write_all(summarized_text.as_bytes())
-                .map_err(|e| anyhow::anyhow!("Error creating file: {}", e))?;
-            file.write_all(b"\n\nTranscription:\n")
-                .map_err(|e| anyhow::anyhow!("Error creating file: {}", e))?;
-            file.write_all(transcription.as_bytes())
-                .map_err(|e| anyhow::anyhow!("Error creating file: {}", e))?;
-
-            spinner.success("Done!");
-            println!(
-                "💾 Summary and transcription written to {}",
-                filename
+        return Ok(());
+    }
+
+    let mut failures = 0;
+    for (index, files) in recordings.iter().enumerate() {
+        if is_batch && !progress::is_quiet() {
+            eprintln!();
+            eprintln!(
+                "{}[{}/{}] {}",
+                progress::icon("🎧"),
+                index + 1,
+                recordings.len(),
+                files[0].display()
             );
         }
-        OutputType::Terminal => {
-            spinner.success("Done!");
-            println!();
-            println!("Summary:\n{}\n", summarized_text);
-            println!("Transcription:\n{}\n", transcription);
+        // With --name-by-hash outputs are named after the input's content, and
+        // batch runs otherwise name each output after its audio file
+        let file_output = if name_by_hash {
+            let hash = naming::content_hash(files)?;
+            ctx.output_types
+                .iter()
+                .find_map(|t| t.extension())
+                .map(|ext| format!("{}.{}", hash, ext))
+        } else if is_batch {
+            let file_path = &files[0];
+            // A suffix keeps a text summary from overwriting its own transcript
+            let stem = file_path.file_stem().unwrap_or_default().to_string_lossy();
+            let suffix = if transcript_input { "-summary" } else { "" };
+            ctx.output_types
+                .iter()
+                .find_map(|t| t.extension())
+                .map(|ext| format!("{}{}.{}", stem, suffix, ext))
+        } else {
+            output_filename.clone()
+        };
+
+        // A used-up quota stops the rest of a batch too
+        ctx.quota.check()?;
+        if let Err(err) = process_recording(&ctx, files, file_output.as_deref()).await {
+            if !is_batch {
+                return Err(err);
+            }
+            eprintln!("Error processing {}: {:#}", files[0].display(), err);
+            progress::event(
+                "error",
+                json!({ "message": format!("{:#}", err), "file": files[0].display().to_string() }),
+            );
+            failures += 1;
         }
-        OutputType::Markdown => {
-            let filename = match &output_filename {
-                Some(f) => f,
-                None => "summary.md",
-            };
-            let mut file = File::create(filename)
-                .map_err(|e| anyhow::anyhow!("Error creating file: {}", e))?;
+    }
 
-            let summary_md = format!("# Summary\n\n{}", summarized_text);
-            let mut transcription_md = format!("\n\n# Transcription\n\n{}", transcription);
-            transcription_md = transcription_md.replace("spk_", "\nspk_");
-            let markdown_content = format!("{}{}", summary_md, transcription_md);
+    if failures > 0 {
+        bail!("\n{} of {} files failed to process", failures, recordings.len());
+    }
 
-            file.write_all(markdown_content.as_bytes())
-                .map_err(|e| anyhow::anyhow!("Error writing Markdown file: {}", e))?;
+    Ok(())
+}
 
-            spinner.success("Done!");
-            println!(
-                "💾 Summary and transcription written to {}",
-                filename
-            );
-        }
-        OutputType::Slack => {
-            let client = ReqwestClient::new();
 
-            let slack_webhook_endpoint = settings
-                .get_string("slack.webhook_endpoint")
-                .unwrap_or_default();
+// Speech runs at about 150 words a minute, and English text at about 4 tokens per 3 words
+const WORDS_PER_MINUTE: f64 = 150.0;
+const TOKENS_PER_WORD: f64 = 4.0 / 3.0;
 
-            if slack_webhook_endpoint.is_empty() {
-                spinner.stop_and_persist(
-                    "⚠️",
-                    "Slack webhook endpoint is not configured. Skipping Slack notification.",
-                );
-                println!("Summary:\n{}\n", summarized_text);
-            } else {
-                let (summary, action_items, rest) = parse_summary_sections(&summarized_text);
-                let _content = format!("A summarization job just completed:\n\n{}\n{}", input_audio_file, summarized_text);
-                let payload = json!({
-                    "Content": input_audio_file,
-                    "SummaryText": summary,
-                    "KeyActions": action_items,
-                    "Others": rest
-                });
-                match client
-                    .post(slack_webhook_endpoint)
-                    .header("Content-Type", "application/json")
-                    .json(&payload)
-                    .send()
-                    .await
-                {
-                    Ok(response) => {
-                        if response.status().is_success() {
-                            spinner.success("Summary sent to Slack!");
-                        } else {
-                            spinner.stop_and_persist("❌", "Failed to send summary to Slack!");
-                            eprintln!("Error sending summary to Slack: {}", response.status());
-                        }
-                    }
-                    Err(err) => {
-                        spinner.stop_and_persist("❌", "Failed to send summary to Slack!");
-                        eprintln!("Error sending summary to Slack: {}", err);
-                    }
-                };
+// What --dry-run prints for one recording: its size and length, the Transcribe
+// and Bedrock usage it would incur with the estimated cost, and roughly how long
+// it would take. Nothing is uploaded and no paid API is called.
+fn preflight(ctx: &RunContext, files: &[PathBuf]) -> Result<()> {
+    let settings = &ctx.settings;
+    let mut estimate = report::RunReport::new(report::Pricing::from_settings(settings));
+    let meeting = Meeting::new(ctx, files);
+
+    // Seconds of speech across the recording's files
+    let mut seconds = 0.0;
+    let mut transcript_words = 0;
+    for file_path in files {
+        if ctx.resume_job.is_some() {
+            eprintln!("{}Transcription job: {} (already transcribed)", progress::icon("📝"), file_path.display());
+            continue;
+        }
+        if !file_path.exists() {
+            bail!("\nThe path {} does not exist.", file_path.display());
+        }
+        let size = std::fs::metadata(file_path)?.len();
+        if ctx.transcript_input {
+            let words = transcribe::read_transcript_file(file_path)?.text().split_whitespace().count();
+            eprintln!("{}{} ({:.1} MB, {} words)", progress::icon("📄"), file_path.display(), size as f64 / 1_000_000.0, words);
+            transcript_words += words;
+            continue;
+        }
+
+        let length = match audio::duration(file_path) {
+            Ok(length) => ctx.clip.map_or(length, |(start, clip)| clip.min(length - start).max(0.0)),
+            Err(err) => {
+                // Without ffprobe the cost can still be estimated from the bitrate
+                // of a typical 128 kbps recording
+                eprintln!("Warning: {:#}; estimating the length from the file size", err);
+                let length = size as f64 / 16_000.0;
+                ctx.clip.map_or(length, |(_, clip)| clip.min(length))
             }
+        };
+        eprintln!(
+            "{}{} ({:.1} MB, {})",
+            progress::icon("🎧"),
+            file_path.display(),
+            size as f64 / 1_000_000.0,
+            transcribe::format_timestamp(length)
+        );
+        // Only Amazon Transcribe's price is known; Whisper runs locally for free
+        if ctx.transcriber.reads_from_s3() {
+            estimate.add_transcribed_audio(length);
         }
+        seconds += length;
+    }
+    if !ctx.transcript_input && !ctx.transcriber.reads_from_s3() {
+        eprintln!("{}Transcribed with {}; nothing is uploaded", progress::icon("🔒"), ctx.transcriber.name());
+    }
+    if !ctx.transcript_input && ctx.resume_job.is_none() {
+        transcript_words = (seconds / 60.0 * WORDS_PER_MINUTE) as usize;
     }
+    eprintln!("{}Meeting: {} ({})", progress::icon("🗓️"), meeting.title, meeting.date);
 
-    // After processing, check if the user wants to delete the S3 object
-    if delete_s3_object == "Y" {
-        s3_client
-            .delete_object()
-            .bucket(&bucket_name)
-            .key(&file_name)
-            .send()
-            .await?;
+    // The prompt is filled in the same way as for a real run, so a broken
+    // template fails here rather than after the upload
+    let prompt_template = match &ctx.prompt_template {
+        Some(template) => template.clone(),
+        None => settings
+            .get_string(ctx.mode.prompt_key())
+            .unwrap_or_else(|_| ctx.mode.default_prompt().to_string()),
+    };
+    let prompt = summarize::fill_prompt(
+        &prompt_template,
+        &summarize::PromptValues {
+            title: &meeting.title,
+            date: &meeting.date,
+            attendees: &ctx.attendees.join(", "),
+            duration: (seconds > 0.0).then_some(seconds),
+        },
+    )?;
+
+    match Summarizer::from_settings(settings)? {
+        Summarizer::Openai => {
+            let chat = openai::Chat::from_settings(settings)?;
+            eprintln!("{}Summarizing with {} at {}", progress::icon("🤖"), chat.model, chat.endpoint);
+        }
+        Summarizer::Bedrock => {
+            if settings.get_string("model.model_id").unwrap_or_default().is_empty() && Agent::from_settings(settings).is_none() {
+                bail!("\nNo model configured. Set model.model_id in config.toml or pass --model.");
+            }
+        }
+    }
+    if ctx.output_types.contains(&OutputType::Slack)
+        && settings.get_string("slack.webhook_endpoint").unwrap_or_default().is_empty()
+        && (settings.get_string("slack.bot_token").unwrap_or_default().is_empty()
+            || settings.get_string("slack.channel").unwrap_or_default().is_empty())
+    {
+        eprintln!("Warning: Slack webhook endpoint is not configured; nothing would be posted to Slack.");
+    }
+    if ctx.output_types.contains(&OutputType::Notion)
+        && (settings.get_string("notion.token").unwrap_or_default().is_empty()
+            || settings.get_string("notion.parent_id").unwrap_or_default().is_empty())
+    {
+        eprintln!("Warning: notion.token or notion.parent_id is not configured; no Notion page would be created.");
+    }
+    if ctx.output_types.contains(&OutputType::Confluence)
+        && ["base_url", "email", "api_token", "space_key"]
+            .iter()
+            .any(|key| settings.get_string(&format!("confluence.{}", key)).unwrap_or_default().is_empty())
+    {
+        eprintln!("Warning: Confluence is not fully configured; no Confluence page would be published.");
+    }
+    if ctx.output_types.contains(&OutputType::Jira)
+        && ["base_url", "email", "api_token", "project_key"]
+            .iter()
+            .any(|key| settings.get_string(&format!("jira.{}", key)).unwrap_or_default().is_empty())
+    {
+        eprintln!("Warning: Jira is not fully configured; no Jira issues would be created.");
+    }
+    if ctx.output_types.contains(&OutputType::Kendra) && knowledge::Index::kendra(settings).is_none() {
+        eprintln!("Warning: Kendra is not configured; no documents would be added to an index.");
+    }
+    if ctx.output_types.contains(&OutputType::QBusiness) && knowledge::Index::q_business(settings).is_none() {
+        eprintln!("Warning: Q Business is not configured; no documents would be added to an index.");
+    }
+    if ctx.output_types.contains(&OutputType::Sns) && sns::Topic::from_settings(settings)?.is_none() {
+        eprintln!("Warning: sns.topic_arn is not configured; nothing would be published to SNS.");
+    }
+    if ctx.output_types.contains(&OutputType::Dynamodb) && database::Store::dynamodb(settings).is_none() {
+        eprintln!("Warning: dynamodb.table is not configured; nothing would be saved to DynamoDB.");
+    }
+    if ctx.output_types.contains(&OutputType::Postgres) && database::Store::postgres(settings).is_none() {
+        eprintln!("Warning: postgres.url is not configured; nothing would be saved to Postgres.");
     }
 
-    Ok(())
-}
+    // Each model call reads the prompt and transcript, and may write up to model.max_tokens
+    let mut model_calls = 0;
+    if !ctx.skip_summary {
+        model_calls += ctx.samples as u64;
+        if ctx.samples > 1 && !ctx.pick_sample {
+            // The judge reads every candidate as well as the transcript
+            model_calls += 1;
+        }
+        if !ctx.agenda_items.is_empty() && !ctx.mode.text_only() {
+            model_calls += 1;
+        }
+        if ctx.mode == Mode::Qa {
+            model_calls += 1;
+        }
+    }
+    let output_tokens = settings.get_int("model.max_tokens").unwrap_or_default().max(0) as u64;
+    let prompt_words = prompt.split_whitespace().count();
 
-// Load the user's aws config, default region to us-east-1 if none is provided or can be found
-async fn load_config(region: Option<Region>) -> SdkConfig {
-    let mut config = aws_config::from_env();
-    match region {
-        Some(region) => config = config.region(region),
-        None => {
-            config = config.region(RegionProviderChain::default_provider().or_else("us-east-1"))
+    // Transcripts over summary.max_words are cut short or summarized in parts
+    let size_limit = &ctx.size_limit;
+    let mut summarized_words = transcript_words;
+    let mut chunk_calls = 0;
+    if !ctx.skip_summary && size_limit.exceeded_by(transcript_words) {
+        match size_limit.policy {
+            limits::Policy::Fail => eprintln!(
+                "Warning: The transcript (about {} words) is longer than summary.max_words ({}); the run would stop before summarizing.",
+                transcript_words, size_limit.max_words
+            ),
+            limits::Policy::Truncate => {
+                eprintln!(
+                    "{}Only the first {} of about {} words would be summarized (summary.oversize_policy = \"truncate\")",
+                    progress::icon("✂️"),
+                    size_limit.max_words,
+                    transcript_words
+                );
+                summarized_words = size_limit.max_words;
+            }
+            limits::Policy::Chunk => {
+                let parts = transcript_words.div_ceil(size_limit.max_words);
+                eprintln!(
+                    "{}The transcript would be summarized in {} parts (summary.oversize_policy = \"chunk\")",
+                    progress::icon("✂️"),
+                    parts
+                );
+                estimate.add_usage(
+                    "Summarization",
+                    summarize::Usage {
+                        input_tokens: ((transcript_words + prompt_words * parts) as f64 * TOKENS_PER_WORD) as u64,
+                        output_tokens: output_tokens * parts as u64,
+                        ..Default::default()
+                    },
+                );
+                chunk_calls = parts as u64;
+                // Later calls read the partial summaries instead of the transcript
+                summarized_words = (output_tokens as f64 * parts as f64 / TOKENS_PER_WORD) as usize;
+            }
         }
     }
 
-    // Resolves issues with uploading large S3 files
-    // See https://github.com/awslabs/aws-sdk-rust/issues/1146
-    config = config
-        .stalled_stream_protection(
-            StalledStreamProtectionConfig::disabled()
+    let input_tokens = ((summarized_words + prompt_words) as f64 * TOKENS_PER_WORD) as u64;
+    if model_calls > 0 {
+        estimate.add_usage(
+            "Summarization",
+            summarize::Usage {
+                input_tokens: input_tokens * model_calls,
+                output_tokens: output_tokens * model_calls,
+                ..Default::default()
+            },
         );
+    }
 
-    config.load().await
+    // Batch transcription typically finishes in about a third of the audio's
+    // length, and a summary in well under a minute
+    let model_calls = model_calls + chunk_calls;
+    let minutes = (seconds * 0.35 + 30.0 * model_calls as f64) / 60.0;
+    estimate.print_cost();
+    eprintln!(
+        "{}Estimated processing time: about {:.0} min ({} model calls, up to {} output tokens each)",
+        progress::icon("⏱️"),
+        minutes.max(1.0),
+        model_calls,
+        output_tokens
+    );
+    Ok(())
 }
 
-fn parse_summary_sections(summarized_text: &str) -> (String, String, String) {
-    // Initialize empty sections
-    let mut summary = String::new();
-    let mut action_items = String::new();
-    let mut rest = String::new();
-
-    // Split text by lines for processing
-    let lines: Vec<&str> = summarized_text.lines().collect();
-    let mut current_section = "";
-    
-    for line in lines {
-        // Check for section headers
-        if line.to_lowercase().contains("key points") || 
-           line.to_lowercase().contains("summary") {
-            current_section = "summary";
-            continue;
-        } else if line.to_lowercase().contains("action item") || 
-                  line.to_lowercase().contains("next step") {
-            current_section = "action";
-            continue;
-        } else if line.trim().is_empty() {
-            continue;
+// `distill session ...`
+fn session_command(settings: &Config, action: SessionCommand) -> Result<()> {
+    match action {
+        SessionCommand::New { name } => session::create(settings, &name),
+        SessionCommand::Use { name } => {
+            session::activate(settings, Some(&name))?;
+            eprintln!("{}Runs are now grouped under session '{}'", progress::icon("🗂️"), session::open(settings, &name)?.name);
+            Ok(())
         }
-
-        // Append content to appropriate section
-        match current_section {
-            "summary" => summary.push_str(&format!("{}\n", line)),
-            "action" => action_items.push_str(&format!("{}\n", line)),
-            _ => rest.push_str(&format!("{}\n", line)),
+        SessionCommand::Close => {
+            if let Some(active) = session::active(settings)? {
+                session::activate(settings, None)?;
+                eprintln!("{}Closed session '{}'", progress::icon("🗂️"), active.name);
+            }
+            Ok(())
+        }
+        SessionCommand::List => session::list(settings),
+        SessionCommand::Export { name, output } => {
+            let session = match name {
+                Some(name) => session::open(settings, &name)?,
+                None => session::active(settings)?
+                    .context("\nNo active session. Name the session to export, e.g. `distill session export q3-planning`.")?,
+            };
+            let path = PathBuf::from(shellexpand::tilde(&output.unwrap_or_else(|| format!("{}.zip", session.id()))).to_string());
+            session.export(&path)?;
+            eprintln!(
+                "{}Exported session '{}' ({} run(s)) to {}",
+                progress::icon("📦"),
+                session.name,
+                session.run_count(),
+                path.display()
+            );
+            Ok(())
         }
     }
-
-    // Trim whitespace from all sections
-    (
-        summary.trim().to_string(),
-        action_items.trim().to_string(),
-        rest.trim().to_string()
-    )
 }
 
-async fn list_buckets(client: &Client) -> Result<Vec<String>> {
-    let resp = client.list_buckets().send().await?;
-    let buckets = resp.buckets();
+// `distill brief`: write the brief of the named or the active session with
+// the model, in Bedrock's region
+async fn brief_command(settings: &Config, series: Option<&str>) -> Result<()> {
+    let session = match series {
+        Some(name) => session::open(settings, name)?,
+        None => session::active(settings)?
+            .context("\nNo active session. Name the series, e.g. `distill brief --series q3-planning`.")?,
+    };
+    let region = settings.get_string("aws.region").ok().filter(|region| !region.is_empty());
+    let config = load_config(settings, region.map(Region::new)).await?;
+    let mut spinner = Progress::new("Reading the session's summaries...");
+    let (path, _) = brief::write(settings, &config, &session, &mut spinner).await?;
+    spinner.success(&format!("Wrote the brief of '{}' ({} run(s)) to {}", session.name, session.run_count(), path.display()));
+    Ok(())
+}
 
-    let bucket_names: Vec<String> = buckets
-        .iter()
-        .map(|bucket| bucket.name().unwrap_or_default().to_string())
-        .collect();
+// What this binary is and what it can do, for inventorying installs
+fn build_info_json() -> serde_json::Value {
+    use clap::ValueEnum;
 
-    Ok(bucket_names)
+    let names = |values: Vec<Option<clap::builder::PossibleValue>>| -> Vec<String> {
+        values
+            .into_iter()
+            .flatten()
+            .map(|v| v.get_name().to_string())
+            .collect()
+    };
+    json!({
+        "name": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "git_sha": env!("DISTILL_GIT_SHA"),
+        "build_date": env!("DISTILL_BUILD_DATE"),
+        "target": env!("DISTILL_TARGET"),
+        "profile": env!("DISTILL_PROFILE"),
+        "backends": {
+            "storage": ["amazon-s3"],
+            "transcription": TranscriberKind::value_variants()
+                .iter()
+                .map(TranscriberKind::backend)
+                .collect::<Vec<_>>(),
+            "summarization": Summarizer::value_variants()
+                .iter()
+                .flat_map(Summarizer::backends)
+                .collect::<Vec<_>>(),
+            "moderation": ["amazon-bedrock-guardrails"],
+        },
+        "output_types": names(OutputType::value_variants().iter().map(|t| t.to_possible_value()).collect()),
+        "modes": names(Mode::value_variants().iter().map(|m| m.to_possible_value()).collect()),
+    })
 }
 
-async fn bucket_region(client: &Client, bucket_name: &str) -> Result<Region> {
-    let resp = client
-        .get_bucket_location()
-        .bucket(bucket_name)
-        .send()
-        .await?;
 
-    let location_constraint = resp
-        .location_constraint()
-        .context("Bucket has no location_constraint")?;
+// Supported audio files directly inside `dir`, sorted by name
+fn audio_files_in(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("Error reading directory: {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && audio::is_audio_file(path))
+        .collect();
+    files.sort();
 
-    if location_constraint.as_str() == "" {
-        Ok(Region::new("us-east-1"))
-    } else {
-        Ok(Region::new(location_constraint.as_str().to_owned()))
-    }
+    Ok(files)
 }
@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+// Upper bounds of the job duration histogram's buckets, in seconds. Most
+// recordings take a few minutes to transcribe and summarize.
+const DURATION_BUCKETS: [f64; 9] = [30.0, 60.0, 120.0, 300.0, 600.0, 900.0, 1800.0, 3600.0, 7200.0];
+
+// What `distill serve` has done since it started, for GET /metrics
+#[derive(Default)]
+pub struct Metrics {
+    succeeded: u64,
+    failed: u64,
+    // Failed jobs by the pipeline stage they failed in, e.g. "transcription"
+    failures: BTreeMap<&'static str, u64>,
+    // Finished jobs by duration bucket; see DURATION_BUCKETS. The last is for
+    // jobs longer than every bucket.
+    durations: [u64; DURATION_BUCKETS.len() + 1],
+    duration_sum: f64,
+}
+
+// Values read from the server when the metrics are rendered
+pub struct Gauges {
+    pub queued: usize,
+    pub running: usize,
+}
+
+impl Metrics {
+    pub fn succeeded(&mut self, duration: Duration) {
+        self.succeeded += 1;
+        self.observe(duration);
+    }
+
+    // A job that failed in `stage`; `duration` is None when it never started
+    pub fn failed(&mut self, stage: &'static str, duration: Option<Duration>) {
+        self.failed += 1;
+        *self.failures.entry(stage).or_default() += 1;
+        if let Some(duration) = duration {
+            self.observe(duration);
+        }
+    }
+
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        let bucket = DURATION_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(DURATION_BUCKETS.len());
+        self.durations[bucket] += 1;
+        self.duration_sum += seconds;
+    }
+
+    // The Prometheus text exposition format, e.g.
+    // distill_jobs_total{status="succeeded"} 12
+    pub fn render(&self, gauges: &Gauges) -> String {
+        let mut text = String::new();
+        header(&mut text, "distill_jobs_total", "counter", "Jobs finished, by status.");
+        let _ = writeln!(text, "distill_jobs_total{{status=\"succeeded\"}} {}", self.succeeded);
+        let _ = writeln!(text, "distill_jobs_total{{status=\"failed\"}} {}", self.failed);
+
+        header(&mut text, "distill_job_failures_total", "counter", "Failed jobs, by the stage they failed in.");
+        for (stage, count) in &self.failures {
+            let _ = writeln!(text, "distill_job_failures_total{{stage=\"{}\"}} {}", stage, count);
+        }
+
+        header(
+            &mut text,
+            "distill_job_duration_seconds",
+            "histogram",
+            "How long jobs took from leaving the queue to finishing.",
+        );
+        let mut cumulative = 0;
+        for (bound, count) in DURATION_BUCKETS.iter().zip(&self.durations) {
+            cumulative += count;
+            let _ = writeln!(text, "distill_job_duration_seconds_bucket{{le=\"{}\"}} {}", bound, cumulative);
+        }
+        let count: u64 = self.durations.iter().sum();
+        let _ = writeln!(text, "distill_job_duration_seconds_bucket{{le=\"+Inf\"}} {}", count);
+        let _ = writeln!(text, "distill_job_duration_seconds_sum {}", self.duration_sum);
+        let _ = writeln!(text, "distill_job_duration_seconds_count {}", count);
+
+        header(&mut text, "distill_jobs_queued", "gauge", "Jobs waiting for a free slot.");
+        let _ = writeln!(text, "distill_jobs_queued {}", gauges.queued);
+        header(&mut text, "distill_jobs_running", "gauge", "Jobs being processed.");
+        let _ = writeln!(text, "distill_jobs_running {}", gauges.running);
+        text
+    }
+}
+
+fn header(text: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(text, "# HELP {} {}", name, help);
+    let _ = writeln!(text, "# TYPE {} {}", name, kind);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_jobs_into_cumulative_buckets() {
+        let mut metrics = Metrics::default();
+        metrics.succeeded(Duration::from_secs(45));
+        metrics.succeeded(Duration::from_secs(10_000));
+        metrics.failed("transcription", Some(Duration::from_secs(20)));
+        metrics.failed("queue", None);
+        let text = metrics.render(&Gauges { queued: 3, running: 1 });
+
+        for line in [
+            "distill_jobs_total{status=\"succeeded\"} 2",
+            "distill_jobs_total{status=\"failed\"} 2",
+            "distill_job_failures_total{stage=\"queue\"} 1",
+            "distill_job_failures_total{stage=\"transcription\"} 1",
+            "distill_job_duration_seconds_bucket{le=\"30\"} 1",
+            "distill_job_duration_seconds_bucket{le=\"60\"} 2",
+            "distill_job_duration_seconds_bucket{le=\"7200\"} 2",
+            "distill_job_duration_seconds_bucket{le=\"+Inf\"} 3",
+            "distill_job_duration_seconds_sum 10065",
+            "distill_job_duration_seconds_count 3",
+            "distill_jobs_queued 3",
+            "distill_jobs_running 1",
+        ] {
+            assert!(text.lines().any(|l| l == line), "missing {:?} in\n{}", line, text);
+        }
+    }
+}
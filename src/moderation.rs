@@ -0,0 +1,40 @@
+use aws_config::SdkConfig;
+use aws_sdk_bedrockruntime::types::{
+    GuardrailAction, GuardrailContentBlock, GuardrailContentSource, GuardrailTextBlock,
+};
+use aws_sdk_bedrockruntime::Client;
+
+use anyhow::{anyhow, Error};
+
+// Run text through a Bedrock guardrail before it leaves the machine.
+// Returns the reason it was flagged, or None if the guardrail let it through.
+pub async fn screen(
+    config: &SdkConfig,
+    text: &str,
+    guardrail_id: &str,
+    guardrail_version: &str,
+) -> Result<Option<String>, Error> {
+    let client = Client::new(config);
+
+    let output = client
+        .apply_guardrail()
+        .guardrail_identifier(guardrail_id)
+        .guardrail_version(guardrail_version)
+        .source(GuardrailContentSource::Output)
+        .content(GuardrailContentBlock::Text(
+            GuardrailTextBlock::builder().text(text).build()?,
+        ))
+        .send()
+        .await
+        .map_err(|e| anyhow!(e))?;
+
+    match output.action() {
+        GuardrailAction::GuardrailIntervened => Ok(Some(
+            output
+                .action_reason()
+                .unwrap_or("Flagged by guardrail")
+                .to_string(),
+        )),
+        _ => Ok(None),
+    }
+}
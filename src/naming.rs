@@ -0,0 +1,180 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Error};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::audio::Metadata;
+use crate::template::{self, Unknown, Value};
+
+// Values available to the S3 key and Transcribe job name templates. They are
+// generated once per file, so the upload and its job share the same {uuid}.
+pub struct NameValues {
+    date: String,
+    uuid: String,
+    filename: String,
+    stem: String,
+    title: String,
+    session: String,
+    // Length of the recording in seconds, when ffprobe could read it
+    duration: Option<f64>,
+    // SHA-256 of the file, only read when a template uses {hash}
+    hash: String,
+}
+
+impl NameValues {
+    // {date} and {title} come from the recording's tags when it has them,
+    // otherwise today's date and the file name
+    pub fn for_file(file_path: &Path, recording: &Metadata) -> Self {
+        let stem = file_path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        NameValues {
+            date: recording.recorded.clone().unwrap_or_else(today),
+            uuid: Uuid::new_v4().to_string(),
+            filename: file_path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+            // A slash in a title would add a level to the S3 key
+            title: recording
+                .title
+                .as_deref()
+                .map_or_else(|| stem.clone(), |title| title.replace(['/', '\\'], "-")),
+            stem,
+            session: String::new(),
+            duration: recording.duration,
+            hash: String::new(),
+        }
+    }
+
+    // {session} is the active session's directory name, and empty outside one
+    pub fn in_session(mut self, session: Option<&str>) -> Self {
+        self.session = session.unwrap_or_default().to_string();
+        self
+    }
+
+    pub fn with_hash(mut self, hash: String) -> Self {
+        self.hash = hash;
+        self
+    }
+
+    // Fill in {date}, {uuid}, {filename}, {stem}, {title}, {session},
+    // {duration} and {hash}, with any filters, e.g. {date | format "%Y/%m"}
+    pub fn render(&self, template: &str) -> Result<String, Error> {
+        template::render(
+            template,
+            &[
+                ("date", Value::Date(self.date.clone())),
+                ("uuid", Value::Text(self.uuid.clone())),
+                ("filename", Value::Text(self.filename.clone())),
+                ("stem", Value::Text(self.stem.clone())),
+                ("title", Value::Text(self.title.clone())),
+                ("session", Value::Text(self.session.clone())),
+                ("duration", self.duration.map_or(Value::Text(String::new()), Value::Duration)),
+                ("hash", Value::Text(self.hash.clone())),
+            ],
+            Unknown::Error,
+        )
+    }
+}
+
+// SHA-256 of the files' contents, in order, as hex. The same recording always
+// gets the same hash, whatever it is called.
+pub fn content_hash(files: &[PathBuf]) -> Result<String, Error> {
+    let mut hasher = Sha256::new();
+    for path in files {
+        let mut file = File::open(path).with_context(|| format!("Error reading {}", path.display()))?;
+        std::io::copy(&mut file, &mut hasher)?;
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+// The ID of a meeting's document or record in an index or database, the same
+// each time the meeting is processed. It comes from the recording's contents
+// when every input is a local file, and otherwise from the inputs themselves:
+// the Transcribe job name with --resume-job, or the S3 URI.
+pub fn record_id(files: &[PathBuf], transcript: &str) -> Result<String, Error> {
+    let hash = if !files.is_empty() && files.iter().all(|path| path.is_file()) {
+        content_hash(files)?
+    } else {
+        let mut hasher = Sha256::new();
+        for path in files {
+            hasher.update(path.to_string_lossy().as_bytes());
+            hasher.update([0]);
+        }
+        // Nothing else identifies the meeting when there are no inputs
+        if files.is_empty() {
+            hasher.update(transcript.as_bytes());
+        }
+        format!("{:x}", hasher.finalize())
+    };
+    Ok(format!("distill-{}", &hash[..32]))
+}
+
+// Today's date as YYYY-MM-DD (UTC)
+pub fn today() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    format_date(seconds)
+}
+
+// Now as an RFC 3339 timestamp in UTC, e.g. 2024-05-14T14:03:27.512Z
+pub fn timestamp() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = now.as_secs();
+    format!(
+        "{}T{:02}:{:02}:{:02}.{:03}Z",
+        format_date(seconds),
+        seconds % 86_400 / 3600,
+        seconds % 3600 / 60,
+        seconds % 60,
+        now.subsec_millis()
+    )
+}
+
+// Transcribe job names may only contain letters, digits, '.', '_' and '-'
+pub fn job_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') { c } else { '-' })
+        .take(200)
+        .collect()
+}
+
+// Seconds since the Unix epoch as YYYY-MM-DD (UTC)
+pub fn format_date(epoch: u64) -> String {
+    // Civil-from-days, see http://howardhinnant.github.io/date_algorithms.html
+    let days = (epoch / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// A YYYY-MM-DD date as seconds since the Unix epoch at midnight UTC
+pub fn parse_date(date: &str) -> Option<u64> {
+    let mut parts = date.trim().splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // Days-from-civil, the inverse of format_date
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    u64::try_from(days * 86_400).ok()
+}
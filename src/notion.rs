@@ -0,0 +1,192 @@
+use anyhow::{anyhow, bail, Error};
+use reqwest::Client;
+use serde_json::{json, Value};
+
+const API_URL: &str = "https://api.notion.com/v1";
+const NOTION_VERSION: &str = "2022-06-28";
+
+// Notion limits each text object to 2,000 characters and each request to 100 blocks
+const MAX_TEXT: usize = 2000;
+const MAX_BLOCKS: usize = 100;
+
+// What goes on the page, in order: the summary, action items as a to-do
+// list, anything else the model wrote, appendices, then the transcript in
+// a collapsed toggle
+pub struct Page<'a> {
+    pub title: &'a str,
+    pub summary: &'a str,
+    pub action_items: &'a str,
+    pub other: &'a str,
+    pub appendices: &'a [(&'a str, String)],
+    pub transcript: &'a str,
+}
+
+fn rich_text(text: &str) -> Value {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let mut end = rest.len().min(MAX_TEXT);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        parts.push(json!({ "type": "text", "text": { "content": &rest[..end] } }));
+        rest = &rest[end..];
+    }
+    Value::Array(parts)
+}
+
+fn block(kind: &str, text: &str) -> Value {
+    json!({ "object": "block", "type": kind, kind: { "rich_text": rich_text(text) } })
+}
+
+// "- item", "* item" and "1. item" lines become list items, the rest paragraphs
+fn text_blocks(text: &str) -> Vec<Value> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| match list_item(line) {
+            Some(item) => block("bulleted_list_item", item),
+            None => block("paragraph", line),
+        })
+        .collect()
+}
+
+// The text of a "- item", "* item" or "1. item" line
+pub fn list_item(line: &str) -> Option<&str> {
+    if let Some(item) = line.strip_prefix(['-', '*', '•']) {
+        return Some(item.trim());
+    }
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    (digits > 0)
+        .then(|| line[digits..].strip_prefix(['.', ')']))
+        .flatten()
+        .map(str::trim)
+}
+
+fn to_do_blocks(action_items: &str) -> Vec<Value> {
+    action_items
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let item = list_item(line).unwrap_or(line);
+            json!({
+                "object": "block",
+                "type": "to_do",
+                "to_do": { "rich_text": rich_text(item), "checked": false },
+            })
+        })
+        .collect()
+}
+
+async fn request(client: &Client, token: &str, method: reqwest::Method, path: &str, body: &Value) -> Result<Value, Error> {
+    let response = client
+        .request(method, format!("{}{}", API_URL, path))
+        .bearer_auth(token)
+        .header("Notion-Version", NOTION_VERSION)
+        .json(body)
+        .send()
+        .await?;
+    let status = response.status();
+    let body: Value = response.json().await?;
+    if !status.is_success() {
+        bail!(
+            "{} {}",
+            status,
+            body["message"].as_str().unwrap_or("(no message)")
+        );
+    }
+    Ok(body)
+}
+
+// Add blocks under a page or block, 100 at a time. Returns the ID of the
+// first block added.
+async fn append(client: &Client, token: &str, parent: &str, blocks: &[Value]) -> Result<Option<String>, Error> {
+    let mut first = None;
+    for chunk in blocks.chunks(MAX_BLOCKS) {
+        let response = request(
+            client,
+            token,
+            reqwest::Method::PATCH,
+            &format!("/blocks/{}/children", parent),
+            &json!({ "children": chunk }),
+        )
+        .await?;
+        if first.is_none() {
+            first = response["results"][0]["id"].as_str().map(str::to_string);
+        }
+    }
+    Ok(first)
+}
+
+// Create the page under `parent_id`, a database (whose title property is
+// filled in) or another page, and return its URL
+pub async fn create_page(
+    client: &Client,
+    token: &str,
+    parent_id: &str,
+    parent_is_database: bool,
+    page: &Page<'_>,
+) -> Result<String, Error> {
+    let mut blocks = Vec::new();
+    if !page.summary.is_empty() {
+        blocks.push(block("heading_2", "Summary"));
+        blocks.extend(text_blocks(page.summary));
+    }
+    if !page.action_items.is_empty() {
+        blocks.push(block("heading_2", "Action items"));
+        blocks.extend(to_do_blocks(page.action_items));
+    }
+    if !page.other.is_empty() {
+        blocks.extend(text_blocks(page.other));
+    }
+    for (title, body) in page.appendices {
+        blocks.push(block("heading_2", title));
+        blocks.extend(text_blocks(body));
+    }
+
+    let parent = if parent_is_database {
+        json!({ "database_id": parent_id })
+    } else {
+        json!({ "page_id": parent_id })
+    };
+    let (first, rest) = blocks.split_at(blocks.len().min(MAX_BLOCKS));
+    let created = request(
+        client,
+        token,
+        reqwest::Method::POST,
+        "/pages",
+        &json!({
+            "parent": parent,
+            "properties": { "title": { "title": rich_text(page.title) } },
+            "children": first,
+        }),
+    )
+    .await?;
+    let page_id = created["id"]
+        .as_str()
+        .ok_or_else(|| anyhow!("Notion did not return a page ID"))?;
+    append(client, token, page_id, rest).await?;
+
+    // The toggle is added on its own so its ID is known, then filled in
+    if !page.transcript.is_empty() {
+        let lines: Vec<Value> = page
+            .transcript
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| block("paragraph", line))
+            .collect();
+        let (first, rest) = lines.split_at(lines.len().min(MAX_BLOCKS));
+        let toggle = json!({
+            "object": "block",
+            "type": "toggle",
+            "toggle": { "rich_text": rich_text("Transcript"), "children": first },
+        });
+        let toggle_id = append(client, token, page_id, &[toggle])
+            .await?
+            .ok_or_else(|| anyhow!("Notion did not return the transcript block ID"))?;
+        append(client, token, &toggle_id, rest).await?;
+    }
+
+    Ok(created["url"].as_str().unwrap_or(page_id).to_string())
+}
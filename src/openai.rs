@@ -0,0 +1,153 @@
+use anyhow::{anyhow, bail, Error};
+use config::Config;
+use serde_json::{json, Value};
+use tracing::{debug, instrument};
+
+use crate::net;
+use crate::progress::Progress;
+use crate::summarize::{Summary, Usage};
+
+const DEFAULT_ENDPOINT: &str = "https://api.openai.com/v1";
+
+// An OpenAI-compatible chat completions API that summaries are written with
+// instead of Bedrock, selected with `--summarizer openai`: OpenAI itself, or
+// a local server such as Ollama or vLLM. openai.endpoint (or --endpoint) is
+// the API's base URL, e.g. http://localhost:11434/v1 for Ollama.
+pub struct Chat {
+    pub endpoint: String,
+    pub model: String,
+    pub api_key: Option<String>,
+}
+
+impl Chat {
+    pub fn from_settings(settings: &Config) -> Result<Self, Error> {
+        let setting = |key: &str| {
+            settings
+                .get_string(key)
+                .ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let Some(model) = setting("openai.model") else {
+            bail!("\nNo model configured for the OpenAI-compatible API. Set openai.model in config.toml or pass --model.");
+        };
+        Ok(Chat {
+            endpoint: setting("openai.endpoint").unwrap_or_else(|| DEFAULT_ENDPOINT.to_string()),
+            model,
+            // Local servers usually don't need a key
+            api_key: setting("openai.api_key").or_else(|| std::env::var("OPENAI_API_KEY").ok()),
+        })
+    }
+
+    // Send `prompt` as a single user message and collect the reply. With
+    // `on_text`, the reply is streamed and passed on as it arrives.
+    #[instrument(name = "chat_completion", skip_all, fields(endpoint = %self.endpoint, model = %self.model))]
+    pub async fn complete(
+        &self,
+        settings: &Config,
+        prompt: &str,
+        temperature: Option<f64>,
+        spinner: &mut Progress,
+        mut on_text: Option<&mut dyn FnMut(&str)>,
+    ) -> Result<Summary, Error> {
+        let mut messages = Vec::new();
+        let system = settings.get_string("anthropic.system").unwrap_or_default();
+        if !system.trim().is_empty() {
+            messages.push(json!({ "role": "system", "content": system }));
+        }
+        messages.push(json!({ "role": "user", "content": prompt }));
+        let mut body = json!({
+            "model": self.model,
+            "messages": messages,
+            "temperature": temperature.unwrap_or_else(|| settings.get_float("model.temperature").unwrap_or(0.7)),
+            "top_p": settings.get_float("model.top_p").unwrap_or(1.0),
+            "stream": on_text.is_some(),
+        });
+        if let Ok(max_tokens) = settings.get_int("model.max_tokens") {
+            body["max_tokens"] = json!(max_tokens);
+        }
+        // Token counts come in the last chunk of a stream only when asked for
+        if on_text.is_some() {
+            body["stream_options"] = json!({ "include_usage": true });
+        }
+
+        spinner.update("Summarizing transcription...");
+        let url = if self.endpoint.ends_with("/chat/completions") {
+            self.endpoint.clone()
+        } else {
+            format!("{}/chat/completions", self.endpoint.trim_end_matches('/'))
+        };
+        let mut request = net::http_client(settings)?.post(&url).json(&body);
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let mut response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body: Value = response.json().await.unwrap_or(Value::Null);
+            bail!(
+                "\nThe OpenAI-compatible API at {} failed: {} {}",
+                self.endpoint,
+                status,
+                body["error"]["message"].as_str().or_else(|| body["error"].as_str()).unwrap_or_default()
+            );
+        }
+
+        let Some(on_text) = on_text.as_mut() else {
+            let reply: Value = response.json().await?;
+            let text = reply["choices"][0]["message"]["content"]
+                .as_str()
+                .ok_or_else(|| anyhow!("Unexpected response from {}: {}", self.model, reply))?;
+            return Ok(Summary {
+                text: text.to_string(),
+                usage: usage(&reply["usage"]),
+            });
+        };
+
+        // A stream is server-sent events, one "data: {...}" line per chunk
+        let mut buffer: Vec<u8> = Vec::new();
+        let mut text = String::new();
+        let mut tokens = Usage::default();
+        while let Some(bytes) = response.chunk().await? {
+            buffer.extend_from_slice(&bytes);
+            while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                let Some(data) = line.trim().strip_prefix("data:").map(str::trim) else {
+                    continue;
+                };
+                if data == "[DONE]" {
+                    break;
+                }
+                let chunk: Value = serde_json::from_str(data)?;
+                if let Some(delta) = chunk["choices"][0]["delta"]["content"].as_str() {
+                    if text.is_empty() {
+                        spinner.success("Summary ready");
+                    }
+                    text.push_str(delta);
+                    on_text(delta);
+                }
+                if chunk["usage"].is_object() {
+                    tokens = usage(&chunk["usage"]);
+                }
+            }
+        }
+
+        if text.is_empty() {
+            return Err(anyhow!("The model returned an empty summary"));
+        }
+        debug!(output_tokens = tokens.output_tokens, "chat completion finished");
+        Ok(Summary { text, usage: tokens })
+    }
+}
+
+// prompt_tokens includes any cached tokens, which Bedrock counts separately
+fn usage(usage: &Value) -> Usage {
+    let cached = usage["prompt_tokens_details"]["cached_tokens"].as_u64().unwrap_or_default();
+    Usage {
+        input_tokens: usage["prompt_tokens"].as_u64().unwrap_or_default().saturating_sub(cached),
+        output_tokens: usage["completion_tokens"].as_u64().unwrap_or_default(),
+        cache_read_tokens: cached,
+        cache_write_tokens: 0,
+    }
+}
@@ -0,0 +1,54 @@
+use std::fs::File;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Error};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+// Write an output file in one step. The content is built in memory, written
+// to a hidden file beside `path`, read back and checked against the expected
+// length and SHA-256, then renamed over `path`. An interrupted run or a full
+// disk leaves the previous file, if any, rather than a truncated one such as
+// a summary.docx Word refuses to open.
+pub fn write_atomic<F>(path: impl AsRef<Path>, build: F) -> Result<(), Error>
+where
+    F: FnOnce(&mut Cursor<Vec<u8>>) -> Result<(), Error>,
+{
+    let path = path.as_ref();
+    let mut buffer = Cursor::new(Vec::new());
+    build(&mut buffer)?;
+    let content = buffer.into_inner();
+
+    let partial = partial_path(path);
+    let result = write_verified(&partial, &content)
+        .and_then(|_| std::fs::rename(&partial, path).context("Error moving the file into place"));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&partial);
+    }
+    result.with_context(|| format!("Error writing {}", path.display()))
+}
+
+// e.g. ".summary.docx.1b4e28ba.part" in the same directory, so the rename
+// never crosses file systems
+fn partial_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let id = Uuid::new_v4().simple().to_string();
+    path.with_file_name(format!(".{}.{}.part", name, &id[..8]))
+}
+
+fn write_verified(partial: &Path, content: &[u8]) -> Result<(), Error> {
+    let mut file = File::create(partial)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    drop(file);
+
+    let written = std::fs::read(partial)?;
+    if written.len() != content.len() {
+        bail!("only {} of {} bytes were written", written.len(), content.len());
+    }
+    if Sha256::digest(&written) != Sha256::digest(content) {
+        bail!("the file on disk does not match what was written");
+    }
+    Ok(())
+}
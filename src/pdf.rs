@@ -0,0 +1,167 @@
+use std::io::BufWriter;
+
+use anyhow::Error;
+use printpdf::{BuiltinFont, Mm, PdfDocument};
+
+use crate::output;
+
+// A4 with 20mm margins
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 20.0;
+
+const BODY_SIZE: f32 = 11.0;
+const HEADING_SIZE: f32 = 16.0;
+const BODY_LEADING: f32 = 5.5;
+const HEADING_LEADING: f32 = 10.0;
+
+// The built-in fonts have no metrics available here, so text is wrapped
+// using Helvetica's average character width
+const AVERAGE_CHAR_WIDTH: f32 = 0.5;
+const PT_TO_MM: f32 = 0.3528;
+
+enum Line {
+    Heading(String),
+    // An optional bold speaker label, then regular text
+    Body(Option<String>, String),
+    Blank,
+}
+
+impl Line {
+    fn height(&self) -> f32 {
+        match self {
+            Line::Heading(_) => HEADING_LEADING,
+            Line::Body(..) | Line::Blank => BODY_LEADING,
+        }
+    }
+}
+
+// Write titled sections to a PDF, with speaker labels in bold and page numbers in the footer
+pub fn write_pdf(filename: &str, title: &str, sections: &[(&str, &str)]) -> Result<(), Error> {
+    let pages = paginate(layout(sections));
+
+    let (doc, first_page, first_layer) =
+        PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+    let regular = doc.add_builtin_font(BuiltinFont::Helvetica)?;
+    let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold)?;
+
+    let page_count = pages.len();
+    for (number, lines) in pages.iter().enumerate() {
+        let (page, layer) = if number == 0 {
+            (first_page, first_layer)
+        } else {
+            doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1")
+        };
+        let layer = doc.get_page(page).get_layer(layer);
+
+        let mut y = PAGE_HEIGHT - MARGIN;
+        for line in lines {
+            y -= line.height();
+            match line {
+                Line::Heading(text) => {
+                    layer.use_text(text.as_str(), HEADING_SIZE, Mm(MARGIN), Mm(y), &bold);
+                }
+                Line::Body(label, text) => {
+                    let mut x = MARGIN;
+                    if let Some(label) = label {
+                        layer.use_text(label.as_str(), BODY_SIZE, Mm(x), Mm(y), &bold);
+                        x += text_width(label) + text_width(" ");
+                    }
+                    layer.use_text(text.as_str(), BODY_SIZE, Mm(x), Mm(y), &regular);
+                }
+                Line::Blank => {}
+            }
+        }
+
+        let footer = format!("Page {} of {}", number + 1, page_count);
+        let x = (PAGE_WIDTH - text_width(&footer) * 0.8) / 2.0;
+        layer.use_text(footer, BODY_SIZE * 0.8, Mm(x), Mm(MARGIN / 2.0), &regular);
+    }
+
+    output::write_atomic(filename, |out| {
+        doc.save(&mut BufWriter::new(out))?;
+        Ok(())
+    })
+}
+
+fn layout(sections: &[(&str, &str)]) -> Vec<Line> {
+    let max_width = PAGE_WIDTH - 2.0 * MARGIN;
+    let mut lines = Vec::new();
+
+    for (title, body) in sections {
+        if !lines.is_empty() {
+            lines.push(Line::Blank);
+        }
+        lines.push(Line::Heading(title.to_string()));
+
+        for paragraph in body.lines() {
+            let paragraph = paragraph.trim_end();
+            if paragraph.is_empty() {
+                lines.push(Line::Blank);
+                continue;
+            }
+
+            // Transcript turns look like "Speaker 1: text" or "Alice: text"
+            let (label, text) = match paragraph.split_once(": ") {
+                Some((label, text)) if label.len() <= 32 && label.split(' ').count() <= 3 => {
+                    (Some(format!("{}:", label)), text)
+                }
+                _ => (None, paragraph),
+            };
+
+            let indent = label.as_deref().map_or(0.0, |l| text_width(l) + text_width(" "));
+            let mut label = label;
+            for wrapped in wrap(text, max_width - indent) {
+                lines.push(Line::Body(label.take(), wrapped));
+            }
+        }
+    }
+
+    lines
+}
+
+fn paginate(lines: Vec<Line>) -> Vec<Vec<Line>> {
+    let available = PAGE_HEIGHT - 2.0 * MARGIN;
+    let mut pages: Vec<Vec<Line>> = vec![Vec::new()];
+    let mut used = 0.0;
+
+    for line in lines {
+        if used + line.height() > available {
+            pages.push(Vec::new());
+            used = 0.0;
+        }
+        // Don't start a page with blank space
+        if used == 0.0 && matches!(line, Line::Blank) {
+            continue;
+        }
+        used += line.height();
+        pages.last_mut().unwrap().push(line);
+    }
+
+    pages
+}
+
+fn wrap(text: &str, max_width: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    for word in text.split_whitespace() {
+        if !current.is_empty() && text_width(&format!("{} {}", current, word)) > max_width {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+
+    lines
+}
+
+// Approximate width in mm of body text
+fn text_width(text: &str) -> f32 {
+    text.chars().count() as f32 * BODY_SIZE * AVERAGE_CHAR_WIDTH * PT_TO_MM
+}
@@ -0,0 +1,2305 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Error};
+use aws_config::SdkConfig;
+use aws_sdk_s3::Client;
+use aws_sdk_transcribe::types::VocabularyFilterMethod;
+use config::Config;
+use docx_rs::{Docx, Paragraph, Run};
+use futures::future::join_all;
+use futures::stream::{self, StreamExt};
+use serde_json::json;
+use tempfile::TempDir;
+use tracing::instrument;
+
+use crate::progress::{self, Progress};
+use crate::status;
+use crate::{
+    actions, agenda, audio, brief, compliance, confidence, confluence, database, eventbridge, events, faq, feedback,
+    html, jira, knowledge, language, limits, moderation, naming, net, notion, output, pdf, quota, report, review,
+    session, settings, slack, sns, speech, subtitles, summarize, terms, transcribe, transforms, upload, voiceprint,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputType {
+    Terminal,
+    Text,
+    Word,
+    Markdown,
+    Slack,
+    Json,
+    Srt,
+    Vtt,
+    Pdf,
+    Html,
+    Notion,
+    Confluence,
+    Jira,
+    Kendra,
+    QBusiness,
+    Sns,
+    Dynamodb,
+    Postgres,
+}
+
+impl std::fmt::Display for OutputType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputType::Terminal => write!(f, "terminal"),
+            OutputType::Text => write!(f, "text"),
+            OutputType::Word => write!(f, "word"),
+            OutputType::Markdown => write!(f, "markdown"),
+            OutputType::Slack => write!(f, "slack"),
+            OutputType::Json => write!(f, "json"),
+            OutputType::Srt => write!(f, "srt"),
+            OutputType::Vtt => write!(f, "vtt"),
+            OutputType::Pdf => write!(f, "pdf"),
+            OutputType::Html => write!(f, "html"),
+            OutputType::Notion => write!(f, "notion"),
+            OutputType::Confluence => write!(f, "confluence"),
+            OutputType::Jira => write!(f, "jira"),
+            OutputType::Kendra => write!(f, "kendra"),
+            OutputType::QBusiness => write!(f, "q-business"),
+            OutputType::Sns => write!(f, "sns"),
+            OutputType::Dynamodb => write!(f, "dynamodb"),
+            OutputType::Postgres => write!(f, "postgres"),
+        }
+    }
+}
+
+impl OutputType {
+    // Extension used when naming file outputs; None for non-file outputs
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            OutputType::Word => Some("docx"),
+            OutputType::Text => Some("txt"),
+            OutputType::Markdown => Some("md"),
+            OutputType::Json => Some("json"),
+            OutputType::Srt => Some("srt"),
+            OutputType::Vtt => Some("vtt"),
+            OutputType::Pdf => Some("pdf"),
+            OutputType::Html => Some("html"),
+            OutputType::Terminal
+            | OutputType::Slack
+            | OutputType::Notion
+            | OutputType::Confluence
+            | OutputType::Jira
+            | OutputType::Kendra
+            | OutputType::QBusiness
+            | OutputType::Sns
+            | OutputType::Dynamodb
+            | OutputType::Postgres => None,
+        }
+    }
+
+    // Outputs that send the summary off this machine. With a guardrail
+    // configured ([moderation]), they are held when it flags the summary.
+    pub fn is_external(&self) -> bool {
+        matches!(
+            self,
+            OutputType::Slack
+                | OutputType::Sns
+                | OutputType::Notion
+                | OutputType::Confluence
+                | OutputType::Jira
+                | OutputType::Kendra
+                | OutputType::QBusiness
+                | OutputType::Dynamodb
+                | OutputType::Postgres
+        )
+    }
+
+    pub fn from_filename(filename: &str) -> Option<Self> {
+        let extension = std::path::Path::new(filename)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|s| s.to_lowercase());
+        
+        match extension.as_deref() {
+            Some("md") => Some(OutputType::Markdown),
+            Some("txt") => Some(OutputType::Text),
+            Some("doc" | "docx") => Some(OutputType::Word),
+            Some("json") => Some(OutputType::Json),
+            Some("srt") => Some(OutputType::Srt),
+            Some("vtt") => Some(OutputType::Vtt),
+            Some("pdf") => Some(OutputType::Pdf),
+            Some("html" | "htm") => Some(OutputType::Html),
+            _ => None
+        }
+    }
+}
+
+const DICTATION_PROMPT: &str = "The following transcript is dictation. Rewrite it as clean, polished prose in the speaker's \
+own voice, as they intended it to be written (for example an email, letter or note). Remove filler words, false starts \
+and spoken corrections, and follow any spoken formatting instructions. Reply with only the rewritten text, in the same \
+language as the provided transcript:";
+
+const VOICEMAIL_PROMPT: &str = "Summarize the following voicemail transcript in one short paragraph. \
+After the paragraph, add a line starting with \"Caller:\" with the caller's name and a line starting with \"Callback:\" \
+with any callback number, writing \"not mentioned\" if either is missing. Answer in the same language as the provided transcript:";
+
+const QA_PROMPT: &str = "The following transcript is a webinar, AMA or similar session with questions from \
+participants. Summarize the presentation and the main themes of the discussion in a few short paragraphs. \
+The individual questions and answers are listed separately, so don't repeat them. Answer in the same language \
+as the provided transcript:";
+
+// What Amazon Transcribe does with words matched by a vocabulary filter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum FilterMethod {
+    // Replace the word with "***"
+    Mask,
+    Remove,
+    // Keep the word, flagged in the Transcribe JSON
+    Tag,
+}
+
+impl FilterMethod {
+    pub fn transcribe_method(&self) -> VocabularyFilterMethod {
+        match self {
+            FilterMethod::Mask => VocabularyFilterMethod::Mask,
+            FilterMethod::Remove => VocabularyFilterMethod::Remove,
+            FilterMethod::Tag => VocabularyFilterMethod::Tag,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Mode {
+    Meeting,
+    Voicemail,
+    Dictation,
+    // Webinars and AMAs: a summary plus the question and answer pairs as an FAQ
+    Qa,
+}
+
+impl Mode {
+    // Settings key holding the prompt template for this mode
+    pub fn prompt_key(&self) -> &'static str {
+        match self {
+            Mode::Meeting => "prompt.template",
+            Mode::Voicemail => "prompt.voicemail_template",
+            Mode::Dictation => "prompt.dictation_template",
+            Mode::Qa => "prompt.qa_template",
+        }
+    }
+
+    // Fallback used when config.toml doesn't define the mode's template
+    pub fn default_prompt(&self) -> &'static str {
+        match self {
+            Mode::Meeting => "",
+            Mode::Voicemail => VOICEMAIL_PROMPT,
+            Mode::Dictation => DICTATION_PROMPT,
+            Mode::Qa => QA_PROMPT,
+        }
+    }
+
+    // Initial Transcribe polling interval; short clips finish in seconds
+    pub fn poll_interval(&self) -> Duration {
+        match self {
+            Mode::Meeting | Mode::Dictation | Mode::Qa => Duration::from_secs(5),
+            Mode::Voicemail => Duration::from_secs(1),
+        }
+    }
+
+    // Dictation output is the rewritten text alone, without a transcript
+    pub fn text_only(&self) -> bool {
+        matches!(self, Mode::Dictation)
+    }
+}
+
+// Which transcribe::Transcriber a run uses, from --transcriber or
+// transcript.transcriber
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TranscriberKind {
+    // Uploaded to S3 and transcribed by Amazon Transcribe
+    Amazon,
+    // Transcribed on this machine with whisper.cpp; nothing is uploaded
+    WhisperLocal,
+}
+
+impl TranscriberKind {
+    // The service or program behind each kind, for `distill --build-info`
+    pub fn backend(&self) -> &'static str {
+        match self {
+            TranscriberKind::Amazon => "amazon-transcribe",
+            TranscriberKind::WhisperLocal => "whisper-local",
+        }
+    }
+}
+
+// Shared state for a run, resolved once before any file is processed. The
+// CLI builds it from its options; programs embedding the pipeline fill it in
+// directly.
+pub struct RunContext {
+    pub settings: Config,
+    pub config: SdkConfig,
+    pub regional_config: SdkConfig,
+    // The bucket's region, or aws.region when it is set
+    pub transcribe_config: SdkConfig,
+    pub s3_client: Client,
+    pub bucket_name: String,
+    pub output_types: Vec<OutputType>,
+    pub language_code: String,
+    pub mode: Mode,
+    pub delete_s3_object: String,
+    pub report: bool,
+    pub always_summarize: bool,
+    pub top_terms: Option<usize>,
+    pub agenda_items: Vec<String>,
+    // Start and length in seconds of the part of each recording to process
+    pub clip: Option<(f64, f64)>,
+    pub skip_summary: bool,
+    // Inputs are saved transcripts, so S3 and Transcribe are skipped
+    pub transcript_input: bool,
+    // Amazon Transcribe, or e.g. Whisper with --transcriber whisper-local, in
+    // which case S3 is skipped
+    pub transcriber: Box<dyn transcribe::Transcriber>,
+    // Speaker names for per-speaker tracks, in the same order as the files
+    pub track_names: Vec<String>,
+    pub samples: u32,
+    pub pick_sample: bool,
+    pub resume_job: Option<String>,
+    // Maximum number of speakers for diarization; None turns it off
+    pub max_speakers: Option<i32>,
+    // Transcribe vocabulary filter name and method
+    pub vocabulary_filter: Option<(String, FilterMethod)>,
+    // From --prompt or --prompt-file; None uses the mode's template
+    pub prompt_template: Option<String>,
+    pub attendees: Vec<String>,
+    // Terms from compliance.watchlist to flag when they are said
+    pub watchlist: Vec<(String, regex::Regex)>,
+    pub annotate_events: bool,
+    // From transforms.pipeline, applied in order before summarizing
+    pub transforms: Vec<Box<dyn transforms::Transform>>,
+    // summary.max_words and what to do with longer transcripts
+    pub size_limit: limits::SizeLimit,
+    // transcript.min_confidence and what to do with less certain transcripts
+    pub confidence_threshold: Option<confidence::Threshold>,
+    // speech.* limits for recordings with no speech; None turns the check off
+    pub speech_check: Option<speech::SpeechCheck>,
+    // From --session or `distill session use`; each run's artifacts are kept in it
+    pub session: Option<session::Session>,
+    // Monthly usage quotas of the --profile in use
+    pub quota: quota::Quota,
+    // Upload each recording under the hash of its content
+    pub name_by_hash: bool,
+    // Whether to ask before e.g. transcribing again in another language
+    pub interactive: bool,
+    // Correct the transcript with --review before it is summarized
+    pub review: bool,
+    // What the settings were loaded from, to load them again the same way
+    pub sources: settings::Sources,
+    // How the run reports progress; tasks it spawns are run in `output.scope`
+    pub output: progress::Output,
+}
+
+// A recording uploaded to S3, ready to transcribe
+pub struct Upload {
+    pub key: String,
+    pub s3_uri: String,
+    pub job_name: String,
+    // The file that was uploaded: a trimmed copy when only part of the
+    // recording is processed, kept until the upload is transcribed
+    pub path: PathBuf,
+    _trimmed: Option<TempDir>,
+    // False for a recording that was already in S3, which is never deleted
+    uploaded: bool,
+}
+
+// The recording a run is about, with the title and date used in prompts,
+// page titles and templates
+pub struct Meeting {
+    pub files: Vec<PathBuf>,
+    pub title: String,
+    pub date: String,
+    pub recording: audio::Metadata,
+}
+
+impl Meeting {
+    // Tags in the recording name and date the meeting when it has them;
+    // otherwise the file name and today
+    pub fn new(ctx: &RunContext, files: &[PathBuf]) -> Self {
+        let recording = if ctx.transcript_input || ctx.resume_job.is_some() {
+            audio::Metadata::default()
+        } else {
+            audio::metadata(&files[0])
+        };
+        Meeting {
+            files: files.to_vec(),
+            title: recording
+                .title
+                .clone()
+                .unwrap_or_else(|| files[0].file_stem().unwrap_or_default().to_string_lossy().into_owned()),
+            date: recording.recorded.clone().unwrap_or_else(naming::today),
+            recording,
+        }
+    }
+
+    // The input files on one line, e.g. for compliance alerts
+    pub fn inputs(&self) -> String {
+        self.files
+            .iter()
+            .map(|f| f.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+// A recording's transcript, after speaker matching, audio events and transforms
+pub struct Transcription {
+    pub transcript: transcribe::Transcript,
+    // The transcript as it is written to the outputs
+    pub text: String,
+    // Compliance watchlist mentions, already reported when transcribing
+    pub alerts: Vec<compliance::Alert>,
+    // How sure Transcribe was of the words; None for plain text transcripts
+    pub confidence: Option<confidence::Confidence>,
+    // The language it was transcribed in: --language-code, or the language
+    // detected in the first attempt
+    pub language_code: String,
+}
+
+// The summary and the sections written after the transcript
+pub struct Summary {
+    // The model's summary, or the transcript when it isn't summarized
+    pub text: String,
+    // Set when the summary has already been printed as it was generated
+    pub streamed: bool,
+    // How the transcript's length was handled, when it was summarized
+    pub size_decision: Option<limits::Decision>,
+    pub appendices: Vec<(&'static str, String)>,
+    // Question and answer pairs in Q&A mode, for the JSON output type
+    pub faq: Vec<serde_json::Value>,
+}
+
+// What a run wrote and published
+pub struct Outputs {
+    pub files: Vec<String>,
+    // URL of the Notion or Confluence page
+    pub url: Option<String>,
+    // Estimated cost of the run, or None when the model's price isn't known
+    pub cost_usd: Option<f64>,
+}
+
+// Transcribe one recording, or several parts of one meeting, then summarize
+// the combined transcript and write its outputs
+#[instrument(name = "recording", skip_all, fields(files = ?files))]
+pub async fn process_recording(
+    ctx: &RunContext,
+    files: &[PathBuf],
+    output_filename: Option<&str>,
+) -> Result<Outputs, Error> {
+    let meeting = Meeting::new(ctx, files);
+    let mut run_report = report::RunReport::new(report::Pricing::from_settings(&ctx.settings));
+    let nothing_to_upload = ctx.transcript_input || ctx.resume_job.is_some() || !ctx.transcriber.reads_from_s3();
+    progress::set_stage(if nothing_to_upload { "transcription" } else { "upload" });
+    let mut spinner = Progress::new(if ctx.transcript_input {
+        "Reading transcript..."
+    } else if ctx.resume_job.is_some() {
+        "Resuming transcription job..."
+    } else if !ctx.transcriber.reads_from_s3() {
+        "Transcribing..."
+    } else {
+        "Uploading file to S3..."
+    });
+
+    let result = async {
+        let transcription = transcribe(ctx, &meeting, &mut spinner, &mut run_report).await?;
+        complete(ctx, &meeting, &transcription, None, output_filename, &mut spinner, &mut run_report).await
+    }
+    .await;
+
+    announce(ctx, &meeting, &run_report, result.as_ref()).await;
+    result
+}
+
+// Everything after transcription: summarize the transcript, or use `summary`
+// when the model already wrote it, write the outputs, keep the run in the
+// session and record its usage
+pub async fn complete(
+    ctx: &RunContext,
+    meeting: &Meeting,
+    transcription: &Transcription,
+    summary: Option<summarize::Summary>,
+    output_filename: Option<&str>,
+    spinner: &mut Progress,
+    run_report: &mut report::RunReport,
+) -> Result<Outputs, Error> {
+    let files = &meeting.files;
+    let summary = summarize_with(ctx, meeting, transcription, summary, spinner, run_report).await?;
+    let mut outputs = render(ctx, meeting, transcription, &summary, output_filename, spinner, run_report).await?;
+
+    if let Some(session) = &ctx.session {
+        let mut record = session::RunRecord::new(
+            &meeting.title,
+            &meeting.date,
+            files.iter().map(|f| f.display().to_string()).collect(),
+        );
+        record.url = outputs.url.clone();
+        let text_only = ctx.mode.text_only() || ctx.skip_summary;
+        let transcript_text = if text_only { "" } else { transcription.text.as_str() };
+        let run_dir = session.add_run(record, &summary.text, transcript_text, &outputs.files)?;
+        status!("🗂️", "Saved to session '{}' in {}", session.name, run_dir.display());
+
+        // Items from earlier meetings are checked off, and the meeting's own tracked
+        if !text_only && ctx.settings.get_bool("actions.track").unwrap_or(true) {
+            let actions_start = Instant::now();
+            let tracked =
+                actions::track(ctx, session, meeting, &transcription.text, &summary.text, spinner, run_report)
+                    .await;
+            run_report.stage("Action items", actions_start);
+            if let Err(err) = tracked {
+                eprintln!("\nWarning: The session's action items could not be updated: {:#}", err);
+            }
+        }
+
+        // brief.auto keeps the session's brief up to date with each meeting
+        if !text_only && ctx.settings.get_bool("brief.auto").unwrap_or(false) {
+            let brief_start = Instant::now();
+            let result = match session::open(&ctx.settings, session.id()) {
+                Ok(session) => brief::write(&ctx.settings, &ctx.config, &session, spinner).await,
+                Err(err) => Err(err),
+            };
+            run_report.stage("Brief", brief_start);
+            match result {
+                Ok((path, usage)) => {
+                    run_report.add_usage("Brief", usage);
+                    status!("📘", "Updated the session's brief in {}", path.display());
+                }
+                Err(err) => eprintln!("\nWarning: The session's brief could not be updated: {:#}", err),
+            }
+        }
+    }
+
+    run_report.set_attempts(spinner.attempts());
+    if ctx.report {
+        run_report.print();
+    }
+    run_report.print_cost();
+    ctx.quota.record(run_report)?;
+    outputs.cost_usd = run_report.total_cost();
+    // Runs that weren't summarized have nothing to rate
+    let run_id = (!ctx.skip_summary).then(feedback::run_id);
+    if let Some(run_id) = &run_id {
+        feedback::record_run(ctx, run_id, meeting)?;
+        status!("⭐", "Run {}; rate its summary with `distill-cli feedback {} --rating 1-5`", run_id, run_id);
+    }
+    progress::event("finished", json!({ "files": outputs.files, "url": outputs.url, "run_id": run_id }));
+    Ok(outputs)
+}
+
+// Put an event for the finished or failed recording on the EventBridge bus,
+// when eventbridge.bus_name is set. A run isn't failed because its event
+// couldn't be sent.
+pub async fn announce(
+    ctx: &RunContext,
+    meeting: &Meeting,
+    run_report: &report::RunReport,
+    result: Result<&Outputs, &Error>,
+) {
+    let Some(bus) = eventbridge::Bus::from_settings(&ctx.settings) else {
+        return;
+    };
+    // Written files as absolute paths, so a consumer on the same machine can find them
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let (detail_type, status, outputs, error) = match result {
+        Ok(outputs) => (
+            "Distill Run Succeeded",
+            "succeeded",
+            json!({
+                "files": outputs.files.iter().map(|file| cwd.join(file).display().to_string()).collect::<Vec<_>>(),
+                "url": outputs.url,
+            }),
+            None,
+        ),
+        Err(err) => ("Distill Run Failed", "failed", json!(null), Some(format!("{:#}", err).trim().to_string())),
+    };
+    let detail = json!({
+        "status": status,
+        "inputs": meeting.files.iter().map(|file| file.display().to_string()).collect::<Vec<_>>(),
+        "title": meeting.title,
+        "date": meeting.date,
+        "bucket": Some(&ctx.bucket_name).filter(|bucket| !bucket.is_empty()),
+        "transcription_jobs": run_report.transcription_jobs,
+        "outputs": outputs,
+        "error": error,
+        "usage": {
+            "input_tokens": run_report.usage.input_tokens,
+            "output_tokens": run_report.usage.output_tokens,
+        },
+    });
+    if let Err(err) = bus.put(&ctx.settings, &ctx.config, detail_type, &detail).await {
+        eprintln!("{}Couldn't send the EventBridge event: {:#}", progress::icon("⚠️"), err);
+    }
+}
+
+// Upload or read each file of the recording and make one transcript of them,
+// then match speakers, mark audio events, apply the transforms and report
+// compliance watchlist mentions
+#[instrument(name = "transcription", skip_all)]
+pub async fn transcribe(
+    ctx: &RunContext,
+    meeting: &Meeting,
+    spinner: &mut Progress,
+    run_report: &mut report::RunReport,
+) -> Result<Transcription, Error> {
+    progress::set_stage("transcription");
+    let settings = &ctx.settings;
+    let files = &meeting.files[..];
+
+    // A recording in another language than --language-code comes back as
+    // nonsense, so it can be transcribed again in the language it is in
+    let mut language_code = ctx.language_code.clone();
+    let mut transcript = loop {
+        let transcript = transcribe_parts(ctx, files, &language_code, spinner, run_report).await?;
+        if !settings.get_bool("transcript.check_language").unwrap_or(true) {
+            break transcript;
+        }
+        let Some(detected) = language::mismatch(&transcript.text(), &language_code) else {
+            break transcript;
+        };
+        spinner.stop_and_persist(
+            "⚠️",
+            &format!(
+                "The transcript looks like {} rather than {}. If the recording is in {}, run again with --language-code {}.",
+                detected.name, language_code, detected.name, detected.code
+            ),
+        );
+        // Saved transcripts and finished jobs can't be transcribed again
+        let audio = !ctx.transcript_input && ctx.resume_job.is_none();
+        if !audio
+            || !ctx.interactive
+            || !progress::confirm(&format!("Transcribe it again as {} ({})?", detected.name, detected.code), true)?
+        {
+            break transcript;
+        }
+        language_code = detected.code.to_string();
+    };
+
+    // Repeats from a participant who joined twice would skew the summary
+    if settings.get_bool("transcript.collapse_duplicates").unwrap_or(true) {
+        let dropped = transcript.collapse_duplicates();
+        if dropped > 0 {
+            spinner.break_line();
+            status!("🧹", "Removed {} repeated turn(s) from the transcript", dropped);
+        }
+    }
+
+    // Enrolled voices replace the generic labels of a single diarized recording
+    let single_recording = !ctx.transcript_input && ctx.resume_job.is_none() && ctx.track_names.is_empty() && files.len() == 1;
+    let offset = ctx.clip.map_or(0.0, |(start, _)| start);
+    if single_recording && ctx.max_speakers.is_some() {
+        match voiceprint::match_speakers(settings, &mut transcript, &files[0], offset, spinner) {
+            Ok(renames) => {
+                for (speaker, name) in renames {
+                    spinner.break_line();
+                    status!("🎙️", "{} is {}", speaker, name);
+                }
+            }
+            Err(err) => eprintln!("\nWarning: Skipping speaker matching: {:#}", err),
+        }
+    }
+
+    if ctx.annotate_events {
+        // Events need the audio, so they are only detected for a single recording
+        let detected = if single_recording {
+            events::detect(settings, &files[0], offset, spinner).unwrap_or_else(|err| {
+                eprintln!("\nWarning: Skipping audio event detection: {:#}", err);
+                Vec::new()
+            })
+        } else {
+            Vec::new()
+        };
+        events::annotate(&mut transcript, &detected, events::min_pause(settings));
+    }
+
+    transforms::apply(&ctx.transforms, &mut transcript);
+
+    // Corrections made before summarizing carry through to the summary
+    if ctx.review {
+        if ctx.interactive {
+            spinner.success("Transcript ready for review");
+            review::review(&mut transcript)?;
+        } else {
+            spinner.break_line();
+            eprintln!("Warning: Skipping the transcript review, which needs a terminal");
+        }
+    }
+
+    let transcription = transcript.text();
+    run_report.set_transcript(&transcription);
+
+    // Watchlist mentions are shown here; the alert webhook quotes the
+    // transcript, so it is only sent once the summary has been screened
+    let alerts = compliance::scan(&transcript, &ctx.watchlist);
+    if !alerts.is_empty() {
+        spinner.break_line();
+        eprintln!(
+            "{}{} compliance watchlist mention(s) in {}:\n{}",
+            progress::icon("🚨"),
+            alerts.len(),
+            meeting.inputs(),
+            compliance::render_alerts(&alerts)
+        );
+    }
+
+    let confidence = confidence::Confidence::of(&transcript);
+    Ok(Transcription {
+        transcript,
+        text: transcription,
+        alerts,
+        confidence,
+        language_code,
+    })
+}
+
+// Summarize a transcript as the mode and prompt say, and prepare the agenda
+// checklist, FAQ and top terms that follow it
+pub async fn summarize(
+    ctx: &RunContext,
+    meeting: &Meeting,
+    transcription: &Transcription,
+    spinner: &mut Progress,
+    run_report: &mut report::RunReport,
+) -> Result<Summary, Error> {
+    summarize_with(ctx, meeting, transcription, None, spinner, run_report).await
+}
+
+// Whether the transcript's summary is a single model call with
+// `summary_prompt`, which a backfill can leave to a batch inference job. Short
+// transcripts, oversized ones and --samples are summarized as usual.
+pub fn single_call(ctx: &RunContext, transcription: &Transcription) -> bool {
+    let min_words = ctx.settings.get_int("summary.min_words").unwrap_or_default().max(0) as usize;
+    let word_count = transcription.text.split_whitespace().count();
+    let short = !ctx.always_summarize && ctx.mode == Mode::Meeting && word_count < min_words;
+    !ctx.skip_summary && !short && !ctx.size_limit.exceeded_by(word_count) && ctx.samples <= 1
+}
+
+// The prompt the transcript is summarized with: --prompt-file or the mode's
+// prompt, filled in with the meeting's details
+pub fn summary_prompt(ctx: &RunContext, meeting: &Meeting, transcription: &Transcription) -> Result<String, Error> {
+    let prompt_template = match &ctx.prompt_template {
+        Some(template) => template.clone(),
+        None => ctx
+            .settings
+            .get_string(ctx.mode.prompt_key())
+            .unwrap_or_else(|_| ctx.mode.default_prompt().to_string()),
+    };
+    let attendees = if ctx.attendees.is_empty() {
+        transcription.transcript.speakers()
+    } else {
+        ctx.attendees.clone()
+    };
+    summarize::fill_prompt(
+        &prompt_template,
+        &summarize::PromptValues {
+            title: &meeting.title,
+            date: &meeting.date,
+            attendees: &attendees.join(", "),
+            duration: transcription.transcript.segments.last().map(|segment| segment.end_time),
+        },
+    )
+}
+
+// Like `summarize`, with the model's summary already written when `summary`
+// is given, e.g. by a batch inference job
+#[instrument(name = "summarization", skip_all)]
+pub async fn summarize_with(
+    ctx: &RunContext,
+    meeting: &Meeting,
+    transcribed: &Transcription,
+    summary: Option<summarize::Summary>,
+    spinner: &mut Progress,
+    run_report: &mut report::RunReport,
+) -> Result<Summary, Error> {
+    progress::set_stage("summarization");
+    let RunContext {
+        settings,
+        config,
+        ..
+    } = ctx;
+    let mode = ctx.mode;
+    let Transcription {
+        transcript,
+        text: transcription,
+        alerts,
+        confidence,
+        ..
+    } = transcribed;
+
+    // A transcript Transcribe was unsure of, e.g. from a bad phone line, makes
+    // a summary that reads well but is wrong, so it is stopped here or flagged
+    let mut low_confidence = None;
+    if let Some(threshold) = &ctx.confidence_threshold {
+        match confidence {
+            Some(confidence) if confidence.average < threshold.min_confidence => {
+                let message = format!(
+                    "Transcription confidence is low ({}), below the minimum of {:.2}",
+                    confidence.describe(),
+                    threshold.min_confidence
+                );
+                if threshold.policy == confidence::Policy::Fail && !ctx.skip_summary {
+                    bail!(
+                        "\n{}. The audio may be unclear or not in {}. Set transcript.low_confidence to \"warn\" in config.toml to summarize it anyway.",
+                        message,
+                        ctx.language_code
+                    );
+                }
+                spinner.break_line();
+                eprintln!("Warning: {}", message);
+                low_confidence = Some(message);
+            }
+            Some(_) => {}
+            None => eprintln!("Warning: The transcript has no confidence scores, so the minimum confidence isn't checked"),
+        }
+    }
+
+    // A song, a silent file or noise selected by mistake isn't worth a model
+    // call. The share of speech is measured against a single recording only.
+    if let (Some(check), false) = (&ctx.speech_check, ctx.skip_summary) {
+        let duration = meeting
+            .recording
+            .duration
+            .map(|total| ctx.clip.map_or(total, |(start, length)| length.min(total - start)))
+            .filter(|_| meeting.files.len() == 1 && ctx.track_names.is_empty());
+        if let Some(reason) = check.no_speech(transcript, duration) {
+            bail!(
+                "\nNo speech detected in {}: {}. Check that the right file was selected, or set speech.check = false in config.toml to summarize it anyway.",
+                meeting.inputs(),
+                reason
+            );
+        }
+    }
+
+    // Short recordings (e.g. voicemails) aren't worth a model call; the
+    // transcript stands in for the summary unless --always-summarize is set
+    let min_words = settings.get_int("summary.min_words").unwrap_or_default().max(0) as usize;
+    let word_count = transcription.split_whitespace().count();
+
+    // With --skip-summary the transcript is the only text written out
+    let text_only = mode.text_only() || ctx.skip_summary;
+
+    // Set when the summary has already been printed as it was generated
+    let mut summary_streamed = false;
+    // How the transcript's length was handled, when it was summarized
+    let mut size_decision = None;
+
+    // Voicemails and dictation are short by nature, but still need the model
+    let mut summarized_text = if ctx.skip_summary {
+        transcription.clone()
+    } else if !ctx.always_summarize && mode == Mode::Meeting && word_count < min_words {
+        spinner.update("Transcript is short, skipping summarization...");
+        format!(
+            "Note: This recording is too short to summarize ({} words), so the full transcript is shown instead.\n\n{}",
+            word_count, transcription
+        )
+    } else if let Some(summary) = summary {
+        // Written by a batch inference job from `summary_prompt`
+        size_decision = Some(limits::Decision::within_limit(word_count, ctx.size_limit.max_words));
+        run_report.add_batch_usage("Summarization", summary.usage);
+        summary.text
+    } else {
+        // Summarize the transcription
+        spinner.update("Summarizing text...");
+        let summarization_start = Instant::now();
+        let prompt_template = summary_prompt(ctx, meeting, transcribed)?;
+
+        // Transcripts longer than summary.max_words are handled as
+        // summary.oversize_policy says, rather than left for the model to reject
+        let size_limit = &ctx.size_limit;
+        let mut decision = limits::Decision::within_limit(word_count, size_limit.max_words);
+        let mut summary_input = std::borrow::Cow::Borrowed(transcription);
+        if size_limit.exceeded_by(word_count) {
+            decision.policy = Some(size_limit.policy);
+            match size_limit.policy {
+                limits::Policy::Fail => bail!(
+                    "\nThe transcript has {} words, more than summary.max_words ({}). Set summary.oversize_policy to \"truncate\" or \"chunk\" in config.toml to summarize it anyway.",
+                    word_count,
+                    size_limit.max_words
+                ),
+                limits::Policy::Truncate => {
+                    let (truncated, kept_words) = limits::truncate(transcription, size_limit.max_words);
+                    spinner.break_line();
+                    eprintln!(
+                        "{}The transcript has {} words; summarizing the first {}",
+                        progress::icon("✂️"),
+                        word_count,
+                        kept_words
+                    );
+                    decision.words_summarized = kept_words;
+                    summary_input = std::borrow::Cow::Owned(truncated);
+                }
+                limits::Policy::Chunk => {
+                    let parts = limits::split(transcription, size_limit.max_words);
+                    spinner.update(&format!(
+                        "Summarizing {} parts, {} at a time...",
+                        parts.len(),
+                        size_limit.parallel_parts.min(parts.len())
+                    ));
+                    // `buffered` runs up to summary.parallel_parts calls at once
+                    // and yields the partial summaries in transcript order
+                    let results: Vec<_> = stream::iter(&parts)
+                        .map(|part| {
+                            let prompt_template = &prompt_template;
+                            async move {
+                                // Spinners for parts summarized together would draw over each other
+                                let mut background = Progress::background();
+                                let partial =
+                                    summarize::summarize_text(settings, config, part, prompt_template, &mut background)
+                                        .await;
+                                (partial, background)
+                            }
+                        })
+                        .buffered(size_limit.parallel_parts)
+                        .collect()
+                        .await;
+                    let mut partial_summaries = Vec::new();
+                    for (index, (partial, background)) in results.into_iter().enumerate() {
+                        spinner.merge_attempts(&background);
+                        let partial =
+                            partial.with_context(|| format!("Error summarizing part {} of {}", index + 1, parts.len()))?;
+                        run_report.add_usage(&format!("Summarization, part {}", index + 1), partial.usage);
+                        partial_summaries.push(partial.text);
+                    }
+                    spinner.update("Combining summaries...");
+                    decision.parts = parts.len();
+                    summary_input = std::borrow::Cow::Owned(limits::combine(&partial_summaries));
+                }
+            }
+        }
+        size_decision = Some(decision);
+
+        let summary = if ctx.samples > 1 {
+            sample_summaries(ctx, &summary_input, &prompt_template, spinner).await?
+        } else if ctx.output_types == [OutputType::Terminal] || progress::has_events() {
+            // Shown as the model writes it rather than all at once at the end,
+            // and sent as summary_text events to anything following the run
+            let print = ctx.output_types == [OutputType::Terminal];
+            let heading = if mode.text_only() {
+                String::new()
+            } else if mode == Mode::Voicemail {
+                progress::icon("📞")
+            } else {
+                "Summary:\n".to_string()
+            };
+            let mut started = false;
+            let summary = summarize::summarize_text_streaming(
+                settings,
+                config,
+                &summary_input,
+                &prompt_template,
+                spinner,
+                &mut |text| {
+                    progress::event("summary_text", json!({ "text": text }));
+                    if !print {
+                        return;
+                    }
+                    if !started {
+                        started = true;
+                        print!("\n{}", heading);
+                    }
+                    print!("{}", text);
+                    let _ = std::io::stdout().flush();
+                },
+            )
+            .await?;
+            if print {
+                println!("\n");
+            }
+            summary_streamed = print;
+            summary
+        } else {
+            summarize::summarize_text(
+                settings,
+                config,
+                &summary_input,
+                &prompt_template,
+                spinner,
+            )
+            .await?
+        };
+        run_report.stage("Summarization", summarization_start);
+        run_report.add_usage("Summarization", summary.usage);
+        summary.text
+    };
+
+    if let Some(message) = low_confidence {
+        summarized_text = format!("Warning: {}. The transcript may be inaccurate.\n\n{}", message, summarized_text);
+    }
+
+    // Extra sections written after the transcription. Dictation output is the
+    // rewritten text alone, so it gets none.
+    let mut appendices: Vec<(&str, String)> = Vec::new();
+
+    // First, so reviewers see them before anything else
+    if !alerts.is_empty() {
+        appendices.push(("Compliance alerts", compliance::render_alerts(alerts)));
+    }
+
+    if !ctx.agenda_items.is_empty() && !text_only {
+        spinner.update("Checking agenda coverage...");
+        let agenda_start = Instant::now();
+        let response = summarize::summarize_json_array(
+            settings,
+            config,
+            &transcript.timestamped_text(),
+            &agenda::agenda_prompt(&ctx.agenda_items),
+            spinner,
+        )
+        .await;
+        run_report.stage("Agenda", agenda_start);
+        // A checklist the model couldn't produce shouldn't cost the summary
+        match response {
+            Ok(response) => {
+                run_report.add_usage("Agenda", response.usage);
+                run_report.json_repairs += response.repairs;
+                appendices.push((
+                    "Agenda",
+                    agenda::render_checklist(&ctx.agenda_items, &response.values),
+                ));
+            }
+            Err(err) => eprintln!("\nWarning: Skipping agenda checklist: {}", err),
+        }
+    }
+
+    // Kept as structured pairs for the JSON output type
+    let mut faq_pairs = Vec::new();
+    if mode == Mode::Qa && !ctx.skip_summary {
+        spinner.update("Extracting questions and answers...");
+        let faq_start = Instant::now();
+        let response = summarize::summarize_json_array(
+            settings,
+            config,
+            &transcript.timestamped_text(),
+            faq::faq_prompt(),
+            spinner,
+        )
+        .await;
+        run_report.stage("FAQ", faq_start);
+        match response {
+            Ok(response) => {
+                run_report.add_usage("FAQ", response.usage);
+                run_report.json_repairs += response.repairs;
+                let rendered = faq::render_faq(&response.values);
+                if rendered.is_empty() {
+                    eprintln!("\nNo answered questions found for the FAQ");
+                } else {
+                    appendices.push(("FAQ", rendered));
+                }
+                faq_pairs = response.values;
+            }
+            Err(err) => eprintln!("\nWarning: Skipping FAQ: {}", err),
+        }
+    }
+
+    if let Some(count) = ctx.top_terms.filter(|_| !mode.text_only()) {
+        // Only what was said, so speaker labels aren't counted as terms
+        let spoken: Vec<&str> = transcript.segments.iter().map(|s| s.text.as_str()).collect();
+        appendices.push((
+            "Top terms",
+            terms::format_terms(&terms::top_terms(&spoken.join("\n"), count)),
+        ));
+    }
+
+    Ok(Summary {
+        text: summarized_text,
+        streamed: summary_streamed,
+        size_decision,
+        appendices,
+        faq: faq_pairs,
+    })
+}
+
+// Write the summary, transcript and appendices to each output type of the run:
+// files, the terminal, Slack, Notion, Confluence, Jira, search indexes and databases
+#[instrument(name = "output", skip_all)]
+pub async fn render(
+    ctx: &RunContext,
+    meeting: &Meeting,
+    transcription: &Transcription,
+    summary: &Summary,
+    output_filename: Option<&str>,
+    spinner: &mut Progress,
+    run_report: &mut report::RunReport,
+) -> Result<Outputs, Error> {
+    progress::set_stage("output");
+    let RunContext {
+        settings,
+        config,
+        ..
+    } = ctx;
+    let mode = ctx.mode;
+    let files = &meeting.files[..];
+    let input_audio_file = meeting.inputs();
+    let (meeting_title, meeting_date) = (&meeting.title, &meeting.date);
+    // From here `transcription` is the text; the external outputs take all of it
+    let transcribed = transcription;
+    let Transcription {
+        transcript,
+        text: transcription,
+        alerts,
+        confidence,
+        language_code,
+    } = transcription;
+    let Summary {
+        text: summarized_text,
+        streamed: summary_streamed,
+        size_decision,
+        appendices,
+        faq: faq_pairs,
+    } = summary;
+    let text_only = mode.text_only() || ctx.skip_summary;
+
+    let output_start = Instant::now();
+    let written = written_label(ctx);
+
+    let many_outputs = ctx.output_types.len() > 1;
+    // URL of the Notion or Confluence page written in this run, for the other outputs to link to
+    let mut published_url: Option<String> = None;
+    // Files written in this run, to keep in the session
+    let mut written_files: Vec<String> = Vec::new();
+
+    // With a guardrail configured, the summary is screened once before it is
+    // sent anywhere. If the guardrail flags it, none of the external outputs
+    // get it; the summary and transcription are written locally for review.
+    let external: Vec<OutputType> = ctx
+        .output_types
+        .iter()
+        .copied()
+        .filter(OutputType::is_external)
+        .collect();
+    let mut external_names: Vec<String> = external.iter().map(OutputType::to_string).collect();
+    // The compliance alert quotes the transcript, so it is held with the outputs
+    let alert_webhook = settings.get_string("compliance.alert_webhook").unwrap_or_default();
+    let send_alert = !alerts.is_empty() && !alert_webhook.is_empty();
+    if send_alert {
+        external_names.push("the compliance alert webhook".to_string());
+    }
+    let guardrail_id = settings.get_string("moderation.guardrail_id").unwrap_or_default();
+    let mut held = false;
+    if !external_names.is_empty() && !guardrail_id.is_empty() {
+        spinner.update("Screening summary...");
+        let guardrail_version = settings
+            .get_string("moderation.guardrail_version")
+            .unwrap_or_else(|_| "DRAFT".to_string());
+        let screening_start = Instant::now();
+        let flagged = moderation::screen(config, summarized_text, &guardrail_id, &guardrail_version).await?;
+        run_report.stage("Screening", screening_start);
+
+        if let Some(reason) = flagged {
+            let stem = files[0].file_stem().unwrap_or_default().to_string_lossy();
+            let filename = format!("{}-held.md", stem);
+            output::write_atomic(&filename, |file| {
+                write!(file, "# Summary\n\n{}\n\n# Transcription\n\n{}", summarized_text, transcription)?;
+                if !alerts.is_empty() {
+                    write!(file, "\n\n# Compliance alerts\n\n{}", compliance::render_alerts(alerts))?;
+                }
+                Ok(())
+            })?;
+            spinner.stop_and_persist(
+                "⚠️",
+                &format!("Summary held for review, not sent to {}", external_names.join(", ")),
+            );
+            println!("Reason: {}", reason);
+            written_files.push(filename.clone());
+            println!("{}Summary and transcription written to {}", progress::icon("💾"), filename);
+            held = true;
+        }
+    }
+
+    if send_alert && !held {
+        let payload = compliance::webhook_payload(&input_audio_file, alerts);
+        match slack::post_webhook(&net::http_client(settings)?, &alert_webhook, &payload).await {
+            Ok(()) => status!("📣", "Compliance alert sent"),
+            Err(err) => eprintln!("Warning: Failed to send compliance alert: {}", err),
+        }
+    }
+
+    for &output_type in &ctx.output_types {
+        if output_type.is_external() {
+            continue;
+        }
+        // With several outputs, one --output-filename is reused with each type's extension
+        let typed_filename = match (output_filename, output_type.extension()) {
+            (Some(filename), Some(ext)) if many_outputs => {
+                Some(Path::new(filename).with_extension(ext).display().to_string())
+            }
+            (filename, _) => filename.map(str::to_string),
+        };
+        let output_filename = typed_filename.as_deref();
+        if many_outputs {
+            spinner.update(&format!("Writing {} output...", output_type));
+        }
+
+        match output_type {
+            OutputType::Word => {
+                let filename = output_filename.unwrap_or("summary.docx");
+
+                // Creating a new document and adding paragraphs
+                let mut doc = Docx::new()
+                    .add_paragraph(Paragraph::new().add_run(Run::new().add_text(summarized_text)));
+                if !text_only {
+                    doc = doc
+                        .add_paragraph(Paragraph::new().add_run(Run::new().add_text("\n\n")))
+                        .add_paragraph(Paragraph::new().add_run(Run::new().add_text("Transcription:\n")));
+                    for line in transcription.lines() {
+                        doc = doc.add_paragraph(Paragraph::new().add_run(Run::new().add_text(line)));
+                    }
+                }
+                for (title, body) in appendices {
+                    doc = doc
+                        .add_paragraph(Paragraph::new().add_run(Run::new().add_text(format!("{}:\n", title))))
+                        .add_paragraph(Paragraph::new().add_run(Run::new().add_text(body)));
+                }
+
+                // Building and saving the document
+                output::write_atomic(filename, |file| {
+                    doc.build()
+                        .pack(file)
+                        .map_err(|e| anyhow::anyhow!("Error writing Word document: {}", e))?;
+                    Ok(())
+                })?;
+
+                spinner.success("Done!");
+                written_files.push(filename.to_string());
+                println!("{}{} written to {}", progress::icon("💾"), written, filename);
+            }
+            OutputType::Text => {
+                let filename = output_filename.unwrap_or("summary.txt");
+                output::write_atomic(filename, |file| {
+                    file.write_all(summarized_text.as_bytes())?;
+                    if !text_only {
+                        file.write_all(b"\n\nTranscription:\n")?;
+                        file.write_all(transcription.as_bytes())?;
+                    }
+                    for (title, body) in appendices {
+                        write!(file, "\n\n{}:\n{}", title, body)?;
+                    }
+                    Ok(())
+                })?;
+
+                spinner.success("Done!");
+                written_files.push(filename.to_string());
+                println!("{}{} written to {}", progress::icon("💾"), written, filename);
+            }
+            OutputType::Pdf => {
+                let filename = output_filename.unwrap_or("summary.pdf");
+
+                let mut sections: Vec<(&str, &str)> = if text_only {
+                    vec![(written, summarized_text.as_str())]
+                } else {
+                    vec![("Summary", summarized_text.as_str()), ("Transcription", transcription.as_str())]
+                };
+                sections.extend(appendices.iter().map(|(title, body)| (*title, body.as_str())));
+                pdf::write_pdf(filename, &input_audio_file, &sections)?;
+
+                spinner.success("Done!");
+                written_files.push(filename.to_string());
+                println!("{}{} written to {}", progress::icon("💾"), written, filename);
+            }
+            OutputType::Html => {
+                let filename = output_filename.unwrap_or("summary.html");
+                let (summary_title, transcript_section) = if text_only {
+                    (written, None)
+                } else {
+                    ("Summary", Some(transcript))
+                };
+                output::write_atomic(filename, |file| {
+                    html::write(
+                        file,
+                        &input_audio_file,
+                        summary_title,
+                        summarized_text,
+                        transcript_section,
+                        appendices,
+                    )
+                    .map_err(|e| anyhow::anyhow!("Error writing HTML file: {}", e))
+                })?;
+
+                spinner.success("Done!");
+                written_files.push(filename.to_string());
+                println!("{}{} written to {}", progress::icon("💾"), written, filename);
+            }
+            OutputType::Terminal => {
+                spinner.success("Done!");
+                if !summary_streamed {
+                    println!();
+                    if text_only {
+                        println!("{}\n", summarized_text);
+                    } else if mode == Mode::Voicemail {
+                        println!("{}{}\n", progress::icon("📞"), summarized_text);
+                    } else {
+                        println!("Summary:\n{}\n", summarized_text);
+                    }
+                }
+                if !text_only && mode != Mode::Voicemail {
+                    println!("Transcription:\n{}\n", transcription);
+                }
+                for (title, body) in appendices {
+                    println!("{}:\n{}\n", title, body);
+                }
+            }
+            OutputType::Markdown => {
+                let filename = output_filename.unwrap_or("summary.md");
+                output::write_atomic(filename, |file| {
+                    if text_only {
+                        write!(file, "{}", summarized_text)?;
+                    } else {
+                        write!(file, "# Summary\n\n{}\n\n# Transcription\n\n", summarized_text)?;
+                        transcript.write_markdown(file)?;
+                    }
+                    for (title, body) in appendices {
+                        write!(file, "\n\n# {}\n\n{}", title, body)?;
+                    }
+                    Ok(())
+                })?;
+
+                spinner.success("Done!");
+                written_files.push(filename.to_string());
+                println!("{}{} written to {}", progress::icon("💾"), written, filename);
+            }
+            // Sent below, all at once
+            OutputType::Slack
+            | OutputType::Notion
+            | OutputType::Confluence
+            | OutputType::Jira
+            | OutputType::Kendra
+            | OutputType::QBusiness
+            | OutputType::Sns
+            | OutputType::Dynamodb
+            | OutputType::Postgres => {}
+            OutputType::Srt | OutputType::Vtt => {
+                let cues = subtitles::cues(transcript)?;
+                let filename = output_filename.unwrap_or(if output_type == OutputType::Srt {
+                    "subtitles.srt"
+                } else {
+                    "subtitles.vtt"
+                });
+                output::write_atomic(filename, |file| {
+                    if output_type == OutputType::Srt {
+                        subtitles::write_srt(file, &cues)
+                    } else {
+                        subtitles::write_vtt(file, &cues)
+                    }
+                    .map_err(|e| anyhow::anyhow!("Error writing subtitle file: {}", e))
+                })?;
+
+                spinner.success("Done!");
+                written_files.push(filename.to_string());
+                println!("{}Subtitles written to {}", progress::icon("💾"), filename);
+                if !text_only {
+                    println!("\nSummary:\n{}\n", summarized_text);
+                }
+                for (title, body) in appendices {
+                    println!("{}:\n{}\n", title, body);
+                }
+            }
+            OutputType::Json => {
+                let (summary, action_items, rest) = if text_only {
+                    (summarized_text.clone(), String::new(), String::new())
+                } else {
+                    parse_summary_sections(summarized_text)
+                };
+                let segments: Vec<_> = transcript
+                    .segments
+                    .iter()
+                    .map(|segment| {
+                        json!({
+                            "speaker": segment.speaker,
+                            "start_time": segment.start_time,
+                            "end_time": segment.end_time,
+                            "text": segment.text,
+                        })
+                    })
+                    .collect();
+                let appendix_map: serde_json::Map<_, _> = appendices
+                    .iter()
+                    .map(|(title, body)| (title.to_string(), json!(body)))
+                    .collect();
+                run_report.set_attempts(spinner.attempts());
+                let document = json!({
+                    "summary": summary,
+                    "action_items": action_items.lines().filter(|l| !l.trim().is_empty()).collect::<Vec<_>>(),
+                    "other": rest,
+                    "transcript": transcription,
+                    "segments": segments,
+                    "appendices": appendix_map,
+                    "faq": faq_pairs,
+                    "compliance_alerts": compliance::alerts_json(alerts),
+                    "timing": run_report.to_json(),
+                    "cost": run_report.cost_json(),
+                    "metadata": {
+                        "input_files": files.iter().map(|f| f.display().to_string()).collect::<Vec<_>>(),
+                        "mode": format!("{:?}", mode).to_lowercase(),
+                        "language_code": language_code,
+                        "model_id": settings.get_string("model.model_id").unwrap_or_default(),
+                        "bucket": ctx.bucket_name,
+                        "title": meeting_title,
+                        "date": meeting_date,
+                        "recording": meeting.recording.to_json(),
+                        "transcript_size": size_decision.as_ref().map(limits::Decision::to_json),
+                        "confidence": confidence.as_ref().map(confidence::Confidence::to_json),
+                    },
+                });
+
+                // Without a filename the document goes to stdout for piping; all
+                // progress output is on stderr
+                spinner.success("Done!");
+                match output_filename {
+                    Some(filename) => {
+                        output::write_atomic(filename, |file| {
+                            serde_json::to_writer_pretty(file, &document)
+                                .map_err(|e| anyhow::anyhow!("Error writing JSON file: {}", e))
+                        })?;
+                        written_files.push(filename.to_string());
+                        eprintln!("{}{} written to {}", progress::icon("💾"), written, filename);
+                    }
+                    None => {
+                        let mut stdout = std::io::stdout().lock();
+                        serde_json::to_writer_pretty(&mut stdout, &document)?;
+                        writeln!(stdout)?;
+                    }
+                }
+            }
+        }
+    }
+
+    // The external outputs are sent at the same time, Notion and Confluence
+    // first so the others can link to the page. Each reports its own result,
+    // and the run fails only once every output has been tried.
+    if !held && !external.is_empty() {
+        spinner.stop_and_persist("📤", &format!("Sending to {}...", external_names.join(", ")));
+        let (pages, others): (Vec<OutputType>, Vec<OutputType>) = external
+            .iter()
+            .partition(|output_type| matches!(output_type, OutputType::Notion | OutputType::Confluence));
+        let mut failed = Vec::new();
+        for batch in [pages, others] {
+            let link = published_url.clone();
+            let results = join_all(batch.into_iter().map(|output_type| {
+                let link = link.as_deref();
+                async move {
+                    // Spinners for outputs sent together would draw over each other
+                    let mut background = Progress::background();
+                    let result =
+                        send_external(ctx, meeting, transcribed, summary, output_type, link, &mut background).await;
+                    (output_type, result, background)
+                }
+            }))
+            .await;
+            for (output_type, result, background) in results {
+                spinner.merge_attempts(&background);
+                match result {
+                    Ok(url) => published_url = published_url.or(url),
+                    Err(err) => {
+                        eprintln!("{}Error sending {} output: {:?}", progress::icon("❌"), output_type, err);
+                        failed.push(output_type.to_string());
+                    }
+                }
+            }
+        }
+        if !failed.is_empty() {
+            bail!("\nCould not send to {}; see the errors above.", failed.join(", "));
+        }
+    }
+    run_report.stage("Output", output_start);
+
+    Ok(Outputs {
+        files: written_files,
+        url: published_url,
+        // Known once the run is finished
+        cost_usd: None,
+    })
+}
+
+// Send the summary to one external output: Slack, a Notion or Confluence page,
+// Jira, a search index, SNS or a database. `link` is the page written earlier
+// in the run, if any. Returns the URL of the Notion or Confluence page created.
+async fn send_external(
+    ctx: &RunContext,
+    meeting: &Meeting,
+    transcription: &Transcription,
+    summary: &Summary,
+    output_type: OutputType,
+    link: Option<&str>,
+    spinner: &mut Progress,
+) -> Result<Option<String>, Error> {
+    let RunContext {
+        settings,
+        config,
+        ..
+    } = ctx;
+    let mode = ctx.mode;
+    let files = &meeting.files[..];
+    let input_audio_file = meeting.inputs();
+    let (meeting_title, meeting_date) = (&meeting.title, &meeting.date);
+    let Transcription {
+        transcript,
+        text: transcription,
+        language_code,
+        ..
+    } = transcription;
+    let Summary {
+        text: summarized_text,
+        appendices,
+        faq: faq_pairs,
+        ..
+    } = summary;
+    let text_only = mode.text_only() || ctx.skip_summary;
+    let written = written_label(ctx);
+
+    match output_type {
+        OutputType::Notion => {
+            let token = settings.get_string("notion.token").unwrap_or_default();
+            let parent_id = settings.get_string("notion.parent_id").unwrap_or_default();
+            if token.is_empty() || parent_id.is_empty() {
+                spinner.stop_and_persist(
+                    "⚠️",
+                    "Notion token or parent_id is not configured. Skipping Notion page.",
+                );
+                return Ok(None);
+            }
+
+            spinner.update("Creating Notion page...");
+            let (summary, action_items, rest) = if text_only {
+                (summarized_text.clone(), String::new(), String::new())
+            } else {
+                parse_summary_sections(summarized_text)
+            };
+            let url = notion::create_page(
+                &net::http_client(settings)?,
+                &token,
+                &parent_id,
+                settings.get_string("notion.parent_type").map_or(true, |t| t != "page"),
+                &notion::Page {
+                    title: &format!("{} ({})", meeting_title, meeting_date),
+                    summary: &summary,
+                    action_items: &action_items,
+                    other: &rest,
+                    appendices,
+                    transcript: if text_only { "" } else { transcription },
+                },
+            )
+            .await
+            .context("Error creating Notion page")?;
+
+            println!("{}Notion page created: {}", progress::icon("📝"), url);
+            Ok(Some(url))
+        }
+        OutputType::Jira => {
+            let setting = |key: &str| settings.get_string(&format!("jira.{}", key)).unwrap_or_default();
+            let (base_url, email, api_token, project_key) =
+                (setting("base_url"), setting("email"), setting("api_token"), setting("project_key"));
+            if [&base_url, &email, &api_token, &project_key].iter().any(|value| value.is_empty()) {
+                spinner.stop_and_persist(
+                    "⚠️",
+                    "Jira base_url, email, api_token or project_key is not configured. Skipping Jira issues.",
+                );
+                return Ok(None);
+            }
+            let issue_type = Some(setting("issue_type")).filter(|t| !t.is_empty()).unwrap_or_else(|| "Task".to_string());
+
+            let (summary, action_items, _) = parse_summary_sections(summarized_text);
+            let action_items: Vec<&str> = action_items.lines().filter(|l| !l.trim().is_empty()).collect();
+            if text_only || action_items.is_empty() {
+                spinner.stop_and_persist("📋", "No action items, so no Jira issues were created");
+                return Ok(None);
+            }
+
+            spinner.update(&format!("Creating {} Jira issues...", action_items.len()));
+            let client = net::http_client(settings)?;
+            let site = jira::Site {
+                base_url: &base_url,
+                email: &email,
+                api_token: &api_token,
+                project_key: &project_key,
+                issue_type: &issue_type,
+            };
+            let title = format!("{} ({})", meeting_title, meeting_date);
+            let meeting = jira::Meeting {
+                title: &title,
+                summary: &summary,
+                link,
+            };
+            let results = join_all(
+                action_items
+                    .iter()
+                    .map(|item| jira::create_issue(&client, &site, item, &meeting)),
+            )
+            .await;
+
+            let mut created = Vec::new();
+            for (item, result) in action_items.iter().zip(results) {
+                match result {
+                    Ok(key) => created.push(key),
+                    Err(err) => eprintln!("Error creating Jira issue for \"{}\": {}", item.trim(), err),
+                }
+            }
+            println!(
+                "{}Created {} of {} Jira issues: {}",
+                progress::icon("📋"),
+                created.len(),
+                action_items.len(),
+                created.join(", ")
+            );
+            if created.len() < action_items.len() {
+                let missing = action_items.len() - created.len();
+                bail!("{} of {} Jira issues could not be created", missing, action_items.len());
+            }
+            Ok(None)
+        }
+        OutputType::Confluence => {
+            let setting = |key: &str| settings.get_string(&format!("confluence.{}", key)).unwrap_or_default();
+            let (base_url, email, api_token, space_key) =
+                (setting("base_url"), setting("email"), setting("api_token"), setting("space_key"));
+            if [&base_url, &email, &api_token, &space_key].iter().any(|value| value.is_empty()) {
+                spinner.stop_and_persist(
+                    "⚠️",
+                    "Confluence base_url, email, api_token or space_key is not configured. Skipping Confluence page.",
+                );
+                return Ok(None);
+            }
+            let parent_id = setting("parent_id");
+
+            spinner.update("Publishing Confluence page...");
+            let (summary, action_items, rest) = if text_only {
+                (summarized_text.clone(), String::new(), String::new())
+            } else {
+                parse_summary_sections(summarized_text)
+            };
+            let url = confluence::publish(
+                &net::http_client(settings)?,
+                &confluence::Site {
+                    base_url: &base_url,
+                    email: &email,
+                    api_token: &api_token,
+                    space_key: &space_key,
+                    parent_id: Some(parent_id.as_str()).filter(|id| !id.is_empty()),
+                },
+                &confluence::Page {
+                    title: &format!("{} ({})", meeting_title, meeting_date),
+                    summary: &summary,
+                    action_items: &action_items,
+                    other: &rest,
+                    appendices,
+                    transcript: if text_only { "" } else { transcription },
+                },
+            )
+            .await
+            .context("Error publishing Confluence page")?;
+
+            println!("{}Confluence page published: {}", progress::icon("📝"), url);
+            Ok(Some(url))
+        }
+        OutputType::Kendra | OutputType::QBusiness => {
+            let (index, required) = if output_type == OutputType::Kendra {
+                (knowledge::Index::kendra(settings), "Kendra index_id is")
+            } else {
+                (knowledge::Index::q_business(settings), "Q Business application_id or index_id is")
+            };
+            let Some(index) = index else {
+                spinner.stop_and_persist(
+                    "⚠️",
+                    &format!("{} not configured. Skipping {}.", required, output_type),
+                );
+                return Ok(None);
+            };
+
+            spinner.update(&format!("Adding to {}...", index.name()));
+            let authors = attendees(ctx, transcript);
+            let id = naming::record_id(files, transcription)?;
+            let title = format!("{} ({})", meeting_title, meeting_date);
+            let mut summary_text = summarized_text.clone();
+            for (title, body) in appendices {
+                summary_text.push_str(&format!("\n\n{}:\n{}", title, body));
+            }
+            let document = |suffix: &str, category, text| knowledge::Document {
+                id: format!("{}-{}", id, suffix),
+                title: if suffix == "summary" { title.clone() } else { format!("{} transcript", title) },
+                category,
+                text,
+                created_at: naming::parse_date(meeting_date),
+                authors: &authors,
+                source_uri: link,
+                language_code,
+            };
+            let mut documents = vec![document("summary", "Meeting summary", &summary_text)];
+            if !text_only {
+                documents.push(document("transcript", "Meeting transcript", transcription));
+            }
+            knowledge::publish(settings, config, &index, &documents)
+                .await
+                .with_context(|| format!("Error adding documents to {}", index.name()))?;
+
+            println!("{}{} added to {}", progress::icon("🔎"), written, index.name());
+            Ok(None)
+        }
+        OutputType::Dynamodb | OutputType::Postgres => {
+            let store = if output_type == OutputType::Dynamodb {
+                database::Store::dynamodb(settings)
+            } else {
+                database::Store::postgres(settings)
+            };
+            let Some(store) = store else {
+                let required = if output_type == OutputType::Dynamodb { "DynamoDB table" } else { "Postgres url" };
+                spinner.stop_and_persist(
+                    "⚠️",
+                    &format!("{} is not configured. Skipping {}.", required, output_type),
+                );
+                return Ok(None);
+            };
+
+            spinner.update(&format!("Saving to {}...", store.name()));
+            let (summary, action_items, rest) = if text_only {
+                (summarized_text.clone(), String::new(), String::new())
+            } else {
+                parse_summary_sections(summarized_text)
+            };
+            let speakers = attendees(ctx, transcript);
+            let record = database::Record {
+                id: naming::record_id(files, transcription)?,
+                title: meeting_title,
+                date: meeting_date,
+                created_at: naming::parse_date(meeting_date),
+                summary: &summary,
+                action_items: action_items.lines().filter(|l| !l.trim().is_empty()).collect(),
+                other: &rest,
+                appendices,
+                faq: faq_pairs,
+                transcript: if text_only { "" } else { transcription },
+                speakers: &speakers,
+                link,
+                metadata: json!({
+                    "input_files": files.iter().map(|f| f.display().to_string()).collect::<Vec<_>>(),
+                    "mode": format!("{:?}", mode).to_lowercase(),
+                    "language_code": language_code,
+                    "model_id": settings.get_string("model.model_id").unwrap_or_default(),
+                }),
+            };
+            database::save(settings, config, &store, &record)
+                .await
+                .with_context(|| format!("Error saving to {}", store.name()))?;
+
+            println!("{}{} saved to {}", progress::icon("🗄️"), written, store.name());
+            Ok(None)
+        }
+        OutputType::Sns => {
+            let Some(topic) = sns::Topic::from_settings(settings)? else {
+                spinner.stop_and_persist("⚠️", "SNS topic_arn is not configured. Skipping SNS notification.");
+                return Ok(None);
+            };
+
+            spinner.update("Publishing to SNS...");
+            let mut summary_text = if text_only { String::new() } else { summarized_text.clone() };
+            if !summary_text.is_empty() {
+                for (title, body) in appendices {
+                    summary_text.push_str(&format!("\n\n{}:\n{}", title, body));
+                }
+            }
+            sns::publish(
+                settings,
+                config,
+                &topic,
+                &sns::Message {
+                    title: meeting_title,
+                    date: meeting_date,
+                    summary: &summary_text,
+                    link,
+                },
+                spinner,
+            )
+            .await
+            .context("Error publishing to SNS")?;
+
+            println!("{}Published to SNS topic {}", progress::icon("📣"), topic.arn);
+            Ok(None)
+        }
+        OutputType::Slack => {
+            let client = net::http_client(settings)?;
+
+            // With a bot token and a channel (--slack-channel or slack.channel)
+            // the summary is posted as the bot, which can target any channel
+            // and start a thread; otherwise it goes to the webhook
+            let slack_bot_token = settings.get_string("slack.bot_token").unwrap_or_default();
+            let slack_channel = settings.get_string("slack.channel").unwrap_or_default();
+            let bot_channel = Some(slack_channel.as_str())
+                .filter(|channel| !channel.is_empty() && !slack_bot_token.is_empty());
+            if !slack_channel.is_empty() && slack_bot_token.is_empty() {
+                eprintln!("Warning: Posting to a Slack channel needs slack.bot_token; using the webhook instead");
+            }
+            let slack_webhook_endpoint = settings
+                .get_string("slack.webhook_endpoint")
+                .unwrap_or_default();
+
+            let summary_payload = if bot_channel.is_none() && slack_webhook_endpoint.is_empty() {
+                spinner.stop_and_persist(
+                    "⚠️",
+                    "Slack webhook endpoint is not configured. Skipping Slack notification.",
+                );
+                println!("Summary:\n{}\n", summarized_text);
+                None
+            } else {
+                let (summary, action_items, rest) = if text_only {
+                    (summarized_text.clone(), String::new(), String::new())
+                } else {
+                    parse_summary_sections(summarized_text)
+                };
+                // Workflow Builder webhooks fill in named variables; incoming
+                // webhooks and the bot get a formatted Block Kit message
+                Some(if bot_channel.is_none() && slack::is_workflow_webhook(&slack_webhook_endpoint) {
+                    json!({
+                        "Content": &input_audio_file,
+                        "SummaryText": summary,
+                        "KeyActions": action_items,
+                        "Others": rest
+                    })
+                } else {
+                    slack::summary_blocks(&slack::Message {
+                        title: meeting_title,
+                        date: meeting_date,
+                        summary: &summary,
+                        action_items: &action_items,
+                        other: &rest,
+                    })
+                })
+            };
+
+            // With a bot token and an owner mapping, DM each owner their action items
+            let owners: HashMap<String, String> = settings
+                .get_table("slack.owners")
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|(name, id)| id.into_string().ok().map(|id| (name, id)))
+                .collect();
+            let routed = if !slack_bot_token.is_empty() && !owners.is_empty() && !text_only {
+                let (_, action_items, _) = parse_summary_sections(summarized_text);
+                slack::route_action_items(&action_items, &owners)
+            } else {
+                HashMap::new()
+            };
+
+            // The full transcript can follow the summary, as a file and/or in
+            // the summary's thread. Both need the bot, as webhooks can neither
+            // upload files nor say which message they posted.
+            let upload_transcript = settings.get_bool("slack.upload_transcript").unwrap_or(false) && !text_only;
+            let thread_transcript = settings.get_bool("slack.thread_transcript").unwrap_or(false) && !text_only;
+            if (upload_transcript || thread_transcript) && bot_channel.is_none() {
+                eprintln!("Warning: Sharing the transcript in Slack needs slack.bot_token and a channel (slack.channel or --slack-channel); not sharing it");
+            }
+
+            // The summary then its transcript, alongside every DM, so one slow
+            // request doesn't hold up the others, and each reports its own result
+            let channel_post = async {
+                let Some(payload) = &summary_payload else {
+                    return (None, None);
+                };
+                let posted = match bot_channel {
+                    Some(channel) => slack::post_payload(&client, &slack_bot_token, channel, payload, None)
+                        .await
+                        .map(Some),
+                    None => slack::post_webhook(&client, &slack_webhook_endpoint, payload)
+                        .await
+                        .map(|_| None),
+                };
+                let thread_ts = match posted {
+                    Ok(ts) => ts.filter(|_| thread_transcript),
+                    Err(err) => return (Some(Err(err)), None),
+                };
+                let shared = match (bot_channel, thread_ts) {
+                    (Some(channel), thread_ts) if upload_transcript => {
+                        let stem = files[0].file_stem().unwrap_or_default().to_string_lossy();
+                        Some(
+                            slack::upload_file(
+                                &client,
+                                &slack_bot_token,
+                                channel,
+                                thread_ts.as_deref(),
+                                &format!("{}-transcript.txt", stem),
+                                &format!("Transcript: {} ({})", meeting_title, meeting_date),
+                                transcription,
+                            )
+                            .await,
+                        )
+                    }
+                    (Some(channel), Some(thread_ts)) => Some(
+                        slack::post_thread_text(&client, &slack_bot_token, channel, &thread_ts, transcription).await,
+                    ),
+                    _ => None,
+                };
+                (Some(Ok(())), shared)
+            };
+            let direct_messages = join_all(routed.into_iter().map(|(slack_id, items)| {
+                let text = format!(
+                    "Your action items from {}:\n{}",
+                    input_audio_file,
+                    items.join("\n")
+                );
+                let (client, slack_bot_token) = (&client, &slack_bot_token);
+                async move {
+                    let result = slack::post_message(client, slack_bot_token, &slack_id, &text).await;
+                    (slack_id, items.len(), result)
+                }
+            }));
+            if summary_payload.is_some() {
+                spinner.update("Sending summary to Slack...");
+            }
+            let ((summary_result, transcript_result), dm_results) = tokio::join!(channel_post, direct_messages);
+
+            // Every post is tried; the output fails if any of them did
+            let mut failed = Vec::new();
+            match summary_result {
+                Some(Ok(())) => spinner.success("Summary sent to Slack!"),
+                Some(Err(err)) => {
+                    spinner.stop_and_persist("❌", "Failed to send summary to Slack!");
+                    eprintln!("Error sending summary to Slack: {}", err);
+                    failed.push("the summary".to_string());
+                }
+                None => {}
+            }
+            match transcript_result {
+                Some(Ok(())) => println!("{}Transcript shared in Slack", progress::icon("📎")),
+                Some(Err(err)) => {
+                    eprintln!("Error sharing transcript in Slack: {}", err);
+                    failed.push("the transcript".to_string());
+                }
+                None => {}
+            }
+            for (slack_id, count, result) in dm_results {
+                match result {
+                    Ok(()) => println!("{}Sent {} action item(s) to {}", progress::icon("📨"), count, slack_id),
+                    Err(err) => {
+                        eprintln!("Error sending action items to {}: {}", slack_id, err);
+                        failed.push(format!("action items to {}", slack_id));
+                    }
+                }
+            }
+            if !failed.is_empty() {
+                bail!("Could not send {} to Slack", failed.join(", "));
+            }
+            Ok(None)
+        }
+        // Written by `render`
+        OutputType::Terminal
+        | OutputType::Text
+        | OutputType::Word
+        | OutputType::Markdown
+        | OutputType::Json
+        | OutputType::Srt
+        | OutputType::Vtt
+        | OutputType::Pdf
+        | OutputType::Html => Ok(None),
+    }
+}
+
+// What the outputs hold, for messages such as "Summary and transcription written to summary.md"
+fn written_label(ctx: &RunContext) -> &'static str {
+    if ctx.skip_summary {
+        "Transcription"
+    } else if ctx.mode.text_only() {
+        "Text"
+    } else {
+        "Summary and transcription"
+    }
+}
+
+// Named speakers, not "Speaker 1", are the people who were there
+fn attendees(ctx: &RunContext, transcript: &transcribe::Transcript) -> Vec<String> {
+    if ctx.attendees.is_empty() {
+        transcript
+            .speakers()
+            .into_iter()
+            .filter(|speaker| !speaker.starts_with("Speaker "))
+            .collect()
+    } else {
+        ctx.attendees.clone()
+    }
+}
+
+// Generate several summaries at summary.sample_temperature and keep the one
+// the model (or, with --pick-sample, the user) judges best
+async fn sample_summaries(
+    ctx: &RunContext,
+    transcription: &str,
+    prompt_template: &str,
+    spinner: &mut Progress,
+) -> Result<summarize::Summary, Error> {
+    let temperature = ctx
+        .settings
+        .get_float("summary.sample_temperature")
+        .unwrap_or(0.8);
+    let mut usage = summarize::Usage::default();
+
+    let mut candidates = Vec::new();
+    for i in 0..ctx.samples {
+        spinner.update(&format!("Summarizing text (sample {} of {})...", i + 1, ctx.samples));
+        let sample = summarize::summarize_text_at(
+            &ctx.settings,
+            &ctx.config,
+            transcription,
+            prompt_template,
+            temperature,
+            spinner,
+        )
+        .await?;
+        usage += sample.usage;
+        candidates.push(sample.text);
+    }
+
+    let choice = if ctx.pick_sample {
+        spinner.success(&format!("{} summaries ready", candidates.len()));
+        for (i, candidate) in candidates.iter().enumerate() {
+            eprintln!("\n--- Summary {} ---\n{}", i + 1, candidate);
+        }
+        eprintln!();
+        let labels: Vec<String> = (1..=candidates.len()).map(|i| format!("Summary {}", i)).collect();
+        let choice = progress::choose("Which summary should be used?", &labels)?;
+        spinner.update("Writing output...");
+        choice
+    } else {
+        spinner.update("Choosing the best summary...");
+        let (choice, verdict) =
+            summarize::pick_best(&ctx.settings, &ctx.config, transcription, &candidates, spinner).await?;
+        usage += verdict.usage;
+        choice
+    };
+
+    Ok(summarize::Summary {
+        text: candidates.swap_remove(choice),
+        usage,
+    })
+}
+
+// Transcribe, or read, each file of a recording in `language_code` and join
+// them into one transcript. Parts of a merged meeting are transcribed
+// separately and stitched in order, while per-speaker tracks are interleaved
+// by time.
+async fn transcribe_parts(
+    ctx: &RunContext,
+    files: &[PathBuf],
+    language_code: &str,
+    spinner: &mut Progress,
+    run_report: &mut report::RunReport,
+) -> Result<transcribe::Transcript, Error> {
+    let transcript = if !ctx.track_names.is_empty() {
+        let mut tracks = Vec::new();
+        for (name, file_path) in ctx.track_names.iter().zip(files) {
+            let track = transcribe_file(ctx, file_path, language_code, spinner, run_report).await?;
+            tracks.push((name.clone(), track));
+        }
+        transcribe::interleave(tracks)
+    } else {
+        let mut transcript = transcribe::Transcript::default();
+        for file_path in files {
+            let part = if ctx.transcript_input {
+                transcribe::read_transcript_file(file_path)?
+            } else if let Some(job_name) = &ctx.resume_job {
+                let transcription_start = Instant::now();
+                let transcript = transcribe::resume_transcription(
+                    &ctx.transcribe_config,
+                    spinner,
+                    &transcribe::TranscribeOptions {
+                        settings: &ctx.settings,
+                        job_name,
+                        language_code,
+                        poll_interval: ctx.mode.poll_interval(),
+                        split_on_pause: None,
+                        max_speakers: ctx.max_speakers,
+                        vocabulary_filter: None,
+                    },
+                )
+                .await?;
+                run_report.stage("Transcription", transcription_start);
+                run_report.transcription_jobs.push(job_name.clone());
+                run_report.add_transcribed_audio(transcript.segments.last().map_or(0.0, |s| s.end_time));
+                transcript
+            } else {
+                transcribe_file(ctx, file_path, language_code, spinner, run_report).await?
+            };
+            transcript.append(part);
+        }
+        transcript
+    };
+    Ok(transcript)
+}
+
+// Transcribe a single audio file, uploading it to S3 first for transcribers
+// that read from there
+async fn transcribe_file(
+    ctx: &RunContext,
+    file_path: &Path,
+    language_code: &str,
+    spinner: &mut Progress,
+    run_report: &mut report::RunReport,
+) -> Result<transcribe::Transcript, Error> {
+    if !ctx.transcriber.reads_from_s3() {
+        if file_path.to_string_lossy().starts_with("s3://") {
+            bail!("\n{} is in S3; {} only transcribes local files.", file_path.display(), ctx.transcriber.name());
+        }
+        if !file_path.exists() {
+            bail!("\nThe path {} does not exist.", file_path.display());
+        }
+        let trimmed = match ctx.clip {
+            Some((start, duration)) => {
+                spinner.update("Trimming recording...");
+                Some(audio::trim(file_path, start, duration)?)
+            }
+            None => None,
+        };
+        let audio = transcribe::Audio {
+            path: trimmed.as_ref().map_or(file_path, |(_, path)| path.as_path()),
+            s3_uri: None,
+        };
+        let transcription_start = Instant::now();
+        let transcript = ctx
+            .transcriber
+            .transcribe(&audio, &transcribe_options(ctx, "", language_code), spinner)
+            .await?;
+        run_report.stage("Transcription", transcription_start);
+        return Ok(transcript);
+    }
+    let upload = match file_path.to_str().filter(|path| path.starts_with("s3://")) {
+        Some(s3_uri) => existing_upload(ctx, s3_uri)?,
+        None => upload(ctx, file_path, spinner, run_report).await?,
+    };
+    transcribe_upload(ctx, &upload, language_code, spinner, run_report).await
+}
+
+// Upload a single audio file to the run's bucket, named by aws.s3_key_template
+// (or its hash with --name-by-hash) and trimmed first when only part of it is
+// processed
+#[instrument(name = "upload", skip_all, fields(file = %file_path.display()))]
+pub async fn upload(
+    ctx: &RunContext,
+    file_path: &Path,
+    spinner: &mut Progress,
+    run_report: &mut report::RunReport,
+) -> Result<Upload, Error> {
+    progress::set_stage("upload");
+    let RunContext {
+        settings,
+        regional_config,
+        bucket_name,
+        ..
+    } = ctx;
+    spinner.update("Uploading file to S3...");
+    let regional_s3_client = Client::new(regional_config);
+
+    // The object key and job name come from templates, e.g. "distill/{date}/{uuid}-{filename}"
+    // With --name-by-hash the key is the file's hash and extension, e.g. "9f86d08...a08.m4a"
+    let key_template = if ctx.name_by_hash {
+        match file_path.extension() {
+            Some(ext) => format!("{{hash}}.{}", ext.to_string_lossy()),
+            None => "{hash}".to_string(),
+        }
+    } else {
+        settings
+            .get_string("aws.s3_key_template")
+            .unwrap_or_else(|_| "{filename}".to_string())
+    };
+    let job_template = settings
+        .get_string("aws.transcribe_job_template")
+        .unwrap_or_else(|_| "transcription-{uuid}".to_string());
+    let mut name_values = naming::NameValues::for_file(file_path, &audio::metadata(file_path))
+        .in_session(ctx.session.as_ref().map(session::Session::id));
+    if key_template.contains("hash") || job_template.contains("hash") {
+        name_values = name_values.with_hash(naming::content_hash(&[file_path.to_path_buf()])?);
+    }
+    let key = name_values.render(&key_template)?;
+    let job_name = naming::job_name(&name_values.render(&job_template)?);
+
+    if !file_path.exists() {
+        bail!("\nThe path {} does not exist.", file_path.display());
+    }
+
+    // Trim before upload so only the requested part is uploaded and transcribed
+    let trimmed = match ctx.clip {
+        Some((start, duration)) => {
+            spinner.update("Trimming recording...");
+            Some(audio::trim(file_path, start, duration)?)
+        }
+        None => None,
+    };
+    let file_path = trimmed.as_ref().map_or(file_path, |(_, path)| path.as_path());
+
+    let canonicalized_path = file_path.canonicalize()?;
+    let upload_start = Instant::now();
+    run_report.bytes_uploaded += std::fs::metadata(&canonicalized_path)?.len();
+    upload::upload_file(
+        &regional_s3_client,
+        bucket_name,
+        &key,
+        &canonicalized_path,
+        spinner,
+        &upload::UploadOptions::from_settings(settings)?,
+    )
+    .await?;
+
+    run_report.stage("Upload", upload_start);
+
+    let path = file_path.to_path_buf();
+    Ok(Upload {
+        s3_uri: format!("s3://{}/{}", bucket_name, key),
+        key,
+        job_name,
+        path,
+        _trimmed: trimmed.map(|(dir, _)| dir),
+        uploaded: true,
+    })
+}
+
+// A recording that is already in S3, given as "s3://bucket/key" (e.g. by
+// `distill serve`), is transcribed where it is instead of being uploaded.
+// Transcribe needs to be able to read it, so the bucket should be in the
+// region Transcribe runs in.
+pub fn existing_upload(ctx: &RunContext, s3_uri: &str) -> Result<Upload, Error> {
+    let Some((bucket, key)) = s3_uri.strip_prefix("s3://").and_then(|location| location.split_once('/')) else {
+        bail!("\nInvalid S3 URI {}; expected s3://bucket/key", s3_uri);
+    };
+    if bucket.is_empty() || key.is_empty() {
+        bail!("\nInvalid S3 URI {}; expected s3://bucket/key", s3_uri);
+    }
+    if ctx.clip.is_some() {
+        bail!("\n--first and --range can't be used with {}, which is transcribed where it is in S3", s3_uri);
+    }
+    let job_template = ctx
+        .settings
+        .get_string("aws.transcribe_job_template")
+        .unwrap_or_else(|_| "transcription-{uuid}".to_string());
+    let path = PathBuf::from(key);
+    let name_values = naming::NameValues::for_file(&path, &audio::Metadata::default());
+    let job_name = naming::job_name(&name_values.render(&job_template)?);
+    Ok(Upload {
+        key: key.to_string(),
+        s3_uri: s3_uri.to_string(),
+        job_name,
+        path,
+        _trimmed: None,
+        uploaded: false,
+    })
+}
+
+// Transcribe an uploaded file with Amazon Transcribe in `language_code`,
+// deleting the upload afterwards when the run says so
+#[instrument(name = "transcribe_job", skip_all, fields(job = %upload.job_name, language_code))]
+pub async fn transcribe_upload(
+    ctx: &RunContext,
+    upload: &Upload,
+    language_code: &str,
+    spinner: &mut Progress,
+    run_report: &mut report::RunReport,
+) -> Result<transcribe::Transcript, Error> {
+    progress::set_stage("transcription");
+    spinner.break_line();
+    spinner.update("Transcribing...");
+
+    // Transcribe the audio
+    let transcription_start = Instant::now();
+    let audio = transcribe::Audio {
+        path: &upload.path,
+        s3_uri: Some(&upload.s3_uri),
+    };
+    let transcript = ctx
+        .transcriber
+        .transcribe(&audio, &transcribe_options(ctx, &upload.job_name, language_code), spinner)
+        .await?;
+    run_report.stage("Transcription", transcription_start);
+    run_report.transcription_jobs.push(upload.job_name.clone());
+    // Approximated by where the speech ends, since the job doesn't report the audio length
+    run_report.add_transcribed_audio(transcript.segments.last().map_or(0.0, |s| s.end_time));
+
+    // The audio is no longer needed once it has been transcribed
+    if ctx.delete_s3_object == "Y" && upload.uploaded {
+        ctx.s3_client
+            .delete_object()
+            .bucket(&ctx.bucket_name)
+            .key(&upload.key)
+            .send()
+            .await?;
+    }
+
+    Ok(transcript)
+}
+
+// The run's transcription options for one recording
+fn transcribe_options<'a>(
+    ctx: &'a RunContext,
+    job_name: &'a str,
+    language_code: &'a str,
+) -> transcribe::TranscribeOptions<'a> {
+    transcribe::TranscribeOptions {
+        settings: &ctx.settings,
+        job_name,
+        language_code,
+        poll_interval: ctx.mode.poll_interval(),
+        // Per-speaker tracks have one long turn each unless split at pauses
+        split_on_pause: (!ctx.track_names.is_empty()).then_some(1.5),
+        // Each track is a single speaker, so there is nobody to tell apart
+        max_speakers: ctx.max_speakers.filter(|_| ctx.track_names.is_empty()),
+        vocabulary_filter: ctx
+            .vocabulary_filter
+            .as_ref()
+            .map(|(name, method)| (name.as_str(), method.transcribe_method())),
+    }
+}
+
+pub fn parse_summary_sections(summarized_text: &str) -> (String, String, String) {
+    // Initialize empty sections
+    let mut summary = String::new();
+    let mut action_items = String::new();
+    let mut rest = String::new();
+
+    // Split text by lines for processing
+    let lines: Vec<&str> = summarized_text.lines().collect();
+    let mut current_section = "";
+    
+    for line in lines {
+        // Check for section headers
+        if line.to_lowercase().contains("key points") || 
+           line.to_lowercase().contains("summary") {
+            current_section = "summary";
+            continue;
+        } else if line.to_lowercase().contains("action item") || 
+                  line.to_lowercase().contains("next step") {
+            current_section = "action";
+            continue;
+        } else if line.trim().is_empty() {
+            continue;
+        }
+
+        // Append content to appropriate section
+        match current_section {
+            "summary" => summary.push_str(&format!("{}\n", line)),
+            "action" => action_items.push_str(&format!("{}\n", line)),
+            _ => rest.push_str(&format!("{}\n", line)),
+        }
+    }
+
+    // Trim whitespace from all sections
+    (
+        summary.trim().to_string(),
+        action_items.trim().to_string(),
+        rest.trim().to_string()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_summary_and_action_items() {
+        let text = "Intro line\n\n## Summary\nThe team met.\nBudget approved.\n\n## Action Items\n- Sam to ship it\n";
+        let (summary, action_items, rest) = parse_summary_sections(text);
+        assert_eq!(summary, "The team met.\nBudget approved.");
+        assert_eq!(action_items, "- Sam to ship it");
+        assert_eq!(rest, "Intro line");
+    }
+
+    #[test]
+    fn reads_next_steps_and_key_points_as_sections() {
+        let (summary, action_items, rest) = parse_summary_sections("Key points:\n- Launch moved\nNext steps:\n- Ana to book");
+        assert_eq!((summary.as_str(), action_items.as_str(), rest.as_str()), ("- Launch moved", "- Ana to book", ""));
+    }
+}
@@ -0,0 +1,516 @@
+use std::cell::Cell;
+use std::future::Future;
+use std::io::IsTerminal;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Error};
+use config::Config;
+use console::Style;
+use dialoguer::theme::{ColorfulTheme, SimpleTheme, Theme};
+use dialoguer::{Confirm, Input, Select};
+use serde_json::{json, Value};
+use spinoff::{spinners, Color, Spinner, Streams};
+use tokio::sync::broadcast;
+
+use crate::naming;
+
+tokio::task_local! {
+    // How the run the current task belongs to reports progress; see `Output::scope`
+    static OUTPUT: Output;
+    // The pipeline stage that progress events belong to, e.g. "upload"
+    static STAGE: Cell<Option<&'static str>>;
+}
+
+const COLORS: [&str; 7] = ["green", "blue", "cyan", "magenta", "yellow", "red", "white"];
+
+// How often accessible mode says that a long step is still running
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(30);
+
+// How progress is reported with --progress
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    // Spinners, or plain status lines where they don't work
+    Human,
+    // One JSON event per line, for wrapping tools and GUIs
+    Json,
+}
+
+// How a run reports progress. It is kept in the run context, and everything
+// this module prints for a task inside `Output::scope` follows it, so runs in
+// the same process (e.g. the jobs of `distill serve`) don't share one mode.
+#[derive(Debug, Clone, Default)]
+pub struct Output {
+    pub plain: bool,
+    pub accessible: bool,
+    pub json: bool,
+    pub quiet: bool,
+    // Accent color for spinners and prompts; None when color is turned off
+    pub accent: Option<&'static str>,
+    // Also receives every progress event, whether or not --progress json is
+    // set, e.g. for a job's event stream in `distill serve`
+    pub events: Option<broadcast::Sender<Value>>,
+}
+
+impl Output {
+    // Decide once per run whether to animate. Plain output is used with
+    // --no-spinner or `appearance.plain`, for dumb terminals, under CI (a non-empty
+    // CI variable) and when stdout or stderr is redirected.
+    // Accessible mode (--a11y) is plain output plus periodic announcements.
+    // Quiet mode (--quiet or `appearance.quiet`) is plain output without the step
+    // lines and status banners, leaving results, warnings and errors.
+    // Color is turned off by --no-color, a non-empty NO_COLOR, or `appearance.color = "none"`.
+    // JSON progress events replace the spinner, with plain lines for everything else.
+    pub fn new(
+        settings: &Config,
+        accessible: bool,
+        no_color: bool,
+        format: Format,
+        no_spinner: bool,
+        quiet: bool,
+    ) -> Result<Self, Error> {
+        let json = format == Format::Json;
+        let quiet = quiet || settings.get_bool("appearance.quiet").unwrap_or(false);
+        let plain = accessible
+            || json
+            || quiet
+            || no_spinner
+            || settings.get_bool("appearance.plain").unwrap_or(false)
+            || std::env::var("TERM").is_ok_and(|term| term == "dumb")
+            || std::env::var("CI").is_ok_and(|ci| !ci.is_empty())
+            || !std::io::stderr().is_terminal()
+            || !std::io::stdout().is_terminal();
+
+        let color = settings
+            .get_string("appearance.color")
+            .unwrap_or_else(|_| "green".to_string())
+            .to_lowercase();
+        let accent = if no_color
+            || json
+            || std::env::var("NO_COLOR").is_ok_and(|value| !value.is_empty())
+            || color == "none"
+        {
+            None
+        } else {
+            match COLORS.iter().find(|c| **c == color) {
+                Some(c) => Some(*c),
+                None => bail!(
+                    "\nUnknown appearance.color '{}'. Use one of: {}, none",
+                    color,
+                    COLORS.join(", ")
+                ),
+            }
+        };
+        Ok(Output { plain, accessible, json, quiet, accent, events: None })
+    }
+
+    // Run `future` with this output mode. Tasks it spawns don't inherit it, so
+    // they are scoped again, e.g. with the run context's `output`.
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        OUTPUT.scope(self, STAGE.scope(Cell::new(None), future)).await
+    }
+}
+
+// Colored text from the console and colored crates is turned off for the
+// whole process, e.g. when the run's output has no accent color
+pub fn disable_colors() {
+    colored::control::set_override(false);
+    console::set_colors_enabled(false);
+    console::set_colors_enabled_stderr(false);
+}
+
+// Look at the output mode of the current task, or the default outside any scope
+fn with_output<T>(f: impl Fn(&Output) -> T) -> T {
+    OUTPUT.try_with(&f).unwrap_or_else(|_| f(&Output::default()))
+}
+
+fn accent() -> Option<&'static str> {
+    with_output(|output| output.accent)
+}
+
+// Theme for interactive prompts, in the accent color
+pub fn theme() -> Box<dyn Theme> {
+    match accent() {
+        Some(color) if !is_plain() => {
+            let style = Style::new().for_stderr().fg(console_color(color));
+            Box::new(ColorfulTheme {
+                active_item_style: style.clone(),
+                active_item_prefix: style.apply_to("❯".to_string()),
+                ..ColorfulTheme::default()
+            })
+        }
+        _ => Box::new(SimpleTheme),
+    }
+}
+
+fn console_color(name: &str) -> console::Color {
+    match name {
+        "blue" => console::Color::Blue,
+        "cyan" => console::Color::Cyan,
+        "magenta" => console::Color::Magenta,
+        "yellow" => console::Color::Yellow,
+        "red" => console::Color::Red,
+        "white" => console::Color::White,
+        _ => console::Color::Green,
+    }
+}
+
+fn spinner_color(name: &str) -> Color {
+    match name {
+        "blue" => Color::Blue,
+        "cyan" => Color::Cyan,
+        "magenta" => Color::Magenta,
+        "yellow" => Color::Yellow,
+        "red" => Color::Red,
+        "white" => Color::White,
+        _ => Color::Green,
+    }
+}
+
+pub fn is_plain() -> bool {
+    with_output(|output| output.plain)
+}
+
+pub fn is_accessible() -> bool {
+    with_output(|output| output.accessible)
+}
+
+pub fn is_json() -> bool {
+    with_output(|output| output.json)
+}
+
+pub fn is_quiet() -> bool {
+    with_output(|output| output.quiet)
+}
+
+// Whether progress events go anywhere, to --progress json or an event sink
+pub fn has_events() -> bool {
+    with_output(|output| output.json || output.events.is_some())
+}
+
+// Name the pipeline stage that the following progress events belong to
+pub fn set_stage(stage: &'static str) {
+    let _ = STAGE.try_with(|current| current.set(Some(stage)));
+}
+
+// The pipeline stage the current task is in, if one has been named
+pub fn stage() -> Option<&'static str> {
+    STAGE.try_with(Cell::get).ok().flatten()
+}
+
+// Write one progress event as a line of JSON on stderr, e.g.
+// {"event":"step","message":"Uploading file to S3...","stage":"upload","timestamp":"2024-05-14T14:03:27.512Z"}
+// Nothing is written unless --progress json is set. The event is also sent to
+// the output's event sink, if it has one.
+pub fn event(event: &str, fields: Value) {
+    if !has_events() {
+        return;
+    }
+    let stage = stage();
+    let mut line = json!({
+        "event": event,
+        "stage": stage,
+        "timestamp": naming::timestamp(),
+    });
+    if let (Some(line), Value::Object(fields)) = (line.as_object_mut(), fields) {
+        line.extend(fields);
+    }
+    with_output(|output| {
+        if let Some(events) = &output.events {
+            // Nobody may be listening
+            let _ = events.send(line.clone());
+        }
+        if output.json {
+            eprintln!("{}", line);
+        }
+    });
+}
+
+// Prefix for a status line, e.g. "💾 " normally and nothing in plain mode
+pub fn icon(emoji: &str) -> String {
+    if is_plain() {
+        String::new()
+    } else {
+        format!("{} ", emoji)
+    }
+}
+
+// eprintln! for a status line with its icon, left out with --quiet, e.g.
+// status!("📂", "Found {} audio files in {}", count, dir.display())
+#[macro_export]
+macro_rules! status {
+    ($emoji:expr, $($arg:tt)*) => {
+        if !$crate::progress::is_quiet() {
+            eprintln!("{}{}", $crate::progress::icon($emoji), format_args!($($arg)*));
+        }
+    };
+}
+
+// Status reporting for a long-running step: a spinner on an interactive
+// terminal, or one timestamped line per update in plain mode
+pub struct Progress {
+    spinner: Option<Spinner>,
+    // When the current step started and when it was last announced
+    step: String,
+    step_started: Instant,
+    last_announced: Instant,
+    // Last 10% mark printed by `progress` in plain mode
+    reported_tenths: Option<u64>,
+    // Last whole percent reported by `progress` as a JSON event
+    reported_percent: Option<u64>,
+    // Calls and attempts made through `retry::retry`, per service
+    attempts: Vec<(String, u32, u32)>,
+    // Never draws a spinner; see `background`
+    background: bool,
+}
+
+impl Progress {
+    pub fn new(text: &str) -> Self {
+        event("step", json!({ "message": text }));
+        let spinner = if is_plain() {
+            plain_line(text);
+            None
+        } else {
+            Some(start_spinner(text))
+        };
+        Progress {
+            spinner,
+            step: text.to_string(),
+            step_started: Instant::now(),
+            last_announced: Instant::now(),
+            reported_tenths: None,
+            reported_percent: None,
+            attempts: Vec::new(),
+            background: false,
+        }
+    }
+
+    // For a step that runs alongside others, e.g. one of several outputs sent
+    // at once. Results and warnings are printed as lines, but no spinner is
+    // drawn, since several would draw over each other.
+    pub fn background() -> Self {
+        Progress {
+            spinner: None,
+            step: String::new(),
+            step_started: Instant::now(),
+            last_announced: Instant::now(),
+            reported_tenths: None,
+            reported_percent: None,
+            attempts: Vec::new(),
+            background: true,
+        }
+    }
+
+    // Starts the spinner again if a previous step stopped it
+    pub fn update(&mut self, text: &str) {
+        event("step", json!({ "message": text }));
+        if is_plain() {
+            plain_line(text);
+        } else if let Some(spinner) = self.spinner.as_mut() {
+            spinner.update(spinners::Dots7, text.to_string(), None);
+        } else if !self.background {
+            self.spinner = Some(start_spinner(text));
+        }
+        self.step = text.to_string();
+        self.step_started = Instant::now();
+        self.last_announced = Instant::now();
+    }
+
+    // The text of the current step, to show again after an interruption
+    pub fn step(&self) -> &str {
+        &self.step
+    }
+
+    // Count one call to `service` that took `attempts` tries
+    pub fn add_attempts(&mut self, service: &str, attempts: u32) {
+        match self.attempts.iter_mut().find(|(name, _, _)| name == service) {
+            Some((_, calls, total)) => {
+                *calls += 1;
+                *total += attempts;
+            }
+            None => self.attempts.push((service.to_string(), 1, attempts)),
+        }
+    }
+
+    // Add the calls and attempts counted by a `background` step
+    pub fn merge_attempts(&mut self, other: &Progress) {
+        for (service, calls, attempts) in &other.attempts {
+            match self.attempts.iter_mut().find(|(name, _, _)| name == service) {
+                Some((_, total_calls, total)) => {
+                    *total_calls += calls;
+                    *total += attempts;
+                }
+                None => self.attempts.push((service.clone(), *calls, *attempts)),
+            }
+        }
+    }
+
+    // Calls and attempts per service, in the order each service was first called
+    pub fn attempts(&self) -> &[(String, u32, u32)] {
+        &self.attempts
+    }
+
+    // Show how far a transfer has got, e.g.
+    // "Uploading file to S3 [#####---------------]  25% (120.5 of 482.0 MB)".
+    // Plain mode prints a line at every 10% instead of redrawing.
+    pub fn progress(&mut self, label: &str, done: u64, total: u64) {
+        let fraction = if total == 0 { 1.0 } else { done as f64 / total as f64 };
+        let filled = (fraction * 20.0).round() as usize;
+        let text = format!(
+            "{} [{}{}] {:>3.0}% ({:.1} of {:.1} MB)",
+            label,
+            "#".repeat(filled),
+            "-".repeat(20 - filled.min(20)),
+            fraction * 100.0,
+            done as f64 / 1_000_000.0,
+            total as f64 / 1_000_000.0
+        );
+
+        let percent = (fraction * 100.0).floor() as u64;
+        if has_events() && self.reported_percent != Some(percent) {
+            self.reported_percent = Some(percent);
+            event(
+                "progress",
+                json!({
+                    "message": label,
+                    "percent": percent,
+                    "done_bytes": done,
+                    "total_bytes": total,
+                }),
+            );
+        }
+        if is_plain() {
+            let tenths = (fraction * 10.0).floor() as u64;
+            if self.reported_tenths != Some(tenths) {
+                self.reported_tenths = Some(tenths);
+                plain_line(&text);
+            }
+        } else if let Some(spinner) = self.spinner.as_mut() {
+            spinner.update_text(text);
+        } else if !self.background {
+            self.spinner = Some(start_spinner(&text));
+        }
+        self.last_announced = Instant::now();
+    }
+
+    // Called while waiting on a long step. In accessible mode and with
+    // progress events this announces that the step is still running, at most
+    // every 30 seconds; otherwise the spinner already shows it.
+    pub fn still_working(&mut self) {
+        if !(is_accessible() || has_events()) || self.last_announced.elapsed() < ANNOUNCE_INTERVAL {
+            return;
+        }
+        let elapsed = self.step_started.elapsed();
+        event("still_working", json!({ "message": self.step, "elapsed_secs": elapsed.as_secs_f64() }));
+        if is_accessible() {
+            let elapsed = elapsed.as_secs();
+            plain_line(&format!(
+                "Still {}: {}m {}s elapsed",
+                self.step.trim_end_matches("...").to_lowercase(),
+                elapsed / 60,
+                elapsed % 60
+            ));
+        }
+        self.last_announced = Instant::now();
+    }
+
+    // Keep the current spinner line on screen and continue below it
+    pub fn break_line(&self) {
+        if self.spinner.is_some() {
+            eprintln!();
+        }
+    }
+
+    pub fn success(&mut self, text: &str) {
+        event("done", json!({ "message": text, "elapsed_secs": self.step_started.elapsed().as_secs_f64() }));
+        if is_plain() {
+            plain_line(text);
+        } else if let Some(mut spinner) = self.spinner.take() {
+            spinner.success(text);
+        } else {
+            eprintln!("✓ {}", text);
+        }
+    }
+
+    pub fn stop_and_persist(&mut self, symbol: &str, text: &str) {
+        event("status", json!({ "message": text, "symbol": symbol }));
+        if is_quiet() {
+            // Warnings and failures still get through --quiet, as lines unless
+            // the event stands in for them
+            if !is_json() && (symbol == "⚠️" || symbol == "❌") {
+                eprintln!("{}", text);
+            }
+        } else if is_plain() {
+            plain_line(text);
+        } else if let Some(mut spinner) = self.spinner.take() {
+            spinner.stop_and_persist(symbol, text);
+        } else {
+            eprintln!("{} {}", symbol, text);
+        }
+    }
+}
+
+fn start_spinner(text: &str) -> Spinner {
+    Spinner::new_with_stream(
+        spinners::Dots7,
+        text.to_string(),
+        accent().map(spinner_color),
+        Streams::Stderr,
+    )
+}
+
+// "[14:03:27] Uploading file to S3..." with the time in UTC; nothing with
+// --quiet, or with --progress json, whose events stand in for these lines
+fn plain_line(text: &str) {
+    if is_quiet() || is_json() {
+        return;
+    }
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+        % 86_400;
+    eprintln!(
+        "[{:02}:{:02}:{:02}] {}",
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60,
+        text
+    );
+}
+
+// Let the user pick one of `items`, returning its index
+pub fn choose(prompt: &str, items: &[String]) -> Result<usize, Error> {
+    if is_accessible() {
+        // Arrow-key menus don't work well with screen readers, so list the
+        // items and ask for a number instead
+        eprintln!("{}:", prompt);
+        for (i, item) in items.iter().enumerate() {
+            eprintln!("{}. {}", i + 1, item);
+        }
+        let count = items.len();
+        let number: usize = Input::with_theme(&SimpleTheme)
+            .with_prompt(format!("Enter a number from 1 to {}", count))
+            .validate_with(|n: &usize| {
+                if (1..=count).contains(n) {
+                    Ok(())
+                } else {
+                    Err(format!("Enter a number from 1 to {}", count))
+                }
+            })
+            .interact_text()?;
+        Ok(number - 1)
+    } else {
+        Ok(Select::with_theme(theme().as_ref())
+            .with_prompt(prompt)
+            .default(0)
+            .items(items)
+            .interact()?)
+    }
+}
+
+// Ask a yes/no question
+pub fn confirm(prompt: &str, default: bool) -> Result<bool, Error> {
+    Ok(Confirm::with_theme(theme().as_ref())
+        .with_prompt(prompt)
+        .default(default)
+        .interact()?)
+}
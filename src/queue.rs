@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Error};
+use config::Config;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{progress, status};
+
+// Arguments that only make sense for the run that queued the entry
+const QUEUE_FLAGS: [&str; 2] = ["--queue", "--from-queue"];
+
+// Environment variables that change what a replayed run does. AWS keys are
+// deliberately left out so no credentials are written to disk.
+const REPLAYED_VARIABLES: [&str; 3] = ["AWS_PROFILE", "AWS_REGION", "AWS_DEFAULT_REGION"];
+
+// A run saved for later: the command line as given, the directory it was
+// given in (for relative paths and config.toml) and the DISTILL_ settings
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    args: Vec<String>,
+    cwd: PathBuf,
+    env: BTreeMap<String, String>,
+    queued_at: u64,
+}
+
+pub fn queue_dir(settings: &Config) -> PathBuf {
+    let dir = settings
+        .get_string("queue.directory")
+        .unwrap_or_else(|_| "~/.distill/queue".to_string());
+    PathBuf::from(shellexpand::tilde(&dir).to_string())
+}
+
+// Save the current run to the queue and return the entry's path
+pub fn add(settings: &Config) -> Result<PathBuf, Error> {
+    let dir = queue_dir(settings);
+    std::fs::create_dir_all(&dir)
+        .with_context(|| format!("Error creating queue directory: {}", dir.display()))?;
+
+    let queued_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let entry = Entry {
+        args: std::env::args_os()
+            .skip(1)
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .filter(|arg| !QUEUE_FLAGS.contains(&arg.as_str()))
+            .collect(),
+        cwd: std::env::current_dir()?,
+        env: std::env::vars()
+            .filter(|(name, _)| name.starts_with("DISTILL_") || REPLAYED_VARIABLES.contains(&name.as_str()))
+            .collect(),
+        queued_at,
+    };
+
+    // Named so that a directory listing sorts oldest first
+    let path = dir.join(format!("{}-{}.json", queued_at, Uuid::new_v4()));
+    let file = File::create(&path)
+        .with_context(|| format!("Error writing queue entry: {}", path.display()))?;
+    serde_json::to_writer_pretty(BufWriter::new(file), &entry)?;
+    Ok(path)
+}
+
+// Replay every queued run, oldest first. Each one runs as a separate distill
+// process so a failure doesn't stop the rest; entries are only removed once
+// their run succeeds.
+pub fn flush(settings: &Config) -> Result<(), Error> {
+    let dir = queue_dir(settings);
+    let mut paths: Vec<PathBuf> = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect(),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Error reading queue directory: {}", dir.display()))
+        }
+    };
+    paths.sort();
+
+    if paths.is_empty() {
+        eprintln!("{}No queued runs in {}", progress::icon("📭"), dir.display());
+        return Ok(());
+    }
+
+    let exe = std::env::current_exe()?;
+    let total = paths.len();
+    let mut flushed = 0;
+    for (index, path) in paths.iter().enumerate() {
+        let entry: Entry = serde_json::from_reader(
+            File::open(path).with_context(|| format!("Error reading queue entry: {}", path.display()))?,
+        )
+        .with_context(|| format!("Invalid queue entry: {}", path.display()))?;
+
+        status!(
+            "📤",
+            "Queued run {} of {}: distill-cli {}",
+            index + 1,
+            total,
+            entry.args.join(" ")
+        );
+        let status = Command::new(&exe)
+            .args(&entry.args)
+            .arg("--from-queue")
+            .current_dir(&entry.cwd)
+            .envs(&entry.env)
+            .status()
+            .with_context(|| format!("Error starting queued run from {}", path.display()))?;
+
+        if status.success() {
+            std::fs::remove_file(path)?;
+            flushed += 1;
+        } else {
+            eprintln!("Queued run failed; keeping {}", path.display());
+        }
+    }
+
+    eprintln!("{}Flushed {} of {} queued runs", progress::icon("✅"), flushed, total);
+    if flushed < total {
+        bail!("\n{} queued runs failed and remain in {}", total - flushed, dir.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,174 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Error};
+use config::Config;
+use serde::{Deserialize, Serialize};
+
+use crate::report::RunReport;
+use crate::{naming, output, status};
+
+// Share of a quota at which runs start printing a warning
+const DEFAULT_WARN_AT: f64 = 0.8;
+
+// Runs without --profile are counted under this name
+const DEFAULT_PROFILE: &str = "default";
+
+// What a profile has used in a month
+#[derive(Default, Serialize, Deserialize)]
+struct Used {
+    runs: u64,
+    audio_minutes: f64,
+    tokens: u64,
+    spend_usd: f64,
+}
+
+// Usage by month ("2024-05"), then by profile
+type Ledger = BTreeMap<String, BTreeMap<String, Used>>;
+
+// Monthly limits for one profile, from its [profile.NAME.quota] section or
+// else [quota]. Usage is kept in a local ledger, so the limits hold for
+// everyone sharing it, e.g. on a team's build machine.
+pub struct Quota {
+    profile: String,
+    audio_minutes: Option<f64>,
+    tokens: Option<u64>,
+    spend_usd: Option<f64>,
+    warn_at: f64,
+    ledger: PathBuf,
+}
+
+impl Quota {
+    pub fn from_settings(settings: &Config, profile: Option<&str>) -> Result<Self, Error> {
+        let value = |key: &str| {
+            profile
+                .and_then(|name| settings.get_float(&format!("profile.{}.quota.{}", name, key)).ok())
+                .or_else(|| settings.get_float(&format!("quota.{}", key)).ok())
+        };
+        let warn_at = value("warn_at").unwrap_or(DEFAULT_WARN_AT);
+        if !(0.0..=1.0).contains(&warn_at) {
+            bail!("\nquota.warn_at must be between 0 and 1, e.g. 0.8 to warn at 80% of a quota.");
+        }
+        let ledger = settings
+            .get_string("quota.ledger")
+            .unwrap_or_else(|_| "~/.distill/usage.json".to_string());
+        Ok(Quota {
+            profile: profile.unwrap_or(DEFAULT_PROFILE).to_string(),
+            audio_minutes: value("audio_minutes"),
+            tokens: value("tokens").map(|tokens| tokens.max(0.0) as u64),
+            spend_usd: value("spend_usd"),
+            warn_at,
+            ledger: PathBuf::from(shellexpand::tilde(&ledger).to_string()),
+        })
+    }
+
+    fn is_set(&self) -> bool {
+        self.audio_minutes.is_some() || self.tokens.is_some() || self.spend_usd.is_some()
+    }
+
+    // Fail when a quota is used up for this month, and warn when one is close
+    pub fn check(&self) -> Result<(), Error> {
+        if !self.is_set() {
+            return Ok(());
+        }
+        let ledger = self.load()?;
+        let Some(used) = ledger.get(&month()).and_then(|profiles| profiles.get(&self.profile)) else {
+            return Ok(());
+        };
+        for (name, used, limit) in self.usage(used) {
+            if used >= limit {
+                bail!(
+                    "\nThe monthly {} quota of profile '{}' is used up ({} of {}). Raise it in config.toml or wait until next month.",
+                    name,
+                    self.profile,
+                    format_amount(name, used),
+                    format_amount(name, limit)
+                );
+            }
+            if used >= limit * self.warn_at {
+                eprintln!(
+                    "Warning: Profile '{}' has used {:.0}% of its monthly {} quota ({} of {})",
+                    self.profile,
+                    used / limit * 100.0,
+                    name,
+                    format_amount(name, used),
+                    format_amount(name, limit)
+                );
+            }
+        }
+        Ok(())
+    }
+
+    // Add a finished run's usage to the ledger
+    pub fn record(&self, report: &RunReport) -> Result<(), Error> {
+        if !self.is_set() {
+            return Ok(());
+        }
+        let cost = report.total_cost();
+        if cost.is_none() && self.spend_usd.is_some() {
+            eprintln!("Warning: The model's price isn't known, so this run's Bedrock cost isn't counted toward the spend quota. Set pricing.input_per_1k_tokens and pricing.output_per_1k_tokens.");
+        }
+
+        let mut ledger = self.load()?;
+        let used = ledger
+            .entry(month())
+            .or_default()
+            .entry(self.profile.clone())
+            .or_default();
+        used.runs += 1;
+        used.audio_minutes += report.audio_minutes();
+        used.tokens += report.tokens();
+        used.spend_usd += cost.unwrap_or_default();
+
+        if let Some(dir) = self.ledger.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        output::write_atomic(&self.ledger, |file| {
+            serde_json::to_writer_pretty(file, &ledger).map_err(|e| anyhow::anyhow!("Error writing usage ledger: {}", e))
+        })?;
+
+        if let Some(used) = ledger.get(&month()).and_then(|profiles| profiles.get(&self.profile)) {
+            let parts: Vec<String> = self
+                .usage(used)
+                .into_iter()
+                .map(|(name, used, limit)| format!("{} of {} {}", format_amount(name, used), format_amount(name, limit), name))
+                .collect();
+            status!("📊", "Monthly usage of profile '{}': {}", self.profile, parts.join(", "));
+        }
+        Ok(())
+    }
+
+    // Each quota that is set, as (name, used, limit)
+    fn usage(&self, used: &Used) -> Vec<(&'static str, f64, f64)> {
+        [
+            ("audio minutes", used.audio_minutes, self.audio_minutes),
+            ("tokens", used.tokens as f64, self.tokens.map(|tokens| tokens as f64)),
+            ("spend", used.spend_usd, self.spend_usd),
+        ]
+        .into_iter()
+        .filter_map(|(name, used, limit)| limit.map(|limit| (name, used, limit)))
+        .collect()
+    }
+
+    fn load(&self) -> Result<Ledger, Error> {
+        match std::fs::read_to_string(&self.ledger) {
+            Ok(text) => serde_json::from_str(&text)
+                .with_context(|| format!("Invalid usage ledger: {}", self.ledger.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Ledger::new()),
+            Err(err) => Err(err).with_context(|| format!("Error reading usage ledger: {}", self.ledger.display())),
+        }
+    }
+}
+
+// This month as YYYY-MM (UTC)
+fn month() -> String {
+    naming::today()[..7].to_string()
+}
+
+fn format_amount(name: &str, amount: f64) -> String {
+    match name {
+        "spend" => format!("${:.2}", amount),
+        "tokens" => format!("{:.0}", amount),
+        _ => format!("{:.1}", amount),
+    }
+}
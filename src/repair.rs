@@ -0,0 +1,79 @@
+use serde_json::Value;
+
+// Parse the first JSON array in a model reply. Models sometimes wrap the
+// array in prose or code fences, so anything outside the outermost brackets
+// is ignored.
+pub fn parse_json_array(response: &str) -> Option<Vec<Value>> {
+    let start = response.find('[')?;
+    let end = response.rfind(']')?;
+    if end < start {
+        return None;
+    }
+    serde_json::from_str(&response[start..=end]).ok()
+}
+
+// Fix the mistakes models most often make in JSON: curly quotes, trailing
+// commas, and replies cut off before the closing brackets
+pub fn repair_json(response: &str) -> String {
+    let text = response
+        .replace(['\u{201c}', '\u{201d}'], "\"")
+        .replace(['\u{2018}', '\u{2019}'], "'");
+    let text = match text.find('[') {
+        Some(start) => &text[start..],
+        None => return text,
+    };
+
+    let mut repaired = String::with_capacity(text.len());
+    let mut open: Vec<char> = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in text.chars() {
+        if in_string {
+            repaired.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '[' => open.push(']'),
+            '{' => open.push('}'),
+            ']' | '}' => {
+                // Drop a trailing comma before the closing bracket
+                let trimmed = repaired.trim_end().len();
+                if repaired[..trimmed].ends_with(',') {
+                    repaired.truncate(trimmed - 1);
+                }
+                open.pop();
+            }
+            _ => {}
+        }
+        repaired.push(c);
+        if open.is_empty() {
+            // End of the outermost array; anything after it is prose
+            return repaired;
+        }
+    }
+
+    // Close whatever the reply left open
+    if in_string {
+        repaired.push('"');
+    }
+    let trimmed = repaired.trim_end().trim_end_matches(',').len();
+    repaired.truncate(trimmed);
+    while let Some(close) = open.pop() {
+        repaired.push(close);
+    }
+    repaired
+}
+
+// Prompt asking the model to fix a reply that couldn't be parsed
+pub fn repair_prompt() -> &'static str {
+    "The text below was supposed to be a JSON array but is not valid JSON. Reply with only the \
+corrected JSON array, keeping the same content:"
+}
@@ -0,0 +1,354 @@
+use crate::{progress, status};
+use crate::summarize::Usage;
+use config::Config;
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+// On-demand Bedrock list prices in USD per 1,000 input and output tokens,
+// matched against the model ID. Override them with pricing.input_per_1k_tokens
+// and pricing.output_per_1k_tokens for other models, regions or discounts.
+const MODEL_PRICES: [(&str, f64, f64); 14] = [
+    ("claude-3-haiku", 0.000_25, 0.001_25),
+    ("claude-3-5-haiku", 0.000_8, 0.004),
+    ("claude-haiku-4-5", 0.001, 0.005),
+    ("claude-3-sonnet", 0.003, 0.015),
+    ("claude-3-5-sonnet", 0.003, 0.015),
+    ("claude-3-7-sonnet", 0.003, 0.015),
+    ("claude-sonnet-4", 0.003, 0.015),
+    ("claude-3-opus", 0.015, 0.075),
+    ("claude-opus-4", 0.015, 0.075),
+    ("nova-micro", 0.000_035, 0.000_14),
+    ("nova-lite", 0.000_06, 0.000_24),
+    ("nova-pro", 0.000_8, 0.003_2),
+    ("nova-premier", 0.002_5, 0.012_5),
+    ("titan-text-express", 0.000_2, 0.000_6),
+];
+
+// Amazon Transcribe standard batch price in USD per minute (first tier)
+const TRANSCRIBE_PER_MINUTE: f64 = 0.024;
+
+// Bedrock batch inference is billed at half the on-demand price
+const BATCH_DISCOUNT: f64 = 0.5;
+
+// Transcribe bills each job by the second, with a 15 second minimum
+const MIN_BILLED_SECONDS: f64 = 15.0;
+
+// Rates used to estimate what a run cost
+pub struct Pricing {
+    transcribe_per_minute: f64,
+    // None when the model's price isn't known, so no Bedrock cost is shown
+    tokens_per_1k: Option<(f64, f64)>,
+    // Share of the price batch inference is billed at
+    batch_factor: f64,
+}
+
+impl Pricing {
+    pub fn from_settings(settings: &Config) -> Self {
+        let model_id = settings.get_string("model.model_id").unwrap_or_default();
+        let listed = MODEL_PRICES
+            .iter()
+            .find(|(name, _, _)| model_id.contains(name))
+            .map(|(_, input, output)| (*input, *output));
+        let tokens_per_1k = match (
+            settings.get_float("pricing.input_per_1k_tokens"),
+            settings.get_float("pricing.output_per_1k_tokens"),
+        ) {
+            (Ok(input), Ok(output)) => Some((input, output)),
+            (Ok(input), Err(_)) => listed.map(|(_, output)| (input, output)),
+            (Err(_), Ok(output)) => listed.map(|(input, _)| (input, output)),
+            (Err(_), Err(_)) => listed,
+        };
+        Pricing {
+            transcribe_per_minute: settings
+                .get_float("pricing.transcribe_per_minute")
+                .unwrap_or(TRANSCRIBE_PER_MINUTE),
+            tokens_per_1k,
+            batch_factor: 1.0 - settings.get_float("pricing.batch_discount").unwrap_or(BATCH_DISCOUNT).clamp(0.0, 1.0),
+        }
+    }
+
+    // Without the model's price the Bedrock part of a run's cost isn't known
+    pub fn knows_model_price(&self) -> bool {
+        self.tokens_per_1k.is_some()
+    }
+
+    // Cache reads are billed at 10% of the input price and writes at 125%
+    fn bedrock_cost(&self, usage: &Usage) -> Option<f64> {
+        self.tokens_per_1k.map(|(input, output)| {
+            (usage.input_tokens as f64
+                + usage.cache_read_tokens as f64 * 0.1
+                + usage.cache_write_tokens as f64 * 1.25)
+                * input
+                / 1000.0
+                + usage.output_tokens as f64 * output / 1000.0
+        })
+    }
+}
+
+// Collects timings and sizes for a single run so they can be printed with --report.
+pub struct RunReport {
+    started: Instant,
+    stages: Vec<(String, Duration)>,
+    pub bytes_uploaded: u64,
+    pub transcript_chars: usize,
+    pub transcript_words: usize,
+    pub usage: Usage,
+    // Bedrock usage of each stage that called a model, in order, with the
+    // share of the price it is billed at
+    stage_usage: Vec<(String, Usage, f64)>,
+    // Audio seconds Transcribe bills for, across every job in the run
+    billed_audio_seconds: f64,
+    pricing: Pricing,
+    pub json_repairs: u32,
+    // Names of the Transcribe jobs the run started or resumed
+    pub transcription_jobs: Vec<String>,
+    // Calls and attempts per AWS service, from the run's spinner
+    attempts: Vec<(String, u32, u32)>,
+}
+
+impl RunReport {
+    pub fn new(pricing: Pricing) -> Self {
+        RunReport {
+            started: Instant::now(),
+            stages: Vec::new(),
+            bytes_uploaded: 0,
+            transcript_chars: 0,
+            transcript_words: 0,
+            usage: Usage::default(),
+            stage_usage: Vec::new(),
+            billed_audio_seconds: 0.0,
+            pricing,
+            json_repairs: 0,
+            transcription_jobs: Vec::new(),
+            attempts: Vec::new(),
+        }
+    }
+
+    // Record a stage that began at `start` and ends now.
+    pub fn stage(&mut self, name: &str, start: Instant) {
+        let elapsed = start.elapsed();
+        info!(stage = name, duration_secs = elapsed.as_secs_f64(), "stage finished");
+        progress::event(
+            "stage_finished",
+            json!({ "stage": name.to_lowercase(), "duration_secs": elapsed.as_secs_f64() }),
+        );
+        self.stages.push((name.to_string(), elapsed));
+    }
+
+    // Add a stage's Bedrock usage to the run total
+    pub fn add_usage(&mut self, stage: &str, usage: Usage) {
+        self.record_usage(stage, usage, 1.0);
+    }
+
+    // Like `add_usage`, for usage of a batch inference job, at the batch price
+    pub fn add_batch_usage(&mut self, stage: &str, usage: Usage) {
+        self.record_usage(&format!("{} (batch)", stage), usage, self.pricing.batch_factor);
+    }
+
+    fn record_usage(&mut self, stage: &str, usage: Usage, price_factor: f64) {
+        debug!(
+            stage,
+            input_tokens = usage.input_tokens,
+            output_tokens = usage.output_tokens,
+            "model usage"
+        );
+        self.usage += usage;
+        match self.stage_usage.iter_mut().find(|(name, _, _)| name == stage) {
+            Some((_, total, _)) => *total += usage,
+            None => self.stage_usage.push((stage.to_string(), usage, price_factor)),
+        }
+    }
+
+    // Record one Transcribe job over `seconds` of audio
+    pub fn add_transcribed_audio(&mut self, seconds: f64) {
+        self.billed_audio_seconds += seconds.ceil().max(MIN_BILLED_SECONDS);
+    }
+
+    fn transcribe_cost(&self) -> f64 {
+        self.billed_audio_seconds / 60.0 * self.pricing.transcribe_per_minute
+    }
+
+    pub fn audio_minutes(&self) -> f64 {
+        self.billed_audio_seconds / 60.0
+    }
+
+    // Every Bedrock token of the run, including those read from or written to the cache
+    pub fn tokens(&self) -> u64 {
+        self.usage.input_tokens + self.usage.output_tokens + self.usage.cache_read_tokens + self.usage.cache_write_tokens
+    }
+
+    // None when a model was called whose price isn't known
+    pub fn total_cost(&self) -> Option<f64> {
+        let mut bedrock = 0.0;
+        for (_, usage, price_factor) in &self.stage_usage {
+            bedrock += self.pricing.bedrock_cost(usage)? * price_factor;
+        }
+        Some(self.transcribe_cost() + bedrock)
+    }
+
+    // Billed usage and estimated cost per stage, for the JSON output type
+    pub fn cost_json(&self) -> Value {
+        let mut stages = Vec::new();
+        if self.billed_audio_seconds > 0.0 {
+            stages.push(json!({
+                "stage": "Transcription",
+                "audio_minutes": self.billed_audio_seconds / 60.0,
+                "estimated_cost_usd": self.transcribe_cost(),
+            }));
+        }
+        for (name, usage, price_factor) in &self.stage_usage {
+            stages.push(json!({
+                "stage": name,
+                "input_tokens": usage.input_tokens,
+                "output_tokens": usage.output_tokens,
+                "cache_read_tokens": usage.cache_read_tokens,
+                "cache_write_tokens": usage.cache_write_tokens,
+                "estimated_cost_usd": self.pricing.bedrock_cost(usage).map(|cost| cost * price_factor),
+            }));
+        }
+        json!({
+            "stages": stages,
+            "audio_minutes": self.billed_audio_seconds / 60.0,
+            "input_tokens": self.usage.input_tokens,
+            "output_tokens": self.usage.output_tokens,
+            "estimated_cost_usd": self.total_cost(),
+        })
+    }
+
+    // One line with what the run used and roughly cost, printed after every run
+    pub fn print_cost(&self) {
+        if self.billed_audio_seconds == 0.0 && self.stage_usage.is_empty() {
+            return;
+        }
+        let mut parts = Vec::new();
+        if self.billed_audio_seconds > 0.0 {
+            parts.push(format!("{:.1} audio minutes", self.billed_audio_seconds / 60.0));
+        }
+        if !self.stage_usage.is_empty() {
+            parts.push(format!(
+                "{} tokens in, {} out",
+                self.usage.input_tokens, self.usage.output_tokens
+            ));
+        }
+        let cost = match self.total_cost() {
+            Some(cost) => format!("${:.4}", cost),
+            // Set pricing.input_per_1k_tokens and output_per_1k_tokens for the model
+            None => "unknown model price".to_string(),
+        };
+        status!(
+            "💵",
+            "Estimated cost: {} ({})",
+            cost,
+            parts.join(", ")
+        );
+    }
+
+    // Take the attempts counted so far; retries made after this aren't included
+    pub fn set_attempts(&mut self, attempts: &[(String, u32, u32)]) {
+        self.attempts = attempts.to_vec();
+    }
+
+    pub fn set_transcript(&mut self, transcript: &str) {
+        self.transcript_chars = transcript.chars().count();
+        self.transcript_words = transcript.split_whitespace().count();
+    }
+
+    // The same figures as `print`, for the JSON output type.
+    pub fn to_json(&self) -> Value {
+        let stages: Vec<Value> = self
+            .stages
+            .iter()
+            .map(|(name, duration)| json!({ "stage": name, "seconds": duration.as_secs_f64() }))
+            .collect();
+        let attempts: Vec<Value> = self
+            .attempts
+            .iter()
+            .map(|(service, calls, attempts)| {
+                json!({ "service": service, "calls": calls, "attempts": attempts, "retries": attempts - calls })
+            })
+            .collect();
+        json!({
+            "stages": stages,
+            "total_seconds": self.started.elapsed().as_secs_f64(),
+            "bytes_uploaded": self.bytes_uploaded,
+            "transcript_chars": self.transcript_chars,
+            "transcript_words": self.transcript_words,
+            "input_tokens": self.usage.input_tokens,
+            "output_tokens": self.usage.output_tokens,
+            "cache_read_tokens": self.usage.cache_read_tokens,
+            "cache_write_tokens": self.usage.cache_write_tokens,
+            "json_repairs": self.json_repairs,
+            "attempts": attempts,
+        })
+    }
+
+    pub fn print(&self) {
+        eprintln!();
+        eprintln!("{}Run report", crate::progress::icon("📊"));
+        eprintln!("  {:<20} {:>10}", "Stage", "Duration");
+        for (name, duration) in &self.stages {
+            eprintln!("  {:<20} {:>9.1}s", name, duration.as_secs_f64());
+        }
+        eprintln!(
+            "  {:<20} {:>9.1}s",
+            "Total",
+            self.started.elapsed().as_secs_f64()
+        );
+        eprintln!();
+        eprintln!("  {:<20} {:>10}", "Bytes uploaded", self.bytes_uploaded);
+        eprintln!(
+            "  {:<20} {:>10}",
+            "Transcript chars", self.transcript_chars
+        );
+        eprintln!(
+            "  {:<20} {:>10}",
+            "Transcript words", self.transcript_words
+        );
+        eprintln!("  {:<20} {:>10}", "Tokens in", self.usage.input_tokens);
+        eprintln!("  {:<20} {:>10}", "Tokens out", self.usage.output_tokens);
+        if self.usage.cache_read_tokens > 0 || self.usage.cache_write_tokens > 0 {
+            eprintln!("  {:<20} {:>10}", "Cache reads", self.usage.cache_read_tokens);
+            eprintln!("  {:<20} {:>10}", "Cache writes", self.usage.cache_write_tokens);
+            // Cache reads are billed at 10% of the input price and writes at 125%
+            let saved = self.usage.cache_read_tokens as f64 * 0.9
+                - self.usage.cache_write_tokens as f64 * 0.25;
+            eprintln!("  {:<20} {:>10.0}", "Input tokens saved", saved);
+        }
+        eprintln!("  {:<20} {:>10}", "JSON repairs", self.json_repairs);
+
+        if !self.attempts.is_empty() {
+            eprintln!();
+            eprintln!("  {:<20} {:>10} {:>10} {:>10}", "Service", "Calls", "Attempts", "Retries");
+            for (service, calls, attempts) in &self.attempts {
+                eprintln!("  {:<20} {:>10} {:>10} {:>10}", service, calls, attempts, attempts - calls);
+            }
+        }
+
+        eprintln!();
+        eprintln!("  {:<20} {:>10} {:>10} {:>10}", "Billed", "Usage", "", "Est. USD");
+        if self.billed_audio_seconds > 0.0 {
+            eprintln!(
+                "  {:<20} {:>9.1}m {:>10} {:>10.4}",
+                "Transcription",
+                self.billed_audio_seconds / 60.0,
+                "",
+                self.transcribe_cost()
+            );
+        }
+        for (name, usage, price_factor) in &self.stage_usage {
+            eprintln!(
+                "  {:<20} {:>7} in {:>6} out {:>10}",
+                name,
+                usage.input_tokens,
+                usage.output_tokens,
+                format_cost(self.pricing.bedrock_cost(usage).map(|cost| cost * price_factor))
+            );
+        }
+        eprintln!("  {:<20} {:>10} {:>10} {:>10}", "Total", "", "", format_cost(self.total_cost()));
+    }
+}
+
+fn format_cost(cost: Option<f64>) -> String {
+    cost.map_or_else(|| "?".to_string(), |cost| format!("{:.4}", cost))
+}
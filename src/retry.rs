@@ -0,0 +1,212 @@
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::{bail, Error};
+use aws_smithy_runtime_api::client::result::SdkError;
+use aws_smithy_runtime_api::http::Response;
+use aws_smithy_types::error::metadata::ProvideErrorMetadata;
+use config::Config;
+use tokio::time::sleep;
+use tracing::debug;
+
+use crate::progress::Progress;
+
+// Error codes AWS services return when a request may succeed if sent again
+const TRANSIENT_CODES: &[&str] = &[
+    "ThrottlingException",
+    "Throttling",
+    "TooManyRequestsException",
+    "Throttled",
+    "LimitExceededException",
+    "RequestLimitExceeded",
+    "SlowDown",
+    "ServiceUnavailable",
+    "ServiceUnavailableException",
+    "InternalServerException",
+    "InternalFailureException",
+    "InternalError",
+    "RequestTimeout",
+    "RequestTimeoutException",
+    "ModelNotReadyException",
+    "ModelTimeoutException",
+];
+
+// How a call to S3, Transcribe, Bedrock or SNS is retried when AWS is
+// throttling or briefly unavailable, on top of the SDK's own quick retries
+// (aws.max_attempts). From [retry] in config.toml, with [retry.s3],
+// [retry.transcribe], [retry.bedrock] and [retry.sns] overriding it for one service.
+#[derive(Debug, Clone)]
+pub struct Policy {
+    // Including the first call; 1 turns retries off
+    pub attempts: u32,
+    // The wait before the first retry, doubled for each one after it
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    // Wait a random part of the backoff, so parallel runs don't retry in step
+    pub jitter: bool,
+}
+
+impl Policy {
+    pub fn from_settings(settings: &Config, service: &str) -> Result<Self, Error> {
+        let float = |key: &str| {
+            settings
+                .get_float(&format!("retry.{}.{}", service, key))
+                .or_else(|_| settings.get_float(&format!("retry.{}", key)))
+                .ok()
+        };
+        let attempts = settings
+            .get_int(&format!("retry.{}.attempts", service))
+            .or_else(|_| settings.get_int("retry.attempts"))
+            .unwrap_or(4);
+        let initial_backoff = float("initial_backoff_secs").unwrap_or(2.0);
+        let max_backoff = float("max_backoff_secs").unwrap_or(60.0);
+        if attempts < 1 || initial_backoff < 0.0 || max_backoff < initial_backoff {
+            bail!(
+                "\nInvalid retry settings for {}: attempts must be at least 1 and max_backoff_secs at least \
+                initial_backoff_secs. Check [retry] in config.toml.",
+                service
+            );
+        }
+        Ok(Policy {
+            attempts: attempts as u32,
+            initial_backoff: Duration::from_secs_f64(initial_backoff),
+            max_backoff: Duration::from_secs_f64(max_backoff),
+            jitter: settings
+                .get_bool(&format!("retry.{}.jitter", service))
+                .or_else(|_| settings.get_bool("retry.jitter"))
+                .unwrap_or(true),
+        })
+    }
+
+    // The wait after the given failed attempt, counting from 1
+    fn backoff(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt - 1))
+            .min(self.max_backoff);
+        if !self.jitter {
+            return backoff;
+        }
+        // "Full jitter": anywhere between no wait and the whole backoff
+        backoff.mul_f64(fastrand::f64())
+    }
+}
+
+// An error that may go away if the call is made again
+pub trait Transient {
+    // Why the call can be retried, or None when it can't
+    fn transient_reason(&self) -> Option<String>;
+}
+
+impl<E: ProvideErrorMetadata> Transient for SdkError<E, Response> {
+    fn transient_reason(&self) -> Option<String> {
+        match self {
+            SdkError::TimeoutError(_) => Some("timed out".to_string()),
+            SdkError::DispatchFailure(_) => Some("connection failed".to_string()),
+            SdkError::ResponseError(_) => Some("incomplete response".to_string()),
+            SdkError::ServiceError(err) => transient_status(err.err().code(), err.raw().status().as_u16()),
+            _ => None,
+        }
+    }
+}
+
+// Services without an SDK client here, such as SNS, are called with reqwest
+impl Transient for reqwest::Error {
+    fn transient_reason(&self) -> Option<String> {
+        if self.is_timeout() {
+            Some("timed out".to_string())
+        } else if self.is_connect() {
+            Some("connection failed".to_string())
+        } else if self.is_body() || self.is_decode() {
+            Some("incomplete response".to_string())
+        } else {
+            None
+        }
+    }
+}
+
+// Why an error reply with this code and HTTP status may succeed if sent again
+pub fn transient_status(code: Option<&str>, status: u16) -> Option<String> {
+    match code {
+        Some(code) if TRANSIENT_CODES.contains(&code) => Some(code.to_string()),
+        _ if status == 429 || status >= 500 => Some(format!("HTTP {}", status)),
+        _ => None,
+    }
+}
+
+// Make `call` until it succeeds, fails with an error that isn't transient, or
+// has been made `policy.attempts` times. Each retry is shown on the spinner,
+// which also counts the attempts for the run report.
+pub async fn retry<T, E, F, Fut>(policy: &Policy, spinner: &mut Progress, service: &str, mut call: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Transient,
+{
+    let mut attempt = 1;
+    loop {
+        let err = match call().await {
+            Ok(value) => {
+                spinner.add_attempts(service, attempt);
+                return Ok(value);
+            }
+            Err(err) => err,
+        };
+        let reason = match err.transient_reason() {
+            Some(reason) if attempt < policy.attempts => reason,
+            _ => {
+                spinner.add_attempts(service, attempt);
+                return Err(err);
+            }
+        };
+        let backoff = policy.backoff(attempt);
+        debug!(service, attempt, %reason, secs = backoff.as_secs_f64(), "retrying");
+        let step = spinner.step().to_string();
+        spinner.stop_and_persist(
+            "⚠️",
+            &format!(
+                "{} failed ({}); retrying in {:.0}s, attempt {} of {}",
+                service,
+                reason,
+                backoff.as_secs_f64().ceil(),
+                attempt + 1,
+                policy.attempts
+            ),
+        );
+        sleep(backoff).await;
+        spinner.update(&step);
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(jitter: bool) -> Policy {
+        Policy {
+            attempts: 10,
+            initial_backoff: Duration::from_secs(2),
+            max_backoff: Duration::from_secs(60),
+            jitter,
+        }
+    }
+
+    #[test]
+    fn doubles_the_backoff_up_to_the_maximum() {
+        let policy = policy(false);
+        let waits: Vec<u64> = (1..=7).map(|attempt| policy.backoff(attempt).as_secs()).collect();
+        assert_eq!(waits, [2, 4, 8, 16, 32, 60, 60]);
+        // Far past the maximum nothing overflows
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn jitters_within_the_backoff() {
+        let (jittered, full) = (policy(true), policy(false));
+        for attempt in 1..=8 {
+            let wait = jittered.backoff(attempt);
+            assert!(wait <= full.backoff(attempt), "attempt {} waited {:?}", attempt, wait);
+        }
+    }
+}
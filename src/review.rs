@@ -0,0 +1,124 @@
+use anyhow::Error;
+use dialoguer::{Editor, Input};
+
+use crate::progress::{self, choose};
+use crate::transcribe::{format_timestamp, Transcript};
+use crate::transforms::phrase_pattern;
+
+const ACTIONS: [&str; 5] = [
+    "Rename a speaker",
+    "Replace a word or phrase",
+    "Edit the transcript in your editor",
+    "Show the transcript",
+    "Done",
+];
+
+// `--review`: let the user correct the diarized transcript before it is
+// summarized, e.g. name "Speaker 2" or fix a misheard product name. A summary
+// made from the corrected transcript doesn't need fixing afterwards.
+pub fn review(transcript: &mut Transcript) -> Result<(), Error> {
+    loop {
+        let speakers = transcript.speakers();
+        eprintln!(
+            "\n{}Review the transcript: {} turn(s), speakers {}",
+            progress::icon("✏️"),
+            transcript.segments.len(),
+            if speakers.is_empty() { "unknown".to_string() } else { speakers.join(", ") }
+        );
+        let actions: Vec<String> = ACTIONS.iter().map(|action| action.to_string()).collect();
+        match choose("What would you like to do?", &actions)? {
+            0 => rename_speaker(transcript, &speakers)?,
+            1 => replace_phrase(transcript)?,
+            2 => edit_in_editor(transcript)?,
+            3 => eprintln!("\n{}", transcript.timestamped_text()),
+            _ => return Ok(()),
+        }
+    }
+}
+
+fn rename_speaker(transcript: &mut Transcript, speakers: &[String]) -> Result<(), Error> {
+    if speakers.is_empty() {
+        eprintln!("The transcript has no speaker labels to rename.");
+        return Ok(());
+    }
+    let speaker = &speakers[choose("Which speaker?", speakers)?];
+    let name: String = Input::with_theme(progress::theme().as_ref())
+        .with_prompt(format!("New name for {}", speaker))
+        .interact_text()?;
+    let name = name.trim();
+    if name.is_empty() {
+        return Ok(());
+    }
+    let mut turns = 0;
+    for segment in &mut transcript.segments {
+        if segment.speaker == *speaker {
+            segment.speaker = name.to_string();
+            turns += 1;
+        }
+    }
+    eprintln!("Renamed {} to {} in {} turn(s)", speaker, name, turns);
+    Ok(())
+}
+
+// Whole words or phrases, ignoring case, as the glossary transform matches them
+fn replace_phrase(transcript: &mut Transcript) -> Result<(), Error> {
+    let theme = progress::theme();
+    let heard: String = Input::with_theme(theme.as_ref())
+        .with_prompt("Replace")
+        .interact_text()?;
+    if heard.trim().is_empty() {
+        return Ok(());
+    }
+    let meant: String = Input::with_theme(theme.as_ref())
+        .with_prompt("With")
+        .allow_empty(true)
+        .interact_text()?;
+    let pattern = phrase_pattern(std::slice::from_ref(&heard))?;
+    let mut count = 0;
+    for segment in &mut transcript.segments {
+        count += pattern.find_iter(&segment.text).count();
+        segment.text = pattern.replace_all(&segment.text, regex::NoExpand(meant.trim())).into_owned();
+    }
+    eprintln!("Replaced {} occurrence(s) of \"{}\"", count, heard.trim());
+    if count > 0 {
+        eprintln!(
+            "To correct it in every run, add \"{}\" = \"{}\" to [transforms.glossary] in config.toml.",
+            heard.trim().to_lowercase(),
+            meant.trim()
+        );
+    }
+    Ok(())
+}
+
+// One "[00:12:30] Speaker 1: text" line per turn. Speakers and text can be
+// changed, but every turn must keep its own line so its times are kept.
+fn edit_in_editor(transcript: &mut Transcript) -> Result<(), Error> {
+    let Some(edited) = Editor::new().extension(".txt").edit(&transcript.timestamped_text())? else {
+        eprintln!("The transcript was not saved, so nothing changed.");
+        return Ok(());
+    };
+    let lines: Vec<&str> = edited.lines().filter(|line| !line.trim().is_empty()).collect();
+    if lines.len() != transcript.segments.len() {
+        eprintln!(
+            "The edited transcript has {} lines, but the transcript has {} turns. Keep one line per turn; nothing was changed.",
+            lines.len(),
+            transcript.segments.len()
+        );
+        return Ok(());
+    }
+    for (segment, line) in transcript.segments.iter_mut().zip(lines) {
+        let line = line.trim();
+        let line = match line.strip_prefix(&format!("[{}]", format_timestamp(segment.start_time))) {
+            Some(rest) => rest.trim_start(),
+            None => line,
+        };
+        match line.split_once(':') {
+            Some((speaker, text)) if !segment.speaker.is_empty() => {
+                segment.speaker = speaker.trim().to_string();
+                segment.text = text.trim().to_string();
+            }
+            _ => segment.text = line.to_string(),
+        }
+    }
+    Ok(())
+}
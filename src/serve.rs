@@ -0,0 +1,751 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Error};
+use axum::body::Body;
+use axum::extract::Request;
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures::future::join_all;
+use futures::stream::{self, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tempfile::TempDir;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{broadcast, mpsc, watch, OwnedSemaphorePermit, Semaphore};
+use tokio::task::{JoinHandle, LocalSet};
+use tracing::{info_span, Instrument};
+use uuid::Uuid;
+
+use crate::audio::is_audio_file;
+use crate::health::{Check, Dependencies};
+use crate::metrics::{Gauges, Metrics};
+use crate::pipeline::{announce, summarize, transcribe, Meeting, Outputs, RunContext, Summary, Transcription};
+use crate::progress::{self, Progress};
+use crate::{feedback, naming, report, status};
+
+// How `distill serve` listens and how much it takes on
+pub struct Options {
+    // e.g. "127.0.0.1:8080"
+    pub listen: String,
+    // Recordings processed at the same time; more wait their turn
+    pub concurrency: usize,
+    // Clients must send "Authorization: Bearer <token>" when set
+    pub token: Option<String>,
+    // Largest audio file accepted, in bytes
+    pub max_upload: u64,
+    // How long running jobs may take to finish once the server is told to stop
+    pub shutdown_timeout: Duration,
+    // Finished jobs are forgotten this long after they finish, or sooner,
+    // oldest first, once more than `max_finished_jobs` are kept
+    pub job_ttl: Duration,
+    pub max_finished_jobs: usize,
+}
+
+// Progress events kept for a job's event stream clients that fall behind
+const EVENT_BUFFER: usize = 256;
+
+// How long GET /readyz answers with the same dependency checks, so frequent
+// probes don't call every service each time
+const READINESS_CACHE: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+// How soon a queued job is started: every high priority job before any
+// normal one, and those before any low one, e.g. an incident call ahead of the
+// recordings of a backfill. Jobs of the same priority start in the order they
+// were sent.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+// A recording sent to the server. Jobs are kept in memory, so they are gone
+// when the server stops, and finished ones are evicted after a while.
+#[derive(Serialize)]
+struct Job {
+    id: String,
+    status: Status,
+    priority: Priority,
+    input: String,
+    created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    finished_at: Option<String>,
+    // When it finished, for eviction
+    #[serde(skip)]
+    finished: Option<Instant>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip)]
+    summary: String,
+    #[serde(skip)]
+    transcript: String,
+    // The job's progress events, for GET /jobs/{id}/events; dropped when it
+    // finishes, which ends the streams
+    #[serde(skip)]
+    events: Option<broadcast::Sender<Value>>,
+}
+
+// What the request handlers share. The run context isn't here: it can't be
+// sent between threads, so jobs are run on the thread that called `serve`.
+struct Server {
+    options: Options,
+    jobs: Mutex<HashMap<String, Job>>,
+    // Uploaded recordings, each in a directory named after its job
+    uploads: TempDir,
+    // New jobs, for `serve` to run
+    queue: mpsc::UnboundedSender<(String, PathBuf, Priority)>,
+    // Set on SIGTERM or Ctrl+C; new jobs are turned away from then on
+    stopping: AtomicBool,
+    // The run's output mode, for the tasks requests are answered on
+    output: progress::Output,
+    metrics: Mutex<Metrics>,
+    // A permit for each recording processed at the same time
+    permits: Arc<Semaphore>,
+    // Checked by GET /readyz, whose last results are kept for READINESS_CACHE
+    dependencies: Dependencies,
+    readiness: tokio::sync::Mutex<Option<(Instant, Vec<Check>)>>,
+}
+
+// An error response: a status and {"error": "..."}
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+#[derive(Deserialize)]
+struct UploadQuery {
+    filename: Option<String>,
+    priority: Option<Priority>,
+}
+
+#[derive(Deserialize)]
+struct S3Input {
+    s3_uri: String,
+    priority: Option<Priority>,
+}
+
+// `distill serve`: a small HTTP API over the pipeline, so the team can send
+// recordings to one machine that has the AWS credentials instead of setting
+// them up on every laptop. Each job is transcribed and summarized with the
+// run's settings (profile, mode, prompt, language and so on), at most
+// `options.concurrency` at a time, by priority.
+//
+//   POST /jobs?filename=standup.m4a   the audio as the request body
+//   POST /jobs                        {"s3_uri": "s3://bucket/key"}
+//   POST /jobs?...&priority=high      either of them ahead of normal jobs
+//   GET  /jobs                        every job and its status
+//   GET  /jobs/{id}                   one job's status, title and any error
+//   GET  /jobs/{id}/summary           the summary, as text
+//   GET  /jobs/{id}/transcript        the transcript, as text
+//   GET  /jobs/{id}/events            the job's progress, as server-sent events
+//   GET  /metrics                     job counts and durations, for Prometheus
+//   GET  /healthz                     200 while the server is up
+//   GET  /readyz                      200 when it can take jobs and reach AWS
+//
+// On SIGTERM (e.g. from ECS or Kubernetes) or Ctrl+C the server stops taking
+// jobs, drops the ones still queued and waits up to `options.shutdown_timeout`
+// for the running ones to finish.
+pub async fn serve(ctx: RunContext, options: Options) -> Result<(), Error> {
+    let listener = tokio::net::TcpListener::bind(&options.listen)
+        .await
+        .with_context(|| format!("Error listening on {}", options.listen))?;
+    let address = listener.local_addr()?;
+    let (queue, mut queued) = mpsc::unbounded_channel();
+    let dependencies = Dependencies::from_context(&ctx)?;
+    let server = Arc::new(Server {
+        jobs: Mutex::new(HashMap::new()),
+        uploads: tempfile::tempdir()?,
+        queue,
+        stopping: AtomicBool::new(false),
+        output: ctx.output.clone(),
+        metrics: Mutex::new(Metrics::default()),
+        permits: Arc::new(Semaphore::new(options.concurrency.max(1))),
+        dependencies,
+        readiness: tokio::sync::Mutex::new(None),
+        options,
+    });
+    status!(
+        "🌐",
+        "Serving the Distill API on http://{} ({} recording(s) at a time). Press Ctrl+C to stop.",
+        address,
+        server.options.concurrency.max(1)
+    );
+    if server.options.token.is_none() && !address.ip().is_loopback() {
+        eprintln!(
+            "Warning: serve.token is not set, so anyone who can reach {} can use your AWS account through it",
+            address
+        );
+    }
+
+    let app = Router::new()
+        .route("/jobs", post(create_job).get(list_jobs))
+        .route("/jobs/{id}", get(get_job))
+        .route("/jobs/{id}/summary", get(get_summary))
+        .route("/jobs/{id}/transcript", get(get_transcript))
+        .route("/jobs/{id}/events", get(job_events))
+        .route("/metrics", get(metrics))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .layer(middleware::from_fn_with_state(server.clone(), with_output))
+        .with_state(server.clone());
+    // Requests already being answered are finished when the listener stops
+    let (stop, mut stopped) = watch::channel(false);
+    let mut listening = tokio::spawn(async move {
+        axum::serve(listener, app)
+            .with_graceful_shutdown(async move {
+                let _ = stopped.wait_for(|stop| *stop).await;
+            })
+            .await
+    });
+
+    let ctx = Rc::new(ctx);
+    LocalSet::new()
+        .run_until(async move {
+            let mut running: Vec<JoinHandle<()>> = Vec::new();
+            // Jobs waiting for a free slot, highest priority and then oldest first
+            let mut pending = BTreeMap::new();
+            let mut sent = 0u64;
+            let shutdown = shutdown_signal();
+            tokio::pin!(shutdown);
+            loop {
+                tokio::select! {
+                    result = &mut listening => {
+                        result?.context("The Distill API stopped")?;
+                        return Ok(());
+                    }
+                    result = &mut shutdown => {
+                        result?;
+                        break;
+                    }
+                    Some((id, input, priority)) = queued.recv() => {
+                        sent += 1;
+                        pending.insert((Reverse(priority), sent), (id, input));
+                    }
+                    Ok(permit) = server.permits.clone().acquire_owned(), if !pending.is_empty() => {
+                        let Some((_, (id, input))) = pending.pop_first() else {
+                            continue;
+                        };
+                        let span = info_span!("job", id = %id);
+                        let job = run_job(ctx.clone(), server.clone(), id, input, permit);
+                        running.retain(|job| !job.is_finished());
+                        running.push(tokio::task::spawn_local(job.instrument(span)));
+                    }
+                }
+            }
+
+            server.stopping.store(true, Ordering::SeqCst);
+            let _ = stop.send(true);
+            for (id, _) in pending.into_values() {
+                never_started(&server, &id);
+            }
+            running.retain(|job| !job.is_finished());
+            let deadline = server.options.shutdown_timeout;
+            status!(
+                "🛑",
+                "Stopping: no new jobs are accepted; waiting up to {}s for the running ones",
+                deadline.as_secs()
+            );
+            if tokio::time::timeout(deadline, join_all(running)).await.is_err() {
+                eprintln!(
+                    "{}Some jobs were still running after {}s and were stopped",
+                    progress::icon("⚠️"),
+                    deadline.as_secs()
+                );
+            }
+            let _ = tokio::time::timeout(Duration::from_secs(5), listening).await;
+            status!("👋", "The Distill API has stopped");
+            Ok(())
+        })
+        .await
+}
+
+// Wait for SIGTERM, as sent by container schedulers, or Ctrl+C
+async fn shutdown_signal() -> Result<(), Error> {
+    #[cfg(unix)]
+    {
+        let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result?,
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await?;
+    Ok(())
+}
+
+// Each request is answered on a task of its own, which is given the run's
+// output mode
+async fn with_output(State(server): State<Arc<Server>>, request: Request, next: Next) -> Response {
+    server.output.clone().scope(next.run(request)).await
+}
+
+fn authorize(server: &Server, headers: &HeaderMap) -> Result<(), ApiError> {
+    let Some(token) = &server.options.token else {
+        return Ok(());
+    };
+    let given = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if given != Some(token.as_str()) {
+        return Err(ApiError(StatusCode::UNAUTHORIZED, "Missing or wrong bearer token".to_string()));
+    }
+    Ok(())
+}
+
+async fn create_job(
+    State(server): State<Arc<Server>>,
+    Query(query): Query<UploadQuery>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    authorize(&server, &headers)?;
+    if server.stopping.load(Ordering::SeqCst) {
+        return Err(ApiError(StatusCode::SERVICE_UNAVAILABLE, "The server is shutting down".to_string()));
+    }
+    let id = Uuid::new_v4().to_string();
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+
+    // The job is listed by the uploaded file's name or the S3 URI
+    let (input, label, priority) = if is_json {
+        let bytes = axum::body::to_bytes(body, 64 * 1024)
+            .await
+            .map_err(|err| ApiError(StatusCode::BAD_REQUEST, err.to_string()))?;
+        let S3Input { s3_uri, priority } = serde_json::from_slice(&bytes).map_err(|err| {
+            ApiError(StatusCode::BAD_REQUEST, format!("Expected {{\"s3_uri\": \"s3://...\"}}: {}", err))
+        })?;
+        if !s3_uri.starts_with("s3://") {
+            return Err(ApiError(StatusCode::BAD_REQUEST, "s3_uri must start with s3://".to_string()));
+        }
+        (PathBuf::from(&s3_uri), s3_uri, priority.or(query.priority))
+    } else {
+        // The file name titles the meeting, as it does on the command line
+        let filename = query
+            .filename
+            .as_deref()
+            .and_then(|name| Path::new(name).file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .ok_or_else(|| {
+                let message = "Name the recording with ?filename=, e.g. ?filename=standup.m4a";
+                ApiError(StatusCode::BAD_REQUEST, message.to_string())
+            })?;
+        if !is_audio_file(Path::new(&filename)) {
+            let message = format!("{} is not a supported audio file", filename);
+            return Err(ApiError(StatusCode::UNSUPPORTED_MEDIA_TYPE, message));
+        }
+        (save_upload(&server, &id, &filename, body).await?, filename, query.priority)
+    };
+    let priority = priority.unwrap_or_default();
+
+    let job = Job {
+        id: id.clone(),
+        status: Status::Queued,
+        priority,
+        input: label.clone(),
+        created_at: naming::timestamp(),
+        finished_at: None,
+        finished: None,
+        title: None,
+        date: None,
+        language_code: None,
+        error: None,
+        summary: String::new(),
+        transcript: String::new(),
+        events: Some(broadcast::channel(EVENT_BUFFER).0),
+    };
+    {
+        let mut jobs = server.jobs.lock().unwrap();
+        evict(&mut jobs, Instant::now(), server.options.job_ttl, server.options.max_finished_jobs);
+        jobs.insert(id.clone(), job);
+    }
+    match priority {
+        Priority::Normal => status!("📥", "Job {} queued: {}", id, label),
+        Priority::High => status!("📥", "Job {} queued with high priority: {}", id, label),
+        Priority::Low => status!("📥", "Job {} queued with low priority: {}", id, label),
+    }
+
+    let _ = server.queue.send((id.clone(), input, priority));
+    Ok((StatusCode::ACCEPTED, Json(json!({ "id": id, "status": Status::Queued }))))
+}
+
+// Stream the request body into the job's upload directory, so large
+// recordings aren't held in memory
+async fn save_upload(server: &Server, id: &str, filename: &str, body: Body) -> Result<PathBuf, ApiError> {
+    let internal = |err: std::io::Error| ApiError(StatusCode::INTERNAL_SERVER_ERROR, err.to_string());
+    let dir = server.uploads.path().join(id);
+    tokio::fs::create_dir_all(&dir).await.map_err(internal)?;
+    let path = dir.join(filename);
+    let mut file = tokio::fs::File::create(&path).await.map_err(internal)?;
+    let mut stream = body.into_data_stream();
+    let mut size = 0;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|err| ApiError(StatusCode::BAD_REQUEST, err.to_string()))?;
+        size += chunk.len() as u64;
+        if size > server.options.max_upload {
+            let _ = tokio::fs::remove_dir_all(&dir).await;
+            return Err(ApiError(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("Recordings are limited to {} MB", server.options.max_upload / 1024 / 1024),
+            ));
+        }
+        file.write_all(&chunk).await.map_err(internal)?;
+    }
+    file.flush().await.map_err(internal)?;
+    if size == 0 {
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+        return Err(ApiError(StatusCode::BAD_REQUEST, "The request has no audio".to_string()));
+    }
+    Ok(path)
+}
+
+// Run the job with the server's output mode, also sending its progress
+// events to the job's event stream
+async fn run_job(ctx: Rc<RunContext>, server: Arc<Server>, id: String, input: PathBuf, permit: OwnedSemaphorePermit) {
+    let events = server.jobs.lock().unwrap().get(&id).and_then(|job| job.events.clone());
+    let output = progress::Output { events, ..ctx.output.clone() };
+    output.scope(process_job(ctx, server, id, input, permit)).await
+}
+
+// A job still queued when the server stops is dropped
+fn never_started(server: &Server, id: &str) {
+    if let Some(job) = server.jobs.lock().unwrap().get_mut(id) {
+        job.status = Status::Failed;
+        job.finished_at = Some(naming::timestamp());
+        job.finished = Some(Instant::now());
+        job.error = Some("The server stopped before the job started".to_string());
+        job.events = None;
+    }
+    server.metrics.lock().unwrap().failed("queue", None);
+}
+
+// Transcribe and summarize the recording as a run would, holding one of the
+// server's slots, and keep the results for the client to fetch
+async fn process_job(ctx: Rc<RunContext>, server: Arc<Server>, id: String, input: PathBuf, _permit: OwnedSemaphorePermit) {
+    let started = Instant::now();
+    if let Some(job) = server.jobs.lock().unwrap().get_mut(&id) {
+        job.status = Status::Running;
+    }
+    status!("⚙️", "Job {} started", id);
+    progress::event("started", json!({ "job": id }));
+
+    let ctx = ctx.as_ref();
+    let files = [input.clone()];
+    let meeting = Meeting::new(ctx, &files);
+    let mut run_report = report::RunReport::new(report::Pricing::from_settings(&ctx.settings));
+    let result = async {
+        ctx.quota.check()?;
+        let mut spinner = Progress::new(if input.starts_with("s3://") {
+            "Submitting transcription job"
+        } else {
+            "Uploading file to S3..."
+        });
+        let transcription = transcribe(ctx, &meeting, &mut spinner, &mut run_report).await?;
+        let summary = summarize(ctx, &meeting, &transcription, &mut spinner, &mut run_report).await?;
+        spinner.success("Done!");
+        run_report.print_cost();
+        ctx.quota.record(&run_report)?;
+        // The job ID is what `distill-cli feedback` takes for a served run
+        if !ctx.skip_summary {
+            feedback::record_run(ctx, &id, &meeting)?;
+        }
+        Ok::<_, Error>((transcription, summary))
+    }
+    .await;
+    match &result {
+        Ok(_) => server.metrics.lock().unwrap().succeeded(started.elapsed()),
+        // Failures before the upload starts, e.g. a used-up quota, count as the upload's
+        Err(_) => {
+            let stage = progress::stage().unwrap_or("upload");
+            server.metrics.lock().unwrap().failed(stage, Some(started.elapsed()));
+        }
+    }
+    // Nothing is written; the client fetches the results
+    let outputs = Outputs { files: Vec::new(), url: None, cost_usd: None };
+    announce(ctx, &meeting, &run_report, result.as_ref().map(|_| &outputs)).await;
+
+    record_result(&server, &id, &meeting, result);
+    // The results are kept; the uploaded audio isn't needed any more
+    let _ = tokio::fs::remove_dir_all(server.uploads.path().join(&id)).await;
+}
+
+// Keep a finished job's results, or its error, for the client to fetch
+fn record_result(server: &Server, id: &str, meeting: &Meeting, result: Result<(Transcription, Summary), Error>) {
+    let mut jobs = server.jobs.lock().unwrap();
+    let Some(job) = jobs.get_mut(id) else {
+        return;
+    };
+    job.finished_at = Some(naming::timestamp());
+    job.finished = Some(Instant::now());
+    match result {
+        Ok((transcription, summary)) => {
+            job.status = Status::Succeeded;
+            job.title = Some(meeting.title.clone());
+            job.date = Some(meeting.date.clone());
+            job.language_code = Some(transcription.language_code);
+            job.summary = summary.text;
+            job.transcript = transcription.text;
+            status!("✅", "Job {} finished", id);
+            progress::event("finished", json!({ "job": id }));
+        }
+        Err(err) => {
+            job.status = Status::Failed;
+            job.error = Some(format!("{:#}", err).trim().to_string());
+            eprintln!("{}Job {} failed: {:#}", progress::icon("❌"), id, err);
+            progress::event("error", json!({ "job": id, "message": format!("{:#}", err) }));
+        }
+    }
+    job.events = None;
+    evict(&mut jobs, Instant::now(), server.options.job_ttl, server.options.max_finished_jobs);
+}
+
+async fn list_jobs(State(server): State<Arc<Server>>, headers: HeaderMap) -> Result<Json<Value>, ApiError> {
+    authorize(&server, &headers)?;
+    let jobs = server.jobs.lock().unwrap();
+    let mut list: Vec<&Job> = jobs.values().collect();
+    list.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+    Ok(Json(json!({ "jobs": list })))
+}
+
+async fn get_job(
+    State(server): State<Arc<Server>>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, ApiError> {
+    authorize(&server, &headers)?;
+    let jobs = server.jobs.lock().unwrap();
+    let job = jobs.get(&id).ok_or_else(|| not_found(&id))?;
+    Ok(Json(json!(job)))
+}
+
+async fn get_summary(
+    State(server): State<Arc<Server>>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+    headers: HeaderMap,
+) -> Result<String, ApiError> {
+    authorize(&server, &headers)?;
+    finished(&server, &id, |job| job.summary.clone())
+}
+
+async fn get_transcript(
+    State(server): State<Arc<Server>>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+    headers: HeaderMap,
+) -> Result<String, ApiError> {
+    authorize(&server, &headers)?;
+    finished(&server, &id, |job| job.transcript.clone())
+}
+
+// `GET /healthz`: the server is up and answering. No token is needed, so
+// orchestrators can probe it.
+async fn healthz() -> Json<Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+// `GET /readyz`: whether the server can take jobs. It can't while it is
+// stopping, or when a service it depends on can't be reached with its
+// credentials. The free slots and queued jobs are reported too; a full server
+// is still ready, since jobs wait their turn. No token is needed, but without
+// it the checks' errors are left out.
+async fn readyz(State(server): State<Arc<Server>>, headers: HeaderMap) -> (StatusCode, Json<Value>) {
+    let stopping = server.stopping.load(Ordering::SeqCst);
+    let checks = if stopping {
+        Vec::new()
+    } else {
+        let mut readiness = server.readiness.lock().await;
+        match readiness.as_ref() {
+            Some((checked, checks)) if checked.elapsed() < READINESS_CACHE => checks.clone(),
+            _ => {
+                let checks = server.dependencies.check().await;
+                *readiness = Some((Instant::now(), checks.clone()));
+                checks
+            }
+        }
+    };
+    let ready = !stopping && checks.iter().all(|check| check.ok);
+    let mut checks = json!(checks);
+    if authorize(&server, &headers).is_err() {
+        for check in checks.as_array_mut().into_iter().flatten() {
+            if let Some(check) = check.as_object_mut() {
+                check.remove("error");
+            }
+        }
+    }
+    let queued = server.jobs.lock().unwrap().values().filter(|job| job.status == Status::Queued).count();
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    let body = json!({
+        "ready": ready,
+        "stopping": stopping,
+        "slots": server.options.concurrency.max(1),
+        "free_slots": server.permits.available_permits(),
+        "queued": queued,
+        "checks": checks,
+    });
+    (status, Json(body))
+}
+
+// `GET /metrics`: what the server has done since it started, in the
+// Prometheus text format
+async fn metrics(State(server): State<Arc<Server>>, headers: HeaderMap) -> Result<Response, ApiError> {
+    authorize(&server, &headers)?;
+    let (mut queued, mut running) = (0, 0);
+    for job in server.jobs.lock().unwrap().values() {
+        match job.status {
+            Status::Queued => queued += 1,
+            Status::Running => running += 1,
+            Status::Succeeded | Status::Failed => {}
+        }
+    }
+    let text = server.metrics.lock().unwrap().render(&Gauges { queued, running });
+    Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], text).into_response())
+}
+
+// Forget the finished jobs that finished more than `ttl` ago, then the oldest
+// finished ones beyond `max_finished`, so a long-running server's memory
+// doesn't grow with every job it has done. Queued and running jobs are kept.
+fn evict(jobs: &mut HashMap<String, Job>, now: Instant, ttl: Duration, max_finished: usize) {
+    jobs.retain(|_, job| job.finished.is_none_or(|finished| now.saturating_duration_since(finished) <= ttl));
+    let mut finished: Vec<(Instant, String)> =
+        jobs.values().filter_map(|job| Some((job.finished?, job.id.clone()))).collect();
+    if finished.len() <= max_finished {
+        return;
+    }
+    finished.sort();
+    for (_, id) in &finished[..finished.len() - max_finished] {
+        jobs.remove(id);
+    }
+}
+
+// `GET /jobs/{id}/events`: the job's status, then each of its progress events
+// as it happens (the same events as --progress json, plus summary_text as the
+// summary is written), named after the event. The stream ends when the job
+// finishes; for a finished job it is just the status.
+async fn job_events(
+    State(server): State<Arc<Server>>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    authorize(&server, &headers)?;
+    let (job, events) = {
+        let jobs = server.jobs.lock().unwrap();
+        let job = jobs.get(&id).ok_or_else(|| not_found(&id))?;
+        (json!(job), job.events.as_ref().map(broadcast::Sender::subscribe))
+    };
+    let status = Event::default().event("status").data(job.to_string());
+    let events = stream::unfold(events, |events| async move {
+        let mut events = events?;
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    let name = event["event"].as_str().unwrap_or("message").to_string();
+                    return Some((Event::default().event(name).data(event.to_string()), Some(events)));
+                }
+                // The client missed some events, but can carry on with the next
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+    let stream = stream::once(async { status }).chain(events).map(Ok);
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+// A result of a job that has succeeded; 409 while it is still running
+fn finished(server: &Server, id: &str, result: impl FnOnce(&Job) -> String) -> Result<String, ApiError> {
+    let jobs = server.jobs.lock().unwrap();
+    let job = jobs.get(id).ok_or_else(|| not_found(id))?;
+    match job.status {
+        Status::Succeeded => Ok(result(job)),
+        Status::Failed => Err(ApiError(
+            StatusCode::CONFLICT,
+            format!("Job {} failed: {}", id, job.error.as_deref().unwrap_or_default()),
+        )),
+        Status::Queued | Status::Running => {
+            Err(ApiError(StatusCode::CONFLICT, format!("Job {} hasn't finished yet", id)))
+        }
+    }
+}
+
+fn not_found(id: &str) -> ApiError {
+    ApiError(StatusCode::NOT_FOUND, format!("No job {}", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(id: &str, finished: Option<Instant>) -> (String, Job) {
+        let job = Job {
+            id: id.to_string(),
+            status: if finished.is_some() { Status::Succeeded } else { Status::Running },
+            priority: Priority::Normal,
+            input: format!("{}.m4a", id),
+            created_at: String::new(),
+            finished_at: None,
+            finished,
+            title: None,
+            date: None,
+            language_code: None,
+            error: None,
+            summary: String::new(),
+            transcript: String::new(),
+            events: None,
+        };
+        (id.to_string(), job)
+    }
+
+    #[test]
+    fn evicts_expired_and_the_oldest_finished_jobs() {
+        let now = Instant::now() + Duration::from_secs(1000);
+        let ago = |secs| Some(now - Duration::from_secs(secs));
+        let mut jobs: HashMap<String, Job> =
+            [job("running", None), job("old", ago(500)), job("a", ago(30)), job("b", ago(20)), job("c", ago(10))]
+                .into_iter()
+                .collect();
+
+        evict(&mut jobs, now, Duration::from_secs(60), 10);
+        let mut ids: Vec<&str> = jobs.keys().map(String::as_str).collect();
+        ids.sort();
+        assert_eq!(ids, ["a", "b", "c", "running"]);
+
+        evict(&mut jobs, now, Duration::from_secs(60), 1);
+        let mut ids: Vec<&str> = jobs.keys().map(String::as_str).collect();
+        ids.sort();
+        assert_eq!(ids, ["c", "running"]);
+    }
+}
@@ -0,0 +1,332 @@
+use std::fs::File;
+use std::io::{Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Error};
+use config::Config;
+use serde::{Deserialize, Serialize};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::actions::ActionItem;
+use crate::template::slug;
+use crate::{output, progress};
+
+// Index of a session's runs, kept as session.json in the session's directory
+pub const INDEX: &str = "session.json";
+
+// Names the active session, in the sessions directory
+pub const ACTIVE: &str = "active";
+
+// A workspace grouping the runs for related recordings, e.g. a series of
+// planning meetings. Each run's summary, transcript and output files are kept
+// in a directory of their own under the session's directory.
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    pub name: String,
+    created_at: u64,
+    #[serde(default)]
+    runs: Vec<RunRecord>,
+    // Action items from the session's meetings, open until a later one
+    // reports them done
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    actions: Vec<ActionItem>,
+    #[serde(skip)]
+    dir: PathBuf,
+}
+
+// One run in a session
+#[derive(Serialize, Deserialize)]
+pub struct RunRecord {
+    pub title: String,
+    pub date: String,
+    pub inputs: Vec<String>,
+    // Notion or Confluence page written in the run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default)]
+    recorded_at: u64,
+    // The run's directory, relative to the session's, and the files in it
+    #[serde(default)]
+    dir: String,
+    #[serde(default)]
+    files: Vec<String>,
+}
+
+impl RunRecord {
+    pub fn new(title: &str, date: &str, inputs: Vec<String>) -> Self {
+        RunRecord {
+            title: title.to_string(),
+            date: date.to_string(),
+            inputs,
+            url: None,
+            recorded_at: now(),
+            dir: String::new(),
+            files: Vec::new(),
+        }
+    }
+
+    // When the run was added to the session, in seconds since the Unix epoch
+    pub fn recorded_at(&self) -> u64 {
+        self.recorded_at
+    }
+}
+
+pub fn sessions_dir(settings: &Config) -> PathBuf {
+    let dir = settings
+        .get_string("session.directory")
+        .unwrap_or_else(|_| "~/.distill/sessions".to_string());
+    PathBuf::from(shellexpand::tilde(&dir).to_string())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+// `distill session new`: create a session and make it the active one
+pub fn create(settings: &Config, name: &str) -> Result<(), Error> {
+    let id = slug(name);
+    if id.is_empty() {
+        bail!("\nA session name needs at least one letter or digit.");
+    }
+    let dir = sessions_dir(settings).join(&id);
+    if dir.join(INDEX).exists() {
+        bail!("\nA session named '{}' already exists. Switch to it with `distill session use {}`.", name, id);
+    }
+    std::fs::create_dir_all(&dir)
+        .with_context(|| format!("Error creating session directory: {}", dir.display()))?;
+    let session = Session {
+        name: name.trim().to_string(),
+        created_at: now(),
+        runs: Vec::new(),
+        actions: Vec::new(),
+        dir,
+    };
+    session.save()?;
+    activate(settings, Some(&id))?;
+    eprintln!(
+        "{}Started session '{}'. Runs are grouped under it until `distill session close`.",
+        progress::icon("🗂️"),
+        session.name
+    );
+    Ok(())
+}
+
+// A session by its name or directory name
+pub fn open(settings: &Config, name: &str) -> Result<Session, Error> {
+    let dir = sessions_dir(settings).join(slug(name));
+    if !dir.join(INDEX).exists() {
+        bail!("\nNo session named '{}'. See the sessions with `distill session list`.", name);
+    }
+    open_dir(&dir)
+}
+
+// The session set with `distill session new` or `use`, if any
+pub fn active(settings: &Config) -> Result<Option<Session>, Error> {
+    match std::fs::read_to_string(sessions_dir(settings).join(ACTIVE)) {
+        Ok(id) if !id.trim().is_empty() => open(settings, id.trim()).map(Some),
+        _ => Ok(None),
+    }
+}
+
+// Make the named session the active one, or none
+pub fn activate(settings: &Config, name: Option<&str>) -> Result<(), Error> {
+    let path = sessions_dir(settings).join(ACTIVE);
+    match name {
+        Some(name) => {
+            let session = open(settings, name)?;
+            output::write_atomic(&path, |file| Ok(file.write_all(session.id().as_bytes())?))?;
+        }
+        None => {
+            if let Err(err) = std::fs::remove_file(&path) {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    return Err(err.into());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// Every session, by directory name
+pub fn all(settings: &Config) -> Result<Vec<Session>, Error> {
+    let mut ids: Vec<String> = match std::fs::read_dir(sessions_dir(settings)) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().join(INDEX).exists())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect(),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => return Err(err.into()),
+    };
+    ids.sort();
+    ids.iter().map(|id| open(settings, id)).collect()
+}
+
+// `distill session list`
+pub fn list(settings: &Config) -> Result<(), Error> {
+    let active = active(settings).ok().flatten().map(|session| session.id().to_string());
+    let sessions = all(settings)?;
+    if sessions.is_empty() {
+        eprintln!("No sessions yet. Start one with `distill session new \"Name\"`.");
+        return Ok(());
+    }
+    for session in sessions {
+        let id = session.id();
+        let marker = if active.as_deref() == Some(id) { "*" } else { " " };
+        println!("{} {} ({}, {} run(s))", marker, session.name, id, session.runs.len());
+    }
+    Ok(())
+}
+
+impl Session {
+    // The session's directory name, also used for the {session} placeholder
+    pub fn id(&self) -> &str {
+        self.dir.file_name().and_then(|name| name.to_str()).unwrap_or_default()
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        let index = self.dir.join(INDEX);
+        output::write_atomic(&index, |file| {
+            serde_json::to_writer_pretty(file, self).map_err(|e| anyhow::anyhow!("Error writing session: {}", e))
+        })
+    }
+
+    // Keep a run's summary and transcript, and copies of the files it wrote,
+    // in a new directory of the session and add it to the index
+    pub fn add_run(&self, mut record: RunRecord, summary: &str, transcript: &str, written: &[String]) -> Result<PathBuf, Error> {
+        // Read again so runs finished in other processes meanwhile are kept
+        let mut session = open_dir(&self.dir)?;
+
+        let base = format!("{}-{}", record.date, slug(&record.title));
+        let mut name = base.clone();
+        let mut counter = 2;
+        while session.dir.join(&name).exists() {
+            name = format!("{}-{}", base, counter);
+            counter += 1;
+        }
+        let run_dir = session.dir.join(&name);
+        std::fs::create_dir_all(&run_dir)
+            .with_context(|| format!("Error creating session directory: {}", run_dir.display()))?;
+
+        let mut files = vec!["summary.md".to_string()];
+        output::write_atomic(run_dir.join("summary.md"), |file| {
+            write!(file, "# {}\n\n{}", record.title, summary)?;
+            if !transcript.is_empty() {
+                write!(file, "\n\n# Transcription\n\n{}", transcript)?;
+            }
+            Ok(())
+        })?;
+        for path in written {
+            let Some(file_name) = Path::new(path).file_name() else {
+                continue;
+            };
+            std::fs::copy(path, run_dir.join(file_name))
+                .with_context(|| format!("Error copying {} into the session", path))?;
+            let file_name = file_name.to_string_lossy().into_owned();
+            if !files.contains(&file_name) {
+                files.push(file_name);
+            }
+        }
+
+        record.dir = name;
+        record.files = files;
+        session.runs.push(record);
+        session.save()?;
+        Ok(run_dir)
+    }
+
+    // Bundle the session's directory, with every run's files and the index,
+    // into a zip archive
+    pub fn export(&self, path: &Path) -> Result<(), Error> {
+        let root = self.id().to_string();
+        output::write_atomic(path, |file| {
+            let mut zip = ZipWriter::new(file);
+            let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+            add_dir(&mut zip, &self.dir, &root, options)?;
+            zip.finish()?;
+            Ok(())
+        })
+    }
+
+    pub fn run_count(&self) -> usize {
+        self.runs.len()
+    }
+
+    pub fn actions(&self) -> &[ActionItem] {
+        &self.actions
+    }
+
+    // Change the session's action items and save them. Like add_run, the
+    // index is read again first.
+    pub fn update_actions(&self, update: impl FnOnce(&mut Vec<ActionItem>)) -> Result<Vec<ActionItem>, Error> {
+        let mut session = open_dir(&self.dir)?;
+        update(&mut session.actions);
+        session.save()?;
+        Ok(session.actions)
+    }
+
+    // The runs in the order they were added
+    pub fn runs(&self) -> &[RunRecord] {
+        &self.runs
+    }
+
+    // A run's summary, from its summary.md without the title and transcript
+    pub fn summary(&self, run: &RunRecord) -> Result<String, Error> {
+        let path = self.dir.join(&run.dir).join("summary.md");
+        let text = std::fs::read_to_string(&path).with_context(|| format!("Error reading {}", path.display()))?;
+        Ok(summary_section(&text, &run.title).to_string())
+    }
+}
+
+// add_run writes "# Title", the summary and then "# Transcription" and the transcript
+fn summary_section<'a>(text: &'a str, title: &str) -> &'a str {
+    let text = text.strip_prefix(&format!("# {}\n\n", title)).unwrap_or(text);
+    let summary = text.split_once("\n\n# Transcription\n\n").map_or(text, |(summary, _)| summary);
+    summary.trim()
+}
+
+fn open_dir(dir: &Path) -> Result<Session, Error> {
+    let index = dir.join(INDEX);
+    let text = std::fs::read_to_string(&index)
+        .with_context(|| format!("Error reading {}", index.display()))?;
+    let mut session: Session = serde_json::from_str(&text)
+        .with_context(|| format!("Error reading {}", index.display()))?;
+    session.dir = dir.to_path_buf();
+    Ok(session)
+}
+
+// Add `dir` and everything in it to `zip` under `prefix`
+pub fn add_dir<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    dir: &Path,
+    prefix: &str,
+    options: SimpleFileOptions,
+) -> Result<(), Error> {
+    let mut entries: Vec<_> = std::fs::read_dir(dir)?.filter_map(|entry| entry.ok()).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    zip.add_directory(format!("{}/", prefix), options)?;
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        // Partial files of a write in progress
+        if name.ends_with(".part") {
+            continue;
+        }
+        let path = entry.path();
+        let archived = format!("{}/{}", prefix, name);
+        if path.is_dir() {
+            add_dir(zip, &path, &archived, options)?;
+        } else {
+            zip.start_file(archived, options)?;
+            std::io::copy(&mut File::open(&path)?, zip)?;
+        }
+    }
+    Ok(())
+}
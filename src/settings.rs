@@ -0,0 +1,73 @@
+use config::{Config, ConfigError, Environment, File, Value};
+
+// Profile keys that stand in for a setting. Of the others, language_code,
+// prompt and prompt_file stand in for command-line options, and quota holds
+// the profile's own usage quotas.
+const PROFILE_SETTINGS: [(&str, &str); 4] = [
+    ("model_id", "model.model_id"),
+    ("slack_channel", "slack.channel"),
+    ("output_types", "output.types"),
+    ("transcriber", "transcript.transcriber"),
+];
+
+pub const PROFILE_KEYS: [&str; 8] = [
+    "language_code",
+    "model_id",
+    "prompt",
+    "prompt_file",
+    "output_types",
+    "slack_channel",
+    "transcriber",
+    "quota",
+];
+
+// Where the settings are loaded from besides config.toml and the environment,
+// kept so they can be loaded again the same way
+#[derive(Clone, Default)]
+pub struct Sources {
+    // The [profile.NAME] section picked with --profile
+    pub profile: Option<String>,
+    // Values read from SSM Parameter Store with --config-from-ssm, by setting,
+    // e.g. "aws.s3_bucket_name"
+    pub parameters: Vec<(String, Value)>,
+    // Values given on the command line, e.g. --model
+    pub overrides: Vec<(&'static str, String)>,
+}
+
+// Load settings from config.toml in the execution directory, then let
+// environment variables override individual keys. Variables are prefixed with
+// `DISTILL_` and use `__` to separate sections, e.g. `DISTILL_AWS__S3_BUCKET_NAME`
+// or `DISTILL_MODEL__MODEL_ID`. The file is optional so containers can be
+// configured purely through the environment.
+// SSM parameters take precedence over config.toml, and the environment over
+// them, so one value can still be changed for a single container. The
+// settings of the profile take precedence over the rest of config.toml and
+// the environment, and the command-line overrides over everything else.
+pub fn load_settings(sources: &Sources) -> Result<Config, ConfigError> {
+    let mut parameters = Config::builder();
+    for (key, value) in &sources.parameters {
+        parameters = parameters.set_override(key.as_str(), value.clone())?;
+    }
+    let base = Config::builder()
+        .add_source(File::with_name("./config.toml").required(false))
+        .add_source(parameters.build()?)
+        .add_source(
+            Environment::with_prefix("DISTILL")
+                .prefix_separator("_")
+                .separator("__"),
+        )
+        .build()?;
+    let mut builder = Config::builder().add_source(base.clone());
+    // A missing profile is reported by main, which can name it
+    if let Some(Ok(profile)) = sources.profile.as_ref().map(|name| base.get_table(&format!("profile.{}", name))) {
+        for (key, setting) in PROFILE_SETTINGS {
+            if let Some(value) = profile.get(key) {
+                builder = builder.set_override(setting, value.clone())?;
+            }
+        }
+    }
+    for (key, value) in &sources.overrides {
+        builder = builder.set_override(*key, value.as_str())?;
+    }
+    builder.build()
+}
@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Error};
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use crate::notion::list_item;
+
+const POST_MESSAGE_URL: &str = "https://slack.com/api/chat.postMessage";
+const GET_UPLOAD_URL: &str = "https://slack.com/api/files.getUploadURLExternal";
+const COMPLETE_UPLOAD_URL: &str = "https://slack.com/api/files.completeUploadExternal";
+
+// Block Kit limits: header text, section text and blocks per message
+const MAX_HEADER_CHARS: usize = 150;
+const MAX_SECTION_CHARS: usize = 3000;
+const MAX_BLOCKS: usize = 50;
+
+// What the channel message shows
+pub struct Message<'a> {
+    pub title: &'a str,
+    pub date: &'a str,
+    pub summary: &'a str,
+    pub action_items: &'a str,
+    pub other: &'a str,
+}
+
+// Group action item lines by owner. An item belongs to every owner whose name
+// appears in it (case-insensitive), keyed by the owner's Slack member ID.
+pub fn route_action_items(
+    action_items: &str,
+    owners: &HashMap<String, String>,
+) -> HashMap<String, Vec<String>> {
+    let mut routed: HashMap<String, Vec<String>> = HashMap::new();
+
+    for line in action_items.lines().filter(|l| !l.trim().is_empty()) {
+        let lowercase_line = line.to_lowercase();
+        for (name, slack_id) in owners {
+            if lowercase_line.contains(&name.to_lowercase()) {
+                routed
+                    .entry(slack_id.clone())
+                    .or_default()
+                    .push(line.trim().to_string());
+            }
+        }
+    }
+
+    routed
+}
+
+// Workflow Builder webhooks take named variables rather than a message
+pub fn is_workflow_webhook(endpoint: &str) -> bool {
+    endpoint.contains("/workflows/") || endpoint.contains("/triggers/")
+}
+
+// Slack mrkdwn treats these three characters as markup
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max_chars - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+// The lines of `text`, escaped and joined into chunks of at most
+// MAX_SECTION_CHARS, after an optional first line
+fn chunks(first: &str, text: &str) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut chunk = first.to_string();
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let line = truncate(&escape(line), MAX_SECTION_CHARS);
+        if chunk.chars().count() + line.chars().count() + 1 > MAX_SECTION_CHARS {
+            chunks.push(std::mem::take(&mut chunk));
+        }
+        if !chunk.is_empty() {
+            chunk.push('\n');
+        }
+        chunk.push_str(&line);
+    }
+    chunks.push(chunk);
+    chunks.retain(|chunk| !chunk.is_empty());
+    chunks
+}
+
+// Sections for `text` under a bold heading, split on line breaks to stay
+// within Slack's limit per section
+fn sections(heading: &str, text: &str) -> Vec<Value> {
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+    let heading = if heading.is_empty() { String::new() } else { format!("*{}*", heading) };
+    chunks(&heading, text)
+        .into_iter()
+        .map(|chunk| json!({ "type": "section", "text": { "type": "mrkdwn", "text": chunk } }))
+        .collect()
+}
+
+// The summary as a Block Kit message: the meeting title as a header, then the
+// summary, the action items as a bulleted list and anything else the model
+// wrote. `text` is the fallback shown in notifications.
+pub fn summary_blocks(message: &Message) -> Value {
+    let mut blocks = vec![
+        json!({
+            "type": "header",
+            "text": { "type": "plain_text", "text": truncate(message.title, MAX_HEADER_CHARS) },
+        }),
+        json!({
+            "type": "context",
+            "elements": [{ "type": "mrkdwn", "text": escape(message.date) }],
+        }),
+    ];
+    let bullets: Vec<String> = message
+        .action_items
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| format!("• {}", list_item(line).unwrap_or(line)))
+        .collect();
+    let parts = [
+        sections("Summary", message.summary),
+        sections("Action items", &bullets.join("\n")),
+        sections("", message.other),
+    ];
+    for (index, part) in parts.into_iter().filter(|part| !part.is_empty()).enumerate() {
+        if index > 0 {
+            blocks.push(json!({ "type": "divider" }));
+        }
+        blocks.extend(part);
+    }
+    blocks.truncate(MAX_BLOCKS);
+
+    let fallback = if message.summary.trim().is_empty() { message.other } else { message.summary };
+
+    json!({
+        "text": truncate(&format!("{}: {}", message.title, fallback), MAX_SECTION_CHARS),
+        "blocks": blocks,
+    })
+}
+
+// Post the summary to an incoming webhook
+pub async fn post_webhook(client: &Client, endpoint: &str, payload: &Value) -> Result<(), Error> {
+    let response = client
+        .post(endpoint)
+        .header("Content-Type", "application/json")
+        .json(payload)
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        bail!("{}", response.status());
+    }
+
+    Ok(())
+}
+
+// Post a message with a bot token. `channel` may be a channel ID or a member ID,
+// in which case Slack delivers it as a DM from the bot.
+pub async fn post_message(
+    client: &Client,
+    bot_token: &str,
+    channel: &str,
+    text: &str,
+) -> Result<(), Error> {
+    post_payload(client, bot_token, channel, &json!({ "text": text }), None)
+        .await
+        .map(|_| ())
+}
+
+// Post a message payload, e.g. from summary_blocks, with a bot token, as a
+// reply in `thread_ts` if given. Returns the message's timestamp, which
+// identifies it for replies.
+pub async fn post_payload(
+    client: &Client,
+    bot_token: &str,
+    channel: &str,
+    payload: &Value,
+    thread_ts: Option<&str>,
+) -> Result<String, Error> {
+    let mut body = payload.clone();
+    body["channel"] = json!(channel);
+    if let Some(thread_ts) = thread_ts {
+        body["thread_ts"] = json!(thread_ts);
+    }
+    let response: Value = client
+        .post(POST_MESSAGE_URL)
+        .bearer_auth(bot_token)
+        .json(&body)
+        .send()
+        .await?
+        .json()
+        .await?;
+    check(&response)?;
+    Ok(response["ts"].as_str().unwrap_or_default().to_string())
+}
+
+// Post long text, e.g. a transcript, as replies in a thread, one message per
+// few thousand characters, in order
+pub async fn post_thread_text(
+    client: &Client,
+    bot_token: &str,
+    channel: &str,
+    thread_ts: &str,
+    text: &str,
+) -> Result<(), Error> {
+    for chunk in chunks("", text) {
+        post_payload(client, bot_token, channel, &json!({ "text": chunk }), Some(thread_ts)).await?;
+    }
+    Ok(())
+}
+
+// The Web API reports failures in the body with a 200 status
+fn check(response: &Value) -> Result<(), Error> {
+    if !response["ok"].as_bool().unwrap_or(false) {
+        bail!(
+            "Slack API error: {}",
+            response["error"].as_str().unwrap_or("unknown error")
+        );
+    }
+    Ok(())
+}
+
+// Share `content` as a file in `channel`, or in `thread_ts` within it, with a
+// bot token (files:write scope). Slack uploads take three steps: reserve an
+// upload URL, send the bytes, then complete the upload into the channel.
+pub async fn upload_file(
+    client: &Client,
+    bot_token: &str,
+    channel: &str,
+    thread_ts: Option<&str>,
+    filename: &str,
+    title: &str,
+    content: &str,
+) -> Result<(), Error> {
+    let reserved: Value = client
+        .post(GET_UPLOAD_URL)
+        .bearer_auth(bot_token)
+        .form(&[("filename", filename), ("length", &content.len().to_string())])
+        .send()
+        .await?
+        .json()
+        .await?;
+    check(&reserved)?;
+    let (Some(upload_url), Some(file_id)) = (reserved["upload_url"].as_str(), reserved["file_id"].as_str()) else {
+        return Err(anyhow!("Slack did not return an upload URL"));
+    };
+
+    let response = client.post(upload_url).body(content.to_string()).send().await?;
+    if !response.status().is_success() {
+        bail!("Slack file upload failed: {}", response.status());
+    }
+
+    let mut complete = json!({ "files": [{ "id": file_id, "title": title }], "channel_id": channel });
+    if let Some(thread_ts) = thread_ts {
+        complete["thread_ts"] = json!(thread_ts);
+    }
+    let completed: Value = client
+        .post(COMPLETE_UPLOAD_URL)
+        .bearer_auth(bot_token)
+        .json(&complete)
+        .send()
+        .await?
+        .json()
+        .await?;
+    check(&completed)
+}
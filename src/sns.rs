@@ -0,0 +1,224 @@
+use anyhow::{bail, Error};
+use aws_config::{Region, SdkConfig};
+use config::Config;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use tracing::{debug, instrument};
+
+use crate::aws::signed_request;
+use crate::progress::Progress;
+use crate::retry::{self, retry, Transient};
+
+// Email subjects longer than this are rejected by SNS
+const MAX_SUBJECT_CHARS: usize = 100;
+
+// Messages are limited to 256 KB, the whole JSON structure included
+const MAX_MESSAGE_BYTES: usize = 200 * 1024;
+
+// An SNS topic that `-o sns` publishes to, from sns.topic_arn, so the email
+// lists and SMS numbers already subscribed to it hear about each meeting.
+// Email and other subscribers get the summary, or only a short notice with
+// sns.message = "notice"; SMS subscribers always get the notice.
+pub struct Topic {
+    pub arn: String,
+    pub notice_only: bool,
+}
+
+impl Topic {
+    // None unless sns.topic_arn is set
+    pub fn from_settings(settings: &Config) -> Result<Option<Self>, Error> {
+        let Some(arn) = settings.get_string("sns.topic_arn").ok().filter(|arn| !arn.trim().is_empty()) else {
+            return Ok(None);
+        };
+        let notice_only = match settings.get_string("sns.message").as_deref() {
+            Ok("notice") => true,
+            Ok("summary") | Err(_) => false,
+            Ok(other) => bail!("\nUnknown sns.message '{}'. Use summary or notice.", other),
+        };
+        Ok(Some(Topic {
+            arn: arn.trim().to_string(),
+            notice_only,
+        }))
+    }
+}
+
+// What is published about one meeting
+pub struct Message<'a> {
+    pub title: &'a str,
+    pub date: &'a str,
+    // The summary with any appendices, or empty for a transcript alone
+    pub summary: &'a str,
+    // Notion or Confluence page written in the same run
+    pub link: Option<&'a str>,
+}
+
+impl Message<'_> {
+    fn subject(&self) -> String {
+        // Subjects must be one line of printable ASCII
+        let subject: String = format!("Meeting notes: {} ({})", self.title, self.date)
+            .chars()
+            .map(|c| if c.is_ascii() && !c.is_ascii_control() { c } else { '?' })
+            .collect();
+        match subject.char_indices().nth(MAX_SUBJECT_CHARS - 3) {
+            Some((end, _)) => format!("{}...", &subject[..end]),
+            None => subject,
+        }
+    }
+
+    fn notice(&self) -> String {
+        let mut notice = format!("Meeting notes for {} ({}) are ready.", self.title, self.date);
+        if let Some(link) = self.link {
+            notice.push_str(&format!("\n{}", link));
+        }
+        notice
+    }
+
+    fn full_text(&self) -> String {
+        if self.summary.trim().is_empty() {
+            return self.notice();
+        }
+        let mut text = format!("{} ({})\n\n{}", self.title, self.date, self.summary.trim());
+        if let Some(link) = self.link {
+            text.push_str(&format!("\n\n{}", link));
+        }
+        if text.len() > MAX_MESSAGE_BYTES {
+            let mut end = MAX_MESSAGE_BYTES;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            text.truncate(end);
+            text.push_str("\n\n[Summary cut short; the full notes are in the meeting's other outputs.]");
+        }
+        text
+    }
+}
+
+// Publish `message` to the topic, in the topic's own region. Returns the
+// SNS message ID.
+#[instrument(name = "publish_sns", skip_all, fields(topic = %topic.arn))]
+pub async fn publish(
+    settings: &Config,
+    sdk_config: &SdkConfig,
+    topic: &Topic,
+    message: &Message<'_>,
+    spinner: &mut Progress,
+) -> Result<String, Error> {
+    // arn:aws:sns:REGION:ACCOUNT:NAME
+    let region = match topic.arn.split(':').nth(3).filter(|region| !region.is_empty()) {
+        Some(region) => region.to_string(),
+        None => bail!("\n{} is not an SNS topic ARN. Check sns.topic_arn in config.toml.", topic.arn),
+    };
+    let sdk_config = sdk_config.to_builder().region(Region::new(region.clone())).build();
+
+    let default = if topic.notice_only { message.notice() } else { message.full_text() };
+    let structure = json!({ "default": default, "sms": message.notice() });
+    let body: String = form_urlencoded::Serializer::new(String::new())
+        .append_pair("Action", "Publish")
+        .append_pair("Version", "2010-03-31")
+        .append_pair("TopicArn", &topic.arn)
+        .append_pair("Subject", &message.subject())
+        .append_pair("MessageStructure", "json")
+        .append_pair("Message", &structure.to_string())
+        .finish();
+
+    let policy = retry::Policy::from_settings(settings, "sns")?;
+    let url = format!("https://sns.{}.amazonaws.com/", region);
+    let message_id = retry(&policy, spinner, "Amazon SNS", || {
+        send(settings, &sdk_config, &url, &body)
+    })
+    .await?;
+    debug!(message_id, "message published");
+    Ok(message_id)
+}
+
+// Why one attempt to publish failed
+#[derive(Debug)]
+enum PublishError {
+    // The request couldn't be signed, e.g. without credentials
+    Sign(Error),
+    // No reply came back, or only part of one
+    Send(reqwest::Error),
+    Rejected {
+        status: StatusCode,
+        code: Option<String>,
+        message: String,
+    },
+}
+
+impl std::fmt::Display for PublishError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PublishError::Sign(err) => write!(f, "{}", err),
+            PublishError::Send(err) => write!(f, "{}", err),
+            PublishError::Rejected { status, message, .. } => {
+                write!(f, "\nSNS rejected the message: {} {}", status, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PublishError {}
+
+impl Transient for PublishError {
+    fn transient_reason(&self) -> Option<String> {
+        match self {
+            PublishError::Sign(_) => None,
+            PublishError::Send(err) => err.transient_reason(),
+            PublishError::Rejected { status, code, .. } => retry::transient_status(code.as_deref(), status.as_u16()),
+        }
+    }
+}
+
+// One signed Publish request. It is signed again for each attempt, as the
+// signature is only valid for a few minutes.
+async fn send(settings: &Config, sdk_config: &SdkConfig, url: &str, body: &str) -> Result<String, PublishError> {
+    let request = signed_request(
+        settings,
+        sdk_config,
+        "POST",
+        "sns",
+        url,
+        &[
+            ("content-type", "application/x-www-form-urlencoded; charset=utf-8"),
+            ("accept", "application/json"),
+        ],
+        body.as_bytes().to_vec(),
+    )
+    .await
+    .map_err(PublishError::Sign)?;
+
+    let response = request.send().await.map_err(PublishError::Send)?;
+    let status = response.status();
+    let text = response.text().await.map_err(PublishError::Send)?;
+    let reply: Value = serde_json::from_str(&text).unwrap_or(Value::Null);
+    if !status.is_success() {
+        return Err(PublishError::Rejected {
+            status,
+            code: reply["Error"]["Code"].as_str().map(str::to_string),
+            message: reply["Error"]["Message"].as_str().map_or_else(|| text.trim().to_string(), str::to_string),
+        });
+    }
+    Ok(reply["PublishResponse"]["PublishResult"]["MessageId"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message<'a>(title: &'a str) -> Message<'a> {
+        Message { title, date: "2024-05-14", summary: "", link: None }
+    }
+
+    #[test]
+    fn keeps_subjects_within_the_limit() {
+        assert_eq!(message("Weekly sync").subject(), "Meeting notes: Weekly sync (2024-05-14)");
+        let long = message(&"x".repeat(200)).subject();
+        assert_eq!(long.len(), MAX_SUBJECT_CHARS);
+        assert!(long.ends_with("..."));
+        // Anything but one line of printable ASCII is replaced
+        assert_eq!(message("Café\nsync").subject(), "Meeting notes: Caf??sync (2024-05-14)");
+    }
+}
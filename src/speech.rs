@@ -0,0 +1,73 @@
+use anyhow::{bail, Error};
+use config::Config;
+
+use crate::transcribe::Transcript;
+
+// Catches a recording with nothing to summarize, e.g. a song, a silent file or
+// background noise selected by mistake, before any model call is paid for
+pub struct SpeechCheck {
+    // Fewer words than this means no speech at all
+    pub min_words: usize,
+    // Share of the recording's length that must be speech, from 0 to 1
+    pub min_share: f64,
+}
+
+impl SpeechCheck {
+    // None when speech.check is turned off
+    pub fn from_settings(settings: &Config) -> Result<Option<Self>, Error> {
+        if !settings.get_bool("speech.check").unwrap_or(true) {
+            return Ok(None);
+        }
+        let min_words = settings.get_int("speech.min_words").unwrap_or(5).max(0) as usize;
+        let min_share = settings.get_float("speech.min_share").unwrap_or(0.1);
+        if !(0.0..=1.0).contains(&min_share) {
+            bail!("\nspeech.min_share must be between 0 and 1, e.g. 0.1.");
+        }
+        Ok(Some(SpeechCheck { min_words, min_share }))
+    }
+
+    // Why `transcript` doesn't look like speech, or None when it does. The
+    // share of speech is only checked when the recording's length in seconds
+    // is known and the transcript has timings, i.e. it came from Transcribe.
+    pub fn no_speech(&self, transcript: &Transcript, duration: Option<f64>) -> Option<String> {
+        // Speaker labels and annotations such as [music] or [pause 4s] aren't speech
+        let words: usize = transcript
+            .segments
+            .iter()
+            .map(|segment| {
+                let mut in_annotation = false;
+                segment
+                    .text
+                    .split_whitespace()
+                    .filter(|word| {
+                        let annotated = in_annotation || word.starts_with('[');
+                        in_annotation = annotated && !word.ends_with(']');
+                        !annotated
+                    })
+                    .count()
+            })
+            .sum();
+        if words < self.min_words {
+            return Some(format!("the transcript has only {} word(s)", words));
+        }
+
+        let duration = duration.filter(|duration| *duration > 0.0)?;
+        let speech: f64 = transcript
+            .segments
+            .iter()
+            .map(|segment| (segment.end_time - segment.start_time).max(0.0))
+            .sum();
+        if speech == 0.0 {
+            return None;
+        }
+        let share = speech / duration;
+        (share < self.min_share).then(|| {
+            format!(
+                "speech makes up {:.0}% of the recording ({:.0}s of {:.0}s), so it is probably music or noise",
+                share * 100.0,
+                speech,
+                duration
+            )
+        })
+    }
+}
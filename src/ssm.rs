@@ -0,0 +1,112 @@
+use anyhow::{bail, Context, Error};
+use aws_config::SdkConfig;
+use config::{Config, Value};
+use serde_json::{json, Value as Json};
+use tracing::{debug, instrument};
+
+use crate::aws::signed_request;
+
+// Read every parameter under `path` in SSM Parameter Store, for
+// --config-from-ssm. Each parameter is a setting named by its path below
+// `path`, e.g. /distill/prod/aws/s3_bucket_name is aws.s3_bucket_name.
+// SecureStrings are decrypted, and a StringList becomes a list, e.g. for
+// output.types.
+#[instrument(skip_all, fields(path = %path))]
+pub async fn parameters(settings: &Config, sdk_config: &SdkConfig, path: &str) -> Result<Vec<(String, Value)>, Error> {
+    let region = sdk_config.region().map_or("us-east-1".to_string(), |r| r.to_string());
+    let path = format!("/{}", path.trim_matches('/'));
+    let mut parameters = Vec::new();
+    let mut token: Option<String> = None;
+    loop {
+        let mut body = json!({
+            "Path": path,
+            "Recursive": true,
+            "WithDecryption": true,
+        });
+        if let Some(token) = &token {
+            body["NextToken"] = json!(token);
+        }
+        let request = signed_request(
+            settings,
+            sdk_config,
+            "POST",
+            "ssm",
+            &format!("https://ssm.{}.amazonaws.com/", region),
+            &[
+                ("content-type", "application/x-amz-json-1.1"),
+                ("x-amz-target", "AmazonSSM.GetParametersByPath"),
+            ],
+            body.to_string().into_bytes(),
+        )
+        .await?;
+
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("Error reading parameters under {} from SSM", path))?;
+        let status = response.status();
+        let body: Json = response.json().await.unwrap_or(Json::Null);
+        if !status.is_success() {
+            bail!(
+                "\nError reading parameters under {} from SSM: {} {}",
+                path,
+                status,
+                body["message"].as_str().or_else(|| body["Message"].as_str()).unwrap_or_default()
+            );
+        }
+        for parameter in body["Parameters"].as_array().into_iter().flatten() {
+            let name = parameter["Name"].as_str().unwrap_or_default();
+            let Some(key) = setting_key(&path, name) else {
+                continue;
+            };
+            let value = parameter["Value"].as_str().unwrap_or_default();
+            parameters.push((key, setting_value(parameter["Type"].as_str().unwrap_or_default(), value)));
+        }
+        token = body["NextToken"].as_str().filter(|token| !token.is_empty()).map(str::to_string);
+        if token.is_none() {
+            break;
+        }
+    }
+    debug!(count = parameters.len(), "read parameters");
+    Ok(parameters)
+}
+
+// "/distill/prod/aws/s3_bucket_name" under "/distill/prod" is "aws.s3_bucket_name"
+fn setting_key(path: &str, name: &str) -> Option<String> {
+    let relative = name.strip_prefix(path.trim_end_matches('/'))?.trim_start_matches('/');
+    if relative.is_empty() {
+        return None;
+    }
+    Some(relative.replace('/', "."))
+}
+
+fn setting_value(kind: &str, value: &str) -> Value {
+    match kind {
+        "StringList" => Value::from(value.split(',').map(|item| item.trim().to_string()).collect::<Vec<_>>()),
+        _ => Value::from(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_settings_after_the_path_below_the_prefix() {
+        assert_eq!(setting_key("/distill/prod", "/distill/prod/aws/s3_bucket_name").as_deref(), Some("aws.s3_bucket_name"));
+        assert_eq!(
+            setting_key("/distill/prod", "/distill/prod/profile/zoom/model_id").as_deref(),
+            Some("profile.zoom.model_id")
+        );
+        assert_eq!(setting_key("/", "/slack/channel").as_deref(), Some("slack.channel"));
+        assert_eq!(setting_key("/distill/prod", "/distill/prod"), None);
+    }
+
+    #[test]
+    fn splits_string_lists() {
+        let value = setting_value("StringList", "markdown, slack");
+        let items: Vec<String> = value.into_array().unwrap().into_iter().map(|v| v.into_string().unwrap()).collect();
+        assert_eq!(items, ["markdown", "slack"]);
+        assert_eq!(setting_value("SecureString", "a,b").into_string().unwrap(), "a,b");
+    }
+}
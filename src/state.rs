@@ -0,0 +1,154 @@
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{bail, Context, Error};
+use config::Config;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{output, progress, queue, session, voiceprint};
+
+// The local state a run leaves behind, as laid out in a state archive: the
+// offline queue, the sessions and the enrolled voices
+const QUEUE: &str = "queue";
+const SESSIONS: &str = "sessions";
+const SPEAKERS: &str = "speakers.json";
+
+// `distill export-state`: bundle the offline queue, sessions and enrolled
+// voices into a zip archive, to carry them to another machine
+pub fn export(settings: &Config, path: &Path) -> Result<(), Error> {
+    let queue_dir = queue::queue_dir(settings);
+    let sessions_dir = session::sessions_dir(settings);
+    let speakers = voiceprint::profiles_path(settings);
+
+    let queued = count_entries(&queue_dir, |path| path.extension().is_some_and(|ext| ext == "json"));
+    let sessions = count_entries(&sessions_dir, |path| path.join(session::INDEX).exists());
+    output::write_atomic(path, |file| {
+        let mut zip = ZipWriter::new(file);
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        if queue_dir.is_dir() {
+            session::add_dir(&mut zip, &queue_dir, QUEUE, options)?;
+        }
+        if sessions_dir.is_dir() {
+            session::add_dir(&mut zip, &sessions_dir, SESSIONS, options)?;
+        }
+        if speakers.is_file() {
+            zip.start_file(SPEAKERS, options)?;
+            std::io::copy(&mut File::open(&speakers)?, &mut zip)?;
+        }
+        zip.finish()?;
+        Ok(())
+    })?;
+
+    eprintln!(
+        "{}Exported {} queued run(s), {} session(s){} to {}",
+        progress::icon("📦"),
+        queued,
+        sessions,
+        if speakers.is_file() { " and the enrolled voices" } else { "" },
+        path.display()
+    );
+    Ok(())
+}
+
+// `distill import-state`: add the contents of an archive from `export-state`
+// to this machine's state. Queued runs and enrolled voices are merged; a
+// session that already exists here is kept as it is unless `overwrite` is set.
+pub fn import(settings: &Config, path: &Path, overwrite: bool) -> Result<(), Error> {
+    let file = File::open(path).with_context(|| format!("Error reading {}", path.display()))?;
+    let mut archive = ZipArchive::new(file)
+        .with_context(|| format!("{} is not an archive from `distill export-state`", path.display()))?;
+
+    let queue_dir = queue::queue_dir(settings);
+    let sessions_dir = session::sessions_dir(settings);
+    let mut queued = 0;
+    let mut samples = 0;
+    let mut imported_sessions = BTreeSet::new();
+    let mut kept_sessions = BTreeSet::new();
+    let mut recognized = false;
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        // Entries that would land outside the state directories are ignored
+        let Some(name) = entry.enclosed_name() else {
+            continue;
+        };
+        if entry.is_dir() {
+            continue;
+        }
+
+        if name == Path::new(SPEAKERS) {
+            recognized = true;
+            let mut exported = Vec::new();
+            entry.read_to_end(&mut exported)?;
+            samples += voiceprint::merge_profiles(settings, &exported)?;
+        } else if let Ok(relative) = name.strip_prefix(QUEUE) {
+            recognized = true;
+            // Queue entries are uniquely named, so one that exists here is the same run
+            let destination = queue_dir.join(relative);
+            if !destination.exists() {
+                write_entry(&mut entry, &destination)?;
+                queued += 1;
+            }
+        } else if let Ok(relative) = name.strip_prefix(SESSIONS) {
+            recognized = true;
+            // Files directly under sessions/ only say which session is active,
+            // which is up to each machine
+            let mut components = relative.components();
+            let (Some(Component::Normal(id)), Some(_)) = (components.next(), components.next()) else {
+                continue;
+            };
+            let id = id.to_string_lossy().into_owned();
+            if kept_sessions.contains(&id) {
+                continue;
+            }
+            if !imported_sessions.contains(&id) && sessions_dir.join(&id).join(session::INDEX).exists() && !overwrite {
+                kept_sessions.insert(id);
+                continue;
+            }
+            write_entry(&mut entry, &sessions_dir.join(relative))?;
+            imported_sessions.insert(id);
+        }
+    }
+    if !recognized {
+        bail!("\n{} is not an archive from `distill export-state`.", path.display());
+    }
+
+    eprintln!(
+        "{}Imported {} queued run(s), {} session(s) and {} voice sample(s)",
+        progress::icon("📥"),
+        queued,
+        imported_sessions.len(),
+        samples
+    );
+    if !kept_sessions.is_empty() {
+        eprintln!(
+            "Kept the local copy of {} session(s) that already exist here: {}. Pass --overwrite to replace them.",
+            kept_sessions.len(),
+            kept_sessions.into_iter().collect::<Vec<_>>().join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn count_entries(dir: &Path, matches: impl Fn(&PathBuf) -> bool) -> usize {
+    std::fs::read_dir(dir).map_or(0, |entries| {
+        entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| matches(path))
+            .count()
+    })
+}
+
+fn write_entry(entry: &mut impl Read, destination: &Path) -> Result<(), Error> {
+    if let Some(dir) = destination.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Error creating directory: {}", dir.display()))?;
+    }
+    output::write_atomic(destination, |file| {
+        std::io::copy(entry, file)?;
+        Ok(())
+    })
+}
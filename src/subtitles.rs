@@ -0,0 +1,114 @@
+use std::io::{self, Write};
+
+use anyhow::{bail, Error};
+
+use crate::transcribe::Transcript;
+
+// Keep cues short enough to read: at most two lines' worth of text, and a few seconds
+const MAX_CUE_CHARS: usize = 84;
+const MAX_CUE_SECONDS: f64 = 6.0;
+
+pub struct Cue {
+    start_time: f64,
+    end_time: f64,
+    speaker: String,
+    text: String,
+}
+
+// Cues are written one at a time, so a long transcript is never held as a
+// second, formatted copy in memory
+pub fn write_srt<W: Write>(out: &mut W, cues: &[Cue]) -> io::Result<()> {
+    for (i, cue) in cues.iter().enumerate() {
+        writeln!(
+            out,
+            "{}\n{} --> {}",
+            i + 1,
+            format_cue_time(cue.start_time, ','),
+            format_cue_time(cue.end_time, ',')
+        )?;
+        if cue.speaker.is_empty() {
+            writeln!(out, "{}\n", cue.text)?;
+        } else {
+            writeln!(out, "{}: {}\n", cue.speaker, cue.text)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn write_vtt<W: Write>(out: &mut W, cues: &[Cue]) -> io::Result<()> {
+    writeln!(out, "WEBVTT\n")?;
+    for cue in cues {
+        writeln!(
+            out,
+            "{} --> {}",
+            format_cue_time(cue.start_time, '.'),
+            format_cue_time(cue.end_time, '.')
+        )?;
+        if cue.speaker.is_empty() {
+            writeln!(out, "{}\n", cue.text)?;
+        } else {
+            writeln!(out, "<v {}>{}\n", cue.speaker, cue.text)?;
+        }
+    }
+    Ok(())
+}
+
+// Split each speaker turn into cues at word boundaries. Turns without word
+// timings become a single cue.
+pub fn cues(transcript: &Transcript) -> Result<Vec<Cue>, Error> {
+    if transcript.segments.iter().all(|s| s.end_time == 0.0) {
+        bail!("\nSubtitles need timestamps, and this transcript has none. Use an audio file or an Amazon Transcribe JSON transcript.");
+    }
+
+    let mut cues = Vec::new();
+    for segment in &transcript.segments {
+        if segment.words.is_empty() {
+            cues.push(Cue {
+                start_time: segment.start_time,
+                end_time: segment.end_time,
+                speaker: segment.speaker.clone(),
+                text: segment.text.clone(),
+            });
+            continue;
+        }
+
+        let mut current: Option<Cue> = None;
+        for word in &segment.words {
+            match current.as_mut() {
+                Some(cue)
+                    if cue.text.len() + word.text.len() < MAX_CUE_CHARS
+                        && word.end_time - cue.start_time <= MAX_CUE_SECONDS =>
+                {
+                    cue.text.push(' ');
+                    cue.text.push_str(&word.text);
+                    cue.end_time = word.end_time;
+                }
+                _ => {
+                    cues.extend(current.take());
+                    current = Some(Cue {
+                        start_time: word.start_time,
+                        end_time: word.end_time,
+                        speaker: segment.speaker.clone(),
+                        text: word.text.clone(),
+                    });
+                }
+            }
+        }
+        cues.extend(current);
+    }
+
+    Ok(cues)
+}
+
+// Format seconds as HH:MM:SS,mmm (SRT) or HH:MM:SS.mmm (WebVTT)
+fn format_cue_time(seconds: f64, separator: char) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        (millis % 3_600_000) / 60_000,
+        (millis % 60_000) / 1000,
+        separator,
+        millis % 1000
+    )
+}
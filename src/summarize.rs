@@ -1,80 +1,490 @@
 use aws_config::SdkConfig;
+use config::Config;
+use aws_sdk_bedrockruntime::types::ResponseStream;
 use aws_sdk_bedrockruntime::{primitives::Blob, Client};
 
-use anyhow::{anyhow, Error};
+use anyhow::{anyhow, bail, Context, Error};
 
-use config::{Config, File};
+use crate::agent::Agent;
+use crate::openai;
+use crate::repair;
+use crate::retry::{self, retry};
+use crate::template::{self, Unknown, Value};
 use serde_json::json;
-use spinoff::Spinner;
+use crate::progress::Progress;
 use std::str::from_utf8;
+use tracing::{debug, instrument};
+
+// What writes the summaries, from model.summarizer or --summarizer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Summarizer {
+    // model.model_id, or the Bedrock Agent in agent.agent_id
+    Bedrock,
+    // An OpenAI-compatible chat API, e.g. OpenAI, Ollama or vLLM, from [openai]
+    Openai,
+}
+
+impl Summarizer {
+    pub fn from_settings(settings: &Config) -> Result<Self, Error> {
+        match settings.get_string("model.summarizer") {
+            Ok(name) => match <Summarizer as clap::ValueEnum>::from_str(&name, true) {
+                Ok(summarizer) => Ok(summarizer),
+                Err(_) => bail!("\nUnknown summarizer '{}' in model.summarizer. Use bedrock or openai.", name),
+            },
+            Err(_) => Ok(Summarizer::Bedrock),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Summarizer::Bedrock => "bedrock",
+            Summarizer::Openai => "openai",
+        }
+    }
+
+    // The services a summary can be written with, for `distill --build-info`
+    pub fn backends(&self) -> &'static [&'static str] {
+        match self {
+            Summarizer::Bedrock => &["amazon-bedrock", "amazon-bedrock-agents"],
+            Summarizer::Openai => &["openai-compatible"],
+        }
+    }
+}
+
+// The model call a summary is written with
+enum Backend {
+    Model(String),
+    Agent(Agent),
+    Chat(openai::Chat),
+}
+
+impl Backend {
+    fn from_settings(settings: &Config) -> Result<Self, Error> {
+        if Summarizer::from_settings(settings)? == Summarizer::Openai {
+            return Ok(Backend::Chat(openai::Chat::from_settings(settings)?));
+        }
+        // With a Bedrock Agent configured it answers instead, using its tools
+        Ok(match Agent::from_settings(settings) {
+            Some(agent) => Backend::Agent(agent),
+            None => Backend::Model(settings.get_string("model.model_id").unwrap_or_default()),
+        })
+    }
+}
+
+pub struct Summary {
+    pub text: String,
+    pub usage: Usage,
+}
+
+// Token counts reported by Bedrock, summed over one or more calls
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Usage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    // Input tokens read from, and written to, the prompt cache
+    pub cache_read_tokens: u64,
+    pub cache_write_tokens: u64,
+}
+
+impl std::ops::AddAssign for Usage {
+    fn add_assign(&mut self, other: Usage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_read_tokens += other.cache_read_tokens;
+        self.cache_write_tokens += other.cache_write_tokens;
+    }
+}
 
 pub async fn summarize_text(
+    settings: &Config,
     config: &SdkConfig,
     transcribed_text: &str,
-    spinner: &mut Spinner,
-) -> Result<String, Error> {
-    let client = Client::new(config);
-    let settings = Config::builder()
-        .add_source(File::with_name("config.toml"))
-        .build()?;
+    prompt_template: &str,
+    spinner: &mut Progress,
+) -> Result<Summary, Error> {
+    invoke_model(settings, config, transcribed_text, prompt_template, None, spinner).await
+}
+
+// Like `summarize_text`, sampling at the given temperature instead of model.temperature
+pub async fn summarize_text_at(
+    settings: &Config,
+    config: &SdkConfig,
+    transcribed_text: &str,
+    prompt_template: &str,
+    temperature: f64,
+    spinner: &mut Progress,
+) -> Result<Summary, Error> {
+    invoke_model(settings, config, transcribed_text, prompt_template, Some(temperature), spinner).await
+}
+
+// Ask the model which of several candidate summaries of the same transcript
+// is best. Returns the zero-based index of the winner and the judging call's usage.
+pub async fn pick_best(
+    settings: &Config,
+    config: &SdkConfig,
+    transcribed_text: &str,
+    candidates: &[String],
+    spinner: &mut Progress,
+) -> Result<(usize, Summary), Error> {
+    let numbered: String = candidates
+        .iter()
+        .enumerate()
+        .map(|(i, candidate)| format!("<summary number=\"{}\">\n{}\n</summary>\n\n", i + 1, candidate))
+        .collect();
+    let prompt = format!(
+        "Below are {} candidate summaries of the same meeting, followed by the meeting transcript. \
+Judge which summary is the most accurate and complete, with the clearest action items, and no \
+claims the transcript doesn't support. Reply with only the number of the best summary.\n\n{}Transcript:",
+        candidates.len(),
+        numbered
+    );
+
+    let verdict = summarize_text(settings, config, transcribed_text, &prompt, spinner).await?;
+    let choice = verdict
+        .text
+        .split(|c: char| !c.is_ascii_digit())
+        .find_map(|n| n.parse::<usize>().ok())
+        .filter(|n| (1..=candidates.len()).contains(n))
+        .ok_or_else(|| anyhow!("Could not read the chosen summary from: {}", verdict.text))?;
+    Ok((choice - 1, verdict))
+}
 
-    let prompt_template = settings.get_string("prompt.template").unwrap_or_default();
+// Values for the placeholders a prompt template may use. {transcript} is
+// filled in when the model is called.
+pub struct PromptValues<'a> {
+    pub title: &'a str,
+    pub date: &'a str,
+    pub attendees: &'a str,
+    // Length of the meeting in seconds, when known
+    pub duration: Option<f64>,
+}
+
+// Fill in {title}, {date}, {attendees} and {duration}, with any filters. Other
+// braces are left alone, since prompts often contain JSON examples.
+pub fn fill_prompt(template: &str, values: &PromptValues) -> Result<String, Error> {
+    template::render(
+        template,
+        &[
+            ("title", Value::Text(values.title.to_string())),
+            ("date", Value::Date(values.date.to_string())),
+            ("attendees", Value::Text(values.attendees.to_string())),
+            ("duration", values.duration.map_or(Value::Text(String::new()), Value::Duration)),
+        ],
+        Unknown::Keep,
+    )
+}
+
+// The transcript goes where the template has {transcript}, or after it
+fn prompt_text(transcribed_text: &str, prompt_template: &str) -> String {
+    if prompt_template.contains("{transcript}") {
+        prompt_template.replace("{transcript}", transcribed_text)
+    } else {
+        format!("{prompt_template}\n\n{transcribed_text}")
+    }
+}
+
+// The InvokeModel request for `model_id`
+fn request_body(
+    settings: &Config,
+    model_id: &str,
+    transcribed_text: &str,
+    prompt_template: &str,
+    temperature: Option<f64>,
+) -> serde_json::Value {
+    let prompt = prompt_text(transcribed_text, prompt_template);
 
-    let prompt = format!("{prompt_template}\n\n{transcribed_text}");
+    let prompt_caching = settings.get_bool("model.prompt_caching").unwrap_or(false);
 
-    // We're using the Anthropic Claude Messages API by default.
-    // If you switch models, you may need to update `messages`
-    // and/or `body`.
+    // Claude models use the Anthropic Messages API and Amazon Nova models their
+    // own request format. Other models may need `body` adjusted.
     // https://docs.aws.amazon.com/bedrock/latest/userguide/model-parameters.html
     // Claude: https://docs.aws.amazon.com/bedrock/latest/userguide/model-parameters-anthropic-claude-messages.html
-    let messages = json!([
-        {
-            "role": "user",
-            "content": [
-                {
-                    "type": "text",
-                    "text": prompt,
-                }
-            ]
+    // Nova: https://docs.aws.amazon.com/nova/latest/userguide/complete-request-schema.html
+    // With prompt caching, a cache checkpoint after the prompt lets repeated
+    // calls with the same prompt and transcript (e.g. --samples) reuse it
+    if is_nova(model_id) {
+        let mut content = vec![json!({ "text": prompt })];
+        if prompt_caching {
+            content.push(json!({ "cachePoint": { "type": "default" } }));
         }
-    ]);
-
-    let body = json!(
-        {
-            "anthropic_version": settings.get_string("anthropic.anthropic_version").unwrap_or_default(),
-            "max_tokens": settings.get_int("model.max_tokens").unwrap_or_default(),
-            "system": settings.get_string("anthropic.system").unwrap_or_default(),
-            "messages": messages,
-            "temperature": settings.get_int("model.temperature").unwrap_or_default(),
-            "top_p": settings.get_int("model.top_p").unwrap_or_default(),
-            "top_k": settings.get_int("model.top_k").unwrap_or_default(),
+        json!({
+            "schemaVersion": "messages-v1",
+            "system": [{ "text": settings.get_string("anthropic.system").unwrap_or_default() }],
+            "messages": [{ "role": "user", "content": content }],
+            "inferenceConfig": {
+                "maxTokens": settings.get_int("model.max_tokens").unwrap_or_default(),
+                "temperature": match temperature {
+                    Some(temperature) => temperature,
+                    None => settings.get_float("model.temperature").unwrap_or(0.7),
+                },
+                "topP": settings.get_float("model.top_p").unwrap_or(0.9),
+                "topK": settings.get_int("model.top_k").unwrap_or(50),
+            },
+        })
+    } else {
+        let mut content = json!({
+            "type": "text",
+            "text": prompt,
+        });
+        if prompt_caching {
+            content["cache_control"] = json!({ "type": "ephemeral" });
         }
-    )
-    .to_string();
+        let messages = json!([
+            {
+                "role": "user",
+                "content": [content]
+            }
+        ]);
+
+        json!(
+            {
+                "anthropic_version": settings.get_string("anthropic.anthropic_version").unwrap_or_default(),
+                "max_tokens": settings.get_int("model.max_tokens").unwrap_or_default(),
+                "system": settings.get_string("anthropic.system").unwrap_or_default(),
+                "messages": messages,
+                "temperature": match temperature {
+                    Some(temperature) => json!(temperature),
+                    None => json!(settings.get_float("model.temperature").unwrap_or(0.7)),
+                },
+                "top_p": settings.get_float("model.top_p").unwrap_or(0.9),
+                "top_k": settings.get_int("model.top_k").unwrap_or_default(),
+            }
+        )
+    }
+}
+
+#[instrument(skip_all, fields(model_id))]
+async fn invoke_model(
+    settings: &Config,
+    config: &SdkConfig,
+    transcribed_text: &str,
+    prompt_template: &str,
+    temperature: Option<f64>,
+    spinner: &mut Progress,
+) -> Result<Summary, Error> {
+    let model_id = match Backend::from_settings(settings)? {
+        Backend::Model(model_id) => model_id,
+        Backend::Agent(agent) => {
+            let input = prompt_text(transcribed_text, prompt_template);
+            return agent.invoke(settings, config, &input, spinner, None).await;
+        }
+        Backend::Chat(chat) => {
+            let prompt = prompt_text(transcribed_text, prompt_template);
+            return chat.complete(settings, &prompt, temperature, spinner, None).await;
+        }
+    };
+    let client = Client::new(config);
+    tracing::Span::current().record("model_id", model_id.as_str());
+    let body = request_body(settings, &model_id, transcribed_text, prompt_template, temperature).to_string();
+    debug!(request_bytes = body.len(), "invoking model");
 
     let blob_body = Blob::new(body);
 
-    spinner.update_text("Summarizing transcription...");
-    let response = client
-        .invoke_model()
-        .body(blob_body)
-        .content_type("application/json")
-        .accept("application/json")
-        .model_id(settings.get_string("model.model_id").unwrap_or_default())
-        .send()
-        .await;
+    spinner.update("Summarizing transcription...");
+    let policy = retry::Policy::from_settings(settings, "bedrock")?;
+    let response = retry(&policy, spinner, "Amazon Bedrock", || {
+        client
+            .invoke_model()
+            .body(blob_body.clone())
+            .content_type("application/json")
+            .accept("application/json")
+            .model_id(&model_id)
+            .send()
+    })
+    .await;
 
     match response {
         Ok(output) => {
             let response_body = from_utf8(output.body.as_ref()).unwrap_or("");
-            let response_json: serde_json::Value = serde_json::from_str(response_body).unwrap();
-
-            let summarization = response_json["content"][0]["text"]
-                .as_str()
-                .unwrap()
-                .replace("\\n", "\n");
-            Ok(summarization.to_string())
+            let response_json: serde_json::Value = serde_json::from_str(response_body)
+                .with_context(|| format!("Unexpected response from {}: {}", model_id, response_body))?;
+            parse_response(&model_id, &response_json)
         }
         Err(e) => Err(anyhow!(e)),
     }
 }
+
+// The summary and token counts in an InvokeModel response, which a batch
+// inference job writes out as each record's modelOutput
+fn parse_response(model_id: &str, response_json: &serde_json::Value) -> Result<Summary, Error> {
+    if is_nova(model_id) {
+        let text = response_json["output"]["message"]["content"][0]["text"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Unexpected response from {}: {}", model_id, response_json))?;
+        let usage = &response_json["usage"];
+        return Ok(Summary {
+            text: text.to_string(),
+            usage: Usage {
+                input_tokens: usage["inputTokens"].as_u64().unwrap_or_default(),
+                output_tokens: usage["outputTokens"].as_u64().unwrap_or_default(),
+                cache_read_tokens: usage["cacheReadInputTokenCount"].as_u64().unwrap_or_default(),
+                cache_write_tokens: usage["cacheWriteInputTokenCount"].as_u64().unwrap_or_default(),
+            },
+        });
+    }
+
+    let summarization = response_json["content"][0]["text"]
+        .as_str()
+        .ok_or_else(|| anyhow!("Unexpected response from {}: {}", model_id, response_json))?
+        .replace("\\n", "\n");
+    let usage = &response_json["usage"];
+    Ok(Summary {
+        text: summarization,
+        usage: Usage {
+            input_tokens: usage["input_tokens"].as_u64().unwrap_or_default(),
+            output_tokens: usage["output_tokens"].as_u64().unwrap_or_default(),
+            cache_read_tokens: usage["cache_read_input_tokens"].as_u64().unwrap_or_default(),
+            cache_write_tokens: usage["cache_creation_input_tokens"].as_u64().unwrap_or_default(),
+        },
+    })
+}
+
+// The Bedrock model that summarizes, for a batch inference job. Agents and
+// OpenAI-compatible APIs have no batch jobs, so they are None.
+pub fn batch_model(settings: &Config) -> Result<Option<String>, Error> {
+    Ok(match Backend::from_settings(settings)? {
+        Backend::Model(model_id) => Some(model_id),
+        Backend::Agent(_) | Backend::Chat(_) => None,
+    })
+}
+
+// A batch inference record's modelInput: the InvokeModel request that
+// `summarize_text` would send
+pub fn batch_input(settings: &Config, model_id: &str, transcribed_text: &str, prompt_template: &str) -> serde_json::Value {
+    request_body(settings, model_id, transcribed_text, prompt_template, None)
+}
+
+// The summary in a batch inference record's modelOutput
+pub fn batch_output(model_id: &str, model_output: &serde_json::Value) -> Result<Summary, Error> {
+    parse_response(model_id, model_output)
+}
+
+// Like `summarize_text`, but the reply is streamed and `on_text` is called
+// with each piece of text as it arrives. The spinner is stopped when the
+// first piece arrives, so the caller can print it straight away.
+pub async fn summarize_text_streaming(
+    settings: &Config,
+    config: &SdkConfig,
+    transcribed_text: &str,
+    prompt_template: &str,
+    spinner: &mut Progress,
+    on_text: &mut dyn FnMut(&str),
+) -> Result<Summary, Error> {
+    let model_id = match Backend::from_settings(settings)? {
+        Backend::Model(model_id) => model_id,
+        Backend::Agent(agent) => {
+            let input = prompt_text(transcribed_text, prompt_template);
+            return agent.invoke(settings, config, &input, spinner, Some(on_text)).await;
+        }
+        Backend::Chat(chat) => {
+            let prompt = prompt_text(transcribed_text, prompt_template);
+            return chat.complete(settings, &prompt, None, spinner, Some(on_text)).await;
+        }
+    };
+    let client = Client::new(config);
+    let body = request_body(settings, &model_id, transcribed_text, prompt_template, None).to_string();
+
+    spinner.update("Summarizing transcription...");
+    // Only the request is retried; once text has been printed, a failure is final
+    let policy = retry::Policy::from_settings(settings, "bedrock")?;
+    let body = Blob::new(body);
+    let mut output = retry(&policy, spinner, "Amazon Bedrock", || {
+        client
+            .invoke_model_with_response_stream()
+            .body(body.clone())
+            .content_type("application/json")
+            .accept("application/json")
+            .model_id(&model_id)
+            .send()
+    })
+    .await
+    .map_err(|e| anyhow!(e))?;
+
+    let mut text = String::new();
+    let mut usage = Usage::default();
+    while let Some(event) = output.body.recv().await.map_err(|e| anyhow!(e))? {
+        let ResponseStream::Chunk(part) = event else {
+            continue;
+        };
+        let Some(bytes) = part.bytes() else {
+            continue;
+        };
+        let chunk: serde_json::Value = serde_json::from_slice(bytes.as_ref())?;
+
+        // Claude sends content_block_delta events and Nova contentBlockDelta
+        let delta = chunk["delta"]["text"]
+            .as_str()
+            .or_else(|| chunk["contentBlockDelta"]["delta"]["text"].as_str());
+        if let Some(delta) = delta {
+            if text.is_empty() {
+                spinner.success("Summary ready");
+            }
+            text.push_str(delta);
+            on_text(delta);
+        }
+
+        // Bedrock adds the token counts to the last chunk
+        let metrics = &chunk["amazon-bedrock-invocationMetrics"];
+        if metrics.is_object() {
+            usage.input_tokens = metrics["inputTokenCount"].as_u64().unwrap_or_default();
+            usage.output_tokens = metrics["outputTokenCount"].as_u64().unwrap_or_default();
+            usage.cache_read_tokens = metrics["cacheReadInputTokenCount"].as_u64().unwrap_or_default();
+            usage.cache_write_tokens = metrics["cacheWriteInputTokenCount"].as_u64().unwrap_or_default();
+        }
+    }
+
+    if text.is_empty() {
+        return Err(anyhow!("The model returned an empty summary"));
+    }
+    Ok(Summary { text, usage })
+}
+
+// Nova model IDs look like "amazon.nova-pro-v1:0", or "us.amazon.nova-pro-v1:0"
+// for a cross-region inference profile
+fn is_nova(model_id: &str) -> bool {
+    model_id.contains("amazon.nova")
+}
+
+pub struct StructuredSummary {
+    pub values: Vec<serde_json::Value>,
+    pub usage: Usage,
+    // How many repair passes the reply needed before it parsed
+    pub repairs: u32,
+}
+
+// Like `summarize_text`, for prompts that ask for a JSON array. A malformed
+// reply is first repaired locally, then sent back to the model once to be fixed.
+pub async fn summarize_json_array(
+    settings: &Config,
+    config: &SdkConfig,
+    transcribed_text: &str,
+    prompt_template: &str,
+    spinner: &mut Progress,
+) -> Result<StructuredSummary, Error> {
+    let summary = summarize_text(settings, config, transcribed_text, prompt_template, spinner).await?;
+    let mut structured = StructuredSummary {
+        values: Vec::new(),
+        usage: summary.usage,
+        repairs: 0,
+    };
+
+    if let Some(values) = repair::parse_json_array(&summary.text) {
+        structured.values = values;
+        return Ok(structured);
+    }
+
+    structured.repairs += 1;
+    if let Some(values) = repair::parse_json_array(&repair::repair_json(&summary.text)) {
+        structured.values = values;
+        return Ok(structured);
+    }
+
+    structured.repairs += 1;
+    spinner.update("Repairing model response...");
+    let retry = summarize_text(settings, config, &summary.text, repair::repair_prompt(), spinner).await?;
+    structured.usage += retry.usage;
+    structured.values = repair::parse_json_array(&retry.text)
+        .or_else(|| repair::parse_json_array(&repair::repair_json(&retry.text)))
+        .ok_or_else(|| anyhow!("Model response is not valid JSON, even after repair"))?;
+    Ok(structured)
+}
@@ -0,0 +1,237 @@
+use anyhow::{bail, Error};
+
+use crate::transcribe;
+
+const MONTHS: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November",
+    "December",
+];
+
+const WEEKDAYS: [&str; 7] = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+
+const FILTERS: &str = "format, human, upper, lower, slug or default";
+
+// A value a template can refer to. Dates and durations can be formatted
+// with filters; on their own they render as 2024-05-14 and 01:05:00.
+pub enum Value {
+    Text(String),
+    // YYYY-MM-DD
+    Date(String),
+    // Seconds
+    Duration(f64),
+}
+
+// What to do with braces that aren't one of the template's placeholders
+#[derive(Clone, Copy, PartialEq)]
+pub enum Unknown {
+    // Fail, for templates that are only placeholders and text, e.g. S3 keys
+    Error,
+    // Leave them as they are, for prompts that contain JSON examples
+    Keep,
+}
+
+// Fill in the placeholders of `template`, shared by every templated setting.
+// A placeholder is a value's name in single or double braces, optionally
+// followed by filters: {date}, {{date | format "%d %B %Y"}}, {duration | human}
+// or {title | slug | default "meeting"}.
+pub fn render(template: &str, values: &[(&str, Value)], unknown: Unknown) -> Result<String, Error> {
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        let (open, close) = if tail.starts_with("{{") { ("{{", "}}") } else { ("{", "}") };
+        let Some(end) = tail[open.len()..].find(close) else {
+            if unknown == Unknown::Keep {
+                rendered.push_str(tail);
+                return Ok(rendered);
+            }
+            bail!("\nUnclosed '{}' in template '{}'", open, template);
+        };
+        let placeholder = &tail[..open.len() + end + close.len()];
+        match fill(&tail[open.len()..open.len() + end], values, template)? {
+            Some(text) => rendered.push_str(&text),
+            None if unknown == Unknown::Keep => rendered.push_str(placeholder),
+            None => {
+                let names: Vec<String> = values.iter().map(|(name, _)| format!("{{{}}}", name)).collect();
+                bail!(
+                    "\nUnknown placeholder {} in template '{}'. Use {}.",
+                    placeholder,
+                    template,
+                    names.join(", ")
+                );
+            }
+        }
+        rest = &tail[placeholder.len()..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+// "Q3 Planning" as q3-planning
+pub fn slug(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.trim().chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+// The text for one placeholder, or None when it doesn't name a value
+fn fill(expression: &str, values: &[(&str, Value)], template: &str) -> Result<Option<String>, Error> {
+    let mut parts = split_filters(expression).into_iter();
+    let name = parts.next().unwrap_or_default();
+    let Some((_, value)) = values.iter().find(|(known, _)| *known == name) else {
+        return Ok(None);
+    };
+    let mut text = match value {
+        Value::Text(text) | Value::Date(text) => text.clone(),
+        Value::Duration(seconds) => transcribe::format_timestamp(*seconds),
+    };
+    for filter in parts {
+        let (filter, argument) = match filter.split_once(char::is_whitespace) {
+            Some((filter, argument)) => (filter, Some(unquote(argument.trim()))),
+            None => (filter, None),
+        };
+        // Values that aren't known, such as the length of a recording without
+        // ffprobe, stay empty until `default`
+        if text.is_empty() && filter != "default" {
+            continue;
+        }
+        text = match (filter, argument, value) {
+            ("format", Some(pattern), Value::Date(date)) => format_date(date, pattern, template)?,
+            ("format", Some(pattern), Value::Duration(seconds)) => format_duration(*seconds, pattern, template)?,
+            ("format", Some(_), Value::Text(_)) => {
+                bail!("\nThe format filter in template '{}' only applies to dates and durations.", template)
+            }
+            ("human", None, Value::Duration(seconds)) => human_duration(*seconds),
+            ("human", None, _) => bail!("\nThe human filter in template '{}' only applies to durations.", template),
+            ("upper", None, _) => text.to_uppercase(),
+            ("lower", None, _) => text.to_lowercase(),
+            ("slug", None, _) => slug(&text),
+            ("default", Some(fallback), _) => {
+                if text.is_empty() {
+                    fallback.to_string()
+                } else {
+                    text
+                }
+            }
+            ("format" | "default", None, _) => {
+                bail!("\nThe {} filter in template '{}' needs a value, e.g. {} \"...\".", filter, template, filter)
+            }
+            ("human" | "upper" | "lower" | "slug", Some(_), _) => {
+                bail!("\nThe {} filter in template '{}' doesn't take a value.", filter, template)
+            }
+            _ => bail!("\nUnknown filter '{}' in template '{}'. Use {}.", filter, template, FILTERS),
+        };
+    }
+    Ok(Some(text))
+}
+
+// "date | format \"%Y | %m\"" as ["date", "format \"%Y | %m\""]
+fn split_filters(expression: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (index, c) in expression.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '|' if !quoted => {
+                parts.push(expression[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(expression[start..].trim());
+    parts
+}
+
+fn unquote(argument: &str) -> &str {
+    argument
+        .strip_prefix('"')
+        .and_then(|argument| argument.strip_suffix('"'))
+        .unwrap_or(argument)
+}
+
+// A YYYY-MM-DD date with strftime-style fields: %Y, %y, %m, %d, %e, %B, %b,
+// %A, %a and %%
+fn format_date(date: &str, pattern: &str, template: &str) -> Result<String, Error> {
+    let fields: Vec<u32> = date.split('-').filter_map(|part| part.parse().ok()).collect();
+    let [year, month, day] = fields[..] else {
+        bail!("\nInvalid date '{}' for template '{}'", date, template);
+    };
+    if !(1..=12).contains(&month) {
+        bail!("\nInvalid date '{}' for template '{}'", date, template);
+    }
+    let month_name = MONTHS[month as usize - 1];
+    let weekday = WEEKDAYS[weekday(year, month, day)];
+    format_fields(pattern, template, |field| {
+        Some(match field {
+            'Y' => format!("{:04}", year),
+            'y' => format!("{:02}", year % 100),
+            'm' => format!("{:02}", month),
+            'd' => format!("{:02}", day),
+            'e' => day.to_string(),
+            'B' => month_name.to_string(),
+            'b' => month_name[..3].to_string(),
+            'A' => weekday.to_string(),
+            'a' => weekday[..3].to_string(),
+            _ => return None,
+        })
+    })
+}
+
+// A length in seconds with %H (whole hours), %M, %S and %%
+fn format_duration(seconds: f64, pattern: &str, template: &str) -> Result<String, Error> {
+    let total = seconds.max(0.0) as u64;
+    format_fields(pattern, template, |field| {
+        Some(match field {
+            'H' => format!("{:02}", total / 3600),
+            'M' => format!("{:02}", (total % 3600) / 60),
+            'S' => format!("{:02}", total % 60),
+            _ => return None,
+        })
+    })
+}
+
+fn format_fields(pattern: &str, template: &str, field: impl Fn(char) -> Option<String>) -> Result<String, Error> {
+    let mut formatted = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            formatted.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => formatted.push('%'),
+            Some(name) => match field(name) {
+                Some(value) => formatted.push_str(&value),
+                None => bail!("\nUnsupported field %{} in template '{}'", name, template),
+            },
+            None => bail!("\nIncomplete field '%' at the end of template '{}'", template),
+        }
+    }
+    Ok(formatted)
+}
+
+// 0 for Sunday, see https://en.wikipedia.org/wiki/Determination_of_the_day_of_the_week
+fn weekday(year: u32, month: u32, day: u32) -> usize {
+    const OFFSETS: [u32; 12] = [0, 3, 2, 5, 0, 3, 5, 1, 4, 6, 2, 4];
+    let year = if month < 3 { year.saturating_sub(1) } else { year };
+    ((year + year / 4 - year / 100 + year / 400 + OFFSETS[month as usize - 1] + day) % 7) as usize
+}
+
+// e.g. "1h 05m", "45m" or "30s"
+fn human_duration(seconds: f64) -> String {
+    let total = seconds.max(0.0).round() as u64;
+    match (total / 3600, (total % 3600) / 60, total % 60) {
+        (0, 0, seconds) => format!("{}s", seconds),
+        (0, minutes, _) => format!("{}m", minutes),
+        (hours, minutes, _) => format!("{}h {:02}m", hours, minutes),
+    }
+}
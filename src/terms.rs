@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+
+// Common English words that carry no topic information
+const STOP_WORDS: &[&str] = &[
+    "about", "after", "again", "all", "also", "and", "any", "are", "because", "been", "before",
+    "being", "but", "can", "could", "did", "does", "doing", "don't", "down", "each", "even",
+    "for", "from", "get", "going", "gonna", "got", "had", "has", "have", "having", "her",
+    "here", "him", "his", "how", "i'm", "into", "it's", "its", "just", "know", "like", "make",
+    "maybe", "more", "most", "much", "need", "not", "now", "off", "okay", "one", "only", "other",
+    "our", "out", "over", "really", "right", "said", "say", "see", "she", "should", "some",
+    "something", "that", "that's", "the", "their", "them", "then", "there", "there's", "these",
+    "they", "thing", "things", "think", "this", "those", "through", "too", "uh", "um", "very",
+    "want", "was", "way", "we're", "well", "were", "what", "when", "where", "which", "while",
+    "who", "why", "will", "with", "would", "yeah", "yes", "you", "you're", "your",
+];
+
+// The `count` most frequent terms in the transcript, most frequent first.
+// Speaker labels, stop words and words shorter than three letters are ignored.
+pub fn top_terms(transcript: &str, count: usize) -> Vec<(String, usize)> {
+    let mut frequencies: HashMap<String, usize> = HashMap::new();
+
+    for word in transcript.split_whitespace() {
+        let term = word
+            .trim_matches(|c: char| !c.is_alphanumeric() && c != '\'')
+            .to_lowercase();
+        if term.chars().count() < 3
+            || term.starts_with("spk_")
+            || term.chars().all(|c| c.is_numeric())
+            || STOP_WORDS.contains(&term.as_str())
+        {
+            continue;
+        }
+        *frequencies.entry(term).or_default() += 1;
+    }
+
+    let mut terms: Vec<(String, usize)> = frequencies.into_iter().collect();
+    // Ties are broken alphabetically so the output is stable between runs
+    terms.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    terms.truncate(count);
+    terms
+}
+
+// One "- term (count)" line per term
+pub fn format_terms(terms: &[(String, usize)]) -> String {
+    terms
+        .iter()
+        .map(|(term, count)| format!("- {} ({})", term, count))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
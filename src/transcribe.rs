@@ -1,71 +1,178 @@
 use aws_config::SdkConfig;
 use aws_sdk_transcribe::types::{
-    LanguageCode, Media, MediaFormat, Settings, TranscriptionJobStatus,
+    LanguageCode, Media, MediaFormat, Settings, TranscriptionJobStatus, VocabularyFilterMethod,
 };
 use aws_sdk_transcribe::Client;
 
 use anyhow::{anyhow, bail, Context, Error};
+use async_trait::async_trait;
+use config::Config;
 use infer::get_from_path;
-use serde_json::Value;
-use spinoff::{spinners, Spinner};
+use serde::Deserialize;
+use crate::progress::Progress;
+use crate::retry::{self, retry};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 use tokio::time::{sleep, Duration};
-use uuid::Uuid;
+use tracing::{debug, instrument};
+
+pub struct TranscribeOptions<'a> {
+    // The run's settings, for retrying status checks and downloading the transcript
+    pub settings: &'a Config,
+    pub job_name: &'a str,
+    pub language_code: &'a str,
+    // Initial delay between job status checks; doubles after each check
+    pub poll_interval: Duration,
+    // Also start a new segment when the speaker pauses for this many seconds
+    pub split_on_pause: Option<f64>,
+    // Maximum number of speakers to tell apart, or None to turn off diarization
+    pub max_speakers: Option<i32>,
+    // Vocabulary filter to apply, and whether matches are masked, removed or tagged
+    pub vocabulary_filter: Option<(&'a str, VocabularyFilterMethod)>,
+}
+
+// The recording handed to a Transcriber
+pub struct Audio<'a> {
+    // The local file, already trimmed when only part of it is processed. For
+    // a recording that was already in S3, its s3:// URI.
+    pub path: &'a Path,
+    // Where the file was uploaded, for transcribers that read from S3
+    pub s3_uri: Option<&'a str>,
+}
+
+// A speech-to-text service. Amazon Transcribe and local Whisper implement it;
+// another provider turns an audio file into a Transcript the same way, and is
+// picked with --transcriber.
+#[async_trait(?Send)]
+pub trait Transcriber {
+    // e.g. "Amazon Transcribe", for progress and error messages
+    fn name(&self) -> &'static str;
+
+    // Whether recordings are uploaded to the run's S3 bucket first and given
+    // to `transcribe` with their s3:// URI. Otherwise no bucket is needed.
+    fn reads_from_s3(&self) -> bool {
+        false
+    }
+
+    async fn transcribe(
+        &self,
+        audio: &Audio<'_>,
+        options: &TranscribeOptions<'_>,
+        spinner: &mut Progress,
+    ) -> Result<Transcript, Error>;
+}
+
+// Amazon Transcribe, which runs a batch job on the uploaded recording
+pub struct AmazonTranscribe {
+    // In the bucket's region, or aws.region when it is set
+    pub config: SdkConfig,
+}
+
+#[async_trait(?Send)]
+impl Transcriber for AmazonTranscribe {
+    fn name(&self) -> &'static str {
+        "Amazon Transcribe"
+    }
+
+    fn reads_from_s3(&self) -> bool {
+        true
+    }
+
+    async fn transcribe(
+        &self,
+        audio: &Audio<'_>,
+        options: &TranscribeOptions<'_>,
+        spinner: &mut Progress,
+    ) -> Result<Transcript, Error> {
+        let s3_uri = audio
+            .s3_uri
+            .ok_or_else(|| anyhow!("{} has not been uploaded to S3", audio.path.display()))?;
+        transcribe_audio(&self.config, audio.path, s3_uri, spinner, options).await
+    }
+}
+
+fn media_format_from_extension(file_path: &Path) -> Result<MediaFormat, Error> {
+    let extension = file_path.extension().and_then(|ext| ext.to_str()).unwrap_or_default().to_lowercase();
+    Ok(match extension.as_str() {
+        "amr" => MediaFormat::Amr,
+        "flac" => MediaFormat::Flac,
+        "m4a" => MediaFormat::M4A,
+        "mp3" => MediaFormat::Mp3,
+        "mp4" => MediaFormat::Mp4,
+        "ogg" | "opus" => MediaFormat::Ogg,
+        "wav" => MediaFormat::Wav,
+        "webm" => MediaFormat::Webm,
+        _ => bail!("\nUnable to determine media format of {} from its extension", file_path.display()),
+    })
+}
 
 pub async fn transcribe_audio(
     config: &SdkConfig,
     file_path: &Path,
     s3_uri: &str,
-    spinner: &mut Spinner,
-    language_code: &str,
-) -> Result<String, Error> {
+    spinner: &mut Progress,
+    options: &TranscribeOptions<'_>,
+) -> Result<Transcript, Error> {
+    let language_code = options.language_code;
     let client = Client::new(config);
 
-    spinner.update(spinners::Dots7, "Submitting transcription job", None);
-    let job_name = format!("transcription-{}", Uuid::new_v4()); // Generate a unique job name
+    spinner.update("Submitting transcription job");
+    let job_name = options.job_name;
     let media = Media::builder().media_file_uri(s3_uri).build();
 
-    let media_format = match get_from_path(file_path) {
-        Ok(Some(kind)) => match kind.mime_type() {
-            "audio/amr" => MediaFormat::Amr,
-            "audio/flac" => MediaFormat::Flac,
-            "audio/m4a" => MediaFormat::M4A,
-            "audio/mpeg" => MediaFormat::Mp3,
-            "audio/mp4" => MediaFormat::Mp4,
-            "video/mp4" => MediaFormat::Mp4,
-            "audio/ogg" => MediaFormat::Ogg,
-            "audio/opus" => MediaFormat::Ogg,
-            "audio/wav" => MediaFormat::Wav,
-            "audio/webm" => MediaFormat::Webm,
-            _ => {
-                // Fallback to checking the file extension (MP3s sometimes cause issues)
+    // A recording that is already in S3 has no local copy to look into
+    let media_format = if !file_path.exists() {
+        media_format_from_extension(file_path)?
+    } else {
+        match get_from_path(file_path) {
+            Ok(Some(kind)) => match kind.mime_type() {
+                "audio/amr" => MediaFormat::Amr,
+                "audio/flac" => MediaFormat::Flac,
+                "audio/m4a" => MediaFormat::M4A,
+                "audio/mpeg" => MediaFormat::Mp3,
+                "audio/mp4" => MediaFormat::Mp4,
+                "video/mp4" => MediaFormat::Mp4,
+                "audio/ogg" => MediaFormat::Ogg,
+                "audio/opus" => MediaFormat::Ogg,
+                "audio/wav" => MediaFormat::Wav,
+                "audio/webm" => MediaFormat::Webm,
+                _ => {
+                    // Fallback to checking the file extension (MP3s sometimes cause issues)
+                    match file_path.extension().and_then(|ext| ext.to_str()) {
+                        Some("mp3") => MediaFormat::Mp3,
+                        _ => {
+                            bail!("\nUnsupported media format: {}", kind.mime_type());
+                        }
+                    }
+                }
+            },
+            Ok(None) => {
+                // Fallback to checking the file extension
                 match file_path.extension().and_then(|ext| ext.to_str()) {
                     Some("mp3") => MediaFormat::Mp3,
                     _ => {
-                        bail!("\nUnsupported media format: {}", kind.mime_type());
+                        bail!("\nUnable to determine media format from file extension");
                     }
                 }
             }
-        },
-        Ok(None) => {
-            // Fallback to checking the file extension
-            match file_path.extension().and_then(|ext| ext.to_str()) {
-                Some("mp3") => MediaFormat::Mp3,
-                _ => {
-                    bail!("\nUnable to determine media format from file extension");
-                }
+            Err(err) => {
+                bail!("\nError determining media format: {}", err);
             }
         }
-        Err(err) => {
-            bail!("\nError determining media format: {}", err);
-        }
     };
 
-    let settings = Settings::builder()
-        .show_speaker_labels(true)
-        .max_speaker_labels(10)
-        .channel_identification(false)
-        .build();
+    let settings = match options.max_speakers {
+        Some(max_speakers) => Settings::builder()
+            .show_speaker_labels(true)
+            .max_speaker_labels(max_speakers),
+        None => Settings::builder().show_speaker_labels(false),
+    }
+    .set_vocabulary_filter_name(options.vocabulary_filter.as_ref().map(|(name, _)| name.to_string()))
+    .set_vocabulary_filter_method(options.vocabulary_filter.as_ref().map(|(_, method)| method.clone()))
+    .channel_identification(false)
+    .build();
 
     let language_code_enum = match language_code {
         "ab-GE" => LanguageCode::AbGe,
@@ -179,7 +286,7 @@ pub async fn transcribe_audio(
 
     let _job = client
         .start_transcription_job()
-        .transcription_job_name(&job_name)
+        .transcription_job_name(job_name)
         .language_code(language_code_enum)
         .media_format(media_format)
         .media(media)
@@ -187,42 +294,61 @@ pub async fn transcribe_audio(
         .send()
         .await?;
 
-    println!();
-    spinner.update(
-        spinners::Dots7,
-        "Waiting for transcription to complete...",
-        None,
-    );
-    let mut poll_interval = Duration::from_secs(5);
-    let mut job_details = client
-        .get_transcription_job()
-        .transcription_job_name(&job_name)
-        .send()
-        .await?;
+    // Printed so an interrupted run can be picked up with --resume-job
+    spinner.stop_and_persist("📝", &format!("Transcription job: {}", job_name));
+    wait_for_job(&client, spinner, options).await
+}
+
+// Reattach to a job started by an earlier run and wait for its transcript
+pub async fn resume_transcription(
+    config: &SdkConfig,
+    spinner: &mut Progress,
+    options: &TranscribeOptions<'_>,
+) -> Result<Transcript, Error> {
+    let client = Client::new(config);
+    wait_for_job(&client, spinner, options).await
+}
+
+// The longest wait between checks on a running Transcribe job
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+#[instrument(name = "poll", skip_all, fields(job = options.job_name))]
+async fn wait_for_job(
+    client: &Client,
+    spinner: &mut Progress,
+    options: &TranscribeOptions<'_>,
+) -> Result<Transcript, Error> {
+    let job_name = options.job_name;
+    spinner.update("Waiting for transcription to complete...");
+    let mut poll_interval = options.poll_interval;
+    // A throttled or failed status check doesn't mean the job has failed
+    let policy = retry::Policy::from_settings(options.settings, "transcribe")?;
+    let mut job_details = retry(&policy, spinner, "Amazon Transcribe", || {
+        client.get_transcription_job().transcription_job_name(job_name).send()
+    })
+    .await?;
 
     while let Some(status) = job_details
         .transcription_job
         .as_ref()
         .and_then(|j| j.transcription_job_status.as_ref())
     {
+        debug!(status = status.as_str(), "transcription job status");
         match status {
-            TranscriptionJobStatus::InProgress => {
+            TranscriptionJobStatus::InProgress | TranscriptionJobStatus::Queued => {
+                debug!(secs = poll_interval.as_secs_f64(), "waiting before the next poll");
                 sleep(poll_interval).await;
-                job_details = client
-                    .get_transcription_job()
-                    .transcription_job_name(&job_name)
-                    .send()
-                    .await?;
-                println!();
-                poll_interval *= 2; // Exponential backoff to show progress
-            }
-            TranscriptionJobStatus::Completed => {
-                break;
-            }
-            _ => {
-                // ToDo Handle other states, e.g., Failed
-                break;
+                spinner.still_working();
+                job_details = retry(&policy, spinner, "Amazon Transcribe", || {
+                    client.get_transcription_job().transcription_job_name(job_name).send()
+                })
+                .await?;
+                spinner.break_line();
+                // Back off, but keep checking often enough that a finished job isn't left waiting
+                poll_interval = (poll_interval * 2).min(MAX_POLL_INTERVAL);
             }
+            TranscriptionJobStatus::Completed | TranscriptionJobStatus::Failed => break,
+            other => bail!("\nTranscription job {} has an unexpected status: {}", job_name, other.as_str()),
         }
     }
 
@@ -237,82 +363,397 @@ pub async fn transcribe_audio(
                 .and_then(|j| j.transcript)
                 .and_then(|t| t.transcript_file_uri)
             {
-                spinner.update(spinners::Dots7, "Transcription job complete", None);
-                let res = reqwest::get(transcript_uri).await?;
-                let body = res.text().await?;
-                let final_transcript = convert_transcribe_json(&body)?;
+                spinner.update("Transcription job complete");
+                // The JSON of a long recording can be tens of MB, so it is
+                // spooled to a temporary file and parsed from there
+                let client = crate::net::http_client(options.settings)?;
+                let mut res = client.get(transcript_uri).send().await?.error_for_status()?;
+                let mut body = tempfile::tempfile()?;
+                while let Some(chunk) = res.chunk().await? {
+                    body.write_all(&chunk)?;
+                }
+                body.seek(SeekFrom::Start(0))?;
+                let final_transcript = parse_transcribe_json(BufReader::new(body), options.split_on_pause)?;
                 Ok(final_transcript)
             } else {
-                println!("Transcript file URI is missing.");
-                Ok("Transcript file URI is missing.".to_string())
+                bail!("\nTranscript file URI is missing.");
             }
         }
         Some(TranscriptionJobStatus::Failed) => {
             if let Some(reason) = job_details.transcription_job.and_then(|j| j.failure_reason) {
-                println!("Transcription job failed: {}", reason);
+                bail!("\nTranscription job failed: {}", reason);
+            } else {
+                bail!("\nTranscription job failed for an unknown reason.");
+            }
+        }
+        _ => bail!("\nJob ended with an unexpected status or status could not be determined."),
+    }
+}
+
+// One speaker turn, with times in seconds from the start of the recording
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub speaker: String,
+    pub start_time: f64,
+    pub end_time: f64,
+    pub text: String,
+    // Word-level timings, when the transcript came from Amazon Transcribe
+    pub words: Vec<Word>,
+}
+
+// A single word (with any trailing punctuation) and its times in seconds
+#[derive(Debug, Clone)]
+pub struct Word {
+    pub start_time: f64,
+    pub end_time: f64,
+    pub text: String,
+    // Amazon Transcribe's confidence in the word, from 0 to 1
+    pub confidence: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Transcript {
+    pub segments: Vec<Segment>,
+}
+
+// Turns with fewer words are never treated as repeats
+const MIN_DUPLICATE_WORDS: usize = 4;
+// How far apart, in seconds, a repeat may start after the end of the original
+const DUPLICATE_WINDOW: f64 = 2.0;
+// Share of the shorter turn's words that must also be in the other turn
+const DUPLICATE_SIMILARITY: f64 = 0.8;
+
+fn normalized_words(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric())
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+// Fraction of the shorter list's words found in the longer one, so an echo
+// that only caught part of a turn still counts
+fn word_overlap(a: &[String], b: &[String]) -> f64 {
+    let (shorter, longer) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    if shorter.is_empty() {
+        return 0.0;
+    }
+    let longer: HashSet<&String> = longer.iter().collect();
+    shorter.iter().filter(|word| longer.contains(word)).count() as f64 / shorter.len() as f64
+}
+
+impl Transcript {
+    // Append the next part of the same meeting, shifting its times to follow on from this one
+    pub fn append(&mut self, other: Transcript) {
+        let offset = self.segments.last().map_or(0.0, |s| s.end_time);
+        self.segments.extend(other.segments.into_iter().map(|mut segment| {
+            segment.start_time += offset;
+            segment.end_time += offset;
+            for word in &mut segment.words {
+                word.start_time += offset;
+                word.end_time += offset;
+            }
+            segment
+        }));
+    }
+
+    // Drop turns that repeat one just before them, as happens when a participant
+    // joins twice (e.g. laptop and phone) and Transcribe hears them as two
+    // speakers. A turn is a repeat when it overlaps, or starts within a couple
+    // of seconds of, an earlier turn with mostly the same words. Short turns
+    // such as "Yes." are kept, since people really do repeat them.
+    // Returns how many turns were dropped.
+    pub fn collapse_duplicates(&mut self) -> usize {
+        let before = self.segments.len();
+        let mut kept: Vec<Segment> = Vec::with_capacity(before);
+        for segment in self.segments.drain(..) {
+            let words = normalized_words(&segment.text);
+            // Without word timings (plain text transcripts) there's no telling when a turn was said
+            let original = if words.len() < MIN_DUPLICATE_WORDS || segment.words.is_empty() {
+                None
             } else {
-                println!("Transcription job failed for an unknown reason.");
+                let recent = kept
+                    .iter()
+                    .rev()
+                    .take_while(|earlier| segment.start_time - earlier.end_time <= DUPLICATE_WINDOW)
+                    .count();
+                (kept.len() - recent..kept.len()).rev().find(|&i| {
+                    word_overlap(&words, &normalized_words(&kept[i].text)) >= DUPLICATE_SIMILARITY
+                })
+            };
+            match original {
+                // Whichever copy caught more of what was said is kept
+                Some(i) => {
+                    if words.len() > normalized_words(&kept[i].text).len() {
+                        kept[i] = segment;
+                    }
+                }
+                None => kept.push(segment),
+            }
+        }
+        self.segments = kept;
+        before - self.segments.len()
+    }
+
+    // One "speaker: text" line per turn, as fed to the model and written to outputs
+    pub fn text(&self) -> String {
+        self.segments
+            .iter()
+            .map(|s| format!("{}\n", s.line()))
+            .collect()
+    }
+
+    // Like text(), with each turn prefixed by its start time, e.g. "[00:12:30] Speaker 1: ..."
+    pub fn timestamped_text(&self) -> String {
+        self.segments
+            .iter()
+            .map(|s| format!("[{}] {}\n", format_timestamp(s.start_time), s.line()))
+            .collect()
+    }
+
+    // Speaker labels in order of first appearance
+    pub fn speakers(&self) -> Vec<String> {
+        let mut speakers: Vec<String> = Vec::new();
+        for segment in &self.segments {
+            if !segment.speaker.is_empty() && !speakers.contains(&segment.speaker) {
+                speakers.push(segment.speaker.clone());
+            }
+        }
+        speakers
+    }
+
+    // One paragraph per turn with the speaker in bold, e.g. "**Speaker 1:** ..."
+    pub fn write_markdown<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        for (i, s) in self.segments.iter().enumerate() {
+            if i > 0 {
+                out.write_all(b"\n\n")?;
+            }
+            if s.speaker.is_empty() {
+                write!(out, "{}", s.text)?;
+            } else {
+                write!(out, "**{}:** {}", s.speaker, s.text)?;
             }
-            Ok("Transcription job failed.".to_string())
         }
-        _ => Ok(
-            "Job ended with an unexpected status or status could not be determined.".to_string(),
-        ),
+        Ok(())
     }
 }
 
-pub fn convert_transcribe_json(json_string: &str) -> Result<String, Error> {
-    let v: Value = serde_json::from_str(json_string).with_context(|| "Failed to parse JSON")?;
+// Combine separately transcribed per-speaker tracks into one transcript.
+// Every segment of a track is attributed to that track's speaker, and turns
+// are ordered by start time.
+pub fn interleave(tracks: Vec<(String, Transcript)>) -> Transcript {
+    let mut segments: Vec<Segment> = tracks
+        .into_iter()
+        .flat_map(|(speaker, transcript)| {
+            transcript.segments.into_iter().map(move |mut segment| {
+                segment.speaker.clone_from(&speaker);
+                segment
+            })
+        })
+        .collect();
+    segments.sort_by(|a, b| a.start_time.total_cmp(&b.start_time));
 
-    let mut final_transcript = String::new();
-    let mut current_speaker: Option<String> = None;
-    let mut current_text = String::new();
+    // Consecutive turns by the same speaker read as one
+    let mut transcript = Transcript::default();
+    for segment in segments {
+        match transcript.segments.last_mut() {
+            Some(last) if last.speaker == segment.speaker => {
+                last.text.push(' ');
+                last.text.push_str(&segment.text);
+                last.end_time = last.end_time.max(segment.end_time);
+                last.words.extend(segment.words);
+            }
+            _ => transcript.segments.push(segment),
+        }
+    }
+
+    transcript
+}
+
+impl Segment {
+    // Plain-text transcripts may not have speaker labels
+    fn line(&self) -> String {
+        if self.speaker.is_empty() {
+            self.text.clone()
+        } else {
+            format!("{}: {}", self.speaker, self.text)
+        }
+    }
+}
+
+// Load a previously saved transcript: either the JSON produced by Amazon
+// Transcribe, or plain text with one turn per line ("Speaker 1: Hello" or just "Hello")
+pub fn read_transcript_file(path: &Path) -> Result<Transcript, Error> {
+    let file = File::open(path).with_context(|| format!("Error reading transcript: {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+
+    // Transcribe JSON is parsed straight from the file
+    let starts_with_brace = reader.fill_buf()?.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'{');
+    if starts_with_brace {
+        return parse_transcribe_json(reader, None);
+    }
+
+    let mut contents = String::new();
+    reader
+        .read_to_string(&mut contents)
+        .with_context(|| format!("Error reading transcript: {}", path.display()))?;
+
+    let segments = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            // Treat a short prefix of up to three words before ':' as the speaker label
+            let (speaker, text) = match line.split_once(": ") {
+                Some((label, text)) if label.len() <= 32 && label.split(' ').count() <= 3 => (label, text),
+                _ => ("", line),
+            };
+            Segment {
+                speaker: speaker_name(speaker),
+                start_time: 0.0,
+                end_time: 0.0,
+                text: text.to_string(),
+                words: Vec::new(),
+            }
+        })
+        .collect();
 
-    for item in v["results"]["items"].as_array().unwrap() {
-        match item["type"].as_str().unwrap() {
+    Ok(Transcript { segments })
+}
+
+// Format seconds as HH:MM:SS
+pub fn format_timestamp(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    format!("{:02}:{:02}:{:02}", total / 3600, (total % 3600) / 60, total % 60)
+}
+
+// The parts of Amazon Transcribe's output that are used. Everything else,
+// such as the full-text copy of the transcript and the per-segment speaker
+// data, is skipped while parsing rather than loaded into memory.
+#[derive(Deserialize)]
+struct TranscribeOutput {
+    results: TranscribeResults,
+}
+
+#[derive(Deserialize)]
+struct TranscribeResults {
+    items: Vec<TranscribeItem>,
+}
+
+#[derive(Deserialize)]
+struct TranscribeItem {
+    #[serde(rename = "type")]
+    kind: String,
+    // Transcribe encodes item times as strings, e.g. "12.34"
+    start_time: Option<String>,
+    end_time: Option<String>,
+    speaker_label: Option<String>,
+    alternatives: Vec<Alternative>,
+}
+
+#[derive(Deserialize)]
+struct Alternative {
+    content: String,
+    // Also a string, e.g. "0.998"
+    confidence: Option<String>,
+}
+
+// Group Transcribe items into speaker turns, optionally also splitting turns at long pauses
+fn parse_transcribe_json<R: Read>(reader: R, split_on_pause: Option<f64>) -> Result<Transcript, Error> {
+    let output: TranscribeOutput =
+        serde_json::from_reader(reader).with_context(|| "Failed to parse JSON")?;
+
+    let mut transcript = Transcript::default();
+    let mut current: Option<Segment> = None;
+
+    for item in &output.results.items {
+        let alternative = item
+            .alternatives
+            .first()
+            .ok_or_else(|| anyhow!("Missing {} content data", item.kind))?;
+        let content = alternative.content.as_str();
+        match item.kind.as_str() {
             "pronunciation" => {
-                let content = item["alternatives"][0]["content"]
-                    .as_str()
-                    .ok_or_else(|| anyhow!("Missing pronunciation content data"))?;
-                let speaker_label = item["speaker_label"]
-                    .as_str()
-                    .ok_or_else(|| anyhow!("Missing 'speaker_label' data"))?;
-
-                if let Some(current_speaker_label) = current_speaker.as_ref() {
-                    if current_speaker_label != speaker_label {
-                        if !current_text.is_empty() {
-                            final_transcript.push_str(&format!(
-                                "{}: {}\n",
-                                current_speaker_label,
-                                current_text.trim()
-                            ));
+                // Words dropped by a vocabulary filter in "remove" mode
+                if content.is_empty() {
+                    continue;
+                }
+                // Transcribe labels speakers spk_0, spk_1, ...; without
+                // diarization there are no labels, and turns are split at pauses
+                let speaker_label = item.speaker_label.as_deref().map(speaker_name).unwrap_or_default();
+                let split_on_pause = if speaker_label.is_empty() {
+                    split_on_pause.or(Some(DEFAULT_PAUSE))
+                } else {
+                    split_on_pause
+                };
+                let start_time = parse_time(item.start_time.as_deref());
+                let end_time = parse_time(item.end_time.as_deref());
+
+                let word = Word {
+                    start_time,
+                    end_time,
+                    text: content.to_string(),
+                    confidence: alternative.confidence.as_deref().and_then(|c| c.parse().ok()),
+                };
+                let paused = |segment: &Segment| {
+                    split_on_pause.is_some_and(|gap| start_time - segment.end_time >= gap)
+                };
+
+                match current.as_mut() {
+                    Some(segment) if segment.speaker == speaker_label && !paused(segment) => {
+                        segment.text.push(' ');
+                        segment.text.push_str(content);
+                        segment.end_time = end_time;
+                        segment.words.push(word);
+                    }
+                    _ => {
+                        if let Some(segment) = current.take() {
+                            transcript.segments.push(segment);
                         }
-                        current_speaker = Some(speaker_label.to_string());
-                        current_text = content.to_string();
-                    } else {
-                        current_text.push(' ');
-                        current_text.push_str(content);
+                        current = Some(Segment {
+                            speaker: speaker_label.clone(),
+                            start_time,
+                            end_time,
+                            text: content.to_string(),
+                            words: vec![word],
+                        });
                     }
-                } else {
-                    current_speaker = Some(speaker_label.to_string());
-                    current_text = content.to_string();
                 }
             }
             "punctuation" => {
-                let content = item["alternatives"][0]["content"]
-                    .as_str()
-                    .ok_or_else(|| anyhow!("Missing punctuation content data"))?;
-                current_text.push_str(content);
+                if let Some(segment) = current.as_mut() {
+                    segment.text.push_str(content);
+                    if let Some(word) = segment.words.last_mut() {
+                        word.text.push_str(content);
+                    }
+                }
             }
             _ => {}
         }
     }
 
-    if let Some(speaker_label) = current_speaker {
-        if !current_text.is_empty() {
-            final_transcript.push_str(&format!("{}: {}\n", speaker_label, current_text.trim()));
-        }
+    if let Some(segment) = current {
+        transcript.segments.push(segment);
     }
 
-    Ok(final_transcript)
+    Ok(transcript)
+}
+
+// Pause, in seconds, that ends a turn when there are no speaker labels
+const DEFAULT_PAUSE: f64 = 1.5;
+
+// "spk_0" becomes "Speaker 1"; other labels are kept as they are
+fn speaker_name(label: &str) -> String {
+    match label.strip_prefix("spk_").and_then(|n| n.parse::<u32>().ok()) {
+        Some(n) => format!("Speaker {}", n + 1),
+        None => label.to_string(),
+    }
+}
+
+fn parse_time(value: Option<&str>) -> f64 {
+    value.and_then(|t| t.parse().ok()).unwrap_or_default()
 }
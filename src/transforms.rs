@@ -0,0 +1,290 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{bail, Context, Error};
+use config::Config;
+use regex::{Regex, RegexBuilder};
+
+use crate::transcribe::Transcript;
+
+const DEFAULT_FILLERS: [&str; 9] = ["um", "umm", "uh", "uhh", "uhm", "erm", "er", "hmm", "mhm"];
+
+// Emails, phone numbers and card or account numbers
+const DEFAULT_REDACT_PATTERNS: [&str; 3] = [
+    r"[\w.+-]+@[\w-]+(\.[\w-]+)+",
+    r"\+?\d[\d\s().-]{7,}\d",
+    r"\b\d(?:[ -]?\d){12,18}\b",
+];
+
+// One step of the transcript pipeline, run after transcription and before the
+// transcript is summarized or written out
+pub trait Transform {
+    fn apply(&self, transcript: &mut Transcript);
+}
+
+// Build the transforms listed in `transforms.pipeline`, in order, e.g.
+// ["trim_sections", "clean_fillers", "glossary", "redact"]
+pub fn pipeline(settings: &Config) -> Result<Vec<Box<dyn Transform>>, Error> {
+    let names: Vec<String> = settings
+        .get_array("transforms.pipeline")
+        .unwrap_or_default()
+        .into_iter()
+        .map(|name| name.into_string())
+        .collect::<Result<_, _>>()
+        .context("transforms.pipeline must be a list of transform names")?;
+
+    let mut transforms: Vec<Box<dyn Transform>> = Vec::new();
+    for name in names {
+        transforms.push(match name.as_str() {
+            "clean_fillers" => Box::new(CleanFillers::from_settings(settings)?),
+            "glossary" => Box::new(Glossary::from_settings(settings)?),
+            "redact" => Box::new(Redact::from_settings(settings)?),
+            "anonymize" => Box::new(Anonymize::from_settings(settings)?),
+            "trim_sections" => Box::new(TrimSections::from_settings(settings)?),
+            other => bail!(
+                "\nUnknown transform '{}' in transforms.pipeline. Use clean_fillers, glossary, redact, anonymize or trim_sections.",
+                other
+            ),
+        });
+    }
+    Ok(transforms)
+}
+
+// Run each transform in turn. Turns whose text changed lose their word
+// timings, so subtitles are built from the transformed text rather than the
+// original words.
+pub fn apply(transforms: &[Box<dyn Transform>], transcript: &mut Transcript) {
+    if transforms.is_empty() {
+        return;
+    }
+    let original: HashSet<String> = transcript.segments.iter().map(|s| s.text.clone()).collect();
+    for transform in transforms {
+        transform.apply(transcript);
+    }
+    for segment in &mut transcript.segments {
+        if !original.contains(&segment.text) {
+            segment.words.clear();
+        }
+    }
+    transcript.segments.retain(|segment| !segment.text.trim().is_empty());
+}
+
+fn strings(settings: &Config, key: &str) -> Result<Option<Vec<String>>, Error> {
+    match settings.get_array(key) {
+        Ok(values) => Ok(Some(
+            values
+                .into_iter()
+                .map(|value| value.into_string())
+                .collect::<Result<_, _>>()
+                .with_context(|| format!("{} must be a list of strings", key))?,
+        )),
+        Err(_) => Ok(None),
+    }
+}
+
+// Whole words or phrases, ignoring case, with optional text around them
+fn phrase_pattern_within(phrases: &[String], before: &str, after: &str) -> Result<Regex, Error> {
+    let alternatives: Vec<String> = phrases.iter().map(|p| regex::escape(p.trim())).collect();
+    Ok(RegexBuilder::new(&format!(r"{}\b(?:{})\b{}", before, alternatives.join("|"), after))
+        .case_insensitive(true)
+        .build()?)
+}
+
+// Any of `phrases` as whole words, ignoring case
+pub fn phrase_pattern(phrases: &[String]) -> Result<Regex, Error> {
+    phrase_pattern_within(phrases, "", "")
+}
+
+// Tidy the spaces and punctuation left behind after words are removed
+fn tidy(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let text = text
+        .replace(" ,", ",")
+        .replace(" .", ".")
+        .replace(" ?", "?")
+        .replace(",,", ",")
+        .replace(",.", ".");
+    text.trim_start_matches([',', ' ']).to_string()
+}
+
+// Drop filler words such as "um" and "uh" (`transforms.fillers` replaces the list)
+struct CleanFillers {
+    pattern: Regex,
+}
+
+impl CleanFillers {
+    fn from_settings(settings: &Config) -> Result<Self, Error> {
+        let fillers = strings(settings, "transforms.fillers")?
+            .unwrap_or_else(|| DEFAULT_FILLERS.iter().map(|f| f.to_string()).collect());
+        // Commas around a filler go with it, e.g. "So, um, the" becomes "So the"
+        Ok(CleanFillers {
+            pattern: phrase_pattern_within(&fillers, r",?\s*", ",?")?,
+        })
+    }
+}
+
+impl Transform for CleanFillers {
+    fn apply(&self, transcript: &mut Transcript) {
+        for segment in &mut transcript.segments {
+            if self.pattern.is_match(&segment.text) {
+                let cleaned = tidy(&self.pattern.replace_all(&segment.text, ""));
+                // Capitalize the new first word if the filler started the sentence
+                let mut chars = cleaned.chars();
+                segment.text = match chars.next() {
+                    Some(first) if segment.text.starts_with(char::is_uppercase) => {
+                        first.to_uppercase().chain(chars).collect()
+                    }
+                    _ => cleaned,
+                };
+            }
+        }
+    }
+}
+
+// Correct misheard terms, from `[transforms.glossary]`, e.g. "cube control" = "kubectl"
+struct Glossary {
+    entries: Vec<(Regex, String)>,
+}
+
+impl Glossary {
+    fn from_settings(settings: &Config) -> Result<Self, Error> {
+        let table: HashMap<String, String> = settings
+            .get_table("transforms.glossary")
+            .context("The glossary transform needs a [transforms.glossary] table")?
+            .into_iter()
+            .map(|(heard, meant)| Ok((heard, meant.into_string()?)))
+            .collect::<Result<_, config::ConfigError>>()?;
+        // Longest first, so "amazon web services" wins over "amazon"
+        let mut heard: Vec<&String> = table.keys().collect();
+        heard.sort_by_key(|h| std::cmp::Reverse(h.len()));
+        let entries = heard
+            .into_iter()
+            .map(|h| Ok((phrase_pattern(std::slice::from_ref(h))?, table[h].clone())))
+            .collect::<Result<_, Error>>()?;
+        Ok(Glossary { entries })
+    }
+}
+
+impl Transform for Glossary {
+    fn apply(&self, transcript: &mut Transcript) {
+        for segment in &mut transcript.segments {
+            for (pattern, meant) in &self.entries {
+                if pattern.is_match(&segment.text) {
+                    segment.text = pattern.replace_all(&segment.text, regex::NoExpand(meant)).into_owned();
+                }
+            }
+        }
+    }
+}
+
+// Replace emails, phone numbers and long digit sequences, plus any
+// `transforms.redact_patterns` regexes, with "[REDACTED]"
+struct Redact {
+    patterns: Vec<Regex>,
+}
+
+impl Redact {
+    fn from_settings(settings: &Config) -> Result<Self, Error> {
+        let mut patterns: Vec<Regex> = DEFAULT_REDACT_PATTERNS
+            .iter()
+            .map(|p| Regex::new(p))
+            .collect::<Result<_, _>>()?;
+        for pattern in strings(settings, "transforms.redact_patterns")?.unwrap_or_default() {
+            patterns.push(
+                Regex::new(&pattern)
+                    .with_context(|| format!("Invalid pattern in transforms.redact_patterns: {}", pattern))?,
+            );
+        }
+        Ok(Redact { patterns })
+    }
+}
+
+impl Transform for Redact {
+    fn apply(&self, transcript: &mut Transcript) {
+        for segment in &mut transcript.segments {
+            for pattern in &self.patterns {
+                if pattern.is_match(&segment.text) {
+                    segment.text = pattern.replace_all(&segment.text, "[REDACTED]").into_owned();
+                }
+            }
+        }
+    }
+}
+
+// Replace speaker labels with "Participant 1", "Participant 2", ... and any
+// names in `transforms.anonymize_names` (plus the speakers' own names) with "[NAME]"
+struct Anonymize {
+    names: Vec<String>,
+}
+
+impl Anonymize {
+    fn from_settings(settings: &Config) -> Result<Self, Error> {
+        Ok(Anonymize {
+            names: strings(settings, "transforms.anonymize_names")?.unwrap_or_default(),
+        })
+    }
+}
+
+impl Transform for Anonymize {
+    fn apply(&self, transcript: &mut Transcript) {
+        let speakers = transcript.speakers();
+        let mut names = self.names.clone();
+        // Generic labels such as "Speaker 1" aren't said aloud
+        names.extend(speakers.iter().filter(|s| !s.starts_with("Speaker ")).cloned());
+        names.retain(|name| !name.trim().is_empty());
+        let pattern = if names.is_empty() { None } else { phrase_pattern(&names).ok() };
+
+        for segment in &mut transcript.segments {
+            if let Some(index) = speakers.iter().position(|s| *s == segment.speaker) {
+                segment.speaker = format!("Participant {}", index + 1);
+            }
+            if let Some(pattern) = &pattern {
+                segment.text = pattern.replace_all(&segment.text, "[NAME]").into_owned();
+            }
+        }
+    }
+}
+
+// Keep only the part of the meeting between `transforms.trim_start` and
+// `transforms.trim_end` (phrases said aloud, e.g. "let's get started" and
+// "stop the recording"), dropping small talk before and after
+struct TrimSections {
+    start: Option<Regex>,
+    end: Option<Regex>,
+}
+
+impl TrimSections {
+    fn from_settings(settings: &Config) -> Result<Self, Error> {
+        let phrase = |key: &str| -> Result<Option<Regex>, Error> {
+            match settings.get_string(key) {
+                Ok(phrase) if !phrase.trim().is_empty() => Ok(Some(phrase_pattern(&[phrase])?)),
+                _ => Ok(None),
+            }
+        };
+        let (start, end) = (phrase("transforms.trim_start")?, phrase("transforms.trim_end")?);
+        if start.is_none() && end.is_none() {
+            bail!("\nThe trim_sections transform needs transforms.trim_start or transforms.trim_end");
+        }
+        Ok(TrimSections { start, end })
+    }
+}
+
+impl Transform for TrimSections {
+    fn apply(&self, transcript: &mut Transcript) {
+        let segments = &transcript.segments;
+        // A phrase that is never said leaves that end of the transcript as it is
+        let first = self
+            .start
+            .as_ref()
+            .and_then(|start| segments.iter().position(|s| start.is_match(&s.text)))
+            .unwrap_or(0);
+        let last = self
+            .end
+            .as_ref()
+            .and_then(|end| segments.iter().rposition(|s| end.is_match(&s.text)))
+            .filter(|last| *last >= first)
+            .unwrap_or(segments.len().saturating_sub(1));
+        if !segments.is_empty() {
+            transcript.segments = transcript.segments.drain(first..=last).collect();
+        }
+    }
+}
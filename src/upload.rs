@@ -0,0 +1,173 @@
+use std::path::Path;
+
+use anyhow::{bail, Context, Error};
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
+use aws_sdk_s3::Client;
+use aws_smithy_runtime_api::client::result::SdkError;
+use aws_smithy_types::body::SdkBody;
+use config::Config;
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+use tracing::debug;
+
+use crate::progress::Progress;
+use crate::retry::{self, retry};
+
+// S3 requires every part but the last to be at least 5 MiB
+const MIN_PART_SIZE: u64 = 5 * 1024 * 1024;
+
+// Each attempt sends a fresh copy of the body. Bodies read from a file or held
+// in memory can always be copied; anything else fails the upload rather than
+// sending an empty body.
+const BODY_NOT_COPYABLE: &str = "The upload body can't be copied to send again";
+
+pub struct UploadOptions {
+    // Files at least this large are uploaded in parts
+    pub multipart_threshold: u64,
+    pub part_size: u64,
+    pub retry: retry::Policy,
+}
+
+impl UploadOptions {
+    // From aws.multipart_threshold_mb and aws.multipart_part_size_mb, in MiB
+    pub fn from_settings(settings: &Config) -> Result<Self, Error> {
+        let mib = |key: &str, default: i64| -> Result<u64, Error> {
+            let value = settings.get_int(key).unwrap_or(default);
+            if value < 1 {
+                bail!("\n{} must be at least 1, not {}.", key, value);
+            }
+            Ok((value as u64).saturating_mul(1024 * 1024))
+        };
+        Ok(UploadOptions {
+            multipart_threshold: mib("aws.multipart_threshold_mb", 64)?,
+            part_size: mib("aws.multipart_part_size_mb", 16)?,
+            retry: retry::Policy::from_settings(settings, "s3")?,
+        })
+    }
+}
+
+// Upload a file to S3. Large files are sent as a multipart upload so progress
+// can be shown as the parts complete.
+pub async fn upload_file(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    path: &Path,
+    spinner: &mut Progress,
+    options: &UploadOptions,
+) -> Result<(), Error> {
+    let size = tokio::fs::metadata(path)
+        .await
+        .with_context(|| format!("Error loading file: {}", path.display()))?
+        .len();
+    let multipart = size >= options.multipart_threshold;
+    debug!(bucket, key, bytes = size, multipart, "uploading to S3");
+
+    if !multipart {
+        let body = ByteStream::from_path(path)
+            .await
+            .with_context(|| format!("Error loading file: {}", path.display()))?
+            .into_inner();
+        retry(&options.retry, spinner, "Amazon S3", || {
+            let body = body.try_clone().map(ByteStream::new);
+            async move {
+                let Some(body) = body else {
+                    return Err(SdkError::construction_failure(BODY_NOT_COPYABLE));
+                };
+                client.put_object().bucket(bucket).key(key).body(body).send().await
+            }
+        })
+        .await
+        .context("Failed to upload to S3")?;
+        return Ok(());
+    }
+
+    let upload = retry(&options.retry, spinner, "Amazon S3", || {
+        client.create_multipart_upload().bucket(bucket).key(key).send()
+    })
+    .await
+    .context("Failed to start multipart upload to S3")?;
+    let upload_id = upload
+        .upload_id()
+        .context("S3 did not return a multipart upload ID")?;
+
+    let result: Result<(), Error> = async {
+        let part_size = options.part_size.max(MIN_PART_SIZE);
+        let mut file = File::open(path)
+            .await
+            .with_context(|| format!("Error loading file: {}", path.display()))?;
+        let mut completed = Vec::new();
+        let mut uploaded = 0;
+
+        spinner.progress("Uploading file to S3", 0, size);
+        loop {
+            let mut buffer = Vec::with_capacity(part_size as usize);
+            (&mut file).take(part_size).read_to_end(&mut buffer).await?;
+            if buffer.is_empty() {
+                break;
+            }
+            let part_number = completed.len() as i32 + 1;
+            let length = buffer.len() as u64;
+
+            let body = SdkBody::from(buffer);
+            let part = retry(&options.retry, spinner, "Amazon S3", || {
+                let body = body.try_clone().map(ByteStream::new);
+                async move {
+                    let Some(body) = body else {
+                        return Err(SdkError::construction_failure(BODY_NOT_COPYABLE));
+                    };
+                    client
+                        .upload_part()
+                        .bucket(bucket)
+                        .key(key)
+                        .upload_id(upload_id)
+                        .part_number(part_number)
+                        .body(body)
+                        .send()
+                        .await
+                }
+            })
+            .await
+            .with_context(|| format!("Failed to upload part {} to S3", part_number))?;
+            debug!(part_number, bytes = length, "uploaded part");
+            completed.push(
+                CompletedPart::builder()
+                    .part_number(part_number)
+                    .set_e_tag(part.e_tag().map(str::to_string))
+                    .build(),
+            );
+
+            uploaded += length;
+            spinner.progress("Uploading file to S3", uploaded, size);
+        }
+
+        // Losing the whole upload to throttling on this last call would be worst of all
+        let parts = CompletedMultipartUpload::builder().set_parts(Some(completed)).build();
+        retry(&options.retry, spinner, "Amazon S3", || {
+            client
+                .complete_multipart_upload()
+                .bucket(bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .multipart_upload(parts.clone())
+                .send()
+        })
+        .await
+        .context("Failed to complete multipart upload to S3")?;
+        Ok(())
+    }
+    .await;
+    if result.is_err() {
+        // Don't leave the uploaded parts behind (they are billed until removed)
+        let _ = client
+            .abort_multipart_upload()
+            .bucket(bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .send()
+            .await;
+    }
+    result
+}
+
@@ -0,0 +1,219 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Error};
+use config::Config;
+
+use crate::audio;
+use crate::progress::{self, Progress};
+use crate::transcribe::Transcript;
+
+// Enrollment samples and speaker turns longer than this are cut short
+const MAX_CLIP_SECONDS: f64 = 30.0;
+
+// Shorter turns don't carry enough of a voice to match reliably
+const MIN_CLIP_SECONDS: f64 = 2.0;
+
+const DEFAULT_THRESHOLD: f64 = 0.75;
+
+// Enrolled voices: each name with one embedding per sample
+type Profiles = BTreeMap<String, Vec<Vec<f64>>>;
+
+pub fn profiles_path(settings: &Config) -> PathBuf {
+    let path = settings
+        .get_string("speakers.profiles")
+        .unwrap_or_else(|_| "~/.distill/speakers.json".to_string());
+    PathBuf::from(shellexpand::tilde(&path).to_string())
+}
+
+fn load_profiles(path: &Path) -> Result<Profiles, Error> {
+    match File::open(path) {
+        Ok(file) => serde_json::from_reader(file)
+            .with_context(|| format!("Invalid speaker profiles: {}", path.display())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Profiles::new()),
+        Err(err) => Err(err).with_context(|| format!("Error reading speaker profiles: {}", path.display())),
+    }
+}
+
+// `speakers.embedding_command` runs a local speaker-embedding model, e.g.
+// ["python3", "embed.py", "{input}"]. It is given a 16 kHz mono WAV file in
+// place of {input} and must print the embedding as a JSON array of numbers.
+fn embedding_command(settings: &Config) -> Option<Vec<String>> {
+    let command: Vec<String> = settings
+        .get_array("speakers.embedding_command")
+        .ok()?
+        .into_iter()
+        .filter_map(|part| part.into_string().ok())
+        .collect();
+    (!command.is_empty()).then_some(command)
+}
+
+fn embed(command: &[String], wav: &Path) -> Result<Vec<f64>, Error> {
+    let args = command[1..]
+        .iter()
+        .map(|arg| arg.replace("{input}", &wav.to_string_lossy()));
+    let result = Command::new(&command[0])
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to run speakers.embedding_command '{}'", command[0]))?;
+    if !result.status.success() {
+        bail!(
+            "\nspeakers.embedding_command failed: {}",
+            String::from_utf8_lossy(&result.stderr).trim()
+        );
+    }
+    let embedding: Vec<f64> = serde_json::from_slice(&result.stdout)
+        .context("speakers.embedding_command must print a JSON array of numbers")?;
+    if embedding.is_empty() {
+        bail!("\nspeakers.embedding_command printed an empty embedding");
+    }
+    Ok(embedding)
+}
+
+fn cosine_similarity(a: &[f64], b: &[f64]) -> f64 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f64]| v.iter().map(|x| x * x).sum::<f64>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot / norms
+    }
+}
+
+fn save_profiles(path: &Path, profiles: &Profiles) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let file = File::create(path)
+        .with_context(|| format!("Error writing speaker profiles: {}", path.display()))?;
+    serde_json::to_writer_pretty(BufWriter::new(file), profiles)?;
+    Ok(())
+}
+
+// Add the samples in an exported speakers.json that aren't enrolled here yet,
+// and return how many were added
+pub fn merge_profiles(settings: &Config, exported: &[u8]) -> Result<usize, Error> {
+    let exported: Profiles = serde_json::from_slice(exported).context("Invalid speaker profiles in the archive")?;
+    let path = profiles_path(settings);
+    let mut profiles = load_profiles(&path)?;
+    let mut added = 0;
+    for (name, exported_samples) in exported {
+        let samples = profiles.entry(name).or_default();
+        for sample in exported_samples {
+            if !samples.contains(&sample) {
+                samples.push(sample);
+                added += 1;
+            }
+        }
+    }
+    if added > 0 {
+        save_profiles(&path, &profiles)?;
+    }
+    Ok(added)
+}
+
+// Add a voice sample for `name`. Several samples per person, e.g. from
+// different microphones, make matching more reliable.
+pub fn enroll(settings: &Config, name: &str, sample: &Path) -> Result<(), Error> {
+    let Some(command) = embedding_command(settings) else {
+        bail!("\nNo speaker-embedding model configured. Set speakers.embedding_command in config.toml.");
+    };
+    if !sample.exists() {
+        bail!("\nThe path {} does not exist.", sample.display());
+    }
+    let (_dir, wav) = audio::extract_wav(sample, 0.0, Some(MAX_CLIP_SECONDS))?;
+    let embedding = embed(&command, &wav)?;
+
+    let path = profiles_path(settings);
+    let mut profiles = load_profiles(&path)?;
+    let samples = profiles.entry(name.to_string()).or_default();
+    samples.push(embedding);
+    let count = samples.len();
+
+    save_profiles(&path, &profiles)?;
+    eprintln!(
+        "{}Enrolled {} ({} sample{}) in {}",
+        progress::icon("🎙️"),
+        name,
+        count,
+        if count == 1 { "" } else { "s" },
+        path.display()
+    );
+    Ok(())
+}
+
+// Rename diarized speakers ("Speaker 1") to enrolled names. Each speaker's
+// longest turn is embedded and compared with every enrolled sample; the best
+// pairs above speakers.match_threshold are applied, one name per speaker.
+// `offset` is where the transcript starts in `audio_file`, for clipped runs.
+// Returns the names that were applied.
+pub fn match_speakers(
+    settings: &Config,
+    transcript: &mut Transcript,
+    audio_file: &Path,
+    offset: f64,
+    spinner: &mut Progress,
+) -> Result<Vec<(String, String)>, Error> {
+    let Some(command) = embedding_command(settings) else {
+        return Ok(Vec::new());
+    };
+    let profiles = load_profiles(&profiles_path(settings))?;
+    if profiles.is_empty() {
+        return Ok(Vec::new());
+    }
+    spinner.update("Matching speakers to enrolled voices...");
+    let threshold = settings
+        .get_float("speakers.match_threshold")
+        .unwrap_or(DEFAULT_THRESHOLD);
+
+    // Longest turn of each speaker, as (start, length)
+    let mut longest: HashMap<&str, (f64, f64)> = HashMap::new();
+    for segment in &transcript.segments {
+        let length = segment.end_time - segment.start_time;
+        let turn = longest.entry(&segment.speaker).or_insert((segment.start_time, 0.0));
+        if length > turn.1 {
+            *turn = (segment.start_time, length);
+        }
+    }
+
+    let mut scores = Vec::new();
+    for (speaker, (start, length)) in longest {
+        if length < MIN_CLIP_SECONDS {
+            continue;
+        }
+        let (_dir, wav) = audio::extract_wav(audio_file, offset + start, Some(length.min(MAX_CLIP_SECONDS)))?;
+        let embedding = embed(&command, &wav)?;
+        for (name, samples) in &profiles {
+            let score = samples
+                .iter()
+                .map(|sample| cosine_similarity(&embedding, sample))
+                .fold(f64::MIN, f64::max);
+            if score >= threshold {
+                scores.push((score, speaker.to_string(), name.clone()));
+            }
+        }
+    }
+
+    // Best matches first, so two speakers never get the same name
+    scores.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let mut renames: Vec<(String, String)> = Vec::new();
+    for (_, speaker, name) in scores {
+        if renames.iter().all(|(s, n)| *s != speaker && *n != name) {
+            renames.push((speaker, name));
+        }
+    }
+
+    for segment in &mut transcript.segments {
+        if let Some((_, name)) = renames.iter().find(|(speaker, _)| *speaker == segment.speaker) {
+            segment.speaker = name.clone();
+        }
+    }
+    Ok(renames)
+}
@@ -0,0 +1,186 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Error};
+use config::Config;
+use notify::{EventKind, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::audio::is_audio_file;
+use crate::output::write_atomic;
+use crate::{progress, status};
+
+// Kept in the watched directory, so moving or syncing the folder keeps it
+const STATE_FILE: &str = ".distill-watch.json";
+
+// Options that name the recording, which watch gives each run itself
+const INPUT_FLAGS: [&str; 4] = ["-i", "--input-audio-file", "--input-transcript", "--track"];
+
+// The recordings watch has already handled, by file name
+#[derive(Default, Serialize, Deserialize)]
+struct State {
+    files: BTreeMap<String, Record>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Record {
+    // "processed", or "existing" for a file that was there before the first watch
+    status: String,
+    at: u64,
+}
+
+// `distill watch <dir>`: summarize each audio file that appears in `dir`,
+// e.g. the folder a Zoom client saves its recordings to. A file is processed
+// once its size has stopped changing for watch.settle_secs, as a separate
+// distill run with watch.args and then `args` (e.g. "--profile zoom -o
+// markdown"), so a failed recording doesn't stop the watch. Files that were
+// already there the first time are left alone unless `existing` is set.
+pub fn watch(settings: &Config, dir: &Path, existing: bool, args: &[String]) -> Result<(), Error> {
+    if !dir.is_dir() {
+        bail!("\nNo directory to watch at {}", dir.display());
+    }
+    let mut run_args: Vec<String> = settings
+        .get_array("watch.args")
+        .unwrap_or_default()
+        .into_iter()
+        .map(|arg| arg.into_string())
+        .collect::<Result<_, _>>()
+        .context("watch.args must be a list of strings")?;
+    run_args.extend(args.iter().cloned());
+    if let Some(flag) = run_args
+        .iter()
+        .find(|arg| INPUT_FLAGS.iter().any(|flag| arg.split('=').next() == Some(flag)))
+    {
+        bail!("\n{} can't be used with watch; each new recording in the directory is the input.", flag);
+    }
+    let settle = Duration::from_secs(settings.get_int("watch.settle_secs").unwrap_or(5).max(1) as u64);
+
+    let state_path = dir.join(STATE_FILE);
+    let first_watch = !state_path.exists();
+    let mut state = load(&state_path)?;
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).context("Error starting the file watcher")?;
+    watcher
+        .watch(dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Error watching {}", dir.display()))?;
+
+    // Files that arrived while nothing was watching are picked up now
+    let mut pending: HashMap<PathBuf, (u64, Instant)> = HashMap::new();
+    for path in audio_files(dir)? {
+        let name = file_name(&path);
+        if state.files.contains_key(&name) {
+            continue;
+        }
+        if first_watch && !existing {
+            state.files.insert(name, Record { status: "existing".to_string(), at: now() });
+        } else {
+            pending.insert(path, (u64::MAX, Instant::now()));
+        }
+    }
+    save(&state_path, &state)?;
+
+    status!(
+        "👀",
+        "Watching {} for new recordings{}. Press Ctrl+C to stop.",
+        dir.display(),
+        if pending.is_empty() { String::new() } else { format!(" ({} waiting)", pending.len()) }
+    );
+
+    let exe = std::env::current_exe()?;
+    loop {
+        match receiver.recv_timeout(Duration::from_secs(1)) {
+            Ok(Ok(event)) => {
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    for path in event.paths {
+                        let name = file_name(&path);
+                        if is_audio_file(&path) && !name.starts_with('.') && !state.files.contains_key(&name) {
+                            debug!(path = %path.display(), "recording changed");
+                            pending.entry(path).or_insert((u64::MAX, Instant::now()));
+                        }
+                    }
+                }
+            }
+            Ok(Err(err)) => eprintln!("{}Error watching {}: {}", progress::icon("⚠️"), dir.display(), err),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => bail!("\nThe file watcher for {} stopped", dir.display()),
+        }
+
+        // A recording is ready once its size has stayed the same for `settle`
+        let mut ready: Vec<PathBuf> = Vec::new();
+        pending.retain(|path, (size, since)| {
+            let Ok(metadata) = std::fs::metadata(path) else {
+                return false;
+            };
+            if metadata.len() != *size {
+                *size = metadata.len();
+                *since = Instant::now();
+            } else if since.elapsed() >= settle && metadata.len() > 0 {
+                ready.push(path.clone());
+                return false;
+            }
+            true
+        });
+        ready.sort();
+
+        for path in ready {
+            let name = file_name(&path);
+            status!("🎙️", "New recording: {}", name);
+            let result = Command::new(&exe)
+                .arg("--input-audio-file")
+                .arg(&path)
+                .args(&run_args)
+                // Nobody is at the terminal to answer questions, e.g. which bucket to use
+                .args((!run_args.iter().any(|arg| arg == "--non-interactive")).then_some("--non-interactive"))
+                .status()
+                .with_context(|| format!("Error starting the run for {}", path.display()))?;
+
+            // A failed recording is tried again the next time watch starts
+            if result.success() {
+                state.files.insert(name, Record { status: "processed".to_string(), at: now() });
+                save(&state_path, &state)?;
+            } else {
+                eprintln!(
+                    "{}Processing {} failed; it will be tried again when watch restarts",
+                    progress::icon("❌"),
+                    name
+                );
+            }
+            status!("👀", "Watching {} for new recordings", dir.display());
+        }
+    }
+}
+
+fn audio_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("Error reading directory: {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && is_audio_file(path) && !file_name(path).starts_with('.'))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+fn load(path: &Path) -> Result<State, Error> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text).with_context(|| format!("Error reading {}", path.display())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(State::default()),
+        Err(err) => Err(err).with_context(|| format!("Error reading {}", path.display())),
+    }
+}
+
+fn save(path: &Path, state: &State) -> Result<(), Error> {
+    write_atomic(path, |out| Ok(serde_json::to_writer_pretty(out, state)?))
+}
@@ -0,0 +1,144 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+use anyhow::{bail, Context, Error};
+use async_trait::async_trait;
+use config::Config;
+use serde::Deserialize;
+use tracing::{debug, instrument};
+
+use crate::audio::extract_wav;
+use crate::progress::Progress;
+use crate::transcribe::{Audio, Segment, TranscribeOptions, Transcriber, Transcript};
+
+// whisper.cpp, run on this machine with `--transcriber whisper-local` so the
+// audio of a confidential recording never leaves it. whisper.command is the
+// whisper.cpp CLI (whisper-cli unless set) and whisper.model the ggml model
+// file it loads, e.g. ~/models/ggml-large-v3-turbo.bin.
+pub struct Whisper {
+    pub command: String,
+    pub model: PathBuf,
+    pub threads: Option<i64>,
+    // Passed on to the command as they are, e.g. ["--flash-attn"]
+    pub args: Vec<String>,
+}
+
+// The -oj output of whisper.cpp, with times in milliseconds
+#[derive(Deserialize)]
+struct Output {
+    transcription: Vec<OutputSegment>,
+}
+
+#[derive(Deserialize)]
+struct OutputSegment {
+    offsets: Offsets,
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct Offsets {
+    from: u64,
+    to: u64,
+}
+
+impl Whisper {
+    pub fn from_settings(settings: &Config) -> Result<Self, Error> {
+        let Some(model) = settings.get_string("whisper.model").ok().filter(|model| !model.trim().is_empty()) else {
+            bail!("\nNo Whisper model configured. Download a ggml model for whisper.cpp and set whisper.model in config.toml.");
+        };
+        let model = PathBuf::from(shellexpand::tilde(model.trim()).to_string());
+        if !model.is_file() {
+            bail!("\nThe Whisper model {} does not exist. Check whisper.model in config.toml.", model.display());
+        }
+        let args = settings
+            .get_array("whisper.args")
+            .unwrap_or_default()
+            .into_iter()
+            .map(|arg| arg.into_string())
+            .collect::<Result<_, _>>()
+            .context("whisper.args must be a list of strings")?;
+        Ok(Whisper {
+            command: settings
+                .get_string("whisper.command")
+                .ok()
+                .filter(|command| !command.trim().is_empty())
+                .unwrap_or_else(|| "whisper-cli".to_string()),
+            model,
+            threads: settings.get_int("whisper.threads").ok().filter(|threads| *threads > 0),
+            args,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Transcriber for Whisper {
+    fn name(&self) -> &'static str {
+        "Whisper"
+    }
+
+    // Whisper doesn't tell speakers apart, so segments have no speaker unless
+    // they come from a --track
+    #[instrument(name = "whisper", skip_all, fields(file = %audio.path.display()))]
+    async fn transcribe(
+        &self,
+        audio: &Audio<'_>,
+        options: &TranscribeOptions<'_>,
+        spinner: &mut Progress,
+    ) -> Result<Transcript, Error> {
+        let file_path = audio.path;
+        spinner.update("Converting recording for Whisper...");
+        let (dir, wav) = extract_wav(file_path, 0.0, None)?;
+
+        spinner.update("Transcribing on this machine with Whisper...");
+        // Whisper wants the language alone, e.g. "es" for es-US
+        let language = options.language_code.split('-').next().unwrap_or_default().to_lowercase();
+        let output_base = dir.path().join("transcript");
+        let mut command = Command::new(&self.command);
+        command
+            .arg("-m")
+            .arg(&self.model)
+            .arg("-f")
+            .arg(&wav)
+            .args(["-l", &language, "-oj", "-np", "-of"])
+            .arg(&output_base);
+        if let Some(threads) = self.threads {
+            command.args(["-t", &threads.to_string()]);
+        }
+        let result = command
+            .args(&self.args)
+            .output()
+            .with_context(|| format!("Failed to run {}; install whisper.cpp or set whisper.command", self.command))?;
+        if !result.status.success() {
+            bail!(
+                "\nWhisper failed to transcribe {}: {}",
+                file_path.display(),
+                String::from_utf8_lossy(&result.stderr).trim()
+            );
+        }
+
+        let output_path = output_base.with_extension("json");
+        let output: Output = serde_json::from_slice(
+            &std::fs::read(&output_path).with_context(|| format!("Whisper wrote no transcript to {}", output_path.display()))?,
+        )
+        .context("Error reading the Whisper transcript")?;
+        let segments: Vec<Segment> = output
+            .transcription
+            .into_iter()
+            .map(|segment| Segment {
+                speaker: String::new(),
+                start_time: segment.offsets.from as f64 / 1000.0,
+                end_time: segment.offsets.to as f64 / 1000.0,
+                text: segment.text.trim().to_string(),
+                words: Vec::new(),
+            })
+            .filter(|segment| !segment.text.is_empty() && !is_tag(&segment.text))
+            .collect();
+        debug!(segments = segments.len(), "whisper transcript read");
+        Ok(Transcript { segments })
+    }
+}
+
+// Silence and noise come back as a tag such as [BLANK_AUDIO]
+fn is_tag(text: &str) -> bool {
+    text.starts_with('[') && text.ends_with(']')
+}